@@ -4,17 +4,22 @@ use std::sync::Arc;
 
 use axum::{
     body::{Body, Bytes},
-    extract::{Path, State},
-    http::{HeaderMap, StatusCode},
+    extract::{Path, Query, State},
+    http::{HeaderMap, HeaderValue, StatusCode},
     response::{IntoResponse, Json},
     routing::{get, any},
     Router,
 };
-use chrono::Utc;
-use core_types::{AlertEvent, AlertSeverity, LogEvent, Metric};
+use chrono::{DateTime, Utc};
+use core_types::{
+    AlertEvent, AlertSeverity, LogEvent, LogLevel, Metric, PluginRecord, PluginStatus,
+};
 use dotenv::dotenv;
-use serde::Deserialize;
-use storage::Db;
+use serde::{Deserialize, Serialize};
+use storage::{
+    AlertFilter, Db, LabelMatcher, LogFilter, MetricFilter, Page, PageRequest, SeriesQuery,
+    SeriesSet, MAX_SERIES_POINTS, MAX_STEP_SECS,
+};
 use tokio::net::TcpListener;
 use tracing::info;
 use tracing_subscriber::EnvFilter;
@@ -40,6 +45,26 @@ struct CreateAlertReq {
     message: String,
 }
 
+/// bot-host 每 30s 刷新一次运行中插件的记录，超过这个时间没有更新就认为 bot-host 已经不在了
+const PLUGIN_STATUS_STALE_SECS: i64 = 90;
+
+#[derive(Serialize)]
+struct PluginView {
+    #[serde(flatten)]
+    record: PluginRecord,
+    /// 状态是 Loaded / Failing 但很久没有更新（bot-host 可能已经崩溃）
+    stale: bool,
+}
+
+impl From<PluginRecord> for PluginView {
+    fn from(record: PluginRecord) -> Self {
+        let running = matches!(record.status, PluginStatus::Loaded | PluginStatus::Failing);
+        let stale = running
+            && (Utc::now() - record.updated_at).num_seconds() > PLUGIN_STATUS_STALE_SECS;
+        Self { record, stale }
+    }
+}
+
 #[tokio::main]
 async fn main() {
     dotenv().ok();
@@ -49,9 +74,12 @@ async fn main() {
     let db_url = std::env::var("MONITOR_AI_DB_URL").unwrap_or_else(|_| "sqlite://database/monitor_ai.db".into());
 
     info!("准备连接数据库: {db_url}");
-    let db = Db::connect(db_type.as_deref(), Some(&db_url))
+    let (db, applied) = Db::connect(db_type.as_deref(), Some(&db_url))
         .await
         .expect("连接数据库失败");
+    for m in applied {
+        info!("已执行数据库迁移 {:04}_{}", m.version, m.name);
+    }
 
     info!("api-server 已连接数据库: {db_url}");
 
@@ -79,13 +107,17 @@ async fn main() {
         // 或者更严格一点：
         // .allow_origin("http://127.0.0.1:5173".parse::<http::HeaderValue>().unwrap())
         .allow_methods([Method::GET, Method::POST, Method::OPTIONS])
-        .allow_headers([header::CONTENT_TYPE]);
+        .allow_headers([header::CONTENT_TYPE])
+        .expose_headers([header::HeaderName::from_static(NEXT_CURSOR_HEADER)]);
 
 
     let app = Router::new()
         .route("/logs", get(get_logs))
         .route("/metrics", get(get_metrics))
+        .route("/metrics/series", get(get_metric_series))
         .route("/alerts", get(get_alerts).post(create_alert))
+        .route("/plugins", get(list_plugins))
+        .route("/plugins/:name", get(get_plugin))
         .route(
             "/plugin-api/:plugin/*rest",
             any(proxy_plugin_api),
@@ -94,7 +126,7 @@ async fn main() {
         .layer(cors);  // 挂上 CORS 层;
 
     let addr: SocketAddr = "127.0.0.1:3001".parse().unwrap();
-    info!("api-server 启动：http://{addr}/logs /metrics /alerts");
+    info!("api-server 启动：http://{addr}/logs /metrics /alerts /plugins");
 
     let listener = TcpListener::bind(addr).await.unwrap();
     axum::serve(listener, app).await.unwrap();
@@ -108,31 +140,219 @@ fn init_tracing() {
         .init();
 }
 
-async fn get_logs(State(state): State<AppState>) -> Json<Vec<LogEvent>> {
-    let list = state
-        .db
-        .latest_logs(100)
-        .await
-        .unwrap_or_else(|_| vec![]);
-    Json(list)
+/// 列表分页最多一次返回多少条
+const MAX_PAGE_LIMIT: i64 = 1000;
+
+/// 有下一页时放在这个响应头里，下次请求带上 `cursor=<值>`
+const NEXT_CURSOR_HEADER: &str = "x-next-cursor";
+
+/// /logs /metrics /alerts 的查询参数，全部可选；不带参数时和以前一样返回最新的一批
+#[derive(Deserialize)]
+struct ListQuery {
+    plugin: Option<String>,
+    /// 指标名（/metrics）或告警关联的指标名（/alerts）
+    name: Option<String>,
+    /// 最低级别：日志 Debug / Info / Warn / Error，告警 Info / Warning / Critical
+    level: Option<String>,
+    /// RFC3339，含
+    start: Option<String>,
+    /// RFC3339，不含
+    end: Option<String>,
+    /// 逗号分隔的 `key=value` / `key!=value`，匹配指标标签、日志字段或告警标签
+    labels: Option<String>,
+    cursor: Option<String>,
+    limit: Option<i64>,
+    /// `desc`（默认，最新的在前）或 `asc`
+    order: Option<String>,
 }
 
-async fn get_metrics(State(state): State<AppState>) -> Json<Vec<Metric>> {
-    let list = state
-        .db
-        .latest_metrics(200)
-        .await
-        .unwrap_or_else(|_| vec![]);
-    Json(list)
+/// (start, end)
+type TimeRange = (Option<DateTime<Utc>>, Option<DateTime<Utc>>);
+
+type ListResponse<T> = Result<(HeaderMap, Json<Vec<T>>), (StatusCode, String)>;
+
+fn parse_time(raw: Option<&str>) -> Result<Option<DateTime<Utc>>, String> {
+    raw.map(|s| {
+        DateTime::parse_from_rfc3339(s)
+            .map(|t| t.with_timezone(&Utc))
+            .map_err(|e| format!("时间 {s} 不是 RFC3339 格式: {e}"))
+    })
+    .transpose()
 }
 
-async fn get_alerts(State(state): State<AppState>) -> Json<Vec<AlertEvent>> {
-    let list = state
-        .db
-        .latest_alerts(200)
-        .await
-        .unwrap_or_else(|_| vec![]);
-    Json(list)
+/// 逗号分隔的 `key=value` / `key!=value`
+fn parse_labels(raw: Option<&str>) -> Result<Vec<LabelMatcher>, String> {
+    raw.unwrap_or_default()
+        .split(',')
+        .filter(|s| !s.trim().is_empty())
+        .map(str::parse)
+        .collect()
+}
+
+impl ListQuery {
+    fn time_range(&self) -> Result<TimeRange, String> {
+        Ok((
+            parse_time(self.start.as_deref())?,
+            parse_time(self.end.as_deref())?,
+        ))
+    }
+
+    fn labels(&self) -> Result<Vec<LabelMatcher>, String> {
+        parse_labels(self.labels.as_deref())
+    }
+
+    fn page(&self, default_limit: i64) -> Result<PageRequest, String> {
+        let descending = match self.order.as_deref() {
+            None | Some("desc") => true,
+            Some("asc") => false,
+            Some(other) => return Err(format!("order = {other} 无效，可选 asc | desc")),
+        };
+        Ok(PageRequest {
+            cursor: self.cursor.as_deref().map(str::parse).transpose()?,
+            limit: self.limit.unwrap_or(default_limit).clamp(1, MAX_PAGE_LIMIT),
+            descending,
+        })
+    }
+}
+
+fn bad_request(msg: String) -> (StatusCode, String) {
+    (StatusCode::BAD_REQUEST, msg)
+}
+
+fn page_response<T>(result: sqlx::Result<Page<T>>, what: &str) -> ListResponse<T> {
+    let page = result.map_err(|e| {
+        tracing::error!("查询{what}失败: {e}");
+        (StatusCode::INTERNAL_SERVER_ERROR, format!("query {what} failed"))
+    })?;
+    let mut headers = HeaderMap::new();
+    if let Some(cursor) = page.next_cursor {
+        headers.insert(
+            NEXT_CURSOR_HEADER,
+            HeaderValue::from_str(&cursor.to_string()).expect("游标只含十六进制字符"),
+        );
+    }
+    Ok((headers, Json(page.items)))
+}
+
+async fn get_logs(
+    State(state): State<AppState>,
+    Query(q): Query<ListQuery>,
+) -> ListResponse<LogEvent> {
+    let (start, end) = q.time_range().map_err(bad_request)?;
+    let min_level = match q.level.as_deref().map(str::to_ascii_lowercase).as_deref() {
+        None => None,
+        Some("debug") => Some(LogLevel::Debug),
+        Some("info") => Some(LogLevel::Info),
+        Some("warn") => Some(LogLevel::Warn),
+        Some("error") => Some(LogLevel::Error),
+        Some(other) => return Err(bad_request(format!("日志级别 {other} 无效"))),
+    };
+    let filter = LogFilter {
+        plugin: q.plugin.clone(),
+        min_level,
+        start,
+        end,
+        fields: q.labels().map_err(bad_request)?,
+    };
+    let page = q.page(100).map_err(bad_request)?;
+    page_response(state.db.logs_page(&filter, &page).await, "日志")
+}
+
+async fn get_metrics(
+    State(state): State<AppState>,
+    Query(q): Query<ListQuery>,
+) -> ListResponse<Metric> {
+    let (start, end) = q.time_range().map_err(bad_request)?;
+    let filter = MetricFilter {
+        plugin: q.plugin.clone(),
+        name: q.name.clone(),
+        start,
+        end,
+        labels: q.labels().map_err(bad_request)?,
+        ..Default::default()
+    };
+    let page = q.page(200).map_err(bad_request)?;
+    page_response(state.db.metrics_page(&filter, &page).await, "指标")
+}
+
+/// /metrics/series 不带 start 时查最近这么久
+const DEFAULT_SERIES_RANGE_SECS: i64 = 3600;
+
+/// /metrics/series 的查询参数
+#[derive(Deserialize)]
+struct SeriesParams {
+    plugin: Option<String>,
+    name: Option<String>,
+    labels: Option<String>,
+    /// RFC3339，默认 end 之前一小时
+    start: Option<String>,
+    /// RFC3339，默认现在
+    end: Option<String>,
+    /// 每个点代表多少秒，默认按时间范围自动选（最多 1000 个点）
+    step: Option<i64>,
+}
+
+/// 按时间范围和 step 自动选原始数据或 1m / 1h / 1d 汇总
+async fn get_metric_series(
+    State(state): State<AppState>,
+    Query(q): Query<SeriesParams>,
+) -> Result<Json<SeriesSet>, (StatusCode, String)> {
+    let end = parse_time(q.end.as_deref())
+        .map_err(bad_request)?
+        .unwrap_or_else(Utc::now);
+    let start = parse_time(q.start.as_deref())
+        .map_err(bad_request)?
+        .unwrap_or(end - chrono::Duration::seconds(DEFAULT_SERIES_RANGE_SECS));
+    if start >= end {
+        return Err(bad_request("start 必须早于 end".to_string()));
+    }
+    if q.step.is_some_and(|s| !(1..=MAX_STEP_SECS).contains(&s)) {
+        return Err(bad_request(format!(
+            "step 必须是 1 到 {MAX_STEP_SECS} 之间的整数（秒）"
+        )));
+    }
+    let query = SeriesQuery {
+        plugin: q.plugin,
+        name: q.name,
+        labels: parse_labels(q.labels.as_deref()).map_err(bad_request)?,
+        start,
+        end,
+        step_secs: q.step,
+    };
+    let points = query.point_count();
+    if points > MAX_SERIES_POINTS {
+        return Err(bad_request(format!(
+            "这个时间范围按 step 算有 {points} 个点，最多 {MAX_SERIES_POINTS} 个，请加大 step"
+        )));
+    }
+    state.db.query_series(&query).await.map(Json).map_err(|e| {
+        tracing::error!("查询指标序列失败: {e}");
+        (StatusCode::INTERNAL_SERVER_ERROR, "query series failed".into())
+    })
+}
+
+async fn get_alerts(
+    State(state): State<AppState>,
+    Query(q): Query<ListQuery>,
+) -> ListResponse<AlertEvent> {
+    let (start, end) = q.time_range().map_err(bad_request)?;
+    let min_severity = match q.level.as_deref().map(str::to_ascii_lowercase).as_deref() {
+        None => None,
+        Some("info") => Some(AlertSeverity::Info),
+        Some("warning") => Some(AlertSeverity::Warning),
+        Some("critical") => Some(AlertSeverity::Critical),
+        Some(other) => return Err(bad_request(format!("告警级别 {other} 无效"))),
+    };
+    let filter = AlertFilter {
+        plugin: q.plugin.clone(),
+        metric_name: q.name.clone(),
+        min_severity,
+        start,
+        end,
+        tags: q.labels().map_err(bad_request)?,
+    };
+    let page = q.page(200).map_err(bad_request)?;
+    page_response(state.db.alerts_page(&filter, &page).await, "告警")
 }
 
 async fn create_alert(
@@ -164,39 +384,65 @@ async fn create_alert(
     Ok(Json(alert))
 }
 
+async fn list_plugins(
+    State(state): State<AppState>,
+) -> Result<Json<Vec<PluginView>>, (StatusCode, String)> {
+    match state.db.list_plugins().await {
+        Ok(list) => Ok(Json(list.into_iter().map(PluginView::from).collect())),
+        Err(e) => {
+            tracing::error!("查询插件列表失败: {e}");
+            Err((StatusCode::INTERNAL_SERVER_ERROR, "query plugins failed".into()))
+        }
+    }
+}
+
+async fn get_plugin(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+) -> Result<Json<PluginView>, (StatusCode, String)> {
+    match state.db.get_plugin(&name).await {
+        Ok(Some(record)) => Ok(Json(record.into())),
+        Ok(None) => Err((StatusCode::NOT_FOUND, format!("插件 {name} 不存在"))),
+        Err(e) => {
+            tracing::error!("查询插件 {name} 失败: {e}");
+            Err((StatusCode::INTERNAL_SERVER_ERROR, "query plugin failed".into()))
+        }
+    }
+}
+
 async fn proxy_plugin_api(
     State(state): State<AppState>,
     Path((plugin, rest)): Path<(String, String)>,
     req: Request<Body>,  // 不用 mut 了
 ) -> impl IntoResponse {
     // 查找 base_url
-    let base_url_opt = {
+    let cached = {
         let guard = state.plugin_apis.read().unwrap();
         guard.get(&plugin).cloned()
     };
 
+    // 缓存里没有时再查一次库：bot-host 热加载的插件是启动之后才注册的
+    let base_url_opt = match cached {
+        Some(u) => Some(u),
+        None => refresh_plugin_apis(&state, &plugin).await,
+    };
+
     let base_url = match base_url_opt {
         Some(u) => u,
         None => {
-            let mut headers = HeaderMap::new();
-            headers.insert(
-                header::CONTENT_TYPE,
-                header::HeaderValue::from_static("text/plain; charset=utf-8"),
-            );
-            let body = Bytes::from(
-                format!("未知插件或未注册 API: {}", plugin).into_bytes()
+            return text_response(
+                StatusCode::NOT_FOUND,
+                format!("未知插件或未注册 API: {}", plugin),
             );
-            return (StatusCode::NOT_FOUND, headers, body);
         }
     };
 
-    // 拼接目标 URL
+    // 拼接目标路径
     let path = if rest.is_empty() {
         "".to_string()
     } else {
         format!("/{}", rest.trim_start_matches('/'))
     };
-    let target = format!("{}{}", base_url.trim_end_matches('/'), path);
 
     // 先取出 method / headers
     let method = req.method().clone();
@@ -210,7 +456,57 @@ async fn proxy_plugin_api(
         .await
         .unwrap_or_default();
 
-    let mut builder = state.http_client.request(method, &target);
+    let mut result = forward(&state, &base_url, &path, &method, &headers, &body_bytes).await;
+
+    // 插件重新加载后地址可能变了（端口由 bot-host 分配），刷新一次再重试
+    if result.is_err()
+        && let Some(fresh) = refresh_plugin_apis(&state, &plugin).await
+        && fresh != base_url
+    {
+        result = forward(&state, &fresh, &path, &method, &headers, &body_bytes).await;
+    }
+
+    match result {
+        Ok(resp) => resp,
+        Err(e) => {
+            tracing::error!("转发到插件 API 失败: {e}");
+            text_response(StatusCode::BAD_GATEWAY, "调用插件 API 失败".to_string())
+        }
+    }
+}
+
+/// 从数据库重新加载所有插件 API 映射，返回指定插件的 base_url
+async fn refresh_plugin_apis(state: &AppState, plugin: &str) -> Option<String> {
+    let apis = state.db.get_all_plugin_apis().await.ok()?;
+    let mut guard = state.plugin_apis.write().unwrap();
+    *guard = apis.into_iter().collect();
+    guard.get(plugin).cloned()
+}
+
+fn text_response(status: StatusCode, text: String) -> (StatusCode, HeaderMap, Bytes) {
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        header::CONTENT_TYPE,
+        header::HeaderValue::from_static("text/plain; charset=utf-8"),
+    );
+    (status, headers, Bytes::from(text.into_bytes()))
+}
+
+/// 把请求转发给插件；base_url 是 `http://...` 或 `http+unix://<百分号编码的 socket 路径><前缀>`
+async fn forward(
+    state: &AppState,
+    base_url: &str,
+    path: &str,
+    method: &Method,
+    headers: &HeaderMap,
+    body: &Bytes,
+) -> Result<(StatusCode, HeaderMap, Bytes), String> {
+    if let Some(rest) = base_url.strip_prefix("http+unix://") {
+        return forward_unix(rest, path, method, headers, body).await;
+    }
+
+    let target = format!("{}{}", base_url.trim_end_matches('/'), path);
+    let mut builder = state.http_client.request(method.clone(), &target);
 
     // 转发部分头（可根据需要筛选）
     for (k, v) in headers.iter() {
@@ -220,20 +516,11 @@ async fn proxy_plugin_api(
         builder = builder.header(k, v);
     }
 
-    let resp = match builder.body(body_bytes.clone()).send().await {
-        Ok(r) => r,
-        Err(e) => {
-            tracing::error!("转发到插件 API 失败: {e}");
-
-            let mut headers = HeaderMap::new();
-            headers.insert(
-                header::CONTENT_TYPE,
-                header::HeaderValue::from_static("text/plain; charset=utf-8"),
-            );
-            let body = Bytes::from("调用插件 API 失败".as_bytes().to_vec());
-            return (StatusCode::BAD_GATEWAY, headers, body);
-        }
-    };
+    let resp = builder
+        .body(body.clone())
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
 
     let status = StatusCode::from_u16(resp.status().as_u16())
         .unwrap_or(StatusCode::BAD_GATEWAY);
@@ -243,8 +530,90 @@ async fn proxy_plugin_api(
         out_headers.insert(name.clone(), value.clone());
     }
 
-    let bytes = resp.bytes().await.unwrap_or_default();
+    let bytes = resp.bytes().await.map_err(|e| e.to_string())?;
+    Ok((status, out_headers, bytes))
+}
+
+/// 通过 Unix domain socket 转发（reqwest 不支持，直接用 hyper 的 http1 客户端）
+#[cfg(unix)]
+async fn forward_unix(
+    socket_and_prefix: &str,
+    path: &str,
+    method: &Method,
+    headers: &HeaderMap,
+    body: &Bytes,
+) -> Result<(StatusCode, HeaderMap, Bytes), String> {
+    use http_body_util::{BodyExt, Full};
+    use hyper_util::rt::TokioIo;
+
+    let (encoded_socket, prefix) = match socket_and_prefix.find('/') {
+        Some(i) => socket_and_prefix.split_at(i),
+        None => (socket_and_prefix, ""),
+    };
+    let socket = percent_decode(encoded_socket)?;
+    let uri = format!("{}{}", prefix.trim_end_matches('/'), path);
+    let uri = if uri.is_empty() { "/".to_string() } else { uri };
+
+    let stream = tokio::net::UnixStream::connect(&socket)
+        .await
+        .map_err(|e| format!("连接 {socket} 失败: {e}"))?;
+    let (mut sender, conn) = hyper::client::conn::http1::handshake(TokioIo::new(stream))
+        .await
+        .map_err(|e| e.to_string())?;
+    tokio::spawn(conn);
+
+    let mut builder = Request::builder().method(method.clone()).uri(&uri);
+    for (k, v) in headers.iter() {
+        if k.as_str().eq_ignore_ascii_case("host") {
+            continue;
+        }
+        builder = builder.header(k, v);
+    }
+    let req = builder
+        .header(header::HOST, "localhost")
+        .body(Full::new(body.clone()))
+        .map_err(|e| e.to_string())?;
+
+    let resp = sender.send_request(req).await.map_err(|e| e.to_string())?;
+    let status = resp.status();
+    let out_headers = resp.headers().clone();
+    let bytes = resp
+        .into_body()
+        .collect()
+        .await
+        .map_err(|e| e.to_string())?
+        .to_bytes();
+    Ok((status, out_headers, bytes))
+}
+
+#[cfg(not(unix))]
+async fn forward_unix(
+    _socket_and_prefix: &str,
+    _path: &str,
+    _method: &Method,
+    _headers: &HeaderMap,
+    _body: &Bytes,
+) -> Result<(StatusCode, HeaderMap, Bytes), String> {
+    Err("当前平台不支持 Unix domain socket".to_string())
+}
 
-    // 三元组 (StatusCode, HeaderMap, Bytes) -> impl IntoResponse
-    (status, out_headers, Bytes::from(bytes))
+#[cfg(unix)]
+fn percent_decode(s: &str) -> Result<String, String> {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = s
+                .get(i + 1..i + 3)
+                .and_then(|h| u8::from_str_radix(h, 16).ok())
+                .ok_or_else(|| format!("无效的 socket 路径编码: {s}"))?;
+            out.push(hex);
+            i += 3;
+        } else {
+            out.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(out).map_err(|e| e.to_string())
 }
@@ -0,0 +1,173 @@
+//! 插件 HTTP API 监听地址的分配。
+//!
+//! 声明了 `CAP_API_LISTEN` 并导出 `plugin_api_info` 的插件，在 `plugin_init` 之前由 host
+//! 分配一个本机端口（`[plugin].api_port_range`）或 Unix socket 路径（`api_transport = "unix"`）。
+//! 插件 bind 之后通过 `confirm_api_bind_fn` 回报实际地址，写入 `plugin_apis` 的 base_url 以回报的为准。
+//! 同一个插件重新加载时尽量沿用原来的地址。
+
+use std::{
+    collections::HashMap,
+    env, fs,
+    net::TcpListener,
+    path::PathBuf,
+    sync::{Mutex, MutexGuard, OnceLock},
+};
+
+use plugin_api::ApiListenAddr;
+use tracing::{info, warn};
+
+use crate::config::ApiTransport;
+
+/// 不配置 `[plugin].api_port_range` 时分配的端口范围（含两端）
+pub const DEFAULT_PORT_RANGE: (u16, u16) = (5600, 5699);
+
+/// worker 子进程通过这个环境变量拿到父进程分配的地址
+pub const API_LISTEN_ENV: &str = "BOT_HOST_API_LISTEN";
+
+struct Allocator {
+    port_range: (u16, u16),
+    socket_dir: PathBuf,
+    /// 插件名 -> 分配的地址（卸载后保留，重新加载时沿用）
+    assigned: HashMap<String, ApiListenAddr>,
+    /// 插件名 -> 插件回报的实际地址
+    bound: HashMap<String, ApiListenAddr>,
+}
+
+static ALLOCATOR: OnceLock<Mutex<Allocator>> = OnceLock::new();
+
+fn allocator() -> MutexGuard<'static, Allocator> {
+    ALLOCATOR
+        .get_or_init(|| {
+            Mutex::new(Allocator {
+                port_range: DEFAULT_PORT_RANGE,
+                socket_dir: env::temp_dir().join("bot-host-sockets"),
+                assigned: HashMap::new(),
+                bound: HashMap::new(),
+            })
+        })
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+}
+
+/// 启动时设置端口范围和 socket 目录
+pub fn configure(port_range: Option<(u16, u16)>, socket_dir: Option<PathBuf>) {
+    let mut alloc = allocator();
+    if let Some((start, end)) = port_range {
+        if start <= end {
+            alloc.port_range = (start, end);
+        } else {
+            warn!("api_port_range = [{start}, {end}] 无效，使用默认范围");
+        }
+    }
+    if let Some(dir) = socket_dir {
+        alloc.socket_dir = dir;
+    }
+    info!(
+        "插件 API 端口范围: {}-{}, socket 目录: {}",
+        alloc.port_range.0,
+        alloc.port_range.1,
+        alloc.socket_dir.display()
+    );
+}
+
+/// 给插件分配监听地址；端口全部被占用或无法创建 socket 目录时返回 None
+pub fn assign(plugin_name: &str, transport: ApiTransport) -> Option<ApiListenAddr> {
+    let mut alloc = allocator();
+    alloc.bound.remove(plugin_name);
+
+    let addr = match transport {
+        ApiTransport::Unix => {
+            if let Err(e) = fs::create_dir_all(&alloc.socket_dir) {
+                warn!(
+                    "无法创建 socket 目录 {}: {e}，插件 {plugin_name} 不分配地址",
+                    alloc.socket_dir.display()
+                );
+                return None;
+            }
+            ApiListenAddr::Unix(alloc.socket_dir.join(format!("{plugin_name}.sock")))
+        }
+        ApiTransport::Tcp => {
+            let previous = alloc
+                .assigned
+                .get(plugin_name)
+                .filter(|addr| matches!(addr, ApiListenAddr::Tcp(a) if port_is_free(a.port())))
+                .cloned();
+            match previous.or_else(|| next_free_port(&alloc, plugin_name)) {
+                Some(addr) => addr,
+                None => {
+                    warn!(
+                        "端口 {}-{} 都已被占用，插件 {plugin_name} 不分配地址",
+                        alloc.port_range.0, alloc.port_range.1
+                    );
+                    return None;
+                }
+            }
+        }
+    };
+
+    remove_stale_socket(&addr);
+    alloc.assigned.insert(plugin_name.to_string(), addr.clone());
+    Some(addr)
+}
+
+fn next_free_port(alloc: &Allocator, plugin_name: &str) -> Option<ApiListenAddr> {
+    let (start, end) = alloc.port_range;
+    (start..=end)
+        .filter(|port| {
+            !alloc.assigned.iter().any(|(name, addr)| {
+                name != plugin_name && matches!(addr, ApiListenAddr::Tcp(a) if a.port() == *port)
+            })
+        })
+        .find(|port| port_is_free(*port))
+        .map(ApiListenAddr::localhost)
+}
+
+fn port_is_free(port: u16) -> bool {
+    TcpListener::bind(("127.0.0.1", port)).is_ok()
+}
+
+/// 上一个进程留下的 socket 文件会让 bind 失败
+fn remove_stale_socket(addr: &ApiListenAddr) {
+    if let ApiListenAddr::Unix(path) = addr {
+        let _ = fs::remove_file(path);
+    }
+}
+
+/// worker 子进程里记录父进程分配的地址
+pub fn set_assigned(plugin_name: &str, addr: ApiListenAddr) {
+    remove_stale_socket(&addr);
+    allocator().assigned.insert(plugin_name.to_string(), addr);
+}
+
+pub fn assigned(plugin_name: &str) -> Option<ApiListenAddr> {
+    allocator().assigned.get(plugin_name).cloned()
+}
+
+pub fn confirm(plugin_name: &str, addr: ApiListenAddr) {
+    info!("插件 {plugin_name} 的 API 已监听在 {addr}");
+    allocator().bound.insert(plugin_name.to_string(), addr);
+}
+
+pub fn bound(plugin_name: &str) -> Option<ApiListenAddr> {
+    allocator().bound.get(plugin_name).cloned()
+}
+
+/// 写入 `plugin_apis` 的 base_url；Unix socket 用 `http+unix://<百分号编码的路径><前缀>`
+pub fn base_url(addr: &ApiListenAddr, prefix: &str) -> String {
+    match addr {
+        ApiListenAddr::Tcp(a) => format!("http://{a}{prefix}"),
+        ApiListenAddr::Unix(path) => {
+            let encoded: String = path
+                .to_string_lossy()
+                .bytes()
+                .map(|b| match b {
+                    b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                        (b as char).to_string()
+                    }
+                    _ => format!("%{b:02X}"),
+                })
+                .collect();
+            format!("http+unix://{encoded}{prefix}")
+        }
+    }
+}
@@ -0,0 +1,555 @@
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    ffi::{CStr, CString},
+    os::raw::{c_char, c_void},
+    sync::OnceLock,
+    thread_local,
+};
+
+use chrono::{DateTime, TimeZone, Utc};
+use core_types::{
+    AlertEvent, AlertSeverity as HostAlertSeverity, LogEvent, LogLevel as HostLogLevel, Metric,
+    PluginRecord,
+};
+use plugin_api::{
+    AlertSample, AlertSeverity as PluginAlertSeverity, ApiListenAddr, EventCallback, KeyValue,
+    LogLevel as PluginLogLevel, MetricBuffer, MetricPoint, MetricQuery, MetricSample,
+};
+use storage::{Db, MetricFilter};
+use tokio::runtime::Handle;
+use tracing::warn;
+
+use crate::api_listen;
+use crate::config::plugin_config_json;
+use crate::event_bus;
+use crate::storage_writer;
+use crate::worker;
+
+// ============ 全局异步写入通道 ============
+
+pub enum StorageMsg {
+    Log(LogEvent),
+    Metric(Metric),
+    Alert(AlertEvent),
+    /// plugins 表里某个插件的最新状态
+    Plugin(PluginRecord),
+}
+
+/// 所有日志 / 指标 / 告警的出口：在 worker 子进程里转发给父进程，否则进有界写入队列
+pub fn send_storage(msg: StorageMsg) {
+    if worker::is_worker() {
+        worker::forward_to_parent(msg);
+        return;
+    }
+    storage_writer::push(msg);
+}
+
+// ⭐ 新增：当前正在执行的插件名称
+thread_local! {
+    static CURRENT_PLUGIN_NAME: RefCell<Option<String>> = const { RefCell::new(None) };
+    /// 本次调用期间插件通过 report_failure_fn 报告的失败原因
+    static REPORTED_FAILURE: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+/// 在当前线程标记“当前插件名”后执行 f，给日志和指标桥接使用；执行完清空，避免污染后续调用
+pub fn with_plugin_scope<R>(plugin_name: &str, f: impl FnOnce() -> R) -> R {
+    CURRENT_PLUGIN_NAME.with(|slot| {
+        *slot.borrow_mut() = Some(plugin_name.to_string());
+    });
+    let result = f();
+    CURRENT_PLUGIN_NAME.with(|slot| {
+        *slot.borrow_mut() = None;
+    });
+    result
+}
+
+/// 取出（并清空）当前线程上插件报告的失败原因
+pub fn take_reported_failure() -> Option<String> {
+    REPORTED_FAILURE.with(|slot| slot.borrow_mut().take())
+}
+
+/// 把拒绝原因写进日志表，方便在 dashboard 上看到
+pub fn record_rejection(plugin_name: &str, reason: &str) {
+    record_plugin_log(
+        plugin_name,
+        HostLogLevel::Error,
+        format!("插件被拒绝加载: {reason}"),
+    );
+}
+
+/// host 自己代某个插件记录的日志（如加载失败、执行失败），直接进存储通道
+pub fn record_plugin_log(plugin_name: &str, level: HostLogLevel, message: String) {
+    let event = LogEvent {
+        time: Utc::now(),
+        level,
+        plugin: Some(plugin_name.to_string()),
+        message,
+        fields: Default::default(),
+    };
+
+    send_storage(StorageMsg::Log(event));
+}
+
+/// host 自己代某个插件记录的指标（如调度耗时），直接进存储通道
+pub fn record_plugin_metric(plugin_name: &str, name: &str, value: f64) {
+    let metric = Metric {
+        time: Utc::now(),
+        plugin: plugin_name.to_string(),
+        name: name.to_string(),
+        value,
+        labels: HashMap::new(),
+    };
+
+    send_storage(StorageMsg::Metric(metric));
+}
+
+// ============ FFI 桥接：Log & Metric ============
+
+pub extern "C" fn host_log_bridge(level: PluginLogLevel, msg: *const c_char) {
+    forward_plugin_log(level, msg, HashMap::new());
+}
+
+pub extern "C" fn host_log_with_fields_bridge(
+    level: PluginLogLevel,
+    msg: *const c_char,
+    fields: *const KeyValue,
+    fields_len: usize,
+) {
+    forward_plugin_log(level, msg, key_values_to_map(fields, fields_len));
+}
+
+fn forward_plugin_log(level: PluginLogLevel, msg: *const c_char, fields: HashMap<String, String>) {
+    if msg.is_null() {
+        return;
+    }
+
+    let message = match unsafe { CStr::from_ptr(msg).to_str() } {
+        Ok(s) => s.to_string(),
+        Err(_) => "<invalid utf-8>".to_string(),
+    };
+
+    let host_level = match level {
+        PluginLogLevel::Debug => HostLogLevel::Debug,
+        PluginLogLevel::Info => HostLogLevel::Info,
+        PluginLogLevel::Warn => HostLogLevel::Warn,
+        PluginLogLevel::Error => HostLogLevel::Error,
+    };
+    emit_plugin_log(host_level, message, fields);
+}
+
+/// 当前插件打的一条日志：写库并打到控制台；WASM 插件的 `log` 导入也走这里
+pub fn emit_plugin_log(host_level: HostLogLevel, message: String, fields: HashMap<String, String>) {
+    // ⭐ 读取当前插件名
+    let plugin_name_opt = CURRENT_PLUGIN_NAME.with(|slot| slot.borrow().clone());
+    let plugin_label = plugin_name_opt
+        .as_deref()
+        .unwrap_or("<unknown-plugin>");
+
+    // 控制台日志也加上插件名前缀（有字段时附在后面）
+    let decorated = if fields.is_empty() {
+        format!("[{plugin_label}] {message}")
+    } else {
+        format!("[{plugin_label}] {message} {fields:?}")
+    };
+
+    // 写入 DB 的事件，现在带上 plugin 字段
+    let event = LogEvent {
+        time: Utc::now(),
+        level: host_level,
+        plugin: plugin_name_opt.clone(),
+        message,
+        fields,
+    };
+
+    send_storage(StorageMsg::Log(event));
+
+    match host_level {
+        HostLogLevel::Debug => tracing::debug!("{decorated}"),
+        HostLogLevel::Info => tracing::info!("{decorated}"),
+        HostLogLevel::Warn => tracing::warn!("{decorated}"),
+        HostLogLevel::Error => tracing::error!("{decorated}"),
+    }
+}
+
+pub extern "C" fn host_emit_metric_bridge(sample: MetricSample) {
+    forward_plugin_metric(sample, HashMap::new());
+}
+
+pub extern "C" fn host_emit_metric_with_labels_bridge(
+    sample: MetricSample,
+    labels: *const KeyValue,
+    labels_len: usize,
+) {
+    forward_plugin_metric(sample, key_values_to_map(labels, labels_len));
+}
+
+fn forward_plugin_metric(sample: MetricSample, labels: HashMap<String, String>) {
+    let name = if sample.name.is_null() {
+        "<unnamed>".to_string()
+    } else {
+        c_str_to_string(sample.name).unwrap_or_else(|| "<invalid metric name>".to_string())
+    };
+    emit_plugin_metric(name, sample.value, sample.timestamp_ms, labels);
+}
+
+/// 当前插件上报的一条指标；WASM 插件的 `emit_metric` 导入也走这里
+pub fn emit_plugin_metric(
+    name: String,
+    value: f64,
+    timestamp_ms: i64,
+    labels: HashMap<String, String>,
+) {
+    let time = timestamp_ms_to_datetime(timestamp_ms);
+
+    // ⭐ 从线程本地拿当前插件名，默认 unknown
+    let plugin_name = CURRENT_PLUGIN_NAME.with(|slot| {
+        slot.borrow()
+            .clone()
+            .unwrap_or_else(|| "unknown".to_string())
+    });
+
+    let metric = Metric {
+        time,
+        plugin: plugin_name,
+        name,
+        value,
+        labels,
+    };
+
+    send_storage(StorageMsg::Metric(metric));
+}
+
+// ============ FFI 桥接：Alert ============
+
+pub extern "C" fn host_emit_alert_bridge(sample: AlertSample) {
+    let plugin_name = CURRENT_PLUGIN_NAME.with(|slot| {
+        slot.borrow()
+            .clone()
+            .unwrap_or_else(|| "unknown".to_string())
+    });
+
+    let severity = match sample.severity {
+        PluginAlertSeverity::Info => HostAlertSeverity::Info,
+        PluginAlertSeverity::Warning => HostAlertSeverity::Warning,
+        PluginAlertSeverity::Critical => HostAlertSeverity::Critical,
+    };
+    let time = if sample.timestamp_ms > 0 {
+        timestamp_ms_to_datetime(sample.timestamp_ms)
+    } else {
+        Utc::now()
+    };
+
+    let alert = AlertEvent {
+        time,
+        plugin: plugin_name,
+        metric_name: c_str_to_string(sample.metric_name).unwrap_or_default(),
+        severity,
+        title: c_str_to_string(sample.title).unwrap_or_else(|| "<untitled>".to_string()),
+        message: c_str_to_string(sample.message).unwrap_or_default(),
+        tags: key_values_to_map(sample.tags, sample.tags_len),
+    };
+
+    tracing::warn!(
+        "[{}] 告警 {:?}: {} - {}",
+        alert.plugin,
+        alert.severity,
+        alert.title,
+        alert.message
+    );
+    record_alert(alert);
+}
+
+/// 告警落库，同时发布到事件总线；worker 子进程里只转发，由父进程发布
+pub fn record_alert(alert: AlertEvent) {
+    if !worker::is_worker() {
+        event_bus::publish_alert(&alert);
+    }
+    send_storage(StorageMsg::Alert(alert));
+}
+
+// ============ FFI 桥接：事件总线 ============
+
+pub extern "C" fn host_publish_bridge(
+    plugin: *const c_char,
+    topic: *const c_char,
+    payload: *const c_char,
+) {
+    let plugin_name = c_str_to_string(plugin)
+        .or_else(|| CURRENT_PLUGIN_NAME.with(|slot| slot.borrow().clone()))
+        .unwrap_or_else(|| "unknown".to_string());
+    let Some(topic) = c_str_to_string(topic).filter(|t| !t.is_empty()) else {
+        warn!("插件 {plugin_name} 发布事件时没有给 topic");
+        return;
+    };
+    let payload = c_str_to_string(payload).unwrap_or_else(|| "null".to_string());
+
+    if worker::is_worker() {
+        worker::forward_publish(topic, payload);
+    } else {
+        event_bus::publish(&plugin_name, &topic, payload);
+    }
+}
+
+pub extern "C" fn host_subscribe_bridge(
+    plugin: *const c_char,
+    topic: *const c_char,
+    callback: EventCallback,
+    user_data: *mut c_void,
+) -> u64 {
+    let Some(plugin_name) =
+        c_str_to_string(plugin).or_else(|| CURRENT_PLUGIN_NAME.with(|slot| slot.borrow().clone()))
+    else {
+        warn!("订阅事件时无法确定插件名");
+        return 0;
+    };
+    let Some(topic) = c_str_to_string(topic).filter(|t| !t.is_empty()) else {
+        warn!("插件 {plugin_name} 订阅事件时没有给 topic");
+        return 0;
+    };
+    // 回调只能在同一个进程里调用，worker 子进程收不到事件；
+    // 明确拒绝并写进插件日志（转发给父进程落库），而不是返回一个永远不会触发的订阅
+    if worker::is_worker() {
+        let message = format!("插件运行在 worker 子进程里，不支持订阅事件 ({topic})");
+        warn!("插件 {plugin_name}: {message}");
+        record_plugin_log(&plugin_name, HostLogLevel::Warn, message);
+        return 0;
+    }
+    event_bus::subscribe(&plugin_name, &topic, callback, user_data)
+}
+
+pub extern "C" fn host_unsubscribe_bridge(id: u64) {
+    event_bus::unsubscribe(id);
+}
+
+// ============ FFI 桥接：历史指标查询 ============
+
+/// 不指定 limit 时返回的条数
+const DEFAULT_QUERY_LIMIT: i64 = 500;
+/// 单次查询最多返回的条数
+const MAX_QUERY_LIMIT: i64 = 10_000;
+
+/// 插件查询历史指标用的数据库连接，以及驱动它的 runtime
+static QUERY_BACKEND: OnceLock<(Db, Handle)> = OnceLock::new();
+
+pub fn set_query_backend(db: Db, handle: Handle) {
+    let _ = QUERY_BACKEND.set((db, handle));
+}
+
+/// 交给插件的 buffer 背后真正持有数据的结构，`MetricBuffer::handle` 指向它
+struct OwnedMetricBuffer {
+    points: Vec<MetricPoint>,
+    _strings: Vec<CString>,
+    _labels: Vec<Vec<KeyValue>>,
+}
+
+pub extern "C" fn host_query_metrics_bridge(query: MetricQuery) -> MetricBuffer {
+    let plugin_name = CURRENT_PLUGIN_NAME.with(|slot| slot.borrow().clone());
+    let limit = match i64::from(query.limit) {
+        0 => DEFAULT_QUERY_LIMIT,
+        n => n.min(MAX_QUERY_LIMIT),
+    };
+    let filter = MetricFilter {
+        plugin: c_str_to_string(query.plugin),
+        name: c_str_to_string(query.name),
+        start: (query.start_ms > 0).then(|| timestamp_ms_to_datetime(query.start_ms)),
+        end: (query.end_ms > 0).then(|| timestamp_ms_to_datetime(query.end_ms)),
+        labels: Vec::new(),
+        limit,
+    };
+
+    match run_metric_query(&filter) {
+        Ok(metrics) => metrics_to_buffer(metrics),
+        Err(e) => {
+            warn!(
+                "[{}] 查询历史指标失败: {e}",
+                plugin_name.as_deref().unwrap_or("<unknown-plugin>")
+            );
+            MetricBuffer {
+                points: std::ptr::null(),
+                len: 0,
+                handle: std::ptr::null_mut(),
+            }
+        }
+    }
+}
+
+/// 插件调用是同步的：在 tokio 工作线程上（比如启动时的 plugin_init）要先让出线程再阻塞
+fn run_metric_query(filter: &MetricFilter) -> Result<Vec<Metric>, String> {
+    let (db, handle) = QUERY_BACKEND
+        .get()
+        .ok_or_else(|| "host 没有可用的数据库连接".to_string())?;
+    let query = db.query_metrics(filter);
+    let result = if Handle::try_current().is_ok() {
+        tokio::task::block_in_place(|| handle.block_on(query))
+    } else {
+        handle.block_on(query)
+    };
+    result.map_err(|e| e.to_string())
+}
+
+fn metrics_to_buffer(metrics: Vec<Metric>) -> MetricBuffer {
+    // CString / Vec 的堆内存不随外层 Vec 扩容移动，指针在 buffer 释放前一直有效
+    let mut strings = Vec::new();
+    let mut c_string = |s: String| {
+        let c = CString::new(s.replace('\0', " ")).unwrap_or_default();
+        let ptr = c.as_ptr();
+        strings.push(c);
+        ptr
+    };
+
+    let mut points = Vec::with_capacity(metrics.len());
+    let mut labels_all = Vec::with_capacity(metrics.len());
+    for metric in metrics {
+        let labels: Vec<KeyValue> = metric
+            .labels
+            .into_iter()
+            .map(|(k, v)| KeyValue {
+                key: c_string(k),
+                value: c_string(v),
+            })
+            .collect();
+        points.push(MetricPoint {
+            plugin: c_string(metric.plugin),
+            name: c_string(metric.name),
+            value: metric.value,
+            timestamp_ms: metric.time.timestamp_millis(),
+            labels: labels.as_ptr(),
+            labels_len: labels.len(),
+        });
+        labels_all.push(labels);
+    }
+
+    let owned = Box::new(OwnedMetricBuffer {
+        points,
+        _strings: strings,
+        _labels: labels_all,
+    });
+    MetricBuffer {
+        points: owned.points.as_ptr(),
+        len: owned.points.len(),
+        handle: Box::into_raw(owned) as *mut c_void,
+    }
+}
+
+/// 只能释放 `host_query_metrics_bridge` 返回的 buffer
+pub extern "C" fn host_free_metric_buffer_bridge(buffer: MetricBuffer) {
+    if buffer.handle.is_null() {
+        return;
+    }
+    unsafe {
+        drop(Box::from_raw(buffer.handle as *mut OwnedMetricBuffer));
+    }
+}
+
+// ============ FFI 桥接：插件配置 ============
+
+/// 插件名为空时退回到“当前插件名”
+pub extern "C" fn host_get_config_bridge(plugin: *const c_char) -> *mut c_char {
+    let plugin_name = c_str_to_string(plugin)
+        .or_else(|| CURRENT_PLUGIN_NAME.with(|slot| slot.borrow().clone()));
+    let Some(plugin_name) = plugin_name else {
+        return std::ptr::null_mut();
+    };
+
+    match CString::new(plugin_config_json(&plugin_name)) {
+        Ok(c) => c.into_raw(),
+        Err(_) => std::ptr::null_mut(),
+    }
+}
+
+/// 只能释放 host 自己分配（CString::into_raw）的字符串
+pub extern "C" fn host_free_string_bridge(s: *mut c_char) {
+    if s.is_null() {
+        return;
+    }
+    unsafe {
+        drop(CString::from_raw(s));
+    }
+}
+
+// ============ FFI 桥接：失败报告 ============
+
+/// 同一次调用里多次报告时保留第一条
+pub extern "C" fn host_report_failure_bridge(reason: *const c_char) {
+    report_failure(c_str_to_string(reason).unwrap_or_else(|| "<no reason>".to_string()));
+}
+
+/// 记下当前调用的失败原因，调用结束后由 `take_reported_failure` 取走
+pub fn report_failure(reason: String) {
+    REPORTED_FAILURE.with(|slot| {
+        slot.borrow_mut().get_or_insert(reason);
+    });
+}
+
+// ============ FFI 桥接：API 监听地址 ============
+
+/// 返回 host 分配给插件的监听地址；插件名为空时用当前调用的插件
+pub extern "C" fn host_get_api_listen_bridge(plugin: *const c_char) -> *mut c_char {
+    let plugin_name = c_str_to_string(plugin)
+        .or_else(|| CURRENT_PLUGIN_NAME.with(|slot| slot.borrow().clone()));
+    let Some(addr) = plugin_name.and_then(|name| api_listen::assigned(&name)) else {
+        return std::ptr::null_mut();
+    };
+
+    match CString::new(addr.to_string()) {
+        Ok(c) => c.into_raw(),
+        Err(_) => std::ptr::null_mut(),
+    }
+}
+
+/// 插件回报实际监听的地址；在 worker 子进程里同时转发给父进程
+pub extern "C" fn host_confirm_api_bind_bridge(plugin: *const c_char, addr: *const c_char) {
+    let plugin_name = c_str_to_string(plugin)
+        .or_else(|| CURRENT_PLUGIN_NAME.with(|slot| slot.borrow().clone()));
+    let Some(plugin_name) = plugin_name else {
+        return;
+    };
+
+    let parsed = c_str_to_string(addr)
+        .ok_or_else(|| "地址为空".to_string())
+        .and_then(|s| s.parse::<ApiListenAddr>());
+    match parsed {
+        Ok(addr) => {
+            if worker::is_worker() {
+                worker::forward_api_bound(&addr);
+            }
+            api_listen::confirm(&plugin_name, addr);
+        }
+        Err(e) => warn!("插件 {plugin_name} 回报的监听地址无效: {e}"),
+    }
+}
+
+// ============ 小工具函数 ============
+
+pub fn c_str_to_string(ptr: *const c_char) -> Option<String> {
+    if ptr.is_null() {
+        return None;
+    }
+    unsafe { CStr::from_ptr(ptr).to_str().ok().map(|s| s.to_string()) }
+}
+
+/// 把插件传来的 KeyValue 数组拷贝成 HashMap；key 为空或非 UTF-8 的项直接跳过
+fn key_values_to_map(ptr: *const KeyValue, len: usize) -> HashMap<String, String> {
+    let mut map = HashMap::new();
+    if ptr.is_null() || len == 0 {
+        return map;
+    }
+
+    let items = unsafe { std::slice::from_raw_parts(ptr, len) };
+    for kv in items {
+        let Some(key) = c_str_to_string(kv.key) else {
+            continue;
+        };
+        let value = c_str_to_string(kv.value).unwrap_or_default();
+        map.insert(key, value);
+    }
+    map
+}
+
+fn timestamp_ms_to_datetime(ms: i64) -> DateTime<Utc> {
+    match Utc.timestamp_millis_opt(ms) {
+        chrono::LocalResult::Single(dt) => dt,
+        _ => Utc::now(),
+    }
+}
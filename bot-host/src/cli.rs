@@ -0,0 +1,626 @@
+//! 命令行入口
+//!
+//! ```text
+//! bot-host [run]                       启动 host（默认）
+//! bot-host plugins list                列出插件目录里的插件，不调用 plugin_init
+//! bot-host plugins run <name> [--once] 只加载一个插件，日志和指标打到控制台，不写库
+//! bot-host config check                检查 config.toml，有错误时退出码为 1
+//! bot-host db status                   查看数据库迁移的执行情况，有未执行的迁移时退出码为 1
+//! bot-host db migrate                  执行未执行的数据库迁移
+//! ```
+
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::Duration,
+};
+
+use clap::{Parser, Subcommand};
+use storage::Db;
+use tokio::task;
+use tracing::{error, info, warn};
+
+use crate::api_listen;
+use crate::bridge::set_query_backend;
+use crate::config::{
+    database_settings, is_prod_mode, load_config, parse_schedule_override, plugin_ext,
+    plugin_mode, read_config, resolve_plugin_dir, set_plugin_configs, AppConfig, PluginConfig,
+    CONFIG_PATH,
+};
+use crate::manifest::{parse_verifying_key, PluginVerifier};
+use crate::registry::{
+    capability_names, discover_plugins, normalize_plugin_name, scan_plugin_dir, LoadedPlugin,
+    PluginFilter, PluginRegistry,
+};
+use crate::scheduler::{resolve_schedule, PluginSchedulers};
+use crate::storage_writer;
+use crate::worker::WORKER_SUBCOMMAND;
+
+#[derive(Parser)]
+#[command(name = "bot-host", version, about = "监控AI机器人：加载并调度插件")]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// 启动 host，加载并调度全部插件（不带子命令时的默认行为）
+    Run,
+    /// 查看、调试插件
+    Plugins {
+        #[command(subcommand)]
+        command: PluginsCommand,
+    },
+    /// 配置文件相关
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommand,
+    },
+    /// 数据库 schema 迁移
+    Db {
+        #[command(subcommand)]
+        command: DbCommand,
+    },
+    /// 在子进程里运行一个插件（`isolation = "process"`，由 host 自己拉起）
+    #[command(name = WORKER_SUBCOMMAND, hide = true)]
+    PluginWorker {
+        /// 插件动态库路径
+        path: PathBuf,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum PluginsCommand {
+    /// 列出插件目录里的插件：版本、ABI、能力、调度方式、是否启用
+    List,
+    /// 只加载指定插件并运行，日志和指标打到控制台，不写库
+    Run {
+        /// 插件名（meta 里的 name）
+        name: String,
+        /// 只执行一轮就退出；不加时按调度方式一直运行，Ctrl-C 退出
+        #[arg(long)]
+        once: bool,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum ConfigCommand {
+    /// 检查 config.toml，有错误时退出码为 1
+    Check,
+}
+
+#[derive(Subcommand)]
+pub enum DbCommand {
+    /// 列出所有迁移及执行时间，有未执行的迁移时退出码为 1
+    Status,
+    /// 执行未执行的迁移（bot-host / api-server 启动时也会自动执行）
+    Migrate,
+}
+
+/// 插件目录、文件名过滤和 prod 模式的清单校验，run / plugins 子命令共用
+struct PluginSetup {
+    mode: String,
+    plugin_dir: PathBuf,
+    filter: PluginFilter,
+    verifier: Option<PluginVerifier>,
+}
+
+impl PluginSetup {
+    fn from_config(plugin_cfg: &PluginConfig) -> Self {
+        let mode = plugin_mode(plugin_cfg);
+        let plugin_dir = resolve_plugin_dir(&mode, plugin_cfg);
+        let verifier =
+            is_prod_mode(&mode).then(|| PluginVerifier::from_config(plugin_cfg, &plugin_dir));
+        Self {
+            filter: PluginFilter::from_config(plugin_cfg, plugin_ext()),
+            mode,
+            plugin_dir,
+            verifier,
+        }
+    }
+
+    /// 清单校验时只加载校验过的副本，见 [`LoadedPlugin::load_shadow_copy`]
+    fn shadow_dir(&self) -> Option<PathBuf> {
+        self.verifier
+            .is_some()
+            .then(|| env::temp_dir().join(format!("bot-host-plugins-{}", std::process::id())))
+    }
+}
+
+// ============ plugins list ============
+
+/// 列出插件；有插件加载失败时返回 1
+pub fn list_plugins() -> i32 {
+    let config = load_config();
+    set_plugin_configs(&config);
+    let plugin_cfg = config.plugin.clone().unwrap_or_default();
+    let setup = PluginSetup::from_config(&plugin_cfg);
+    let default_interval = Duration::from_secs(plugin_cfg.default_interval.unwrap_or(5).max(1));
+
+    println!(
+        "插件目录: {} (mode={}, 文件名匹配 \"{}\")",
+        setup.plugin_dir.display(),
+        setup.mode,
+        setup.filter.pattern()
+    );
+    if let Some(verifier) = &setup.verifier {
+        println!("插件清单: {}", verifier.manifest_path().display());
+    }
+
+    let (candidates, skipped) = scan_plugin_dir(&setup.plugin_dir, &setup.filter);
+    let shadow_dir = setup.shadow_dir();
+    let registry = PluginRegistry::new(setup.filter, setup.verifier, shadow_dir.clone());
+
+    let mut failed = 0;
+    for path in &candidates {
+        println!();
+        match registry.inspect(path) {
+            Ok(plugin) => print_plugin(&plugin, registry.filter(), default_interval),
+            Err(reason) => {
+                failed += 1;
+                println!("{}", path.display());
+                println!("  无法加载: {reason}");
+            }
+        }
+    }
+    for (path, reason) in &skipped {
+        println!();
+        println!("{}", path.display());
+        println!("  跳过: {reason}");
+    }
+    if candidates.is_empty() && skipped.is_empty() {
+        println!("未发现任何插件动态库，确认已构建插件。");
+    }
+
+    if let Some(dir) = shadow_dir {
+        let _ = fs::remove_dir_all(dir);
+    }
+    i32::from(failed > 0)
+}
+
+fn print_plugin(plugin: &LoadedPlugin, filter: &PluginFilter, default_interval: Duration) {
+    println!("{} {} (kind={})", plugin.name, plugin.version, plugin.kind);
+    println!("  文件: {}", plugin.path.display());
+    match plugin.wasm_limits() {
+        Some((fuel, memory)) => println!(
+            "  运行时: WASM（ABI {}，每次调用 fuel {fuel}，内存上限 {}MB）",
+            plugin.abi_version,
+            memory / 1024 / 1024
+        ),
+        None => println!("  ABI: {}", plugin.abi_version),
+    }
+    println!(
+        "  能力: {} ({:#x})",
+        capability_names(plugin.capabilities).join(", "),
+        plugin.capabilities
+    );
+    if plugin.is_schedulable() {
+        println!("  调度: {}", resolve_schedule(plugin, default_interval));
+    } else {
+        println!("  调度: 无（只有 plugin_init，常驻服务）");
+    }
+    if let Some((port, prefix)) = plugin.declared_api() {
+        println!("  HTTP API: 端口 {port}，前缀 {prefix}");
+    }
+    match filter.check_name(&plugin.name) {
+        Ok(()) => println!("  启用: 是"),
+        Err(reason) => println!("  启用: 否，{reason}"),
+    }
+}
+
+// ============ plugins run ============
+
+/// 只加载一个插件并运行；插件加载失败或 `--once` 那一轮失败时返回 1
+#[tokio::main]
+pub async fn run_plugin(name: &str, once: bool) -> i32 {
+    let config = load_config();
+    set_plugin_configs(&config);
+    let mut plugin_cfg = config.plugin.clone().unwrap_or_default();
+    // 只启用这一个插件；按名字指定时 disabled 列表不再生效
+    plugin_cfg.enabled = Some(vec![name.to_string()]);
+    plugin_cfg.disabled = None;
+    let setup = PluginSetup::from_config(&plugin_cfg);
+    let default_interval = Duration::from_secs(plugin_cfg.default_interval.unwrap_or(5).max(1));
+
+    storage_writer::print_to_console();
+
+    // 插件可能要查询历史指标；连不上数据库也照样运行，查询返回失败
+    let (db_type, db_url) = database_settings();
+    let db = match Db::connect(db_type.as_deref(), Some(&db_url)).await {
+        Ok((db, applied)) => {
+            for m in applied {
+                info!("已执行数据库迁移 {:04}_{}", m.version, m.name);
+            }
+            set_query_backend(db.clone(), tokio::runtime::Handle::current());
+            Some(db)
+        }
+        Err(e) => {
+            warn!("连接数据库 {db_url} 失败: {e}，插件无法查询历史指标");
+            None
+        }
+    };
+
+    api_listen::configure(
+        plugin_cfg.api_port_range,
+        plugin_cfg.api_socket_dir.as_ref().map(PathBuf::from),
+    );
+
+    let shadow_dir = setup.shadow_dir();
+    let PluginSetup {
+        plugin_dir,
+        filter,
+        verifier,
+        ..
+    } = setup;
+    let registry_shadow_dir = shadow_dir.clone();
+    let search_dir = plugin_dir.clone();
+    // dlopen + plugin_init 都是同步调用，不占用 tokio worker
+    let loaded = task::spawn_blocking(move || {
+        let registry = PluginRegistry::new(filter, verifier, registry_shadow_dir);
+        registry.load_all(&discover_plugins(&search_dir, registry.filter()));
+        registry
+    })
+    .await;
+    let registry = match loaded {
+        Ok(registry) => registry,
+        Err(e) => {
+            error!("加载插件时任务异常: {e}");
+            return 1;
+        }
+    };
+
+    let code = match registry.plugins().into_iter().next() {
+        None => {
+            if registry.rejected().is_empty() {
+                error!("在 {} 中没有找到插件 {name}", plugin_dir.display());
+            }
+            1
+        }
+        Some(plugin) if once => {
+            if plugin.is_schedulable() {
+                let runner = plugin.clone();
+                match task::spawn_blocking(move || runner.run_once()).await {
+                    Ok(Ok(())) => {
+                        info!("插件 {name} 执行完成");
+                        0
+                    }
+                    Ok(Err(reason)) => {
+                        error!("插件 {name} 执行失败: {reason}");
+                        1
+                    }
+                    Err(e) => {
+                        error!("插件 {name} 执行任务异常: {e}");
+                        1
+                    }
+                }
+            } else {
+                info!("插件 {name} 没有 run / run_with_ctx，plugin_init 已执行");
+                0
+            }
+        }
+        Some(plugin) => {
+            let schedulers = PluginSchedulers::new(default_interval, Duration::ZERO);
+            schedulers.start(&plugin);
+            info!("插件 {name} 已启动，Ctrl-C 退出");
+            crate::shutdown_signal().await;
+            schedulers.stop_all().await;
+            0
+        }
+    };
+
+    let _ = task::spawn_blocking(move || registry.shutdown_all()).await;
+    if let Some(db) = db {
+        db.close().await;
+    }
+    if let Some(dir) = shadow_dir {
+        let _ = fs::remove_dir_all(dir);
+    }
+    code
+}
+
+// ============ config check ============
+
+/// 检查结果：错误会让 host 拒绝相应的配置或插件，警告只是提示
+#[derive(Default)]
+struct CheckReport {
+    errors: Vec<String>,
+    warnings: Vec<String>,
+}
+
+impl CheckReport {
+    fn error(&mut self, msg: impl Into<String>) {
+        self.errors.push(msg.into());
+    }
+
+    fn warn(&mut self, msg: impl Into<String>) {
+        self.warnings.push(msg.into());
+    }
+}
+
+/// 检查配置文件；有错误时返回 1
+pub fn check_config() -> i32 {
+    let path = Path::new(CONFIG_PATH);
+    let config = match read_config(path) {
+        Ok(Some(config)) => config,
+        Ok(None) => {
+            println!("未找到 {}，使用默认配置", path.display());
+            AppConfig::default()
+        }
+        Err(e) => {
+            println!("错误: {e}");
+            return 1;
+        }
+    };
+
+    let mut report = CheckReport::default();
+    check_plugin_section(&config.plugin.clone().unwrap_or_default(), &mut report);
+    check_storage_section(&config, &mut report);
+    check_retention_section(&config, &mut report);
+    let mut names: Vec<&String> = config.plugins.keys().collect();
+    names.sort();
+    for name in names {
+        check_plugin_table(name, &config.plugins[name], &mut report);
+    }
+
+    for msg in &report.warnings {
+        println!("警告: {msg}");
+    }
+    for msg in &report.errors {
+        println!("错误: {msg}");
+    }
+    if report.errors.is_empty() {
+        println!(
+            "{} 检查通过（{} 条警告）",
+            path.display(),
+            report.warnings.len()
+        );
+        0
+    } else {
+        println!(
+            "{} 有 {} 个错误、{} 条警告",
+            path.display(),
+            report.errors.len(),
+            report.warnings.len()
+        );
+        1
+    }
+}
+
+fn check_plugin_section(cfg: &PluginConfig, report: &mut CheckReport) {
+    if let Some(mode) = cfg.mode.as_deref()
+        && !matches!(mode, "dev" | "prod" | "release")
+    {
+        report.error(format!(
+            "[plugin].mode = \"{mode}\" 无效，可选 dev | prod | release"
+        ));
+    }
+    let mode = plugin_mode(cfg);
+    let plugin_dir = resolve_plugin_dir(&mode, cfg);
+    if !plugin_dir.is_dir() {
+        report.warn(format!(
+            "插件目录 {} 不存在（mode={mode}）",
+            plugin_dir.display()
+        ));
+    }
+
+    if let Some(pattern) = cfg.name_pattern.as_deref().map(str::trim)
+        && let Err(e) = glob::Pattern::new(pattern)
+    {
+        report.error(format!("[plugin].name_pattern 不是合法的 glob: {e}"));
+    }
+    if let (Some(enabled), Some(disabled)) = (&cfg.enabled, &cfg.disabled) {
+        let disabled: Vec<String> = disabled.iter().map(|n| normalize_plugin_name(n)).collect();
+        let overlap = enabled
+            .iter()
+            .filter(|n| disabled.contains(&normalize_plugin_name(n)));
+        for name in overlap {
+            report.warn(format!(
+                "插件 {name} 同时在 enabled 和 disabled 列表中，不会被加载"
+            ));
+        }
+    }
+
+    if let Some((start, end)) = cfg.api_port_range
+        && (start > end || start == 0)
+    {
+        report.error(format!("[plugin].api_port_range = [{start}, {end}] 无效"));
+    }
+    if cfg.default_interval == Some(0) {
+        report.warn("[plugin].default_interval = 0，按 1 秒处理");
+    }
+
+    for key in cfg.trusted_keys.iter().flatten() {
+        if let Err(e) = parse_verifying_key(key) {
+            report.error(format!("[plugin].trusted_keys 中的公钥 {key} 无效: {e}"));
+        }
+    }
+    if is_prod_mode(&mode) {
+        let verifier = PluginVerifier::from_config(cfg, &plugin_dir);
+        if let Some(reason) = verifier.refusal() {
+            report.error(format!("prod 模式会拒绝加载所有插件: {reason}"));
+        }
+        if let Err(e) = verifier.manifest_len() {
+            report.error(e);
+        }
+    } else if cfg.manifest.is_some() || cfg.trusted_keys.is_some() {
+        report.warn(format!(
+            "mode={mode} 时不校验插件清单，manifest / trusted_keys 不生效"
+        ));
+    }
+}
+
+fn check_storage_section(config: &AppConfig, report: &mut CheckReport) {
+    let Some(storage) = &config.storage else {
+        return;
+    };
+    if storage.queue_capacity == Some(0) {
+        report.error("[storage].queue_capacity 不能为 0");
+    }
+    if let (Some(batch), Some(capacity)) = (storage.batch_size, storage.queue_capacity)
+        && batch > capacity
+    {
+        report.warn(format!(
+            "[storage].batch_size = {batch} 大于 queue_capacity = {capacity}，按 {capacity} 处理"
+        ));
+    }
+    if storage.batch_size == Some(0) {
+        report.warn("[storage].batch_size = 0，按 1 处理");
+    }
+}
+
+fn check_retention_section(config: &AppConfig, report: &mut CheckReport) {
+    let Some(retention) = &config.retention else {
+        return;
+    };
+    match retention.policy() {
+        Ok(policy) if policy.rules.is_empty() => {
+            report.warn("[retention] 没有配置任何保留期限，不会清理数据")
+        }
+        Ok(_) => {}
+        Err(e) => report.error(e),
+    }
+    if retention.batch_size.is_some_and(|n| n <= 0) {
+        report.warn("[retention].batch_size 应大于 0，按默认值 5000 处理");
+    }
+}
+
+/// `[plugins.<name>]` 中由 host 解释的字段
+fn check_plugin_table(name: &str, table: &toml::Value, report: &mut CheckReport) {
+    let Some(table) = table.as_table() else {
+        report.error(format!("[plugins.{name}] 必须是一个表"));
+        return;
+    };
+
+    match table.get("isolation") {
+        None => {}
+        Some(toml::Value::String(s)) if s == "none" || s == "process" => {}
+        Some(other) => report.error(format!(
+            "[plugins.{name}].isolation = {other} 无效，可选 \"none\" | \"process\""
+        )),
+    }
+    match table.get("api_transport") {
+        None => {}
+        Some(toml::Value::String(s)) if s == "tcp" || (s == "unix" && cfg!(unix)) => {}
+        Some(other) => report.error(format!(
+            "[plugins.{name}].api_transport = {other} 无效或当前平台不支持"
+        )),
+    }
+    match table.get("cron") {
+        None => {}
+        Some(toml::Value::String(expr)) => {
+            if let Err(e) = cron::Schedule::from_str(expr) {
+                report.error(format!(
+                    "[plugins.{name}].cron = \"{expr}\" 不是合法的 cron 表达式: {e}"
+                ));
+            }
+        }
+        // 类型不对由下面的整体解析报告
+        Some(_) => {}
+    }
+    match table.get("interval_secs") {
+        None => {}
+        Some(toml::Value::Integer(secs)) if *secs > 0 => {}
+        Some(toml::Value::Integer(0)) => report.warn(format!(
+            "[plugins.{name}].interval_secs = 0，不覆盖插件自己的调度"
+        )),
+        Some(_) => {}
+    }
+    // 和运行时用同一个解析：任何一个调度字段类型不对，cron 和 interval_secs 都不会生效
+    let parsed = serde_json::to_string(table)
+        .map_err(|e| e.to_string())
+        .and_then(|json| parse_schedule_override(&json).map_err(|e| e.to_string()));
+    if let Err(e) = parsed {
+        report.error(format!(
+            "[plugins.{name}] 的调度字段无法解析: {e}，cron / interval_secs 覆盖都不会生效"
+        ));
+    }
+    for key in ["wasm_fuel", "wasm_max_memory_mb", "worker_timeout_secs"] {
+        match table.get(key) {
+            None | Some(toml::Value::Integer(1..)) => {}
+            Some(other) => report.error(format!(
+                "[plugins.{name}].{key} = {other} 必须是正整数"
+            )),
+        }
+    }
+    if table.contains_key("cron") && table.contains_key("interval_secs") {
+        report.warn(format!(
+            "[plugins.{name}] 同时配置了 cron 和 interval_secs，以 cron 为准"
+        ));
+    }
+}
+
+// ============ db status / migrate ============
+
+/// 只连接、不迁移，连不上时打印错误
+async fn open_db() -> Option<Db> {
+    let (db_type, db_url) = database_settings();
+    match Db::open(db_type.as_deref(), Some(&db_url)).await {
+        Ok(db) => Some(db),
+        Err(e) => {
+            println!("错误: 连接数据库 {db_url} 失败: {e}");
+            None
+        }
+    }
+}
+
+/// 打印迁移状态；有未执行的迁移或查询失败时返回 1
+#[tokio::main]
+pub async fn db_status() -> i32 {
+    let Some(db) = open_db().await else {
+        return 1;
+    };
+    let statuses = match db.migration_status().await {
+        Ok(statuses) => statuses,
+        Err(e) => {
+            println!("错误: 读取 schema_migrations 失败: {e}");
+            return 1;
+        }
+    };
+    let mut pending = 0;
+    for s in &statuses {
+        let state = match s.applied_at {
+            Some(_) if s.unknown => "已执行（当前版本不认识这个迁移）".to_string(),
+            Some(at) => format!("已执行于 {}", at.to_rfc3339()),
+            None => {
+                pending += 1;
+                "未执行".to_string()
+            }
+        };
+        println!("{:04}_{}  {state}", s.version, s.name);
+    }
+    db.close().await;
+    if pending > 0 {
+        println!("有 {pending} 个迁移未执行，运行 bot-host db migrate");
+        1
+    } else {
+        0
+    }
+}
+
+/// 执行未执行的迁移；失败时返回 1
+#[tokio::main]
+pub async fn db_migrate() -> i32 {
+    let Some(db) = open_db().await else {
+        return 1;
+    };
+    let code = match db.migrate().await {
+        Ok(applied) if applied.is_empty() => {
+            println!("数据库已是最新");
+            0
+        }
+        Ok(applied) => {
+            for m in applied {
+                println!("已执行 {:04}_{}", m.version, m.name);
+            }
+            0
+        }
+        Err(e) => {
+            println!("错误: 迁移失败: {e}");
+            1
+        }
+    };
+    db.close().await;
+    code
+}
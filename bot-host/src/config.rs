@@ -0,0 +1,616 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        OnceLock, RwLock,
+    },
+    time::Duration,
+};
+
+use serde::{de::DeserializeOwned, Deserialize};
+use storage::{Resolution, RetentionPolicy, RetentionRule, RetentionTarget};
+use tracing::{error, info};
+
+// ============ 配置结构 ============
+
+#[derive(Debug, Deserialize, Default, Clone)]
+pub struct PluginConfig {
+    pub mode: Option<String>,
+    pub dev_dir: Option<String>,
+    pub prod_dir: Option<String>,
+    /// 插件动态库文件名的 glob（如 `lib*_monitor.so`）；不含通配符时按“文件名包含”匹配
+    pub name_pattern: Option<String>,
+    /// 只加载这些插件（按 meta 里的插件名）；不配置表示不限制。
+    ///
+    /// 要读到 meta 必须先 dlopen，所以不在列表里的插件仍会被 dlopen 一次（不调用 plugin_init）
+    pub enabled: Option<Vec<String>>,
+    /// 不加载这些插件，优先于 enabled。
+    ///
+    /// 文件名（去掉 `lib` 前缀和扩展名，`-` 与 `_` 视为相同）对得上的在 dlopen 之前就跳过；
+    /// 对不上的要 dlopen 读过 meta 后按插件名排除（不调用 plugin_init）
+    pub disabled: Option<Vec<String>>,
+    /// prod 模式的插件清单路径，默认是插件目录下的 manifest.toml
+    pub manifest: Option<String>,
+    /// 信任的 ed25519 公钥（hex）；配置后清单里的每个插件都必须带有效签名。
+    /// 不配置时清单必须在插件目录之外，否则 prod 模式拒绝加载所有插件
+    pub trusted_keys: Option<Vec<String>>,
+    /// 分配给插件 HTTP API 的端口范围 `[起, 止]`（含两端）
+    pub api_port_range: Option<(u16, u16)>,
+    /// `api_transport = "unix"` 的插件的 socket 文件目录
+    pub api_socket_dir: Option<String>,
+    pub default_interval: Option<u64>,
+    /// 每次调度额外加上的随机抖动上限（毫秒），避免所有插件同时触发
+    pub jitter_ms: Option<u64>,
+    /// 监听插件目录并热加载变化的动态库；不配置时 dev 模式开启、prod 模式关闭
+    pub watch: Option<bool>,
+}
+
+/// 存储队列满了以后怎么办
+#[derive(Debug, Deserialize, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum OverflowPolicy {
+    /// 丢掉队列里最旧的一条，给新消息腾位置
+    #[default]
+    DropOldest,
+    /// 丢掉新来的消息
+    DropNewest,
+    /// 发送方等到队列有空位；在 current_thread runtime 的线程上最多等 1 秒，之后丢掉新消息
+    Block,
+}
+
+/// `[storage]`：日志 / 指标写入队列
+#[derive(Debug, Deserialize, Default, Clone)]
+pub struct StorageConfig {
+    /// 队列最多缓存的消息数
+    pub queue_capacity: Option<usize>,
+    /// 攒够这么多条就写一次库
+    pub batch_size: Option<usize>,
+    /// 第一条消息进队后最多等这么久就写库（毫秒）
+    pub flush_interval_ms: Option<u64>,
+    pub overflow: Option<OverflowPolicy>,
+    /// 退出时最多等这么久把队列里剩下的消息写完（毫秒）
+    pub drain_timeout_ms: Option<u64>,
+}
+
+/// `[self_metrics]`：bot-host 自身的指标
+#[derive(Debug, Deserialize, Default, Clone)]
+pub struct SelfMetricsConfig {
+    /// 默认开启
+    pub enabled: Option<bool>,
+    /// 上报间隔（秒），默认 10
+    pub interval_secs: Option<u64>,
+}
+
+/// `[rollup]`：指标降采样
+#[derive(Debug, Deserialize, Default, Clone)]
+pub struct RollupConfig {
+    /// 默认开启
+    pub enabled: Option<bool>,
+    /// 多久检查一次有没有新的完整桶（秒），默认 60
+    pub interval_secs: Option<u64>,
+}
+
+/// `[retention]`：按保留期限清理旧数据；期限写成 "90s" / "30m" / "12h" / "7d" / "4w" / "1y"，
+/// 不配置的表不清理
+#[derive(Debug, Deserialize, Default, Clone)]
+pub struct RetentionConfig {
+    /// 多久清理一次（秒），默认 3600
+    pub interval_secs: Option<u64>,
+    /// 每批最多删除的行数，默认 5000
+    pub batch_size: Option<i64>,
+    pub metrics: Option<String>,
+    pub logs: Option<String>,
+    pub alerts: Option<String>,
+    pub rollups_1m: Option<String>,
+    pub rollups_1h: Option<String>,
+    pub rollups_1d: Option<String>,
+    /// `[[retention.rules]]`：按插件 / 指标名单独设置，比表级的设置优先
+    #[serde(default)]
+    pub rules: Vec<RetentionRuleConfig>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct RetentionRuleConfig {
+    /// metrics | logs | alerts | rollups_1m | rollups_1h | rollups_1d
+    pub table: String,
+    pub plugin: Option<String>,
+    /// 指标名（告警是关联的指标名），日志不支持
+    pub name: Option<String>,
+    pub keep: String,
+}
+
+impl RetentionConfig {
+    /// 转成 storage 的清理规则；表名或期限写错时返回错误
+    pub fn policy(&self) -> Result<RetentionPolicy, String> {
+        let mut rules = Vec::new();
+        let tables = [
+            ("metrics", &self.metrics),
+            ("logs", &self.logs),
+            ("alerts", &self.alerts),
+            ("rollups_1m", &self.rollups_1m),
+            ("rollups_1h", &self.rollups_1h),
+            ("rollups_1d", &self.rollups_1d),
+        ];
+        for (table, keep) in tables {
+            if let Some(keep) = keep {
+                rules.push(RetentionRule {
+                    target: retention_target(table)?,
+                    plugin: None,
+                    name: None,
+                    keep: parse_retention(keep).map_err(|e| format!("[retention].{table}: {e}"))?,
+                });
+            }
+        }
+        for rule in &self.rules {
+            let target = retention_target(&rule.table)?;
+            if target == RetentionTarget::Logs && rule.name.is_some() {
+                return Err("[[retention.rules]] 中 table = \"logs\" 的规则不支持 name".to_string());
+            }
+            rules.push(RetentionRule {
+                target,
+                plugin: rule.plugin.clone(),
+                name: rule.name.clone(),
+                keep: parse_retention(&rule.keep)
+                    .map_err(|e| format!("[[retention.rules]] table = \"{}\": {e}", rule.table))?,
+            });
+        }
+        Ok(RetentionPolicy {
+            rules,
+            batch_size: self.batch_size.unwrap_or(0),
+        })
+    }
+}
+
+fn retention_target(table: &str) -> Result<RetentionTarget, String> {
+    Ok(match table {
+        "metrics" => RetentionTarget::Metrics,
+        "logs" => RetentionTarget::Logs,
+        "alerts" => RetentionTarget::Alerts,
+        "rollups_1m" => RetentionTarget::Rollups(Resolution::Minute),
+        "rollups_1h" => RetentionTarget::Rollups(Resolution::Hour),
+        "rollups_1d" => RetentionTarget::Rollups(Resolution::Day),
+        other => {
+            return Err(format!(
+                "未知的表 {other}，可选 metrics | logs | alerts | rollups_1m | rollups_1h | rollups_1d"
+            ))
+        }
+    })
+}
+
+/// "7d" 这样的保留期限；单位 s / m / h / d / w / y（一年按 365 天算）
+fn parse_retention(s: &str) -> Result<chrono::Duration, String> {
+    let s = s.trim();
+    let invalid = || format!("保留期限 \"{s}\" 无效，应该是数字加单位，如 7d、12h");
+    let unit_at = s.find(|c: char| !c.is_ascii_digit()).ok_or_else(invalid)?;
+    let value: i64 = s[..unit_at].parse().map_err(|_| invalid())?;
+    let secs = match &s[unit_at..] {
+        "s" => 1,
+        "m" => 60,
+        "h" => 3600,
+        "d" => 86400,
+        "w" => 7 * 86400,
+        "y" => 365 * 86400,
+        _ => return Err(invalid()),
+    };
+    if value == 0 {
+        return Err(invalid());
+    }
+    value
+        .checked_mul(secs)
+        .and_then(chrono::Duration::try_seconds)
+        .ok_or_else(invalid)
+}
+
+#[derive(Debug, Deserialize, Default)]
+pub struct AppConfig {
+    pub plugin: Option<PluginConfig>,
+    pub storage: Option<StorageConfig>,
+    pub self_metrics: Option<SelfMetricsConfig>,
+    pub rollup: Option<RollupConfig>,
+    pub retention: Option<RetentionConfig>,
+    /// `[plugins.<name>]`：各插件自己的配置，原样转成 JSON 交给插件
+    #[serde(default)]
+    pub plugins: HashMap<String, toml::Value>,
+}
+
+// ============ 配置加载 ============
+
+/// 配置文件路径（相对工作目录）
+pub const CONFIG_PATH: &str = "config.toml";
+
+/// 读取并解析配置文件；文件不存在时返回 Ok(None)
+pub fn read_config(path: &Path) -> Result<Option<AppConfig>, String> {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(format!("读取配置文件 {} 失败: {e}", path.display())),
+    };
+    toml::from_str::<AppConfig>(&content)
+        .map(Some)
+        .map_err(|e| format!("解析配置文件 {} 失败: {e}", path.display()))
+}
+
+pub fn load_config() -> AppConfig {
+    let path = PathBuf::from(CONFIG_PATH);
+    match read_config(&path) {
+        Ok(Some(cfg)) => {
+            info!("已加载配置文件: {}", path.display());
+            cfg
+        }
+        Ok(None) => {
+            info!("未找到配置文件 {}，使用默认配置", path.display());
+            AppConfig::default()
+        }
+        Err(e) => {
+            error!("{e}，使用默认配置");
+            AppConfig::default()
+        }
+    }
+}
+
+/// 运行模式：`MONITOR_AI_PLUGIN_MODE` > `[plugin].mode` > dev
+pub fn plugin_mode(cfg: &PluginConfig) -> String {
+    std::env::var("MONITOR_AI_PLUGIN_MODE")
+        .ok()
+        .or(cfg.mode.clone())
+        .unwrap_or_else(|| "dev".to_string())
+}
+
+/// prod 模式要校验插件清单
+pub fn is_prod_mode(mode: &str) -> bool {
+    matches!(mode, "prod" | "release")
+}
+
+/// 数据库类型和连接串：`DB_TYPE` / `MONITOR_AI_DB_URL`，默认本地 SQLite
+pub fn database_settings() -> (Option<String>, String) {
+    let db_type = std::env::var("DB_TYPE").ok();
+    let db_url = std::env::var("MONITOR_AI_DB_URL")
+        .unwrap_or_else(|_| "sqlite://database/monitor_ai.db".to_string());
+    (db_type, db_url)
+}
+
+pub fn resolve_plugin_dir(mode: &str, cfg: &PluginConfig) -> PathBuf {
+    match mode {
+        mode if is_prod_mode(mode) => PathBuf::from(
+            cfg.prod_dir
+                .clone()
+                .unwrap_or_else(|| "plugins-bin".to_string()),
+        ),
+        _ => PathBuf::from(
+            cfg.dev_dir
+                .clone()
+                .unwrap_or_else(|| "target/debug".to_string()),
+        ),
+    }
+}
+
+pub fn plugin_ext() -> &'static str {
+    if cfg!(target_os = "windows") {
+        "dll"
+    } else if cfg!(target_os = "macos") {
+        "dylib"
+    } else {
+        "so"
+    }
+}
+
+// ============ 插件配置 ============
+
+/// 各插件的配置：插件名 -> `[plugins.<name>]` 转成的 JSON（SIGHUP 时整体替换）
+static PLUGIN_CONFIGS: OnceLock<RwLock<HashMap<String, String>>> = OnceLock::new();
+
+/// 每次替换插件配置时加一，调度任务据此判断是否需要重新读取调度方式
+static CONFIG_GENERATION: AtomicU64 = AtomicU64::new(0);
+
+pub fn config_generation() -> u64 {
+    CONFIG_GENERATION.load(Ordering::Acquire)
+}
+
+/// 把 `[plugins.<name>]` 转成 JSON 放进全局配置表
+pub fn set_plugin_configs(config: &AppConfig) {
+    let mut map = HashMap::new();
+    for (name, value) in &config.plugins {
+        match serde_json::to_string(value) {
+            Ok(json) => {
+                map.insert(name.clone(), json);
+            }
+            Err(e) => error!("插件 {name} 的配置无法转成 JSON: {e}"),
+        }
+    }
+
+    let lock = PLUGIN_CONFIGS.get_or_init(Default::default);
+    *lock.write().unwrap_or_else(|e| e.into_inner()) = map;
+    CONFIG_GENERATION.fetch_add(1, Ordering::AcqRel);
+}
+
+/// 只替换一个插件的配置（worker 子进程收到父进程推送的新配置时用）
+pub fn set_plugin_config(plugin_name: &str, json: String) {
+    let lock = PLUGIN_CONFIGS.get_or_init(Default::default);
+    lock.write()
+        .unwrap_or_else(|e| e.into_inner())
+        .insert(plugin_name.to_string(), json);
+    CONFIG_GENERATION.fetch_add(1, Ordering::AcqRel);
+}
+
+/// 读取某个插件的配置 JSON，没有配置时返回 `{}`
+pub fn plugin_config_json(plugin_name: &str) -> String {
+    PLUGIN_CONFIGS
+        .get()
+        .and_then(|lock| {
+            lock.read()
+                .unwrap_or_else(|e| e.into_inner())
+                .get(plugin_name)
+                .cloned()
+        })
+        .unwrap_or_else(|| "{}".to_string())
+}
+
+/// 原生插件在哪里执行
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Isolation {
+    /// 在 bot-host 进程里直接调用
+    None,
+    /// 在 worker 子进程里执行
+    Process,
+}
+
+/// 插件的 `isolation = "none" | "process"`，只在加载时读取；没有配置时返回 None，
+/// 由 host 按插件是否声明了 `CAP_PANIC_SAFE` 决定
+pub fn plugin_isolation(plugin_name: &str) -> Option<Isolation> {
+    #[derive(Deserialize, Default)]
+    struct IsolationConfig {
+        isolation: Option<String>,
+    }
+
+    let cfg: IsolationConfig = plugin_fields(plugin_name, "isolation");
+    match cfg.isolation.as_deref() {
+        None => None,
+        Some("none") => Some(Isolation::None),
+        Some("process") => Some(Isolation::Process),
+        Some(other) => {
+            error!("插件 {plugin_name} 的 isolation = \"{other}\" 无效，可选 none | process");
+            None
+        }
+    }
+}
+
+/// 插件 HTTP API 用什么方式监听
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ApiTransport {
+    Tcp,
+    Unix,
+}
+
+/// 插件 API 的监听方式（`api_transport = "tcp" | "unix"`），只在加载时读取
+pub fn plugin_api_transport(plugin_name: &str) -> ApiTransport {
+    #[derive(Deserialize, Default)]
+    struct Transport {
+        api_transport: Option<String>,
+    }
+
+    let cfg: Transport = plugin_fields(plugin_name, "api_transport");
+    match cfg.api_transport.as_deref() {
+        None | Some("tcp") => ApiTransport::Tcp,
+        Some("unix") if cfg!(unix) => ApiTransport::Unix,
+        Some(other) => {
+            error!("插件 {plugin_name} 的 api_transport = \"{other}\" 无效或当前平台不支持，使用 tcp");
+            ApiTransport::Tcp
+        }
+    }
+}
+
+/// worker 子进程里 plugin_init 和每次执行的期限（`worker_timeout_secs`），没配置时用默认值
+pub fn plugin_worker_timeout(plugin_name: &str) -> Option<Duration> {
+    #[derive(Deserialize, Default)]
+    struct WorkerTimeout {
+        worker_timeout_secs: Option<u64>,
+    }
+
+    let cfg: WorkerTimeout = plugin_fields(plugin_name, "worker_timeout_secs");
+    positive(plugin_name, "worker_timeout_secs", cfg.worker_timeout_secs).map(Duration::from_secs)
+}
+
+/// `[plugins.<name>]` 中由 host 解释的调度字段
+#[derive(Debug, Deserialize, Default)]
+pub struct ScheduleOverride {
+    /// cron 表达式（`秒 分 时 日 月 周 [年]`，UTC）
+    pub cron: Option<String>,
+    /// 固定间隔（秒）
+    pub interval_secs: Option<u64>,
+}
+
+/// 从 `[plugins.<name>]` 转成的 JSON 里取出调度字段；`config check` 也用它检查
+pub fn parse_schedule_override(json: &str) -> Result<ScheduleOverride, serde_json::Error> {
+    serde_json::from_str(json)
+}
+
+/// 读取某个插件在配置里覆盖的调度方式；调度开始时和每次配置更新后调用，SIGHUP 后立即生效。
+///
+/// 字段类型不对时整个覆盖都不生效，记一条错误日志，不会悄悄忽略
+pub fn plugin_schedule_override(plugin_name: &str) -> ScheduleOverride {
+    match parse_schedule_override(&plugin_config_json(plugin_name)) {
+        Ok(overrides) => overrides,
+        Err(e) => {
+            error!("插件 {plugin_name} 的调度配置无效: {e}，忽略 cron / interval_secs");
+            ScheduleOverride::default()
+        }
+    }
+}
+
+/// `[plugins.<name>]` 中 WASM 插件的资源上限
+#[cfg(feature = "wasm")]
+#[derive(Debug, Deserialize, Default)]
+pub struct WasmLimits {
+    /// 每次调用（plugin_init / run_with_ctx / plugin_shutdown）可用的 fuel
+    pub wasm_fuel: Option<u64>,
+    /// 线性内存上限（MB）
+    pub wasm_max_memory_mb: Option<u64>,
+}
+
+/// WASM 插件的资源上限，只在加载时读取；没有配置或配置无效的项为 None
+#[cfg(feature = "wasm")]
+pub fn plugin_wasm_limits(plugin_name: &str) -> WasmLimits {
+    let cfg: WasmLimits = plugin_fields(plugin_name, "wasm_fuel / wasm_max_memory_mb");
+    WasmLimits {
+        wasm_fuel: positive(plugin_name, "wasm_fuel", cfg.wasm_fuel),
+        wasm_max_memory_mb: positive(plugin_name, "wasm_max_memory_mb", cfg.wasm_max_memory_mb),
+    }
+}
+
+/// 读出 `[plugins.<name>]` 里由 host 解释的字段。
+///
+/// 和 [`plugin_schedule_override`] 一样，类型写错（比如 `isolation = true`、`wasm_fuel = "1e9"`）
+/// 时记一条错误日志再用默认值，不悄悄忽略；`bot-host config check` 会提前报告同样的问题。
+fn plugin_fields<T: DeserializeOwned + Default>(plugin_name: &str, fields: &str) -> T {
+    match serde_json::from_str(&plugin_config_json(plugin_name)) {
+        Ok(cfg) => cfg,
+        Err(e) => {
+            error!("插件 {plugin_name} 的 {fields} 配置无效: {e}，使用默认值");
+            T::default()
+        }
+    }
+}
+
+/// 0 表示配置写错了，报告后按没有配置处理
+fn positive(plugin_name: &str, field: &str, value: Option<u64>) -> Option<u64> {
+    if value == Some(0) {
+        error!("插件 {plugin_name} 的 {field} 必须大于 0，使用默认值");
+        return None;
+    }
+    value
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 类型写错或写成 0 的字段按没有配置处理，其他字段不受影响
+    #[test]
+    fn mistyped_plugin_fields_fall_back_to_defaults() {
+        set_plugin_config(
+            "fields-ok",
+            r#"{"isolation": "process", "api_transport": "tcp", "worker_timeout_secs": 5}"#
+                .to_string(),
+        );
+        assert_eq!(plugin_isolation("fields-ok"), Some(Isolation::Process));
+        assert_eq!(plugin_api_transport("fields-ok"), ApiTransport::Tcp);
+        assert_eq!(
+            plugin_worker_timeout("fields-ok"),
+            Some(Duration::from_secs(5))
+        );
+
+        set_plugin_config(
+            "fields-bad",
+            r#"{"isolation": true, "api_transport": 1, "worker_timeout_secs": 0, "interval_secs": 30}"#
+                .to_string(),
+        );
+        assert_eq!(plugin_isolation("fields-bad"), None);
+        assert_eq!(plugin_api_transport("fields-bad"), ApiTransport::Tcp);
+        assert_eq!(plugin_worker_timeout("fields-bad"), None);
+        assert_eq!(
+            plugin_schedule_override("fields-bad").interval_secs,
+            Some(30)
+        );
+
+        set_plugin_config(
+            "fields-bad-timeout",
+            r#"{"worker_timeout_secs": "5"}"#.to_string(),
+        );
+        assert_eq!(plugin_worker_timeout("fields-bad-timeout"), None);
+    }
+
+    #[cfg(feature = "wasm")]
+    #[test]
+    fn mistyped_wasm_limits_fall_back_to_defaults() {
+        set_plugin_config(
+            "wasm-ok",
+            r#"{"wasm_fuel": 1000, "wasm_max_memory_mb": 0}"#.to_string(),
+        );
+        let limits = plugin_wasm_limits("wasm-ok");
+        assert_eq!(limits.wasm_fuel, Some(1000));
+        assert_eq!(limits.wasm_max_memory_mb, None);
+
+        set_plugin_config(
+            "wasm-bad",
+            r#"{"wasm_fuel": "1e9", "wasm_max_memory_mb": 16}"#.to_string(),
+        );
+        let limits = plugin_wasm_limits("wasm-bad");
+        assert_eq!(limits.wasm_fuel, None);
+        assert_eq!(limits.wasm_max_memory_mb, None);
+    }
+
+    #[test]
+    fn retention_accepts_every_unit() {
+        for (text, secs) in [
+            ("90s", 90),
+            ("30m", 30 * 60),
+            ("12h", 12 * 3600),
+            (" 7d ", 7 * 86400),
+            ("4w", 4 * 7 * 86400),
+            ("1y", 365 * 86400),
+        ] {
+            assert_eq!(
+                parse_retention(text),
+                Ok(chrono::Duration::seconds(secs)),
+                "{text:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn malformed_retention_is_rejected() {
+        for bad in [
+            "",
+            "7",
+            "d",
+            "0d",
+            "-7d",
+            "+7d",
+            "7 d",
+            "7D",
+            "7days",
+            "1.5h",
+            "99999999999999999999d", // 超出 i64
+            "9223372036854775807y",  // 换算成秒时溢出
+        ] {
+            let err = parse_retention(bad).expect_err(bad);
+            assert!(err.contains("无效"), "{bad:?}: {err}");
+        }
+    }
+
+    #[test]
+    fn retention_policy_reports_the_offending_setting() {
+        let config: RetentionConfig = toml::from_str(
+            r#"
+            metrics = "7d"
+            [[rules]]
+            table = "metrics"
+            plugin = "cpu"
+            keep = "30d"
+            "#,
+        )
+        .unwrap();
+        let policy = config.policy().unwrap();
+        assert_eq!(policy.rules.len(), 2);
+        assert_eq!(policy.rules[1].plugin.as_deref(), Some("cpu"));
+        assert_eq!(policy.rules[1].keep, chrono::Duration::days(30));
+
+        let err = |toml: &str| {
+            toml::from_str::<RetentionConfig>(toml)
+                .unwrap()
+                .policy()
+                .unwrap_err()
+        };
+        assert!(err(r#"logs = "0d""#).starts_with("[retention].logs:"));
+        assert!(
+            err(r#"rules = [{ table = "alerts", keep = "1x" }]"#)
+                .starts_with("[[retention.rules]] table = \"alerts\":")
+        );
+        assert!(err(r#"rules = [{ table = "events", keep = "1d" }]"#).contains("未知的表 events"));
+        assert!(
+            err(r#"rules = [{ table = "logs", name = "x", keep = "1d" }]"#).contains("不支持 name")
+        );
+    }
+}
@@ -0,0 +1,229 @@
+//! 进程内的发布 / 订阅总线（`CAP_EVENT_BUS`）
+//!
+//! 发布只是把事件放进有界队列，由一个专门的分发线程按订阅顺序调用回调，
+//! 发布方不会被订阅方拖慢。回调在订阅方的插件作用域里执行，
+//! 回调里打的日志、上报的指标都记在订阅方名下。
+//!
+//! topic 精确匹配；订阅的 topic 以 `*` 结尾时按前缀匹配（`timer.*`、`*`）。
+
+use std::{
+    ffi::CString,
+    os::raw::c_void,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        mpsc::{self, SyncSender, TrySendError},
+        Arc, Mutex, OnceLock,
+    },
+    thread,
+};
+
+use core_types::AlertEvent;
+use plugin_api::{EventCallback, TOPIC_ALERT};
+use tracing::{error, warn};
+
+use crate::bridge::with_plugin_scope;
+
+/// 等待分发的事件上限，满了以后新事件直接丢弃
+const QUEUE_CAPACITY: usize = 1024;
+
+/// host 自己发布事件时用的发布方名字
+const HOST_PUBLISHER: &str = "bot-host";
+
+/// 一条待分发的事件
+struct BusEvent {
+    publisher: String,
+    topic: String,
+    payload: String,
+}
+
+struct Subscription {
+    id: u64,
+    plugin: String,
+    pattern: String,
+    callback: EventCallback,
+    user_data: UserData,
+    /// 取消订阅后置 false，已经取到快照里的订阅也不会再被调用
+    active: AtomicBool,
+}
+
+impl Subscription {
+    fn matches(&self, topic: &str) -> bool {
+        match self.pattern.strip_suffix('*') {
+            Some(prefix) => topic.starts_with(prefix),
+            None => self.pattern == topic,
+        }
+    }
+}
+
+/// 插件传进来的 user_data，host 只负责原样交还给回调
+#[derive(Clone, Copy)]
+struct UserData(*mut c_void);
+
+// 指针由插件自己保证在订阅期间有效，host 不会解引用它
+unsafe impl Send for UserData {}
+unsafe impl Sync for UserData {}
+
+struct Bus {
+    subscriptions: Mutex<Vec<Arc<Subscription>>>,
+    sender: SyncSender<BusEvent>,
+    next_id: AtomicU64,
+    /// 分发线程执行回调期间持有；卸载插件时拿一次，等正在跑的回调返回
+    dispatching: Mutex<()>,
+}
+
+static BUS: OnceLock<Bus> = OnceLock::new();
+
+fn bus() -> &'static Bus {
+    BUS.get_or_init(|| {
+        let (sender, receiver) = mpsc::sync_channel::<BusEvent>(QUEUE_CAPACITY);
+        thread::Builder::new()
+            .name("event-bus".to_string())
+            .spawn(move || {
+                for event in receiver {
+                    dispatch(&event);
+                }
+            })
+            .expect("无法启动事件分发线程");
+        Bus {
+            subscriptions: Mutex::new(Vec::new()),
+            sender,
+            next_id: AtomicU64::new(1),
+            dispatching: Mutex::new(()),
+        }
+    })
+}
+
+// ============ 发布 / 订阅 ============
+
+/// 发布一条事件；队列满时丢弃并记一条警告
+pub fn publish(publisher: &str, topic: &str, payload: String) {
+    let event = BusEvent {
+        publisher: publisher.to_string(),
+        topic: topic.to_string(),
+        payload,
+    };
+    match bus().sender.try_send(event) {
+        Ok(()) => {}
+        Err(TrySendError::Full(event)) => {
+            warn!(
+                "事件队列已满，丢弃 {} 发布的 {} 事件",
+                event.publisher, event.topic
+            );
+        }
+        Err(TrySendError::Disconnected(_)) => {
+            error!("事件分发线程已退出，无法发布事件");
+        }
+    }
+}
+
+/// 插件上报的告警同时发布到 `alert` topic
+pub fn publish_alert(alert: &AlertEvent) {
+    match serde_json::to_string(alert) {
+        Ok(payload) => publish(HOST_PUBLISHER, TOPIC_ALERT, payload),
+        Err(e) => warn!("序列化告警事件失败: {e}"),
+    }
+}
+
+pub fn subscribe(
+    plugin: &str,
+    pattern: &str,
+    callback: EventCallback,
+    user_data: *mut c_void,
+) -> u64 {
+    let bus = bus();
+    let id = bus.next_id.fetch_add(1, Ordering::Relaxed);
+    bus.subscriptions
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .push(Arc::new(Subscription {
+            id,
+            plugin: plugin.to_string(),
+            pattern: pattern.to_string(),
+            callback,
+            user_data: UserData(user_data),
+            active: AtomicBool::new(true),
+        }));
+    id
+}
+
+pub fn unsubscribe(id: u64) {
+    let Some(bus) = BUS.get() else {
+        return;
+    };
+    bus.subscriptions
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .retain(|sub| {
+            if sub.id == id {
+                sub.active.store(false, Ordering::Release);
+                false
+            } else {
+                true
+            }
+        });
+}
+
+/// 移除插件的全部订阅，并等正在执行的回调返回；在 plugin_shutdown / 卸载动态库之前调用
+pub fn unsubscribe_plugin(plugin: &str) {
+    let Some(bus) = BUS.get() else {
+        return;
+    };
+    let mut removed = 0;
+    bus.subscriptions
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .retain(|sub| {
+            if sub.plugin == plugin {
+                sub.active.store(false, Ordering::Release);
+                removed += 1;
+                false
+            } else {
+                true
+            }
+        });
+    if removed > 0 {
+        drop(bus.dispatching.lock().unwrap_or_else(|e| e.into_inner()));
+    }
+}
+
+// ============ 分发 ============
+
+fn dispatch(event: &BusEvent) {
+    let Some(bus) = BUS.get() else {
+        return;
+    };
+    // 先拿快照再调用，回调里可以继续订阅 / 发布
+    let matched: Vec<Arc<Subscription>> = bus
+        .subscriptions
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .iter()
+        .filter(|sub| sub.matches(&event.topic))
+        .cloned()
+        .collect();
+    if matched.is_empty() {
+        return;
+    }
+
+    let (Ok(topic), Ok(payload)) = (
+        CString::new(event.topic.as_str()),
+        CString::new(event.payload.as_str()),
+    ) else {
+        warn!(
+            "{} 发布的 {} 事件包含 NUL 字符，已丢弃",
+            event.publisher, event.topic
+        );
+        return;
+    };
+
+    for sub in matched {
+        let _guard = bus.dispatching.lock().unwrap_or_else(|e| e.into_inner());
+        if !sub.active.load(Ordering::Acquire) {
+            continue;
+        }
+        // 回调是插件的 extern "C" 函数，里面的 panic 在这里兜不住，要插件自己用 catch_panic_no_ctx
+        with_plugin_scope(&sub.plugin, || {
+            (sub.callback)(topic.as_ptr(), payload.as_ptr(), sub.user_data.0)
+        });
+    }
+}
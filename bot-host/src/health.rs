@@ -0,0 +1,81 @@
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use chrono::{DateTime, Utc};
+
+/// 第一次失败后的退避时间，之后每次翻倍
+const BASE_BACKOFF: Duration = Duration::from_secs(10);
+/// 退避时间上限
+const MAX_BACKOFF: Duration = Duration::from_secs(600);
+
+/// 插件健康状态：连续失败后进入退避，期间调度器跳过该插件
+#[derive(Debug, Default)]
+pub struct PluginHealth {
+    state: Mutex<HealthState>,
+}
+
+#[derive(Debug, Default)]
+struct HealthState {
+    consecutive_failures: u32,
+    /// 退避结束的时间点；None 表示健康
+    retry_at: Option<Instant>,
+    last_run_at: Option<DateTime<Utc>>,
+    /// 最近一次失败的原因，恢复后仍保留
+    last_error: Option<String>,
+}
+
+/// 写入 plugins 表用的健康状态快照
+#[derive(Debug, Clone, Default)]
+pub struct HealthSnapshot {
+    pub consecutive_failures: u32,
+    pub last_run_at: Option<DateTime<Utc>>,
+    pub last_error: Option<String>,
+}
+
+impl PluginHealth {
+    fn lock(&self) -> std::sync::MutexGuard<'_, HealthState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// 还在退避期内时返回剩余时间
+    pub fn backoff_remaining(&self) -> Option<Duration> {
+        let retry_at = self.lock().retry_at?;
+        let remaining = retry_at.saturating_duration_since(Instant::now());
+        (!remaining.is_zero()).then_some(remaining)
+    }
+
+    /// 记一次成功，返回之前是否处于失败状态（用于打印“已恢复”）
+    pub fn record_success(&self) -> bool {
+        let mut state = self.lock();
+        let recovered = state.consecutive_failures > 0;
+        state.consecutive_failures = 0;
+        state.retry_at = None;
+        state.last_run_at = Some(Utc::now());
+        recovered
+    }
+
+    /// 记一次失败，返回 (连续失败次数, 本次退避时间)
+    pub fn record_failure(&self, reason: &str) -> (u32, Duration) {
+        let mut state = self.lock();
+        state.consecutive_failures = state.consecutive_failures.saturating_add(1);
+        state.last_run_at = Some(Utc::now());
+        state.last_error = Some(reason.to_string());
+
+        let exponent = (state.consecutive_failures - 1).min(16);
+        let backoff = BASE_BACKOFF.saturating_mul(1 << exponent).min(MAX_BACKOFF);
+        state.retry_at = Some(Instant::now() + backoff);
+
+        (state.consecutive_failures, backoff)
+    }
+
+    pub fn snapshot(&self) -> HealthSnapshot {
+        let state = self.lock();
+        HealthSnapshot {
+            consecutive_failures: state.consecutive_failures,
+            last_run_at: state.last_run_at,
+            last_error: state.last_error.clone(),
+        }
+    }
+}
@@ -0,0 +1,167 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+
+use core_types::PluginStatus;
+use notify::{RecursiveMode, Watcher};
+use storage::Db;
+use tokio::{sync::mpsc, task, task::JoinHandle};
+use tracing::{error, info, warn};
+
+use crate::registry::{
+    is_plugin_file, register_plugin_api, scan_plugin_dir, PluginFilter, PluginRegistry,
+};
+use crate::scheduler::PluginSchedulers;
+
+/// 最后一个文件事件之后再等这么久才处理，避免 cargo 写到一半就去加载
+const DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// 用修改时间 + 大小判断动态库有没有变化
+#[derive(Debug, PartialEq, Eq)]
+struct FileStamp {
+    modified: Option<SystemTime>,
+    len: u64,
+}
+
+/// 只记录匹配 name_pattern 的候选文件；跳过原因启动时已经打印过，这里不重复
+fn snapshot(dir: &Path, filter: &PluginFilter) -> HashMap<PathBuf, FileStamp> {
+    scan_plugin_dir(dir, filter)
+        .0
+        .into_iter()
+        .filter_map(|path| {
+            let meta = fs::metadata(&path).ok()?;
+            let stamp = FileStamp {
+                modified: meta.modified().ok(),
+                len: meta.len(),
+            };
+            Some((path, stamp))
+        })
+        .collect()
+}
+
+/// 监听插件目录：新增的库加载，变化的库先卸载再加载，删除的库卸载；其他插件不受影响
+pub fn spawn_plugin_watcher(
+    dir: PathBuf,
+    ext: &'static str,
+    registry: Arc<PluginRegistry>,
+    schedulers: Arc<PluginSchedulers>,
+    db: Db,
+) -> JoinHandle<()> {
+    task::spawn(async move {
+        let (tx, mut rx) = mpsc::unbounded_channel::<()>();
+
+        let mut watcher = match notify::recommended_watcher(move |res: notify::Result<notify::Event>| {
+            let Ok(event) = res else {
+                return;
+            };
+            let relevant = event.paths.iter().any(|p| is_plugin_file(p, ext));
+            if relevant {
+                let _ = tx.send(());
+            }
+        }) {
+            Ok(w) => w,
+            Err(e) => {
+                error!("创建插件目录监听失败: {e}");
+                return;
+            }
+        };
+        if let Err(e) = watcher.watch(&dir, RecursiveMode::NonRecursive) {
+            error!("监听插件目录 {} 失败: {e}", dir.display());
+            return;
+        }
+        info!("已开启插件热加载，监听目录: {}", dir.display());
+
+        let mut known = snapshot(&dir, registry.filter());
+
+        while rx.recv().await.is_some() {
+            // 一段时间内没有新事件，说明文件已经写完
+            loop {
+                match tokio::time::timeout(DEBOUNCE, rx.recv()).await {
+                    Ok(Some(())) => continue,
+                    Ok(None) => return,
+                    Err(_) => break,
+                }
+            }
+
+            let current = snapshot(&dir, registry.filter());
+
+            for path in known.keys().filter(|p| !current.contains_key(*p)) {
+                info!("插件动态库已删除: {}", path.display());
+                unload_plugin(path, &registry, &schedulers, &db).await;
+            }
+
+            for (path, stamp) in &current {
+                match known.get(path) {
+                    None => {
+                        info!("发现新的插件动态库: {}", path.display());
+                        load_plugin(path, &registry, &schedulers, &db).await;
+                    }
+                    Some(old) if old != stamp => {
+                        info!("插件动态库已更新，重新加载: {}", path.display());
+                        unload_plugin(path, &registry, &schedulers, &db).await;
+                        load_plugin(path, &registry, &schedulers, &db).await;
+                    }
+                    Some(_) => {}
+                }
+            }
+
+            known = current;
+        }
+    })
+}
+
+async fn load_plugin(
+    path: &Path,
+    registry: &Arc<PluginRegistry>,
+    schedulers: &PluginSchedulers,
+    db: &Db,
+) {
+    let registry = registry.clone();
+    let lib_path = path.to_path_buf();
+    // dlopen + plugin_init 都是同步调用，不占用 tokio worker
+    let loaded = task::spawn_blocking(move || registry.load_one(&lib_path)).await;
+
+    match loaded {
+        Ok(Some(plugin)) => {
+            register_plugin_api(&plugin, db).await;
+            schedulers.start(&plugin);
+            info!("插件 {} 已加载 (version={})", plugin.name, plugin.version);
+        }
+        // 拒绝 / 跳过的原因已经在 load_one 里记录过了
+        Ok(None) => {}
+        Err(e) => error!("加载插件 {} 时任务异常: {e}", path.display()),
+    }
+}
+
+async fn unload_plugin(
+    path: &Path,
+    registry: &PluginRegistry,
+    schedulers: &PluginSchedulers,
+    db: &Db,
+) {
+    // 先停调度并等当前这一轮执行完，再 shutdown
+    schedulers.stop(path).await;
+
+    let Some(plugin) = registry.remove(path) else {
+        return;
+    };
+    let name = plugin.name.clone();
+    let had_api = plugin.api_base_url().is_some();
+    plugin.report_status(Some(PluginStatus::Unloaded));
+
+    // plugin_shutdown 可能要 join 后台线程；Arc 在这里释放，库随之卸载
+    if let Err(e) = task::spawn_blocking(move || plugin.shutdown()).await {
+        warn!("插件 {name} shutdown 时任务异常: {e}");
+    }
+
+    if had_api
+        && let Err(e) = db.delete_plugin_api(&name).await
+    {
+        error!("删除插件 API 失败: plugin={name}, err={e}");
+    }
+    info!("插件 {name} 已卸载");
+}
@@ -1,110 +1,108 @@
-use std::{
-    env,
-    ffi::CStr,
-    fs,
-    os::raw::c_char,
-    path::{Path, PathBuf},
-    sync::OnceLock,
-    time::Duration,
-};
-
-use chrono::{DateTime, TimeZone, Utc};
-use core_types::{LogEvent, LogLevel as HostLogLevel, Metric};
+mod api_listen;
+mod bridge;
+mod cli;
+mod config;
+mod event_bus;
+mod health;
+mod hot_reload;
+mod maintenance;
+mod manifest;
+mod registry;
+mod scheduler;
+mod self_metrics;
+mod storage_writer;
+mod wasm;
+mod worker;
+
+use std::{env, fs, path::PathBuf, sync::Arc, time::Duration};
+
+use clap::Parser;
+use core_types::PluginStatus;
 use dotenv::dotenv;
-use libloading::{Library, Symbol};
-use plugin_api::{
-    LogLevel as PluginLogLevel, MetricSample, PluginContext, PluginMeta, PluginMetaFunc,
-    PluginRunFunc, PluginRunWithContextFunc,
-};
-use serde::Deserialize;
-use tokio::sync::mpsc;
 use tokio::task;
 use tracing::{error, info};
 use tracing_subscriber::EnvFilter;
-use plugin_api::{PluginApiInfoFunc, PluginApiInfo};
-use std::collections::HashSet;
 
 use storage::Db;
 
-// ⭐ 新增：线程本地存储当前正在执行的插件名
-use std::cell::RefCell;
-use std::thread_local;
-
-// ============ 全局异步写入通道 ============
-
-enum StorageMsg {
-    Log(LogEvent),
-    Metric(Metric),
-}
-
-static GLOBAL_SENDER: OnceLock<mpsc::UnboundedSender<StorageMsg>> = OnceLock::new();
-
-
-// ⭐ 新增：当前正在执行的插件名称
-thread_local! {
-    static CURRENT_PLUGIN_NAME: RefCell<Option<String>> = RefCell::new(None);
-}
+use crate::bridge::set_query_backend;
+use crate::cli::{Cli, Command, ConfigCommand, DbCommand, PluginsCommand};
+use crate::config::{
+    database_settings, is_prod_mode, load_config, plugin_config_json, plugin_ext, plugin_mode,
+    resolve_plugin_dir, set_plugin_configs,
+};
+use crate::hot_reload::spawn_plugin_watcher;
+use crate::manifest::PluginVerifier;
+use crate::registry::{
+    discover_plugins, register_plugin_api, PluginFilter, PluginRegistry, WASM_EXT,
+};
+use crate::scheduler::PluginSchedulers;
 
-// ============ 配置结构 ============
+// ============ 入口 ============
 
-#[derive(Debug, Deserialize, Default, Clone)]
-struct PluginConfig {
-    mode: Option<String>,
-    dev_dir: Option<String>,
-    prod_dir: Option<String>,
-    name_pattern: Option<String>,
-    default_interval: Option<u64>,
-}
+fn main() {
+    dotenv().ok();
+    init_tracing();
 
-#[derive(Debug, Deserialize, Default)]
-struct AppConfig {
-    plugin: Option<PluginConfig>,
+    let code = match Cli::parse().command.unwrap_or(Command::Run) {
+        Command::Run => {
+            run_host();
+            0
+        }
+        Command::Plugins { command } => match command {
+            PluginsCommand::List => cli::list_plugins(),
+            PluginsCommand::Run { name, once } => cli::run_plugin(&name, once),
+        },
+        Command::Config { command } => match command {
+            ConfigCommand::Check => cli::check_config(),
+        },
+        Command::Db { command } => match command {
+            DbCommand::Status => cli::db_status(),
+            DbCommand::Migrate => cli::db_migrate(),
+        },
+        // 子进程隔离：bot-host plugin-worker <动态库路径>
+        Command::PluginWorker { path } => worker::run_worker(&path),
+    };
+    std::process::exit(code);
 }
 
-// ============ 入口 ============
-
 #[tokio::main]
-async fn main() {
-    dotenv().ok();
-    init_tracing();
+async fn run_host() {
 
     info!("=== 监控AI机器人 bot-host 启动 ===");
 
     let config = load_config();
+    set_plugin_configs(&config);
     let plugin_cfg = config.plugin.clone().unwrap_or_default();
-    let plugin_api_registered = std::sync::Arc::new(tokio::sync::Mutex::new(HashSet::<String>::new()));
 
-
-    let mode = env::var("MONITOR_AI_PLUGIN_MODE")
-        .ok()
-        .or(plugin_cfg.mode.clone())
-        .unwrap_or_else(|| "dev".to_string());
+    let mode = plugin_mode(&plugin_cfg);
 
     let plugin_dir = resolve_plugin_dir(&mode, &plugin_cfg);
     let plugin_ext = plugin_ext();
-    let name_pattern = plugin_cfg
-        .name_pattern
-        .clone()
-        .unwrap_or_else(|| "_monitor".to_string());
-    let default_interval = plugin_cfg.default_interval.unwrap_or(5);
+    let filter = PluginFilter::from_config(&plugin_cfg, plugin_ext);
+    let default_interval = Duration::from_secs(plugin_cfg.default_interval.unwrap_or(5).max(1));
+    let max_jitter = Duration::from_millis(plugin_cfg.jitter_ms.unwrap_or(500));
+    // 开发模式默认开启热加载
+    let watch_plugins = plugin_cfg.watch.unwrap_or(mode == "dev");
 
     info!(
-        "运行模式: {mode}, 插件目录: {}, 扩展名: {}, 名称包含: \"{}\"",
+        "运行模式: {mode}, 插件目录: {}, 扩展名: {} / {WASM_EXT}, 文件名匹配: \"{}\"",
         plugin_dir.display(),
         plugin_ext,
-        name_pattern
+        filter.pattern()
     );
 
     // 初始化数据库
-    let db_type = std::env::var("DB_TYPE").ok();
-    let db_url = std::env::var("MONITOR_AI_DB_URL")
-        .unwrap_or_else(|_| "sqlite://database/monitor_ai.db".to_string());
+    let (db_type, db_url) = database_settings();
 
     info!("准备连接数据库: {db_url}");
 
-    let db = Db::connect(db_type.as_deref(), Some(&db_url))
+    let (db, applied) = Db::connect(db_type.as_deref(), Some(&db_url))
         .await
         .expect("连接数据库失败");
+    for m in applied {
+        info!("已执行数据库迁移 {:04}_{}", m.version, m.name);
+    }
 
         
     // ⭐ 新增这一行，把 Option<String> 映射为 &str
@@ -113,30 +111,15 @@ async fn main() {
     info!("bot-host 已连接 {db_kind} 数据库: {db_url}");
 
 
-    // 初始化全局 sender + 异步存储任务
-    let (tx, mut rx) = mpsc::unbounded_channel::<StorageMsg>();
-    GLOBAL_SENDER.set(tx).expect("GLOBAL_SENDER 已初始化");
+    // 插件通过 query_metrics_fn 直接读这个连接
+    set_query_backend(db.clone(), tokio::runtime::Handle::current());
 
-    let db_clone = db.clone();
-    task::spawn(async move {
-        while let Some(msg) = rx.recv().await {
-            match msg {
-                StorageMsg::Log(e) => {
-                    if let Err(e) = db_clone.insert_log(&e).await {
-                        error!("写入日志失败: {e}");
-                    }
-                }
-                StorageMsg::Metric(m) => {
-                    if let Err(e) = db_clone.insert_metric(&m).await {
-                        error!("写入指标失败: {e}");
-                    }
-                }
-            }
-        }
-    });
+    // 有界写入队列 + 批量写库任务
+    let storage_writer =
+        storage_writer::start(db.clone(), &config.storage.clone().unwrap_or_default());
 
     // 扫描插件
-    let plugins = discover_plugins(&plugin_dir, plugin_ext);
+    let plugins = discover_plugins(&plugin_dir, &filter);
     if plugins.is_empty() {
         info!("未发现任何插件动态库，确认已构建插件。");
     } else {
@@ -146,310 +129,195 @@ async fn main() {
         }
     }
 
-    // 简单调度循环：每轮执行所有插件
-    loop {
-        run_plugins_once(&plugins, &db, plugin_api_registered.clone()).await;
-        tokio::time::sleep(Duration::from_secs(default_interval)).await;
-    }
-}
-
-// ============ tracing 初始化 ============
-
-fn init_tracing() {
-    let env_filter =
-        EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
-    tracing_subscriber::fmt().with_env_filter(env_filter).init();
-}
+    // 插件 HTTP API 的端口 / socket 由 host 统一分配，避免端口冲突
+    api_listen::configure(
+        plugin_cfg.api_port_range,
+        plugin_cfg.api_socket_dir.as_ref().map(PathBuf::from),
+    );
 
-// ============ 配置加载 ============
+    // prod 模式加载前校验插件清单
+    let verifier = is_prod_mode(&mode).then(|| {
+        let verifier = PluginVerifier::from_config(&plugin_cfg, &plugin_dir);
+        info!("插件清单: {}", verifier.manifest_path().display());
+        verifier
+    });
 
-fn load_config() -> AppConfig {
-    let path = PathBuf::from("config.toml");
-    match fs::read_to_string(&path) {
-        Ok(content) => match toml::from_str::<AppConfig>(&content) {
-            Ok(cfg) => {
-                info!("已加载配置文件: {}", path.display());
-                cfg
-            }
-            Err(e) => {
-                error!("解析配置文件 {} 失败: {e}，使用默认配置", path.display());
-                AppConfig::default()
-            }
-        },
-        Err(_) => {
-            info!("未找到配置文件 {}，使用默认配置", path.display());
-            AppConfig::default()
-        }
+    // 热加载时加载的是副本，原文件可以随时被重新编译覆盖；
+    // 校验清单时也只加载校验过的副本，避免校验之后文件又被替换
+    let shadow_dir = (watch_plugins || verifier.is_some())
+        .then(|| env::temp_dir().join(format!("bot-host-plugins-{}", std::process::id())));
+
+    // ⭐ 启动时加载一次：dlopen + meta + ABI 协商 + plugin_init；之后只有热加载会增删
+    let registry = Arc::new(PluginRegistry::new(filter, verifier, shadow_dir.clone()));
+    // dlopen + plugin_init（worker 插件还要等子进程 init）都是同步调用，不占用 tokio worker
+    let loader = registry.clone();
+    if let Err(e) = task::spawn_blocking(move || loader.load_all(&plugins)).await {
+        error!("加载插件时任务异常: {e}");
     }
-}
-
-fn resolve_plugin_dir(mode: &str, cfg: &PluginConfig) -> PathBuf {
-    match mode {
-        "prod" | "release" => PathBuf::from(
-            cfg.prod_dir
-                .clone()
-                .unwrap_or_else(|| "plugins-bin".to_string()),
-        ),
-        _ => PathBuf::from(
-            cfg.dev_dir
-                .clone()
-                .unwrap_or_else(|| "target/debug".to_string()),
-        ),
+    info!(
+        "已加载 {} 个插件，拒绝 {} 个",
+        registry.plugins().len(),
+        registry.rejected().len()
+    );
+    for plugin in registry.plugins() {
+        register_plugin_api(&plugin, &db).await;
     }
-}
 
-fn plugin_ext() -> &'static str {
-    if cfg!(target_os = "windows") {
-        "dll"
-    } else if cfg!(target_os = "macos") {
-        "dylib"
-    } else {
-        "so"
-    }
-}
+    // 定期刷新 plugins 表的 updated_at，api-server 据此判断 host 是否还活着
+    spawn_plugin_status_heartbeat(registry.clone());
 
-// ============ 扫描插件 ============
+    // SIGHUP：重新读取 config.toml 并通知插件
+    #[cfg(unix)]
+    spawn_config_reload_listener(registry.clone());
 
-fn discover_plugins(dir: &Path, ext: &str) -> Vec<PathBuf> {
-    let mut result = Vec::new();
+    // 插件执行耗时 / 失败次数、写库耗时、队列长度、进程内存和 CPU，记在 bot-host 名下
+    let self_metrics = self_metrics::spawn(
+        registry.clone(),
+        &config.self_metrics.clone().unwrap_or_default(),
+    );
 
-    let read_dir = match fs::read_dir(dir) {
-        Ok(rd) => rd,
-        Err(e) => {
-            error!("无法读取插件目录 {}: {e}", dir.display());
-            return result;
-        }
-    };
+    // 原始指标汇总成 1m / 1h / 1d，长时间范围的查询读汇总表
+    let rollup = maintenance::spawn_rollup(db.clone(), &config.rollup.clone().unwrap_or_default());
+    // 按 [retention] 删除过期的指标、日志、告警和汇总
+    let retention = maintenance::spawn_retention(
+        db.clone(),
+        &config.retention.clone().unwrap_or_default(),
+    );
 
-    for entry in read_dir {
-        if let Ok(entry) = entry {
-            let path = entry.path();
-            if !path.is_file() {
-                continue;
-            }
+    // 每个插件一个调度任务，互不阻塞
+    let schedulers = Arc::new(PluginSchedulers::new(default_interval, max_jitter));
+    for plugin in registry.plugins() {
+        schedulers.start(&plugin);
+    }
 
-            // 只检查扩展名
-            let is_ext_ok = path
-                .extension()
-                .and_then(|s| s.to_str())
-                .map(|s| s.eq_ignore_ascii_case(ext))
-                .unwrap_or(false);
+    let watcher = watch_plugins.then(|| {
+        spawn_plugin_watcher(
+            plugin_dir.clone(),
+            plugin_ext,
+            registry.clone(),
+            schedulers.clone(),
+            db.clone(),
+        )
+    });
 
-            if !is_ext_ok {
-                continue;
-            }
+    shutdown_signal().await;
 
-            result.push(path);
-        }
+    // 退出顺序：停调度（等正在执行的一轮结束）→ plugin_shutdown → 写完存储队列 → 关闭连接池
+    info!("收到退出信号，正在停止插件...");
+    if let Some(watcher) = watcher {
+        watcher.abort();
+    }
+    if let Some(self_metrics) = self_metrics {
+        self_metrics.abort();
     }
+    if let Some(rollup) = rollup {
+        rollup.abort();
+    }
+    if let Some(retention) = retention {
+        retention.abort();
+    }
+    schedulers.stop_all().await;
+    let stopping = registry.clone();
+    // 子进程隔离的插件要等 worker 退出，放到阻塞线程里
+    let _ = task::spawn_blocking(move || stopping.shutdown_all()).await;
+    for plugin in registry.plugins() {
+        plugin.report_status(Some(PluginStatus::Stopped));
+    }
+    storage_writer::shutdown(storage_writer).await;
+    db.close().await;
 
-    result
+    if let Some(dir) = shadow_dir {
+        let _ = fs::remove_dir_all(dir);
+    }
+    info!("=== bot-host 已退出 ===");
 }
 
-// ============ 插件调度 ============
-
-async fn run_plugins_once(
-    plugins: &[PathBuf],
-    db: &Db,
-    plugin_api_registered: std::sync::Arc<tokio::sync::Mutex<HashSet<String>>>,
-) {
-    for path in plugins {
-        info!("执行插件: {}", path.display());
-
-        let lib = match unsafe { Library::new(path) } {
-            Ok(lib) => lib,
-            Err(e) => {
-                error!("加载插件 {} 失败: {e}", path.display());
-                continue;
-            }
-        };
-
-        unsafe {
-            let meta_func: Symbol<PluginMetaFunc> = match lib.get(b"meta") {
-                Ok(sym) => sym,
-                Err(e) => {
-                    error!("插件 {} 缺少 meta 函数: {e}", path.display());
-                    continue;
-                }
-            };
-            let meta: PluginMeta = meta_func();
-            let plugin_name =
-                c_str_to_string(meta.name).unwrap_or_else(|| "<unknown>".to_string());
-            let plugin_version =
-                c_str_to_string(meta.version).unwrap_or_else(|| "<unknown>".to_string());
-            let plugin_kind =
-                c_str_to_string(meta.kind).unwrap_or_else(|| "<unknown>".to_string());
-
-            info!(
-                "插件信息: name={}, version={}, kind={}",
-                plugin_name, plugin_version, plugin_kind
-            );
-
-            // ⭐ 在当前线程标记“当前插件名”，给日志和指标桥接使用
-            CURRENT_PLUGIN_NAME.with(|slot| {
-                *slot.borrow_mut() = Some(plugin_name.clone());
-            });
-
-            // ⭐ 新增：尝试读取插件的 API 信息，并写入 DB
-            if let Ok(api_info_fn) = lib.get::<PluginApiInfoFunc>(b"plugin_api_info") {
-                let info: PluginApiInfo = api_info_fn();
-
-                let prefix = c_str_to_string(info.prefix).unwrap_or_else(|| "/".to_string());
-                let base_url = format!("http://127.0.0.1:{}{}", info.port, prefix);
-
-                {
-                    let mut guard = plugin_api_registered.lock().await;
-                    if !guard.contains(&plugin_name) {
-                        // 第一次注册，写入 DB
-                        if let Err(e) = db.upsert_plugin_api(&plugin_name, &base_url).await {
-                            error!(
-                                "注册插件 API 失败: plugin={}, base_url={}, err={e}",
-                                plugin_name, base_url
-                            );
-                        } else {
-                            info!(
-                                "已注册插件 API: plugin={}, base_url={}",
-                                plugin_name, base_url
-                            );
-                            guard.insert(plugin_name.clone());
-                        }
-                    }
-                }
-            } else {
-                // 没有 plugin_api_info，说明该插件不暴露 HTTP API，直接略过
-            }
-
+// ============ 插件状态 ============
 
+/// plugins 表的心跳间隔；api-server 超过三个间隔没看到更新就认为状态已过期
+const PLUGIN_STATUS_HEARTBEAT: Duration = Duration::from_secs(30);
 
-            let run_with_ctx: Result<Symbol<PluginRunWithContextFunc>, _> =
-                lib.get(b"run_with_ctx");
-
-            if let Ok(run_with_ctx) = run_with_ctx {
-                let mut ctx = PluginContext {
-                    host_version: 1,
-                    log_fn: host_log_bridge,
-                    emit_metric_fn: host_emit_metric_bridge,
-                };
-
-                info!("调用 run_with_ctx()...");
-                run_with_ctx(&mut ctx as *mut PluginContext);
-            } else {
-                let run_func: Result<Symbol<PluginRunFunc>, _> = lib.get(b"run");
-                match run_func {
-                    Ok(run) => {
-                        info!("调用旧版 run()...");
-                        run();
-                    }
-                    Err(e) => {
-                        error!(
-                            "插件 {} 既没有 run_with_ctx 也没有 run: {e}",
-                            path.display()
-                        );
-                    }
-                }
+fn spawn_plugin_status_heartbeat(registry: Arc<PluginRegistry>) {
+    task::spawn(async move {
+        let mut ticker = tokio::time::interval(PLUGIN_STATUS_HEARTBEAT);
+        // 第一次 tick 立即返回，加载时已经写过一次
+        ticker.tick().await;
+        loop {
+            ticker.tick().await;
+            for plugin in registry.plugins() {
+                plugin.report_status(None);
             }
-
-            // ⭐ 执行完清空当前插件名，避免污染后续调用
-            CURRENT_PLUGIN_NAME.with(|slot| {
-                *slot.borrow_mut() = None;
-            });
         }
-        // ⭐ 关键：不要在这里让 lib 被 drop（否则起后台 HTTP 线程的插件会崩）
-        //    对于已经成功执行过 run_with_ctx / run 的插件，我们让它常驻内存
-        std::mem::forget(lib);
-    }
+    });
 }
 
-// ============ FFI 桥接：Log & Metric ============
-
-extern "C" fn host_log_bridge(level: PluginLogLevel, msg: *const c_char) {
-    if msg.is_null() {
-        return;
-    }
-
-    let message = match unsafe { CStr::from_ptr(msg).to_str() } {
-        Ok(s) => s.to_string(),
-        Err(_) => "<invalid utf-8>".to_string(),
-    };
+// ============ 信号处理 ============
 
-    // ⭐ 读取当前插件名
-    let plugin_name_opt = CURRENT_PLUGIN_NAME.with(|slot| slot.borrow().clone());
-    let plugin_label = plugin_name_opt
-        .as_deref()
-        .unwrap_or("<unknown-plugin>");
-
-    let host_level = match level {
-        PluginLogLevel::Debug => HostLogLevel::Debug,
-        PluginLogLevel::Info => HostLogLevel::Info,
-        PluginLogLevel::Warn => HostLogLevel::Warn,
-        PluginLogLevel::Error => HostLogLevel::Error,
+/// 等待 Ctrl-C（SIGINT）或 SIGTERM
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            error!("监听 Ctrl-C 失败: {e}");
+            std::future::pending::<()>().await;
+        }
     };
 
-    // 写入 DB 的事件，现在带上 plugin 字段
-    let event = LogEvent {
-        time: Utc::now(),
-        level: host_level,
-        plugin: plugin_name_opt.clone(),
-        message: message.clone(),
-        fields: Default::default(),
+    #[cfg(unix)]
+    let terminate = async {
+        use tokio::signal::unix::{signal, SignalKind};
+        match signal(SignalKind::terminate()) {
+            Ok(mut sig) => {
+                sig.recv().await;
+            }
+            Err(e) => {
+                error!("监听 SIGTERM 失败: {e}");
+                std::future::pending::<()>().await;
+            }
+        }
     };
 
-    if let Some(sender) = GLOBAL_SENDER.get() {
-        let _ = sender.send(StorageMsg::Log(event));
-    }
-
-    // 控制台日志也加上插件名前缀
-    let decorated = format!("[{plugin_label}] {message}");
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
 
-    match host_level {
-        HostLogLevel::Debug => tracing::debug!("{decorated}"),
-        HostLogLevel::Info => tracing::info!("{decorated}"),
-        HostLogLevel::Warn => tracing::warn!("{decorated}"),
-        HostLogLevel::Error => tracing::error!("{decorated}"),
+    tokio::select! {
+        _ = ctrl_c => {}
+        _ = terminate => {}
     }
 }
 
-extern "C" fn host_emit_metric_bridge(sample: MetricSample) {
-    let name = if sample.name.is_null() {
-        "<unnamed>".to_string()
-    } else {
-        c_str_to_string(sample.name).unwrap_or_else(|| "<invalid metric name>".to_string())
-    };
+#[cfg(unix)]
+fn spawn_config_reload_listener(registry: Arc<PluginRegistry>) {
+    use tokio::signal::unix::{signal, SignalKind};
 
-    let time = timestamp_ms_to_datetime(sample.timestamp_ms);
-
-    // ⭐ 从线程本地拿当前插件名，默认 unknown
-    let plugin_name = CURRENT_PLUGIN_NAME.with(|slot| {
-        slot.borrow()
-            .clone()
-            .unwrap_or_else(|| "unknown".to_string())
-    });
+    task::spawn(async move {
+        let mut hangup = match signal(SignalKind::hangup()) {
+            Ok(sig) => sig,
+            Err(e) => {
+                error!("监听 SIGHUP 失败: {e}");
+                return;
+            }
+        };
 
-    let metric = Metric {
-        time,
-        plugin: plugin_name,
-        name,
-        value: sample.value,
-        labels: Default::default(),
-    };
+        while hangup.recv().await.is_some() {
+            info!("收到 SIGHUP，重新加载配置");
+            set_plugin_configs(&load_config());
 
-    if let Some(sender) = GLOBAL_SENDER.get() {
-        let _ = sender.send(StorageMsg::Metric(metric));
-    }
+            // 子进程隔离的插件要等它当前这一轮跑完，放到阻塞线程里
+            let registry = registry.clone();
+            let _ = task::spawn_blocking(move || {
+                for plugin in registry.plugins() {
+                    plugin.reload_config(&plugin_config_json(&plugin.name));
+                }
+            })
+            .await;
+        }
+    });
 }
 
-// ============ 小工具函数 ============
-
-fn c_str_to_string(ptr: *const c_char) -> Option<String> {
-    if ptr.is_null() {
-        return None;
-    }
-    unsafe { CStr::from_ptr(ptr).to_str().ok().map(|s| s.to_string()) }
-}
+// ============ tracing 初始化 ============
 
-fn timestamp_ms_to_datetime(ms: i64) -> DateTime<Utc> {
-    match Utc.timestamp_millis_opt(ms) {
-        chrono::LocalResult::Single(dt) => dt,
-        _ => Utc::now(),
-    }
+fn init_tracing() {
+    let env_filter =
+        EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+    tracing_subscriber::fmt().with_env_filter(env_filter).init();
 }
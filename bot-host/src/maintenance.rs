@@ -0,0 +1,74 @@
+//! 数据库的后台维护任务：指标降采样、按保留期限清理旧数据
+
+use std::time::Duration;
+
+use storage::Db;
+use tokio::task::{self, JoinHandle};
+use tracing::{debug, error, info, warn};
+
+use crate::config::{RetentionConfig, RollupConfig};
+
+const DEFAULT_ROLLUP_INTERVAL: Duration = Duration::from_secs(60);
+const DEFAULT_RETENTION_INTERVAL: Duration = Duration::from_secs(3600);
+
+/// 定期把原始指标汇总成 1m / 1h / 1d；`[rollup].enabled = false` 时返回 None
+pub fn spawn_rollup(db: Db, config: &RollupConfig) -> Option<JoinHandle<()>> {
+    if !config.enabled.unwrap_or(true) {
+        info!("指标降采样已关闭");
+        return None;
+    }
+    let interval = config
+        .interval_secs
+        .filter(|s| *s > 0)
+        .map(Duration::from_secs)
+        .unwrap_or(DEFAULT_ROLLUP_INTERVAL);
+
+    Some(task::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            match db.rollup_metrics().await {
+                Ok(0) => {}
+                Ok(rows) => debug!("指标降采样写入 {rows} 条汇总"),
+                Err(e) => warn!("指标降采样失败: {e}"),
+            }
+        }
+    }))
+}
+
+/// 定期按 `[retention]` 删除过期数据；没有任何规则或配置写错时返回 None
+pub fn spawn_retention(db: Db, config: &RetentionConfig) -> Option<JoinHandle<()>> {
+    let policy = match config.policy() {
+        Ok(policy) if policy.rules.is_empty() => return None,
+        Ok(policy) => policy,
+        Err(e) => {
+            error!("数据保留配置无效，不做清理: {e}");
+            return None;
+        }
+    };
+    let interval = config
+        .interval_secs
+        .filter(|s| *s > 0)
+        .map(Duration::from_secs)
+        .unwrap_or(DEFAULT_RETENTION_INTERVAL);
+    info!(
+        "数据保留已开启：{} 条规则，每 {}s 清理一次",
+        policy.rules.len(),
+        interval.as_secs()
+    );
+
+    Some(task::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            match db.prune(&policy).await {
+                Ok(report) if report.total() == 0 => debug!("数据保留：没有过期数据"),
+                Ok(report) => info!(
+                    "数据保留：删除 metrics {} 行、logs {} 行、alerts {} 行、汇总 {} 行，释放 {} 页",
+                    report.metrics, report.logs, report.alerts, report.rollups, report.vacuumed_pages
+                ),
+                Err(e) => warn!("数据保留清理失败: {e}"),
+            }
+        }
+    }))
+}
@@ -0,0 +1,437 @@
+//! prod 模式下的插件清单校验。
+//!
+//! 插件目录里的 `manifest.toml` 列出允许加载的动态库：
+//!
+//! ```toml
+//! [[plugin]]
+//! file = "libcpu_monitor.so"
+//! name = "cpu-monitor"
+//! version = "0.2.0"
+//! sha256 = "9f86d0…"          # 动态库内容的 SHA-256，hex
+//! signature = "3a4b…"         # 可选：ed25519 签名，hex
+//! ```
+//!
+//! 签名的内容是 `"{name}:{version}:{sha256}"`（sha256 为小写 hex）。
+//! 配置了 `[plugin].trusted_keys` 时每个插件都必须有能被其中某个公钥验证的签名。
+//!
+//! 没有配置公钥时只能校验 sha256，防不住同时改清单的人，所以要求清单放在插件目录之外
+//! （能写插件目录的人不一定能改它）；清单在插件目录里时拒绝加载所有插件。
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use ed25519_dalek::{Signature, VerifyingKey};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use tracing::{error, warn};
+
+use crate::config::PluginConfig;
+
+/// 不配置 `[plugin].manifest` 时使用插件目录下的这个文件
+pub const DEFAULT_MANIFEST_FILE: &str = "manifest.toml";
+
+#[derive(Debug, Deserialize, Default)]
+struct Manifest {
+    #[serde(default)]
+    plugin: Vec<ManifestEntry>,
+}
+
+/// 清单里的一条记录
+#[derive(Debug, Clone, Deserialize)]
+pub struct ManifestEntry {
+    /// 动态库文件名（不含目录）
+    pub file: String,
+    pub name: String,
+    pub version: String,
+    pub sha256: String,
+    pub signature: Option<String>,
+}
+
+impl ManifestEntry {
+    /// dlopen 之后核对 meta 里的名字和版本，防止换了一个 sha256 登记过的其他插件进来
+    pub fn check_meta(&self, name: &str, version: &str) -> Result<(), String> {
+        if self.name == name && self.version == version {
+            Ok(())
+        } else {
+            Err(format!(
+                "与插件清单不一致: 清单={}@{}, meta={name}@{version}",
+                self.name, self.version
+            ))
+        }
+    }
+
+    /// 签名覆盖的内容
+    fn signed_message(&self) -> String {
+        format!(
+            "{}:{}:{}",
+            self.name,
+            self.version,
+            self.sha256.to_ascii_lowercase()
+        )
+    }
+}
+
+/// 加载动态库之前校验它是否在清单里、内容是否被改过
+pub struct PluginVerifier {
+    manifest_path: PathBuf,
+    trust: Trust,
+}
+
+/// 凭什么相信清单本身没有被改过
+enum Trust {
+    /// 每个插件都要有能被其中某个公钥验证的签名
+    Signed(Vec<VerifyingKey>),
+    /// 没有配置公钥，但清单在插件目录之外，只校验 sha256
+    ChecksumOnly,
+    /// 清单不可信，拒绝所有插件
+    Refuse(String),
+}
+
+impl PluginVerifier {
+    pub fn from_config(cfg: &PluginConfig, plugin_dir: &Path) -> Self {
+        let manifest_path = cfg
+            .manifest
+            .as_ref()
+            .map(PathBuf::from)
+            .unwrap_or_else(|| plugin_dir.join(DEFAULT_MANIFEST_FILE));
+
+        let configured = cfg.trusted_keys.as_deref().unwrap_or_default();
+        let trust = if !configured.is_empty() {
+            let keys = configured
+                .iter()
+                .filter_map(|key| match parse_verifying_key(key) {
+                    Ok(k) => Some(k),
+                    Err(e) => {
+                        error!("trusted_keys 中的公钥 {key} 无效: {e}");
+                        None
+                    }
+                })
+                .collect::<Vec<_>>();
+            if keys.is_empty() {
+                Trust::Refuse("[plugin].trusted_keys 中没有有效的公钥".to_string())
+            } else {
+                Trust::Signed(keys)
+            }
+        } else if is_inside(&manifest_path, plugin_dir) {
+            Trust::Refuse(format!(
+                "未配置 [plugin].trusted_keys，而插件清单 {} 在插件目录里，能替换插件的人也能改清单；\
+                 请配置 trusted_keys，或用 [plugin].manifest 把清单放到插件目录之外",
+                manifest_path.display()
+            ))
+        } else {
+            warn!("未配置 [plugin].trusted_keys，prod 模式只校验 sha256，不校验签名");
+            Trust::ChecksumOnly
+        };
+        if let Trust::Refuse(reason) = &trust {
+            error!("prod 模式将拒绝加载所有插件: {reason}");
+        }
+
+        Self {
+            manifest_path,
+            trust,
+        }
+    }
+
+    /// 清单不可信、所有插件都会被拒绝时返回原因
+    pub fn refusal(&self) -> Option<&str> {
+        match &self.trust {
+            Trust::Refuse(reason) => Some(reason),
+            Trust::Signed(_) | Trust::ChecksumOnly => None,
+        }
+    }
+
+    pub fn manifest_path(&self) -> &Path {
+        &self.manifest_path
+    }
+
+    /// 校验动态库内容，通过时返回清单里的记录。
+    ///
+    /// 每次都重新读取清单，热加载时更新清单即可生效。
+    pub fn verify(&self, path: &Path, contents: &[u8]) -> Result<ManifestEntry, String> {
+        if let Some(reason) = self.refusal() {
+            return Err(reason.to_string());
+        }
+        let manifest = self.read_manifest()?;
+
+        let file_name = path
+            .file_name()
+            .and_then(|s| s.to_str())
+            .unwrap_or_default();
+        let entry = manifest
+            .plugin
+            .into_iter()
+            .find(|e| e.file == file_name)
+            .ok_or_else(|| format!("{file_name} 不在插件清单中"))?;
+
+        let actual = hex::encode(Sha256::digest(contents));
+        if !actual.eq_ignore_ascii_case(entry.sha256.trim()) {
+            return Err(format!(
+                "sha256 不匹配，动态库可能被篡改: 清单={}, 实际={actual}",
+                entry.sha256
+            ));
+        }
+
+        if let Trust::Signed(keys) = &self.trust {
+            let signature = entry
+                .signature
+                .as_deref()
+                .ok_or_else(|| "已配置 trusted_keys，但清单中没有签名".to_string())?;
+            let signature = parse_signature(signature)?;
+            let message = entry.signed_message();
+            let trusted = keys
+                .iter()
+                .any(|key| key.verify_strict(message.as_bytes(), &signature).is_ok());
+            if !trusted {
+                return Err("签名无法被任何 trusted_keys 验证".to_string());
+            }
+        }
+
+        Ok(entry)
+    }
+}
+
+impl PluginVerifier {
+    /// 清单里登记了几个插件；清单读不到或格式错误时返回 Err
+    pub fn manifest_len(&self) -> Result<usize, String> {
+        self.read_manifest().map(|m| m.plugin.len())
+    }
+
+    fn read_manifest(&self) -> Result<Manifest, String> {
+        let raw = fs::read_to_string(&self.manifest_path)
+            .map_err(|e| format!("无法读取插件清单 {}: {e}", self.manifest_path.display()))?;
+        toml::from_str(&raw)
+            .map_err(|e| format!("解析插件清单 {} 失败: {e}", self.manifest_path.display()))
+    }
+}
+
+/// `path` 是否在 `dir` 里面（按真实路径比较，清单文件不存在时看它所在的目录）
+fn is_inside(path: &Path, dir: &Path) -> bool {
+    let real = |p: &Path| p.canonicalize().unwrap_or_else(|_| p.to_path_buf());
+    let parent = path
+        .parent()
+        .filter(|p| !p.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    real(parent).starts_with(real(dir))
+}
+
+pub fn parse_verifying_key(hex_key: &str) -> Result<VerifyingKey, String> {
+    let bytes: [u8; 32] = from_hex(hex_key)?
+        .try_into()
+        .map_err(|_| "公钥应为 32 字节".to_string())?;
+    VerifyingKey::from_bytes(&bytes).map_err(|e| e.to_string())
+}
+
+fn parse_signature(hex_sig: &str) -> Result<Signature, String> {
+    let bytes: [u8; 64] = from_hex(hex_sig)
+        .map_err(|e| format!("签名格式错误: {e}"))?
+        .try_into()
+        .map_err(|_| "签名应为 64 字节".to_string())?;
+    Ok(Signature::from_bytes(&bytes))
+}
+
+fn from_hex(s: &str) -> Result<Vec<u8>, String> {
+    hex::decode(s.trim()).map_err(|e| format!("不是合法的 hex: {e}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use ed25519_dalek::{Signer, SigningKey};
+
+    use super::*;
+
+    const LIB: &[u8] = b"not really a shared library";
+
+    /// 每个测试一个独立目录：`<tmp>/plugins` 是插件目录，清单按需放在里面或外面
+    struct Fixture {
+        root: PathBuf,
+    }
+
+    impl Fixture {
+        fn new(test: &str) -> Self {
+            let root = std::env::temp_dir()
+                .join(format!("bot-host-manifest-{}-{test}", std::process::id()));
+            let _ = fs::remove_dir_all(&root);
+            fs::create_dir_all(root.join("plugins")).unwrap();
+            Self { root }
+        }
+
+        fn plugin_dir(&self) -> PathBuf {
+            self.root.join("plugins")
+        }
+
+        fn lib_path(&self) -> PathBuf {
+            self.plugin_dir().join("libdemo.so")
+        }
+
+        /// 写清单并返回对应的 verifier
+        fn verifier(
+            &self,
+            manifest_in: &Path,
+            manifest: &str,
+            keys: &[&SigningKey],
+        ) -> PluginVerifier {
+            let manifest_path = manifest_in.join("manifest.toml");
+            fs::write(&manifest_path, manifest).unwrap();
+            let cfg = PluginConfig {
+                manifest: Some(manifest_path.display().to_string()),
+                trusted_keys: (!keys.is_empty()).then(|| {
+                    keys.iter()
+                        .map(|k| hex::encode(k.verifying_key().as_bytes()))
+                        .collect()
+                }),
+                ..Default::default()
+            };
+            PluginVerifier::from_config(&cfg, &self.plugin_dir())
+        }
+    }
+
+    impl Drop for Fixture {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.root);
+        }
+    }
+
+    fn sha256_hex(contents: &[u8]) -> String {
+        hex::encode(Sha256::digest(contents))
+    }
+
+    fn entry(sha256: &str, signature: Option<String>) -> String {
+        let signature = signature
+            .map(|s| format!("signature = \"{s}\"\n"))
+            .unwrap_or_default();
+        format!(
+            "[[plugin]]\nfile = \"libdemo.so\"\nname = \"demo\"\nversion = \"1.0.0\"\nsha256 = \"{sha256}\"\n{signature}"
+        )
+    }
+
+    fn sign(key: &SigningKey, sha256: &str) -> String {
+        let message = format!("demo:1.0.0:{sha256}");
+        hex::encode(key.sign(message.as_bytes()).to_bytes())
+    }
+
+    #[test]
+    fn valid_signature_passes() {
+        let fx = Fixture::new("valid");
+        let key = SigningKey::from_bytes(&[7; 32]);
+        let sha = sha256_hex(LIB);
+        let verifier = fx.verifier(
+            &fx.plugin_dir(),
+            &entry(&sha, Some(sign(&key, &sha))),
+            &[&key],
+        );
+
+        let entry = verifier.verify(&fx.lib_path(), LIB).expect("签名有效");
+        assert_eq!(entry.name, "demo");
+        assert_eq!(entry.version, "1.0.0");
+    }
+
+    #[test]
+    fn bad_signature_is_rejected() {
+        let fx = Fixture::new("bad-signature");
+        let trusted = SigningKey::from_bytes(&[7; 32]);
+        let other = SigningKey::from_bytes(&[8; 32]);
+        let sha = sha256_hex(LIB);
+        let verifier = fx.verifier(
+            &fx.plugin_dir(),
+            &entry(&sha, Some(sign(&other, &sha))),
+            &[&trusted],
+        );
+
+        let err = verifier.verify(&fx.lib_path(), LIB).unwrap_err();
+        assert!(err.contains("签名无法被任何 trusted_keys 验证"), "{err}");
+    }
+
+    #[test]
+    fn missing_signature_is_rejected_when_keys_configured() {
+        let fx = Fixture::new("missing-signature");
+        let key = SigningKey::from_bytes(&[7; 32]);
+        let verifier = fx.verifier(&fx.plugin_dir(), &entry(&sha256_hex(LIB), None), &[&key]);
+
+        let err = verifier.verify(&fx.lib_path(), LIB).unwrap_err();
+        assert!(err.contains("没有签名"), "{err}");
+    }
+
+    #[test]
+    fn sha256_mismatch_is_rejected() {
+        let fx = Fixture::new("sha256");
+        let key = SigningKey::from_bytes(&[7; 32]);
+        let sha = sha256_hex(b"the library that was signed");
+        let verifier = fx.verifier(
+            &fx.plugin_dir(),
+            &entry(&sha, Some(sign(&key, &sha))),
+            &[&key],
+        );
+
+        let err = verifier.verify(&fx.lib_path(), LIB).unwrap_err();
+        assert!(err.contains("sha256 不匹配"), "{err}");
+    }
+
+    #[test]
+    fn library_missing_from_manifest_is_rejected() {
+        let fx = Fixture::new("missing");
+        let key = SigningKey::from_bytes(&[7; 32]);
+        let sha = sha256_hex(LIB);
+        let verifier = fx.verifier(
+            &fx.plugin_dir(),
+            &entry(&sha, Some(sign(&key, &sha))),
+            &[&key],
+        );
+
+        let err = verifier
+            .verify(&fx.plugin_dir().join("libother.so"), LIB)
+            .unwrap_err();
+        assert!(err.contains("libother.so 不在插件清单中"), "{err}");
+    }
+
+    #[test]
+    fn meta_must_match_manifest_entry() {
+        let entry = ManifestEntry {
+            file: "libdemo.so".to_string(),
+            name: "demo".to_string(),
+            version: "1.0.0".to_string(),
+            sha256: String::new(),
+            signature: None,
+        };
+
+        assert!(entry.check_meta("demo", "1.0.0").is_ok());
+        let err = entry.check_meta("evil", "1.0.0").unwrap_err();
+        assert!(err.contains("清单=demo@1.0.0, meta=evil@1.0.0"), "{err}");
+        assert!(entry.check_meta("demo", "1.0.1").is_err());
+    }
+
+    #[test]
+    fn unsigned_manifest_inside_plugin_dir_refuses_everything() {
+        let fx = Fixture::new("unsigned-inside");
+        let verifier = fx.verifier(&fx.plugin_dir(), &entry(&sha256_hex(LIB), None), &[]);
+
+        assert!(verifier.refusal().is_some());
+        let err = verifier.verify(&fx.lib_path(), LIB).unwrap_err();
+        assert!(err.contains("trusted_keys"), "{err}");
+    }
+
+    #[test]
+    fn unsigned_manifest_outside_plugin_dir_checks_sha256_only() {
+        let fx = Fixture::new("unsigned-outside");
+        let verifier = fx.verifier(&fx.root, &entry(&sha256_hex(LIB), None), &[]);
+
+        assert!(verifier.refusal().is_none());
+        assert!(verifier.verify(&fx.lib_path(), LIB).is_ok());
+        assert!(verifier.verify(&fx.lib_path(), b"tampered").is_err());
+    }
+
+    #[test]
+    fn all_invalid_keys_refuse_everything() {
+        let fx = Fixture::new("invalid-keys");
+        fs::write(fx.root.join("manifest.toml"), entry(&sha256_hex(LIB), None)).unwrap();
+        let cfg = PluginConfig {
+            manifest: Some(fx.root.join("manifest.toml").display().to_string()),
+            trusted_keys: Some(vec!["not-hex".to_string()]),
+            ..Default::default()
+        };
+        let verifier = PluginVerifier::from_config(&cfg, &fx.plugin_dir());
+
+        assert!(verifier.verify(&fx.lib_path(), LIB).is_err());
+    }
+}
@@ -0,0 +1,1083 @@
+use std::{
+    collections::HashMap,
+    ffi::CString,
+    fs,
+    mem::ManuallyDrop,
+    panic::{self, AssertUnwindSafe},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex, RwLock,
+    },
+};
+
+use chrono::{DateTime, Utc};
+use core_types::{PluginRecord, PluginStatus};
+use glob::Pattern;
+use libloading::Library;
+use object::Object;
+use plugin_api::{
+    negotiate_capabilities, PluginAbiVersionFunc, PluginApiInfoFunc, PluginCapabilitiesFunc,
+    PluginContext, PluginInitFunc, PluginMetaFunc, PluginReloadConfigFunc, PluginRunFunc,
+    PluginRunWithContextFunc, PluginScheduleFunc, PluginShutdownFunc, CAP_API_LISTEN,
+    CAP_EMIT_ALERT, CAP_EMIT_METRIC, CAP_EVENT_BUS, CAP_LIFECYCLE, CAP_LOG, CAP_LOG_FIELDS, CAP_METRIC_LABELS,
+    CAP_PANIC_SAFE, CAP_PLUGIN_CONFIG, CAP_QUERY_METRICS, CAP_REPORT_FAILURE, PLUGIN_ABI_VERSION,
+};
+use storage::Db;
+use tracing::{error, info, warn};
+
+use crate::api_listen;
+use crate::bridge::{
+    c_str_to_string, host_confirm_api_bind_bridge, host_emit_alert_bridge,
+    host_emit_metric_bridge, host_emit_metric_with_labels_bridge, host_free_metric_buffer_bridge,
+    host_free_string_bridge, host_get_api_listen_bridge, host_get_config_bridge, host_log_bridge,
+    host_log_with_fields_bridge, host_publish_bridge, host_query_metrics_bridge,
+    host_report_failure_bridge, host_subscribe_bridge, host_unsubscribe_bridge, record_rejection, send_storage, take_reported_failure, with_plugin_scope, StorageMsg,
+};
+use crate::config::{
+    plugin_api_transport, plugin_isolation, plugin_worker_timeout, Isolation, PluginConfig,
+};
+use crate::event_bus;
+use crate::health::PluginHealth;
+use crate::manifest::PluginVerifier;
+use crate::wasm::{WasmPlugin, WASM_ABI_VERSION, WASM_CAPABILITIES};
+use crate::worker::{WorkerCommand, WorkerProcess, RUN_TIMEOUT};
+
+/// host 当前能提供的全部能力；`CAP_PANIC_SAFE` 是插件单方面的承诺，总是接受
+pub const HOST_CAPABILITIES: u64 = CAP_LOG
+    | CAP_EMIT_METRIC
+    | CAP_METRIC_LABELS
+    | CAP_LOG_FIELDS
+    | CAP_LIFECYCLE
+    | CAP_PLUGIN_CONFIG
+    | CAP_REPORT_FAILURE
+    | CAP_API_LISTEN
+    | CAP_EMIT_ALERT
+    | CAP_QUERY_METRICS
+    | CAP_EVENT_BUS
+    | CAP_PANIC_SAFE;
+
+/// 能力位的名字，`bot-host plugins list` 显示用
+const CAPABILITY_NAMES: &[(u64, &str)] = &[
+    (CAP_LOG, "log"),
+    (CAP_EMIT_METRIC, "emit_metric"),
+    (CAP_METRIC_LABELS, "metric_labels"),
+    (CAP_LOG_FIELDS, "log_fields"),
+    (CAP_LIFECYCLE, "lifecycle"),
+    (CAP_PLUGIN_CONFIG, "plugin_config"),
+    (CAP_REPORT_FAILURE, "report_failure"),
+    (CAP_API_LISTEN, "api_listen"),
+    (CAP_EMIT_ALERT, "emit_alert"),
+    (CAP_QUERY_METRICS, "query_metrics"),
+    (CAP_EVENT_BUS, "event_bus"),
+    (CAP_PANIC_SAFE, "panic_safe"),
+];
+
+/// 把能力位展开成名字列表
+pub fn capability_names(capabilities: u64) -> Vec<&'static str> {
+    CAPABILITY_NAMES
+        .iter()
+        .filter(|(bit, _)| capabilities & bit != 0)
+        .map(|(_, name)| *name)
+        .collect()
+}
+
+// ============ 扫描插件 ============
+
+/// 插件发现规则：文件名 glob + 按插件名的启用 / 禁用列表（只在启动时读取）
+#[derive(Debug, Clone)]
+pub struct PluginFilter {
+    ext: &'static str,
+    pattern: Pattern,
+    enabled: Option<Vec<String>>,
+    disabled: Vec<String>,
+}
+
+impl PluginFilter {
+    pub fn from_config(cfg: &PluginConfig, ext: &'static str) -> Self {
+        let pattern = match cfg.name_pattern.as_deref().map(str::trim) {
+            None | Some("") => Pattern::new("*"),
+            // 兼容旧配置：没有通配符时当作“文件名包含”
+            Some(p) if !p.contains(['*', '?', '[']) => Pattern::new(&format!("*{p}*")),
+            Some(p) => Pattern::new(p),
+        };
+        let pattern = pattern.unwrap_or_else(|e| {
+            error!("name_pattern 不是合法的 glob: {e}，不按文件名过滤");
+            Pattern::new("*").expect("\"*\" 是合法的 glob")
+        });
+
+        Self {
+            ext,
+            pattern,
+            enabled: cfg.enabled.clone(),
+            disabled: cfg.disabled.clone().unwrap_or_default(),
+        }
+    }
+
+    pub fn pattern(&self) -> &str {
+        self.pattern.as_str()
+    }
+
+    /// dlopen 之前按文件名排除 disabled 的插件，命中时返回对应的名字。
+    ///
+    /// 去掉扩展名和 `lib` 前缀后比较，`-` 和 `_` 视为相同（cargo 会把 crate 名里的 `-` 换成 `_`）
+    pub fn disabled_by_file_name(&self, path: &Path) -> Option<&str> {
+        let stem = path.file_stem().and_then(|s| s.to_str())?;
+        let stem = normalize_plugin_name(stem.strip_prefix("lib").unwrap_or(stem));
+        self.disabled
+            .iter()
+            .find(|name| normalize_plugin_name(name) == stem)
+            .map(String::as_str)
+    }
+
+    /// 按插件名判断是否加载；不加载时返回原因。和 [`Self::disabled_by_file_name`] 一样
+    /// 不区分大小写，`-` 和 `_` 视为相同
+    pub fn check_name(&self, name: &str) -> Result<(), String> {
+        let name = normalize_plugin_name(name);
+        let listed = |names: &[String]| names.iter().any(|n| normalize_plugin_name(n) == name);
+        if listed(&self.disabled) {
+            return Err("在 [plugin].disabled 列表中".to_string());
+        }
+        if let Some(enabled) = &self.enabled
+            && !listed(enabled)
+        {
+            return Err("不在 [plugin].enabled 列表中".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// enabled / disabled 列表和文件名比较时用的插件名：不区分大小写，`-` 与 `_` 视为相同
+pub(crate) fn normalize_plugin_name(name: &str) -> String {
+    name.to_ascii_lowercase().replace('-', "_")
+}
+
+/// 扫描插件目录，返回 (候选动态库, 被跳过的文件及原因)。扩展名不对的文件不算候选，不出现在结果里。
+pub fn scan_plugin_dir(dir: &Path, filter: &PluginFilter) -> (Vec<PathBuf>, Vec<(PathBuf, String)>) {
+    let mut candidates = Vec::new();
+    let mut skipped = Vec::new();
+
+    let read_dir = match fs::read_dir(dir) {
+        Ok(rd) => rd,
+        Err(e) => {
+            error!("无法读取插件目录 {}: {e}", dir.display());
+            return (candidates, skipped);
+        }
+    };
+
+    for entry in read_dir.flatten() {
+        let path = entry.path();
+        if !path.is_file() {
+            continue;
+        }
+
+        if !is_plugin_file(&path, filter.ext) {
+            continue;
+        }
+
+        let file_name = path.file_name().and_then(|s| s.to_str()).unwrap_or_default();
+        if !filter.pattern.matches(file_name) {
+            let reason = format!("文件名不匹配 name_pattern \"{}\"", filter.pattern);
+            skipped.push((path, reason));
+            continue;
+        }
+        if let Some(name) = filter.disabled_by_file_name(&path) {
+            let reason = format!("文件名对应 [plugin].disabled 中的 \"{name}\"，不 dlopen");
+            skipped.push((path, reason));
+            continue;
+        }
+
+        candidates.push(path);
+    }
+
+    candidates.sort();
+    (candidates, skipped)
+}
+
+/// WASM 插件的扩展名，和原生动态库一起扫描
+pub const WASM_EXT: &str = "wasm";
+
+/// 扩展名是原生动态库（`ext`）或 `.wasm`
+pub fn is_plugin_file(path: &Path, ext: &str) -> bool {
+    path.extension()
+        .and_then(|s| s.to_str())
+        .is_some_and(|s| s.eq_ignore_ascii_case(ext) || s.eq_ignore_ascii_case(WASM_EXT))
+}
+
+fn is_wasm_file(path: &Path) -> bool {
+    path.extension()
+        .and_then(|s| s.to_str())
+        .is_some_and(|s| s.eq_ignore_ascii_case(WASM_EXT))
+}
+
+/// 扫描插件目录并打印被跳过的文件
+pub fn discover_plugins(dir: &Path, filter: &PluginFilter) -> Vec<PathBuf> {
+    let (candidates, skipped) = scan_plugin_dir(dir, filter);
+    for (path, reason) in &skipped {
+        info!("跳过 {}: {reason}", path.display());
+    }
+    candidates
+}
+
+/// 不 dlopen，直接读动态库的导出符号表，确认导出了 `meta`。
+///
+/// 插件目录里可能还有别的 cdylib（比如 Tauri 的库），dlopen 它们会执行初始化代码，
+/// 所以在加载之前先排除掉。
+pub fn check_plugin_exports(contents: &[u8]) -> Result<(), String> {
+    let file = object::File::parse(contents).map_err(|e| format!("无法解析动态库: {e}"))?;
+    let exports = file.exports().map_err(|e| format!("无法读取导出符号: {e}"))?;
+
+    // Mach-O 的 C 符号带前导下划线
+    let has_meta = exports
+        .iter()
+        .any(|export| matches!(export.name(), b"meta" | b"_meta"));
+    if has_meta {
+        Ok(())
+    } else {
+        Err("没有导出 meta，不是插件".to_string())
+    }
+}
+
+// ============ 已加载的插件 ============
+
+/// 加载失败的原因；插件名拿不到时用文件名代替
+pub struct LoadError {
+    pub plugin_name: String,
+    pub reason: String,
+}
+
+/// 插件自己声明的调度方式，已从 FFI 结构拷贝出来
+#[derive(Debug, Clone, Default)]
+pub struct DeclaredSchedule {
+    pub interval_ms: Option<u64>,
+    pub cron: Option<String>,
+}
+
+/// 一个已经 dlopen、读过 meta、完成 ABI 协商并解析好符号的插件。
+///
+/// 启动时构建一次，之后每轮调度只调用缓存的函数指针。
+pub struct LoadedPlugin {
+    pub path: PathBuf,
+    pub name: String,
+    pub version: String,
+    pub kind: String,
+    pub abi_version: u32,
+    /// 协商后的能力位
+    pub capabilities: u64,
+    /// 插件通过 `plugin_schedule` 声明的调度方式（加载时读取一次）
+    pub declared_schedule: DeclaredSchedule,
+    /// 连续失败次数和退避状态
+    pub health: PluginHealth,
+    pub loaded_at: DateTime<Utc>,
+
+    run_with_ctx: Option<PluginRunWithContextFunc>,
+    run: Option<PluginRunFunc>,
+    init: Option<PluginInitFunc>,
+    shutdown: Option<PluginShutdownFunc>,
+    reload_config: Option<PluginReloadConfigFunc>,
+    api_info: Option<PluginApiInfoFunc>,
+
+    /// `isolation = "process"` 时为 Some：init / run / shutdown 都转发给 worker 子进程，
+    /// 本进程只用这个库读 meta 和 plugin_api_info。子进程崩溃后里面是 None，下次执行时重新拉起。
+    worker: Option<Mutex<Option<WorkerProcess>>>,
+
+    /// `.wasm` 插件：init / run / shutdown 都交给 wasmtime 实例，上面的函数指针全是 None
+    wasm: Option<WasmPlugin>,
+
+    /// 上面的函数指针都指向这个库，是否卸载由 Drop 决定；WASM 插件没有动态库
+    library: ManuallyDrop<Option<Library>>,
+    /// 热加载模式下实际 dlopen 的副本，卸载后删除
+    shadow_copy: Option<PathBuf>,
+}
+
+impl LoadedPlugin {
+    /// dlopen + 读取 meta + ABI 协商 + 解析所有可选符号
+    pub fn load(path: &Path) -> Result<Self, LoadError> {
+        Self::load_from(path, path)
+    }
+
+    /// 把已经读到内存里的动态库内容写成 shadow_dir 下一个唯一的文件名，再加载副本。
+    ///
+    /// 同一路径的库如果没能真正卸载（比如插件里有带析构函数的 thread_local），
+    /// 再次 dlopen 会直接拿到旧的那份；换成新文件名后每次都是全新加载，
+    /// 重新编译时覆盖原文件也不会影响已经映射到内存里的代码。
+    /// prod 模式下加载的正是校验过的这份内容，校验之后再替换原文件也没有用。
+    pub fn load_shadow_copy(
+        path: &Path,
+        contents: &[u8],
+        shadow_dir: &Path,
+    ) -> Result<Self, LoadError> {
+        static COPY_SEQ: AtomicU64 = AtomicU64::new(0);
+
+        let file_name = file_name_of(path);
+        let copy_err = |e: std::io::Error| LoadError {
+            plugin_name: file_name.clone(),
+            reason: format!("复制动态库到 {} 失败: {e}", shadow_dir.display()),
+        };
+
+        let mut dir_builder = fs::DirBuilder::new();
+        dir_builder.recursive(true);
+        // 副本目录只允许本用户访问
+        #[cfg(unix)]
+        std::os::unix::fs::DirBuilderExt::mode(&mut dir_builder, 0o700);
+        dir_builder.create(shadow_dir).map_err(copy_err)?;
+        let copy = shadow_dir.join(format!(
+            "{}-{}",
+            COPY_SEQ.fetch_add(1, Ordering::Relaxed),
+            file_name
+        ));
+        fs::write(&copy, contents).map_err(copy_err)?;
+
+        match Self::load_from(path, &copy) {
+            Ok(mut plugin) => {
+                plugin.shadow_copy = Some(copy);
+                Ok(plugin)
+            }
+            Err(e) => {
+                let _ = fs::remove_file(&copy);
+                Err(e)
+            }
+        }
+    }
+
+    /// `path` 是插件在插件目录里的路径（用于标识），`lib_path` 是实际 dlopen 的文件
+    fn load_from(path: &Path, lib_path: &Path) -> Result<Self, LoadError> {
+        let file_name = file_name_of(path);
+
+        let library = unsafe { Library::new(lib_path) }.map_err(|e| LoadError {
+            plugin_name: file_name.clone(),
+            reason: format!("加载动态库失败: {e}"),
+        })?;
+
+        unsafe {
+            let meta_func = library.get::<PluginMetaFunc>(b"meta").map_err(|e| LoadError {
+                plugin_name: file_name.clone(),
+                reason: format!("缺少 meta 函数: {e}"),
+            })?;
+            let meta = meta_func();
+            let name = c_str_to_string(meta.name).unwrap_or_else(|| "<unknown>".to_string());
+            let version =
+                c_str_to_string(meta.version).unwrap_or_else(|| "<unknown>".to_string());
+            let kind = c_str_to_string(meta.kind).unwrap_or_else(|| "<unknown>".to_string());
+
+            let (abi_version, capabilities) =
+                negotiate_abi(&library).map_err(|reason| LoadError {
+                    plugin_name: name.clone(),
+                    reason,
+                })?;
+
+            let run_with_ctx = library
+                .get::<PluginRunWithContextFunc>(b"run_with_ctx")
+                .ok()
+                .map(|f| *f);
+            let run = library.get::<PluginRunFunc>(b"run").ok().map(|f| *f);
+            let init = library.get::<PluginInitFunc>(b"plugin_init").ok().map(|f| *f);
+            let shutdown = library
+                .get::<PluginShutdownFunc>(b"plugin_shutdown")
+                .ok()
+                .map(|f| *f);
+            let reload_config = library
+                .get::<PluginReloadConfigFunc>(b"plugin_reload_config")
+                .ok()
+                .map(|f| *f);
+            let api_info = library
+                .get::<PluginApiInfoFunc>(b"plugin_api_info")
+                .ok()
+                .map(|f| *f);
+
+            let declared_schedule = library
+                .get::<PluginScheduleFunc>(b"plugin_schedule")
+                .map(|f| {
+                    let schedule = f();
+                    DeclaredSchedule {
+                        interval_ms: (schedule.interval_ms > 0).then_some(schedule.interval_ms),
+                        cron: c_str_to_string(schedule.cron),
+                    }
+                })
+                .unwrap_or_default();
+
+            if run_with_ctx.is_none() && run.is_none() && init.is_none() {
+                return Err(LoadError {
+                    plugin_name: name,
+                    reason: "既没有 run_with_ctx / run，也没有 plugin_init".to_string(),
+                });
+            }
+
+            Ok(Self {
+                path: path.to_path_buf(),
+                name,
+                version,
+                kind,
+                abi_version,
+                capabilities,
+                declared_schedule,
+                health: PluginHealth::default(),
+                loaded_at: Utc::now(),
+                run_with_ctx,
+                run,
+                init,
+                shutdown,
+                reload_config,
+                api_info,
+                worker: None,
+                wasm: None,
+                library: ManuallyDrop::new(Some(library)),
+                shadow_copy: None,
+            })
+        }
+    }
+
+    /// 编译 `.wasm` 插件并读取 meta；内容已经在内存里，不需要 shadow 副本
+    pub fn load_wasm(path: &Path, contents: &[u8]) -> Result<Self, LoadError> {
+        let (wasm, meta) = WasmPlugin::load(contents).map_err(|reason| LoadError {
+            plugin_name: file_name_of(path),
+            reason,
+        })?;
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            name: meta.name,
+            version: meta.version,
+            kind: meta.kind,
+            abi_version: WASM_ABI_VERSION,
+            capabilities: WASM_CAPABILITIES,
+            declared_schedule: DeclaredSchedule::default(),
+            health: PluginHealth::default(),
+            loaded_at: Utc::now(),
+            run_with_ctx: None,
+            run: None,
+            init: None,
+            shutdown: None,
+            reload_config: None,
+            api_info: None,
+            worker: None,
+            wasm: Some(wasm),
+            library: ManuallyDrop::new(None),
+            shadow_copy: None,
+        })
+    }
+
+    pub fn is_wasm(&self) -> bool {
+        self.wasm.is_some()
+    }
+
+    /// WASM 插件每次调用的 (fuel, 内存上限字节数)
+    pub fn wasm_limits(&self) -> Option<(u64, usize)> {
+        self.wasm.as_ref().map(WasmPlugin::limits)
+    }
+
+    /// 给插件用的上下文；只在一次调用期间有效
+    fn context(&self) -> PluginContext {
+        PluginContext {
+            host_version: PLUGIN_ABI_VERSION,
+            log_fn: host_log_bridge,
+            emit_metric_fn: host_emit_metric_bridge,
+            capabilities: self.capabilities,
+            emit_metric_with_labels_fn: host_emit_metric_with_labels_bridge,
+            log_with_fields_fn: host_log_with_fields_bridge,
+            get_config_fn: host_get_config_bridge,
+            free_string_fn: host_free_string_bridge,
+            report_failure_fn: host_report_failure_bridge,
+            get_api_listen_fn: host_get_api_listen_bridge,
+            confirm_api_bind_fn: host_confirm_api_bind_bridge,
+            emit_alert_fn: host_emit_alert_bridge,
+            query_metrics_fn: host_query_metrics_bridge,
+            free_metric_buffer_fn: host_free_metric_buffer_bridge,
+            publish_fn: host_publish_bridge,
+            subscribe_fn: host_subscribe_bridge,
+            unsubscribe_fn: host_unsubscribe_bridge,
+        }
+    }
+
+    /// 实际 dlopen 的文件；worker 子进程也加载这一份
+    fn library_path(&self) -> &Path {
+        self.shadow_copy.as_deref().unwrap_or(&self.path)
+    }
+
+    /// 之后的 init / run / shutdown 都在 worker 子进程里执行
+    fn isolate_in_worker(&mut self) {
+        self.worker = Some(Mutex::new(None));
+    }
+
+    pub fn is_isolated(&self) -> bool {
+        self.worker.is_some()
+    }
+
+    /// 插件承诺自己的导出函数都兜住了 panic
+    pub fn is_panic_safe(&self) -> bool {
+        self.capabilities & CAP_PANIC_SAFE != 0
+    }
+
+    /// 调用插件函数：标记插件作用域，收集插件报告的失败。
+    ///
+    /// 这里的 catch_unwind 只兜得住 host 这一侧的 panic（比如 WASM 运行时）。
+    /// 原生插件 `extern "C"` 函数里的 panic 到不了这里，进程直接 abort；
+    /// 所以没有声明 `CAP_PANIC_SAFE` 的原生插件默认在 worker 子进程里运行，见 [`PluginRegistry::load_one`]。
+    fn call_guarded<R>(&self, f: impl FnOnce() -> R) -> Result<R, String> {
+        take_reported_failure();
+        let result = panic::catch_unwind(AssertUnwindSafe(|| with_plugin_scope(&self.name, f)));
+        let reported = take_reported_failure();
+
+        match result {
+            Ok(r) => match reported {
+                Some(reason) => Err(reason),
+                None => Ok(r),
+            },
+            Err(payload) => {
+                let detail = payload
+                    .downcast_ref::<&str>()
+                    .map(|s| s.to_string())
+                    .or_else(|| payload.downcast_ref::<String>().cloned())
+                    .unwrap_or_else(|| "<non-string panic payload>".to_string());
+                Err(format!("panic: {detail}"))
+            }
+        }
+    }
+
+    /// 在 worker 里执行一条命令；子进程不在（首次或上次崩溃）时先拉起
+    fn worker_request(
+        &self,
+        slot: &Mutex<Option<WorkerProcess>>,
+        cmd: &WorkerCommand,
+    ) -> Result<(), String> {
+        let mut slot = slot.lock().unwrap_or_else(|e| e.into_inner());
+        let worker = match slot.as_mut() {
+            Some(w) => w,
+            None => slot.insert(WorkerProcess::spawn(&self.name, self.library_path())?),
+        };
+
+        let timeout = plugin_worker_timeout(&self.name).unwrap_or(RUN_TIMEOUT);
+        let result = worker.request(cmd, timeout);
+        if result.is_err() {
+            // 崩溃或超时：子进程状态已经不可信，kill 掉，下次重新拉起
+            if let Some(w) = slot.take() {
+                w.kill();
+            }
+        }
+        result
+    }
+
+    /// 调用 plugin_init（没有导出则直接成功）
+    pub fn init(&self) -> Result<(), String> {
+        if let Some(wasm) = &self.wasm {
+            if wasm.has_init() {
+                info!("调用 WASM 插件 {} 的 plugin_init()...", self.name);
+            }
+            return self.call_guarded(|| wasm.init()).and_then(|r| r);
+        }
+
+        if let Some(slot) = &self.worker {
+            let worker = WorkerProcess::spawn(&self.name, self.library_path())?;
+            *slot.lock().unwrap_or_else(|e| e.into_inner()) = Some(worker);
+            return Ok(());
+        }
+
+        let Some(init) = self.init else {
+            return Ok(());
+        };
+
+        info!("调用插件 {} 的 plugin_init()...", self.name);
+        let mut ctx = self.context();
+        let code = self.call_guarded(|| init(&mut ctx as *mut PluginContext))?;
+        if code == 0 {
+            Ok(())
+        } else {
+            Err(format!("plugin_init 返回错误码 {code}"))
+        }
+    }
+
+    /// 是否有需要定时调用的入口；纯后台服务型插件（只有 plugin_init）不参与调度
+    pub fn is_schedulable(&self) -> bool {
+        self.run_with_ctx.is_some() || self.run.is_some() || self.wasm.as_ref().is_some_and(WasmPlugin::has_run)
+    }
+
+    /// 执行一轮：优先 run_with_ctx，其次旧版 run；纯后台服务型插件什么都不做。
+    /// panic、插件报告的失败、worker 崩溃都作为 Err 返回。
+    pub fn run_once(&self) -> Result<(), String> {
+        if let Some(wasm) = &self.wasm {
+            info!("执行 WASM 插件 {}: run_with_ctx()", self.name);
+            return self.call_guarded(|| wasm.run()).and_then(|r| r);
+        }
+
+        if let Some(slot) = &self.worker {
+            return self.worker_request(slot, &WorkerCommand::Run);
+        }
+
+        if let Some(run_with_ctx) = self.run_with_ctx {
+            info!("执行插件 {}: run_with_ctx()", self.name);
+            let mut ctx = self.context();
+            self.call_guarded(|| run_with_ctx(&mut ctx as *mut PluginContext))
+        } else if let Some(run) = self.run {
+            info!("执行插件 {}: 旧版 run()", self.name);
+            self.call_guarded(|| run())
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn shutdown(&self) {
+        // 先摘掉订阅，plugin_shutdown 之后回调就不能再被调用了
+        event_bus::unsubscribe_plugin(&self.name);
+
+        if let Some(wasm) = &self.wasm {
+            if let Err(e) = self.call_guarded(|| wasm.shutdown()).and_then(|r| r) {
+                error!("WASM 插件 {} 的 plugin_shutdown 失败: {e}", self.name);
+            }
+            return;
+        }
+
+        if let Some(slot) = &self.worker {
+            if let Some(worker) = slot.lock().unwrap_or_else(|e| e.into_inner()).take() {
+                info!("停止插件 {} 的 worker 子进程", self.name);
+                worker.shutdown(&self.name);
+            }
+            return;
+        }
+
+        if let Some(shutdown) = self.shutdown {
+            info!("调用插件 {} 的 plugin_shutdown()", self.name);
+            if let Err(e) = self.call_guarded(|| shutdown()) {
+                error!("插件 {} 的 plugin_shutdown 失败: {e}", self.name);
+            }
+        }
+    }
+
+    /// 推送新配置；没有实现 plugin_reload_config 的插件忽略
+    pub fn reload_config(&self, json: &str) {
+        if let Some(slot) = &self.worker {
+            // worker 还没起来时下次拉起会直接读到新配置
+            if slot.lock().unwrap_or_else(|e| e.into_inner()).is_none() {
+                return;
+            }
+            let cmd = WorkerCommand::ReloadConfig {
+                config: json.to_string(),
+            };
+            if let Err(e) = self.worker_request(slot, &cmd) {
+                warn!("插件 {} 的 worker 应用新配置失败: {e}", self.name);
+            }
+            return;
+        }
+
+        let Some(reload) = self.reload_config else {
+            return;
+        };
+
+        let c_json = match CString::new(json) {
+            Ok(c) => c,
+            Err(e) => {
+                error!("插件 {} 配置包含非法字符: {e}", self.name);
+                return;
+            }
+        };
+
+        match self.call_guarded(|| reload(c_json.as_ptr())) {
+            Ok(0) => info!("插件 {} 已应用新配置", self.name),
+            Ok(code) => warn!("插件 {} 应用新配置失败: code={code}", self.name),
+            Err(e) => warn!("插件 {} 应用新配置失败: {e}", self.name),
+        }
+    }
+
+    /// 插件通过 plugin_api_info 暴露的 HTTP API 地址
+    /// 有 HTTP API 且支持由 host 分配监听地址
+    fn wants_api_listen(&self) -> bool {
+        self.api_info.is_some() && self.capabilities & CAP_API_LISTEN != 0
+    }
+
+    /// plugin_api_info 声明的 (端口, 路径前缀)
+    pub fn declared_api(&self) -> Option<(u16, String)> {
+        let api_info = self.api_info?;
+        let info = unsafe { api_info() };
+        let prefix = c_str_to_string(info.prefix).unwrap_or_else(|| "/".to_string());
+        Some((info.port, prefix))
+    }
+
+    /// 插件回报过实际监听地址时以它为准，否则用 plugin_api_info 里的端口
+    pub fn api_base_url(&self) -> Option<String> {
+        let (port, prefix) = self.declared_api()?;
+        match api_listen::bound(&self.name) {
+            Some(addr) => Some(api_listen::base_url(&addr, &prefix)),
+            None => Some(format!("http://127.0.0.1:{port}{prefix}")),
+        }
+    }
+
+    /// plugins 表里这个插件的一行；`status` 为 None 时按连续失败次数取 Loaded / Failing
+    pub fn status_record(&self, status: Option<PluginStatus>) -> PluginRecord {
+        let health = self.health.snapshot();
+        let status = status.unwrap_or(if health.consecutive_failures > 0 {
+            PluginStatus::Failing
+        } else {
+            PluginStatus::Loaded
+        });
+        PluginRecord {
+            name: self.name.clone(),
+            version: self.version.clone(),
+            kind: self.kind.clone(),
+            path: self.path.display().to_string(),
+            status,
+            loaded_at: Some(self.loaded_at),
+            last_run_at: health.last_run_at,
+            last_error: health.last_error,
+            consecutive_failures: health.consecutive_failures,
+            updated_at: Utc::now(),
+        }
+    }
+
+    /// 通过存储通道更新 plugins 表
+    pub fn report_status(&self, status: Option<PluginStatus>) {
+        send_storage(StorageMsg::Plugin(self.status_record(status)));
+    }
+}
+
+impl Drop for LoadedPlugin {
+    fn drop(&mut self) {
+        // 有 plugin_init 却没有 plugin_shutdown 的插件可能还有后台线程在跑，
+        // 卸载后线程会执行到已释放的代码，只能让它常驻内存（子进程隔离的插件在本进程里没有 init 过）
+        if self.worker.is_none() && self.init.is_some() && self.shutdown.is_none() {
+            warn!("插件 {} 没有 plugin_shutdown，动态库保持常驻", self.name);
+            return;
+        }
+        unsafe { ManuallyDrop::drop(&mut self.library) };
+        if let Some(copy) = &self.shadow_copy {
+            let _ = fs::remove_file(copy);
+        }
+    }
+}
+
+// ============ 插件注册表 ============
+
+/// [`PluginRegistry::open`] 失败的原因
+enum OpenError {
+    /// 没有导出 `meta`，不是插件，跳过即可
+    NotPlugin(String),
+    /// 是插件但不能加载
+    Rejected(LoadError),
+}
+
+/// 已加载的插件和被拒绝的插件；热加载时会在运行中增删
+pub struct PluginRegistry {
+    plugins: RwLock<Vec<Arc<LoadedPlugin>>>,
+    /// 被拒绝加载的插件：路径 -> 原因
+    rejected: RwLock<HashMap<PathBuf, String>>,
+    /// 热加载模式下先把动态库复制到这里再加载，见 [`LoadedPlugin::load_shadow_copy`]
+    shadow_dir: Option<PathBuf>,
+    /// 文件名 glob 和启用 / 禁用列表
+    filter: PluginFilter,
+    /// prod 模式下的清单校验
+    verifier: Option<PluginVerifier>,
+}
+
+impl PluginRegistry {
+    /// 有 `verifier` 时必须同时给出 `shadow_dir`，保证加载的就是校验过的内容
+    pub fn new(
+        filter: PluginFilter,
+        verifier: Option<PluginVerifier>,
+        shadow_dir: Option<PathBuf>,
+    ) -> Self {
+        Self {
+            plugins: RwLock::default(),
+            rejected: RwLock::default(),
+            shadow_dir,
+            filter,
+            verifier,
+        }
+    }
+
+    pub fn filter(&self) -> &PluginFilter {
+        &self.filter
+    }
+
+    /// 启动时调用一次：加载并初始化所有插件，失败的记录原因
+    pub fn load_all(&self, paths: &[PathBuf]) {
+        for path in paths {
+            self.load_one(path);
+        }
+    }
+
+    /// 加载并初始化一个插件；失败时记录原因并返回 None，不是插件或被配置排除时只打日志
+    pub fn load_one(&self, path: &Path) -> Option<Arc<LoadedPlugin>> {
+        write_lock(&self.rejected).remove(path);
+
+        let mut plugin = match self.open(path) {
+            Ok(p) => p,
+            Err(OpenError::NotPlugin(reason)) => {
+                info!("跳过 {}: {reason}", path.display());
+                return None;
+            }
+            Err(OpenError::Rejected(e)) => {
+                self.reject(path, &e.plugin_name, e.reason);
+                return None;
+            }
+        };
+
+        // 文件名对不上 disabled 的插件只能在读过 meta 之后按插件名排除；
+        // 还没有调用 plugin_init，直接丢弃即可卸载
+        if let Err(reason) = self.filter.check_name(&plugin.name) {
+            info!("跳过插件 {} ({}): {reason}", plugin.name, path.display());
+            return None;
+        }
+
+        // 插件 extern "C" 函数里的 panic 会让整个进程 abort，host 兜不住；
+        // 没有声明 CAP_PANIC_SAFE 的原生插件默认放进子进程
+        match (plugin_isolation(&plugin.name), plugin.is_wasm()) {
+            (Some(Isolation::Process), true) => {
+                warn!(
+                    "WASM 插件 {} 本来就在沙箱里运行，忽略 isolation = \"process\"",
+                    plugin.name
+                );
+            }
+            (_, true) => {}
+            (Some(Isolation::Process), false) => plugin.isolate_in_worker(),
+            (None, false) if !plugin.is_panic_safe() => {
+                info!(
+                    "插件 {} 没有声明 CAP_PANIC_SAFE，在 worker 子进程中运行（isolation = \"none\" 可以改回本进程）",
+                    plugin.name
+                );
+                plugin.isolate_in_worker();
+            }
+            (Some(Isolation::None), false) if !plugin.is_panic_safe() => {
+                warn!(
+                    "插件 {} 没有声明 CAP_PANIC_SAFE，按 isolation = \"none\" 在本进程中运行，它 panic 时整个 bot-host 会退出",
+                    plugin.name
+                );
+            }
+            (_, false) => {}
+        }
+
+        info!(
+            "插件信息: name={}, version={}, kind={}, abi_version={}, capabilities={:#x}, isolated={}, path={}",
+            plugin.name,
+            plugin.version,
+            plugin.kind,
+            plugin.abi_version,
+            plugin.capabilities,
+            plugin.is_isolated(),
+            plugin.path.display()
+        );
+
+        // 地址要在 plugin_init（或拉起 worker）之前分配好
+        if plugin.wants_api_listen() {
+            api_listen::assign(&plugin.name, plugin_api_transport(&plugin.name));
+        }
+
+        if let Err(reason) = plugin.init() {
+            error!("插件 {} 初始化失败: {reason}", plugin.name);
+            record_rejection(&plugin.name, &reason);
+            report_rejected(path, &plugin.name, &plugin.version, &plugin.kind, &reason);
+            write_lock(&self.rejected).insert(path.to_path_buf(), reason);
+            event_bus::unsubscribe_plugin(&plugin.name);
+            // init 过程中可能已经起了后台线程，库不能卸载
+            std::mem::forget(plugin);
+            return None;
+        }
+
+        let plugin = Arc::new(plugin);
+        write_lock(&self.plugins).push(plugin.clone());
+        plugin.report_status(Some(PluginStatus::Loaded));
+        Some(plugin)
+    }
+
+    /// 读取 + 清单校验 + 检查导出符号 + dlopen，不检查启用列表、不调用 plugin_init。
+    ///
+    /// `bot-host plugins list` 用它查看插件信息，丢弃返回值即可卸载。
+    pub fn inspect(&self, path: &Path) -> Result<LoadedPlugin, String> {
+        self.open(path).map_err(|e| match e {
+            OpenError::NotPlugin(reason) => reason,
+            OpenError::Rejected(e) => e.reason,
+        })
+    }
+
+    fn open(&self, path: &Path) -> Result<LoadedPlugin, OpenError> {
+        let rejected = |reason: String| {
+            OpenError::Rejected(LoadError {
+                plugin_name: file_name_of(path),
+                reason,
+            })
+        };
+
+        // 只读一次文件：校验、检查导出符号和写 shadow 副本用的是同一份内容
+        let contents = fs::read(path).map_err(|e| rejected(format!("读取动态库失败: {e}")))?;
+
+        let manifest_entry = match &self.verifier {
+            Some(verifier) => Some(verifier.verify(path, &contents).map_err(rejected)?),
+            None => None,
+        };
+
+        let plugin = if is_wasm_file(path) {
+            LoadedPlugin::load_wasm(path, &contents)
+        } else {
+            check_plugin_exports(&contents).map_err(OpenError::NotPlugin)?;
+            match &self.shadow_dir {
+                Some(dir) => LoadedPlugin::load_shadow_copy(path, &contents, dir),
+                None => LoadedPlugin::load(path),
+            }
+        }
+        .map_err(OpenError::Rejected)?;
+
+        if let Some(entry) = manifest_entry
+            && let Err(reason) = entry.check_meta(&plugin.name, &plugin.version)
+        {
+            return Err(OpenError::Rejected(LoadError {
+                plugin_name: plugin.name.clone(),
+                reason,
+            }));
+        }
+        Ok(plugin)
+    }
+
+    fn reject(&self, path: &Path, plugin_name: &str, reason: String) {
+        error!("拒绝加载插件 {plugin_name} ({}): {reason}", path.display());
+        record_rejection(plugin_name, &reason);
+        report_rejected(path, plugin_name, "<unknown>", "<unknown>", &reason);
+        write_lock(&self.rejected).insert(path.to_path_buf(), reason);
+    }
+
+    /// 从注册表里移除某个路径的插件；不调用 shutdown，调用方要先停掉它的调度
+    pub fn remove(&self, path: &Path) -> Option<Arc<LoadedPlugin>> {
+        write_lock(&self.rejected).remove(path);
+        let mut plugins = write_lock(&self.plugins);
+        let index = plugins.iter().position(|p| p.path == path)?;
+        Some(plugins.remove(index))
+    }
+
+    /// 当前已加载插件的快照
+    pub fn plugins(&self) -> Vec<Arc<LoadedPlugin>> {
+        self.plugins
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    pub fn rejected(&self) -> HashMap<PathBuf, String> {
+        self.rejected
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    /// 依次调用所有插件的 plugin_shutdown
+    pub fn shutdown_all(&self) {
+        for plugin in self.plugins() {
+            plugin.shutdown();
+        }
+    }
+}
+
+/// 被拒绝的插件也写进 plugins 表，插件名拿不到时是文件名
+fn report_rejected(path: &Path, plugin_name: &str, version: &str, kind: &str, reason: &str) {
+    send_storage(StorageMsg::Plugin(PluginRecord {
+        name: plugin_name.to_string(),
+        version: version.to_string(),
+        kind: kind.to_string(),
+        path: path.display().to_string(),
+        status: PluginStatus::Rejected,
+        loaded_at: None,
+        last_run_at: None,
+        last_error: Some(reason.to_string()),
+        consecutive_failures: 0,
+        updated_at: Utc::now(),
+    }));
+}
+
+/// 插件名拿不到时用文件名标识插件
+fn file_name_of(path: &Path) -> String {
+    path.file_name()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_else(|| path.display().to_string())
+}
+
+fn write_lock<T>(lock: &RwLock<T>) -> std::sync::RwLockWriteGuard<'_, T> {
+    lock.write().unwrap_or_else(|e| e.into_inner())
+}
+
+// ============ 插件 API 注册 ============
+
+/// 把插件通过 plugin_api_info 暴露的 HTTP API 写入 plugin_apis 表
+pub async fn register_plugin_api(plugin: &LoadedPlugin, db: &Db) {
+    let Some(base_url) = plugin.api_base_url() else {
+        // 没有 plugin_api_info，说明该插件不暴露 HTTP API，直接略过
+        return;
+    };
+
+    if let Err(e) = db.upsert_plugin_api(&plugin.name, &base_url).await {
+        error!(
+            "注册插件 API 失败: plugin={}, base_url={}, err={e}",
+            plugin.name, base_url
+        );
+    } else {
+        info!(
+            "已注册插件 API: plugin={}, base_url={}",
+            plugin.name, base_url
+        );
+    }
+}
+
+// ============ ABI 协商 ============
+
+/// 读取插件导出的 `plugin_abi_version` / `plugin_capabilities`，和 host 的能力做协商，
+/// 返回 (ABI 版本, 协商后的能力位)；规则见 [`negotiate_capabilities`]。
+///
+/// 没有导出 `plugin_abi_version` 的按 ABI 1（旧插件）处理。
+unsafe fn negotiate_abi(lib: &Library) -> Result<(u32, u64), String> {
+    let abi_version = match unsafe { lib.get::<PluginAbiVersionFunc>(b"plugin_abi_version") } {
+        Ok(f) => f(),
+        Err(_) => 1,
+    };
+    let declared = unsafe { lib.get::<PluginCapabilitiesFunc>(b"plugin_capabilities") }
+        .ok()
+        .map(|f| f());
+    let capabilities = negotiate_capabilities(abi_version, declared, HOST_CAPABILITIES)?;
+    Ok((abi_version, capabilities))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn disabled_matches_file_name_before_dlopen() {
+        let cfg = PluginConfig {
+            disabled: Some(vec!["ai-analyzer".to_string(), "Cpu_Monitor".to_string()]),
+            ..Default::default()
+        };
+        let filter = PluginFilter::from_config(&cfg, "so");
+
+        let hit = |file: &str| filter.disabled_by_file_name(Path::new(file));
+        assert_eq!(hit("plugins/libai_analyzer.so"), Some("ai-analyzer"));
+        assert_eq!(hit("plugins/ai-analyzer.wasm"), Some("ai-analyzer"));
+        assert_eq!(hit("plugins/libcpu_monitor.so"), Some("Cpu_Monitor"));
+        assert_eq!(hit("plugins/libapi_monitor.so"), None);
+        assert_eq!(hit("plugins/libai_analyzer_v2.so"), None);
+
+        // 文件名对不上、dlopen 之后按 meta 里的名字排除时用同样的规则
+        assert!(filter.check_name("cpu-monitor").is_err());
+        assert!(filter.check_name("AI_Analyzer").is_err());
+        assert!(filter.check_name("api-monitor").is_ok());
+        assert!(filter.check_name("ai-analyzer-v2").is_ok());
+    }
+
+    #[test]
+    fn enabled_list_uses_the_same_name_rules() {
+        let cfg = PluginConfig {
+            enabled: Some(vec!["cpu_monitor".to_string()]),
+            disabled: Some(vec!["cpu-monitor".to_string()]),
+            ..Default::default()
+        };
+        let filter = PluginFilter::from_config(&cfg, "so");
+        // disabled 优先
+        assert_eq!(
+            filter.check_name("Cpu-Monitor"),
+            Err("在 [plugin].disabled 列表中".to_string())
+        );
+
+        let cfg = PluginConfig {
+            enabled: Some(vec!["cpu_monitor".to_string()]),
+            ..Default::default()
+        };
+        let filter = PluginFilter::from_config(&cfg, "so");
+        assert!(filter.check_name("cpu-monitor").is_ok());
+        assert_eq!(
+            filter.check_name("api-monitor"),
+            Err("不在 [plugin].enabled 列表中".to_string())
+        );
+    }
+}
@@ -0,0 +1,383 @@
+use std::{
+    collections::HashMap,
+    fmt,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use chrono::Utc;
+use core_types::LogLevel;
+use tokio::{sync::watch, task, task::JoinHandle};
+use tracing::{debug, error, info, warn};
+
+use crate::bridge::{record_plugin_log, record_plugin_metric};
+use crate::config::{config_generation, plugin_schedule_override, ScheduleOverride};
+use crate::registry::{DeclaredSchedule, LoadedPlugin};
+use crate::self_metrics;
+
+/// 每次执行的耗时（毫秒）
+pub const PLUGIN_RUN_DURATION_METRIC: &str = "plugin_run_duration_ms";
+/// 上一轮还没跑完、本次调度被跳过时记 1
+pub const PLUGIN_RUN_OVERRUN_METRIC: &str = "plugin_run_overrun";
+
+// ============ 调度方式 ============
+
+pub enum Schedule {
+    Interval(Duration),
+    Cron {
+        expr: String,
+        schedule: Box<cron::Schedule>,
+    },
+}
+
+impl Schedule {
+    /// 距离下一次触发还要等多久
+    fn next_delay(&self) -> Duration {
+        match self {
+            Schedule::Interval(d) => *d,
+            Schedule::Cron { schedule, .. } => schedule
+                .upcoming(Utc)
+                .next()
+                .and_then(|at| (at - Utc::now()).to_std().ok())
+                // 表达式不会再触发（比如指定了过去的年份），退化成每分钟检查一次
+                .unwrap_or(Duration::from_secs(60)),
+        }
+    }
+}
+
+impl fmt::Display for Schedule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Schedule::Interval(d) => write!(f, "每 {}ms", d.as_millis()),
+            Schedule::Cron { expr, .. } => write!(f, "cron \"{expr}\""),
+        }
+    }
+}
+
+impl PartialEq for Schedule {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Schedule::Interval(a), Schedule::Interval(b)) => a == b,
+            (Schedule::Cron { expr: a, .. }, Schedule::Cron { expr: b, .. }) => a == b,
+            _ => false,
+        }
+    }
+}
+
+fn parse_cron(plugin_name: &str, expr: &str) -> Option<Schedule> {
+    match cron::Schedule::from_str(expr) {
+        Ok(schedule) => Some(Schedule::Cron {
+            expr: expr.to_string(),
+            schedule: Box::new(schedule),
+        }),
+        Err(e) => {
+            warn!("插件 {plugin_name} 的 cron 表达式 \"{expr}\" 无效: {e}，忽略");
+            None
+        }
+    }
+}
+
+/// 确定插件的调度方式，优先级从高到低：
+///
+/// 1. `[plugins.<name>].cron`
+/// 2. `[plugins.<name>].interval_secs`
+/// 3. 插件 `plugin_schedule` 声明的 cron
+/// 4. 插件 `plugin_schedule` 声明的 interval_ms
+/// 5. `[plugin].default_interval`
+pub fn resolve_schedule(plugin: &LoadedPlugin, default_interval: Duration) -> Schedule {
+    let overrides = plugin_schedule_override(&plugin.name);
+    pick_schedule(
+        &plugin.name,
+        &overrides,
+        &plugin.declared_schedule,
+        default_interval,
+    )
+}
+
+/// [`resolve_schedule`] 的优先级判断；无效的 cron 跳过，继续看下一级
+fn pick_schedule(
+    plugin_name: &str,
+    overrides: &ScheduleOverride,
+    declared: &DeclaredSchedule,
+    default_interval: Duration,
+) -> Schedule {
+    if let Some(schedule) = overrides
+        .cron
+        .as_deref()
+        .and_then(|expr| parse_cron(plugin_name, expr))
+    {
+        return schedule;
+    }
+    if let Some(secs) = overrides.interval_secs.filter(|s| *s > 0) {
+        return Schedule::Interval(Duration::from_secs(secs));
+    }
+    if let Some(schedule) = declared
+        .cron
+        .as_deref()
+        .and_then(|expr| parse_cron(plugin_name, expr))
+    {
+        return schedule;
+    }
+    if let Some(ms) = declared.interval_ms {
+        return Schedule::Interval(Duration::from_millis(ms));
+    }
+    Schedule::Interval(default_interval)
+}
+
+fn jitter(max: Duration) -> Duration {
+    let max_ms = max.as_millis() as u64;
+    if max_ms == 0 {
+        return Duration::ZERO;
+    }
+    Duration::from_millis(fastrand::u64(0..=max_ms))
+}
+
+// ============ 每个插件一个调度任务 ============
+
+/// 一个调度任务：停止信号 + 任务句柄
+type ScheduleTask = (watch::Sender<bool>, JoinHandle<()>);
+
+/// 所有插件的调度任务，按插件路径单独启停（热加载时只动变化的那个）
+pub struct PluginSchedulers {
+    default_interval: Duration,
+    max_jitter: Duration,
+    tasks: Mutex<HashMap<PathBuf, ScheduleTask>>,
+}
+
+impl PluginSchedulers {
+    pub fn new(default_interval: Duration, max_jitter: Duration) -> Self {
+        Self {
+            default_interval,
+            max_jitter,
+            tasks: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// 为有 run 入口的插件起一个独立的 tokio 任务
+    pub fn start(&self, plugin: &Arc<LoadedPlugin>) {
+        if !plugin.is_schedulable() {
+            return;
+        }
+
+        let (stop_tx, stop_rx) = watch::channel(false);
+        let handle = task::spawn(run_plugin_schedule(
+            plugin.clone(),
+            self.default_interval,
+            self.max_jitter,
+            stop_rx,
+        ));
+
+        let previous = self
+            .tasks
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(plugin.path.clone(), (stop_tx, handle));
+        if let Some((old_stop, _)) = previous {
+            let _ = old_stop.send(true);
+        }
+    }
+
+    /// 停止某个插件的调度，等它当前这一轮执行完
+    pub async fn stop(&self, path: &Path) {
+        let entry = self
+            .tasks
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(path);
+        if let Some((stop_tx, handle)) = entry {
+            let _ = stop_tx.send(true);
+            let _ = handle.await;
+        }
+    }
+
+    /// 停止全部调度，等所有正在执行的插件跑完
+    pub async fn stop_all(&self) {
+        let entries: Vec<_> = self
+            .tasks
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .drain()
+            .map(|(_, entry)| entry)
+            .collect();
+
+        for (stop_tx, _) in &entries {
+            let _ = stop_tx.send(true);
+        }
+        for (_, handle) in entries {
+            let _ = handle.await;
+        }
+    }
+}
+
+async fn run_plugin_schedule(
+    plugin: Arc<LoadedPlugin>,
+    default_interval: Duration,
+    max_jitter: Duration,
+    mut shutdown: watch::Receiver<bool>,
+) {
+    let mut generation = config_generation();
+    let mut schedule = resolve_schedule(&plugin, default_interval);
+    info!("插件 {} 调度方式: {schedule}", plugin.name);
+
+    let mut in_flight: Option<JoinHandle<()>> = None;
+
+    // 启动后第一次只等一个随机抖动，各插件错开执行
+    let mut delay = jitter(max_jitter);
+
+    loop {
+        tokio::select! {
+            _ = shutdown.changed() => break,
+            _ = tokio::time::sleep(delay) => {}
+        }
+
+        // SIGHUP 之后重新确定调度方式
+        let current = config_generation();
+        if current != generation {
+            generation = current;
+            let updated = resolve_schedule(&plugin, default_interval);
+            if updated != schedule {
+                info!("插件 {} 调度方式变更: {schedule} -> {updated}", plugin.name);
+                schedule = updated;
+            }
+        }
+
+        if in_flight.as_ref().is_some_and(|h| !h.is_finished()) {
+            warn!("插件 {} 上一轮尚未结束，跳过本次调度", plugin.name);
+            record_plugin_metric(&plugin.name, PLUGIN_RUN_OVERRUN_METRIC, 1.0);
+        } else if let Some(remaining) = plugin.health.backoff_remaining() {
+            debug!(
+                "插件 {} 处于失败退避中，{}s 后再试",
+                plugin.name,
+                remaining.as_secs()
+            );
+        } else {
+            let plugin = plugin.clone();
+            // 插件调用是同步 FFI，放到阻塞线程池，避免卡住其他插件的调度
+            in_flight = Some(task::spawn_blocking(move || run_and_record(&plugin)));
+        }
+
+        delay = schedule.next_delay() + jitter(max_jitter);
+    }
+
+    if let Some(handle) = in_flight {
+        let _ = handle.await;
+    }
+}
+
+/// 执行一轮并记录耗时和健康状态；失败后按指数退避暂停调度
+fn run_and_record(plugin: &LoadedPlugin) {
+    let started = Instant::now();
+    let result = plugin.run_once();
+    let elapsed = started.elapsed();
+    record_plugin_metric(
+        &plugin.name,
+        PLUGIN_RUN_DURATION_METRIC,
+        elapsed.as_secs_f64() * 1000.0,
+    );
+    self_metrics::record_plugin_run(&plugin.name, elapsed, result.is_ok());
+
+    match result {
+        Ok(()) => {
+            if plugin.health.record_success() {
+                info!("插件 {} 已恢复正常", plugin.name);
+            }
+        }
+        Err(reason) => {
+            let (failures, backoff) = plugin.health.record_failure(&reason);
+            let message = format!(
+                "插件执行失败（连续 {failures} 次）: {reason}，{}s 后重试",
+                backoff.as_secs()
+            );
+            error!("[{}] {message}", plugin.name);
+            record_plugin_log(&plugin.name, LogLevel::Error, message);
+        }
+    }
+    plugin.report_status(None);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::parse_schedule_override;
+
+    const DEFAULT: Duration = Duration::from_secs(60);
+
+    fn pick(overrides: ScheduleOverride, declared: DeclaredSchedule) -> String {
+        pick_schedule("test", &overrides, &declared, DEFAULT).to_string()
+    }
+
+    fn overrides(cron: Option<&str>, interval_secs: Option<u64>) -> ScheduleOverride {
+        ScheduleOverride {
+            cron: cron.map(str::to_string),
+            interval_secs,
+        }
+    }
+
+    fn declared(cron: Option<&str>, interval_ms: Option<u64>) -> DeclaredSchedule {
+        DeclaredSchedule {
+            cron: cron.map(str::to_string),
+            interval_ms,
+        }
+    }
+
+    #[test]
+    fn config_cron_wins_over_everything() {
+        let got = pick(
+            overrides(Some("0 * * * * *"), Some(30)),
+            declared(Some("0 0 * * * *"), Some(500)),
+        );
+        assert_eq!(got, "cron \"0 * * * * *\"");
+    }
+
+    #[test]
+    fn config_interval_wins_over_declared() {
+        let got = pick(
+            overrides(None, Some(30)),
+            declared(Some("0 0 * * * *"), Some(500)),
+        );
+        assert_eq!(got, "每 30000ms");
+    }
+
+    #[test]
+    fn declared_cron_wins_over_declared_interval() {
+        let got = pick(
+            overrides(None, None),
+            declared(Some("0 0 * * * *"), Some(500)),
+        );
+        assert_eq!(got, "cron \"0 0 * * * *\"");
+    }
+
+    #[test]
+    fn declared_interval_wins_over_default() {
+        let got = pick(overrides(None, None), declared(None, Some(500)));
+        assert_eq!(got, "每 500ms");
+    }
+
+    #[test]
+    fn falls_back_to_default_interval() {
+        let got = pick(overrides(None, None), declared(None, None));
+        assert_eq!(got, "每 60000ms");
+    }
+
+    #[test]
+    fn invalid_cron_and_zero_interval_fall_through() {
+        let got = pick(
+            overrides(Some("not a cron"), Some(0)),
+            declared(Some("also bad"), Some(500)),
+        );
+        assert_eq!(got, "每 500ms");
+    }
+
+    #[test]
+    fn mistyped_field_is_an_error_not_a_silent_default() {
+        assert!(
+            parse_schedule_override(r#"{"cron": "0 * * * * *", "interval_secs": "30"}"#).is_err()
+        );
+        let parsed = parse_schedule_override(r#"{"interval_secs": 30, "isolation": "process"}"#)
+            .expect("无关字段不影响解析");
+        assert_eq!(parsed.interval_secs, Some(30));
+        assert_eq!(parsed.cron, None);
+    }
+}
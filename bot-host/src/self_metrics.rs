@@ -0,0 +1,215 @@
+//! bot-host 自身的指标：记在插件名 `bot-host` 下，和插件上报的指标走同一条写入路径，
+//! dashboard 和 ai-analyzer 不用区分就能监控 host 自己。
+//!
+//! | 指标 | 标签 | 含义 |
+//! |---|---|---|
+//! | `plugin_runs_total` | `plugin` | 累计执行次数 |
+//! | `plugin_run_errors_total` | `plugin` | 累计失败次数 |
+//! | `plugin_run_duration_avg_ms` | `plugin` | 本周期内的平均耗时，没有执行时不上报 |
+//! | `plugin_run_duration_max_ms` | `plugin` | 本周期内的最长耗时，没有执行时不上报 |
+//! | `plugins_loaded` | | 当前已加载的插件数 |
+//! | `storage_write_duration_avg_ms` | | 本周期内每批写库的平均耗时，没有写库时不上报 |
+//! | `storage_write_errors_total` | | 累计写库失败的消息数 |
+//! | `storage_queue_depth` | | 写入队列里等待的消息数 |
+//! | `storage_dropped_total` | | 写入队列累计丢弃的消息数 |
+//! | `process_rss_bytes` | | bot-host 进程的常驻内存 |
+//! | `process_cpu_percent` | | bot-host 进程的 CPU 占用（100 表示占满一个核） |
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, MutexGuard, OnceLock},
+    time::Duration,
+};
+
+use chrono::Utc;
+use core_types::Metric;
+use sysinfo::{Pid, System};
+use tokio::task::{self, JoinHandle};
+use tracing::{info, warn};
+
+use crate::config::SelfMetricsConfig;
+use crate::registry::PluginRegistry;
+use crate::storage_writer;
+
+/// host 自己的指标记在这个插件名下
+pub const HOST_PLUGIN_NAME: &str = "bot-host";
+
+const DEFAULT_INTERVAL: Duration = Duration::from_secs(10);
+
+/// 某个插件的执行统计；`window_*` 每次上报后清零
+#[derive(Default)]
+struct RunStats {
+    runs: u64,
+    errors: u64,
+    window_runs: u64,
+    window_total_ms: f64,
+    window_max_ms: f64,
+}
+
+#[derive(Default)]
+struct Stats {
+    runs: HashMap<String, RunStats>,
+    storage_write_errors: u64,
+    window_writes: u64,
+    window_write_ms: f64,
+}
+
+static STATS: OnceLock<Mutex<Stats>> = OnceLock::new();
+
+fn stats() -> MutexGuard<'static, Stats> {
+    STATS
+        .get_or_init(Mutex::default)
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+}
+
+// ============ 记录 ============
+
+/// 调度器每执行完一轮调用一次
+pub fn record_plugin_run(plugin_name: &str, elapsed: Duration, ok: bool) {
+    let ms = elapsed.as_secs_f64() * 1000.0;
+    let mut stats = stats();
+    let run = stats.runs.entry(plugin_name.to_string()).or_default();
+    run.runs += 1;
+    if !ok {
+        run.errors += 1;
+    }
+    run.window_runs += 1;
+    run.window_total_ms += ms;
+    run.window_max_ms = run.window_max_ms.max(ms);
+}
+
+/// 写入任务每写完一批调用一次；`failed` 是这一批里没能写进去的消息数
+pub fn record_storage_write(elapsed: Duration, failed: usize) {
+    let mut stats = stats();
+    stats.window_writes += 1;
+    stats.window_write_ms += elapsed.as_secs_f64() * 1000.0;
+    stats.storage_write_errors += failed as u64;
+}
+
+// ============ 定期上报 ============
+
+/// 启动上报任务；`[self_metrics].enabled = false` 时返回 None
+pub fn spawn(registry: Arc<PluginRegistry>, config: &SelfMetricsConfig) -> Option<JoinHandle<()>> {
+    if !config.enabled.unwrap_or(true) {
+        info!("bot-host 自身指标已关闭");
+        return None;
+    }
+    let interval = config
+        .interval_secs
+        .filter(|s| *s > 0)
+        .map(Duration::from_secs)
+        .unwrap_or(DEFAULT_INTERVAL);
+
+    Some(task::spawn(async move {
+        let mut process = ProcessProbe::new();
+        let mut reported_dropped = 0;
+        let mut ticker = tokio::time::interval(interval);
+        // 第一次 tick 立即返回，先采一次样作为 CPU 占用的基准
+        ticker.tick().await;
+        process.sample();
+        loop {
+            ticker.tick().await;
+            let metrics = collect(&registry, &mut process, &mut reported_dropped);
+            storage_writer::push_host_metrics(metrics);
+        }
+    }))
+}
+
+fn collect(
+    registry: &PluginRegistry,
+    process: &mut ProcessProbe,
+    reported_dropped: &mut u64,
+) -> Vec<Metric> {
+    let mut metrics = Vec::new();
+    let mut push = |name: &str, value: f64, plugin: Option<&str>| {
+        let labels = plugin
+            .map(|p| HashMap::from([("plugin".to_string(), p.to_string())]))
+            .unwrap_or_default();
+        metrics.push(Metric {
+            time: Utc::now(),
+            plugin: HOST_PLUGIN_NAME.to_string(),
+            name: name.to_string(),
+            value,
+            labels,
+        });
+    };
+
+    {
+        let mut stats = stats();
+        for (plugin, run) in stats.runs.iter_mut() {
+            push("plugin_runs_total", run.runs as f64, Some(plugin));
+            push("plugin_run_errors_total", run.errors as f64, Some(plugin));
+            if run.window_runs > 0 {
+                let avg = run.window_total_ms / run.window_runs as f64;
+                push("plugin_run_duration_avg_ms", avg, Some(plugin));
+                push("plugin_run_duration_max_ms", run.window_max_ms, Some(plugin));
+            }
+            run.window_runs = 0;
+            run.window_total_ms = 0.0;
+            run.window_max_ms = 0.0;
+        }
+
+        if stats.window_writes > 0 {
+            let avg = stats.window_write_ms / stats.window_writes as f64;
+            push("storage_write_duration_avg_ms", avg, None);
+        }
+        push(
+            "storage_write_errors_total",
+            stats.storage_write_errors as f64,
+            None,
+        );
+        stats.window_writes = 0;
+        stats.window_write_ms = 0.0;
+    }
+
+    if let Some(queue) = storage_writer::queue_stats() {
+        if queue.dropped > *reported_dropped {
+            warn!(
+                "存储队列已满（容量 {}，策略 {:?}），最近丢弃了 {} 条消息",
+                queue.capacity,
+                queue.policy,
+                queue.dropped - *reported_dropped
+            );
+            *reported_dropped = queue.dropped;
+        }
+        push("storage_queue_depth", queue.depth as f64, None);
+        push("storage_dropped_total", queue.dropped as f64, None);
+    }
+
+    push("plugins_loaded", registry.plugins().len() as f64, None);
+
+    if let Some((rss, cpu)) = process.sample() {
+        push("process_rss_bytes", rss as f64, None);
+        push("process_cpu_percent", cpu as f64, None);
+    }
+    metrics
+}
+
+/// 采集本进程的内存和 CPU；CPU 占用是相对上一次采样算的
+struct ProcessProbe {
+    system: System,
+    pid: Option<Pid>,
+}
+
+impl ProcessProbe {
+    fn new() -> Self {
+        let pid = sysinfo::get_current_pid()
+            .map_err(|e| warn!("无法获取 bot-host 进程号，不上报进程指标: {e}"))
+            .ok();
+        Self {
+            system: System::new(),
+            pid,
+        }
+    }
+
+    /// (常驻内存字节数, CPU 占用百分比)
+    fn sample(&mut self) -> Option<(u64, f32)> {
+        let pid = self.pid?;
+        if !self.system.refresh_process(pid) {
+            return None;
+        }
+        let process = self.system.process(pid)?;
+        Some((process.memory(), process.cpu_usage()))
+    }
+}
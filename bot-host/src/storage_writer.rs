@@ -0,0 +1,571 @@
+//! 日志 / 指标 / 告警 / 插件状态的异步写入
+//!
+//! `send_storage` 把消息放进有界队列，写入任务攒够 `batch_size` 条、或者第一条消息
+//! 进队后等满 `flush_interval_ms`，就整批写库（日志和指标是多行 INSERT + 事务）。
+//! 队列满时按 `[storage].overflow` 丢最旧的、丢最新的，或者让发送方等待。
+//! 写库失败的消息放回队首，退避后重试；连续失败 `MAX_WRITE_ATTEMPTS` 次才丢弃，计入丢弃条数。
+//! 退出时 `shutdown` 关闭队列，写入任务把剩下的消息写完后结束。
+//!
+//! `bot-host plugins run` 调试插件时不写库，`print_to_console` 之后指标直接打印出来。
+
+use std::{
+    collections::VecDeque,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Condvar, Mutex, MutexGuard, OnceLock,
+    },
+    time::Duration,
+};
+
+use core_types::{LogEvent, Metric};
+use storage::Db;
+use tokio::{
+    runtime::{Handle, RuntimeFlavor},
+    sync::Notify,
+    task::{self, JoinHandle},
+    time::{self, Instant},
+};
+use tracing::{debug, error, info, warn};
+
+use crate::bridge::StorageMsg;
+use crate::config::{OverflowPolicy, StorageConfig};
+use crate::self_metrics;
+
+const DEFAULT_QUEUE_CAPACITY: usize = 10_000;
+const DEFAULT_BATCH_SIZE: usize = 500;
+const DEFAULT_FLUSH_INTERVAL: Duration = Duration::from_millis(200);
+/// docker stop 默认 10 秒后 SIGKILL，留出停插件的时间
+const DEFAULT_DRAIN_TIMEOUT: Duration = Duration::from_secs(5);
+/// block 策略在 current_thread runtime 上最多等这么久，之后按 drop_newest 处理
+const BLOCK_TIMEOUT: Duration = Duration::from_secs(1);
+/// 写库失败后第一次重试前等多久，之后每次翻倍，最多 `MAX_RETRY_BACKOFF`
+const RETRY_BACKOFF: Duration = Duration::from_millis(200);
+const MAX_RETRY_BACKOFF: Duration = Duration::from_secs(5);
+/// 连续这么多次写库失败后丢弃这批消息，避免一条写不进去的消息永远卡住队列（约 16 秒）
+const MAX_WRITE_ATTEMPTS: u32 = 8;
+
+#[derive(Default)]
+struct QueueState {
+    items: VecDeque<StorageMsg>,
+    /// 关闭后不再接收新消息
+    closed: bool,
+}
+
+struct StorageQueue {
+    state: Mutex<QueueState>,
+    /// block 策略下发送方在这里等空位
+    not_full: Condvar,
+    /// 有新消息时唤醒写入任务
+    ready: Notify,
+    capacity: usize,
+    policy: OverflowPolicy,
+    dropped: AtomicU64,
+}
+
+static QUEUE: OnceLock<StorageQueue> = OnceLock::new();
+static PRINT_TO_CONSOLE: AtomicBool = AtomicBool::new(false);
+
+impl StorageQueue {
+    fn new(capacity: usize, policy: OverflowPolicy) -> Self {
+        Self {
+            state: Mutex::new(QueueState {
+                items: VecDeque::with_capacity(capacity.min(DEFAULT_QUEUE_CAPACITY)),
+                closed: false,
+            }),
+            not_full: Condvar::new(),
+            ready: Notify::new(),
+            capacity,
+            policy,
+            dropped: AtomicU64::new(0),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, QueueState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn push(&self, msg: StorageMsg, policy: OverflowPolicy) {
+        let mut state = self.lock();
+        if state.items.len() >= self.capacity && !state.closed {
+            match policy {
+                OverflowPolicy::DropNewest => {
+                    self.dropped.fetch_add(1, Ordering::Relaxed);
+                    return;
+                }
+                OverflowPolicy::DropOldest => {
+                    state.items.pop_front();
+                    self.dropped.fetch_add(1, Ordering::Relaxed);
+                }
+                OverflowPolicy::Block => {
+                    state = match Handle::try_current().map(|h| h.runtime_flavor()) {
+                        // 在 runtime 线程上等待时先把线程让出来，写入任务才能继续跑
+                        Ok(RuntimeFlavor::MultiThread) => {
+                            task::block_in_place(|| self.wait_for_space(state, None))
+                        }
+                        // current_thread runtime 不能 block_in_place；正好在 runtime 线程上时
+                        // 写入任务跑不起来，只能限时等待，等不到就丢掉
+                        Ok(_) => self.wait_for_space(state, Some(BLOCK_TIMEOUT)),
+                        Err(_) => self.wait_for_space(state, None),
+                    };
+                    if state.items.len() >= self.capacity && !state.closed {
+                        self.dropped.fetch_add(1, Ordering::Relaxed);
+                        return;
+                    }
+                }
+            }
+        }
+        if state.closed {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+            return;
+        }
+        state.items.push_back(msg);
+        drop(state);
+        self.ready.notify_one();
+    }
+
+    /// 等到队列有空位或者已关闭；`timeout` 为 None 时一直等
+    fn wait_for_space<'a>(
+        &self,
+        state: MutexGuard<'a, QueueState>,
+        timeout: Option<Duration>,
+    ) -> MutexGuard<'a, QueueState> {
+        let full = |state: &mut QueueState| state.items.len() >= self.capacity && !state.closed;
+        match timeout {
+            Some(t) => {
+                self.not_full
+                    .wait_timeout_while(state, t, full)
+                    .unwrap_or_else(|e| e.into_inner())
+                    .0
+            }
+            None => self
+                .not_full
+                .wait_while(state, full)
+                .unwrap_or_else(|e| e.into_inner()),
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.lock().items.len()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.lock().items.is_empty()
+    }
+
+    fn is_closed(&self) -> bool {
+        self.lock().closed
+    }
+
+    fn take(&self, max: usize) -> Vec<StorageMsg> {
+        let mut state = self.lock();
+        let n = state.items.len().min(max);
+        let batch: Vec<StorageMsg> = state.items.drain(..n).collect();
+        drop(state);
+        self.not_full.notify_all();
+        batch
+    }
+
+    /// 写库失败的消息放回队首，下一批先写它们。
+    ///
+    /// 这里不看容量：放回去之后队列可能暂时超出 `capacity`，之后进队的消息照常按溢出策略处理
+    /// （block 时发送方等待，drop_oldest 时先丢这些最旧的），丢弃的照样计数
+    fn requeue(&self, batch: Vec<StorageMsg>) {
+        let mut state = self.lock();
+        for msg in batch.into_iter().rev() {
+            state.items.push_front(msg);
+        }
+    }
+
+    fn close(&self) {
+        self.lock().closed = true;
+        self.not_full.notify_all();
+        self.ready.notify_one();
+    }
+}
+
+/// 放进写入队列；写入任务还没启动时丢弃
+pub fn push(msg: StorageMsg) {
+    if PRINT_TO_CONSOLE.load(Ordering::Relaxed) {
+        print_message(&msg);
+        return;
+    }
+    if let Some(queue) = QUEUE.get() {
+        queue.push(msg, queue.policy);
+    }
+}
+
+/// host 自己的指标：写入任务不能等空位，也不该让上报任务卡住，队列满时直接丢掉
+pub fn push_host_metrics(metrics: Vec<Metric>) {
+    if PRINT_TO_CONSOLE.load(Ordering::Relaxed) {
+        return;
+    }
+    if let Some(queue) = QUEUE.get() {
+        for metric in metrics {
+            queue.push(StorageMsg::Metric(metric), OverflowPolicy::DropNewest);
+        }
+    }
+}
+
+/// 写入队列的当前状态
+pub struct QueueStats {
+    pub depth: usize,
+    pub dropped: u64,
+    pub capacity: usize,
+    pub policy: OverflowPolicy,
+}
+
+/// 写入任务还没启动时返回 None
+pub fn queue_stats() -> Option<QueueStats> {
+    let queue = QUEUE.get()?;
+    Some(QueueStats {
+        depth: queue.len(),
+        dropped: queue.dropped.load(Ordering::Relaxed),
+        capacity: queue.capacity,
+        policy: queue.policy,
+    })
+}
+
+/// 不再写库，之后的消息都打到控制台；用于 `bot-host plugins run`
+pub fn print_to_console() {
+    PRINT_TO_CONSOLE.store(true, Ordering::Relaxed);
+}
+
+/// 日志和告警在 bridge 里已经打过一次，这里只补上指标和插件状态
+fn print_message(msg: &StorageMsg) {
+    match msg {
+        StorageMsg::Metric(m) if m.labels.is_empty() => {
+            info!("[{}] 指标 {} = {}", m.plugin, m.name, m.value);
+        }
+        StorageMsg::Metric(m) => {
+            info!("[{}] 指标 {} = {} {:?}", m.plugin, m.name, m.value, m.labels);
+        }
+        StorageMsg::Plugin(p) => debug!("[{}] 状态 {:?}", p.name, p.status),
+        StorageMsg::Log(_) | StorageMsg::Alert(_) => {}
+    }
+}
+
+/// 写入任务的句柄，退出时交给 `shutdown`
+pub struct StorageWriter {
+    handle: JoinHandle<()>,
+    drain_timeout: Duration,
+}
+
+/// 创建队列并启动写入任务，只能调用一次
+pub fn start(db: Db, config: &StorageConfig) -> StorageWriter {
+    let capacity = config.queue_capacity.unwrap_or(DEFAULT_QUEUE_CAPACITY).max(1);
+    let batch_size = config.batch_size.unwrap_or(DEFAULT_BATCH_SIZE).clamp(1, capacity);
+    let flush_interval = config
+        .flush_interval_ms
+        .map(Duration::from_millis)
+        .unwrap_or(DEFAULT_FLUSH_INTERVAL);
+
+    let queue = StorageQueue::new(capacity, config.overflow.unwrap_or_default());
+    if QUEUE.set(queue).is_err() {
+        panic!("存储队列已初始化");
+    }
+    let queue = QUEUE.get().expect("存储队列已初始化");
+
+    StorageWriter {
+        handle: task::spawn(run_writer(
+            db,
+            queue,
+            batch_size,
+            flush_interval,
+            RETRY_BACKOFF,
+        )),
+        drain_timeout: config
+            .drain_timeout_ms
+            .map(Duration::from_millis)
+            .unwrap_or(DEFAULT_DRAIN_TIMEOUT),
+    }
+}
+
+/// 关闭队列并等写入任务把剩下的消息写完，最多等 `[storage].drain_timeout_ms`。
+///
+/// 超时后中止写入任务并等它真正结束，调用方之后可以放心关闭连接池
+pub async fn shutdown(writer: StorageWriter) {
+    let StorageWriter {
+        mut handle,
+        drain_timeout,
+    } = writer;
+    let Some(queue) = QUEUE.get() else {
+        return;
+    };
+    let pending = queue.len();
+    queue.close();
+    info!("存储队列已关闭，等待写入剩余 {pending} 条消息");
+
+    if time::timeout(drain_timeout, &mut handle).await.is_ok() {
+        info!("存储队列已写完");
+        return;
+    }
+    warn!(
+        "等待存储队列写完超时（{}ms），中止写入，正在写的一批和队列里剩余的 {} 条消息都会丢弃",
+        drain_timeout.as_millis(),
+        queue.len()
+    );
+    // 正在执行的写入随任务一起取消，连接归还给连接池
+    handle.abort();
+    let _ = handle.await;
+}
+
+async fn run_writer(
+    db: Db,
+    queue: &'static StorageQueue,
+    batch_size: usize,
+    flush_interval: Duration,
+    retry_backoff: Duration,
+) {
+    // 连续写库失败的次数，写成功一批就清零
+    let mut attempts = 0;
+    loop {
+        // 等第一条消息
+        while queue.is_empty() {
+            if queue.is_closed() {
+                return;
+            }
+            queue.ready.notified().await;
+        }
+
+        // 攒批：够 batch_size 条或者到了 flush_interval
+        let deadline = Instant::now() + flush_interval;
+        while queue.len() < batch_size && !queue.is_closed() {
+            tokio::select! {
+                _ = queue.ready.notified() => {}
+                _ = time::sleep_until(deadline) => break,
+            }
+        }
+
+        let started = Instant::now();
+        let failed = write_batch(&db, queue.take(batch_size)).await;
+        self_metrics::record_storage_write(started.elapsed(), failed.len());
+        if failed.is_empty() {
+            attempts = 0;
+            continue;
+        }
+
+        attempts += 1;
+        if attempts >= MAX_WRITE_ATTEMPTS {
+            let n = failed.len();
+            error!("连续 {attempts} 次写库失败，丢弃 {n} 条消息");
+            queue.dropped.fetch_add(n as u64, Ordering::Relaxed);
+            attempts = 0;
+            continue;
+        }
+        let delay = retry_backoff
+            .saturating_mul(1 << (attempts - 1))
+            .min(MAX_RETRY_BACKOFF);
+        warn!(
+            "{} 条消息写库失败，{}ms 后重试（第 {attempts} 次）",
+            failed.len(),
+            delay.as_millis()
+        );
+        queue.requeue(failed);
+        time::sleep(delay).await;
+    }
+}
+
+/// 写一批消息，返回没能写进去的那些
+async fn write_batch(db: &Db, batch: Vec<StorageMsg>) -> Vec<StorageMsg> {
+    let mut logs: Vec<LogEvent> = Vec::new();
+    let mut metrics: Vec<Metric> = Vec::new();
+    let mut others = Vec::new();
+    for msg in batch {
+        match msg {
+            StorageMsg::Log(e) => logs.push(e),
+            StorageMsg::Metric(m) => metrics.push(m),
+            other => others.push(other),
+        }
+    }
+
+    let mut failed = Vec::new();
+    if let Err(e) = db.insert_logs(&logs).await {
+        error!("批量写入 {} 条日志失败: {e}", logs.len());
+        failed.extend(logs.into_iter().map(StorageMsg::Log));
+    }
+    if let Err(e) = db.insert_metrics(&metrics).await {
+        error!("批量写入 {} 条指标失败: {e}", metrics.len());
+        failed.extend(metrics.into_iter().map(StorageMsg::Metric));
+    }
+    // 告警和插件状态量很小，逐条写
+    for msg in others {
+        match msg {
+            StorageMsg::Alert(a) => {
+                if let Err(e) = db.insert_alert(&a).await {
+                    error!("写入告警失败: {e}");
+                    failed.push(StorageMsg::Alert(a));
+                }
+            }
+            StorageMsg::Plugin(p) => {
+                if let Err(e) = db.upsert_plugin(&p).await {
+                    error!("更新插件状态失败: plugin={}, err={e}", p.name);
+                    failed.push(StorageMsg::Plugin(p));
+                }
+            }
+            StorageMsg::Log(_) | StorageMsg::Metric(_) => {}
+        }
+    }
+    failed
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, sync::Arc};
+
+    use chrono::Utc;
+
+    use super::*;
+
+    fn metric(value: f64) -> StorageMsg {
+        StorageMsg::Metric(Metric {
+            time: Utc::now(),
+            plugin: "test".to_string(),
+            name: "value".to_string(),
+            value,
+            labels: HashMap::new(),
+        })
+    }
+
+    fn values(queue: &StorageQueue) -> Vec<f64> {
+        queue
+            .lock()
+            .items
+            .iter()
+            .map(|msg| match msg {
+                StorageMsg::Metric(m) => m.value,
+                _ => unreachable!(),
+            })
+            .collect()
+    }
+
+    fn fill(queue: &StorageQueue, n: usize) {
+        for i in 0..n {
+            queue.push(metric(i as f64), queue.policy);
+        }
+    }
+
+    #[test]
+    fn drop_newest_keeps_the_oldest_messages() {
+        let queue = StorageQueue::new(2, OverflowPolicy::DropNewest);
+        fill(&queue, 4);
+
+        assert_eq!(values(&queue), [0.0, 1.0]);
+        assert_eq!(queue.dropped.load(Ordering::Relaxed), 2);
+    }
+
+    #[test]
+    fn drop_oldest_keeps_the_newest_messages() {
+        let queue = StorageQueue::new(2, OverflowPolicy::DropOldest);
+        fill(&queue, 4);
+
+        assert_eq!(values(&queue), [2.0, 3.0]);
+        assert_eq!(queue.dropped.load(Ordering::Relaxed), 2);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn block_waits_for_space_on_multi_thread_runtime() {
+        let queue = Arc::new(StorageQueue::new(2, OverflowPolicy::Block));
+        fill(&queue, 2);
+
+        let sender = queue.clone();
+        let blocked = tokio::spawn(async move { sender.push(metric(2.0), sender.policy) });
+        time::sleep(Duration::from_millis(100)).await;
+        assert!(!blocked.is_finished(), "队列满时发送方应该在等");
+
+        assert_eq!(queue.take(1).len(), 1);
+        blocked.await.unwrap();
+        assert_eq!(values(&queue), [1.0, 2.0]);
+        assert_eq!(queue.dropped.load(Ordering::Relaxed), 0);
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn block_gives_up_on_current_thread_runtime() {
+        let queue = StorageQueue::new(2, OverflowPolicy::Block);
+        fill(&queue, 3);
+
+        // 不能 panic，也不能一直卡住 runtime 线程
+        assert_eq!(values(&queue), [0.0, 1.0]);
+        assert_eq!(queue.dropped.load(Ordering::Relaxed), 1);
+    }
+
+    /// 没执行迁移的临时 SQLite 库：表还不存在，写库一定失败；执行迁移后恢复
+    async fn unmigrated_db(test: &str) -> (Db, std::path::PathBuf) {
+        let dir = std::env::temp_dir().join(format!("bot-host-{}-{test}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let url = format!("sqlite://{}", dir.join("test.db").display());
+        let db = Db::open(Some("sqlite"), Some(&url)).await.unwrap();
+        (db, dir)
+    }
+
+    #[tokio::test]
+    async fn failed_batch_is_retried_until_the_database_recovers() {
+        let (db, dir) = unmigrated_db("retry").await;
+        let queue: &'static StorageQueue =
+            Box::leak(Box::new(StorageQueue::new(10, OverflowPolicy::Block)));
+        fill(queue, 3);
+        let writer = tokio::spawn(run_writer(
+            db.clone(),
+            queue,
+            10,
+            Duration::from_millis(1),
+            Duration::from_millis(50),
+        ));
+
+        // 第一次写失败后整批放回队首，不算丢弃
+        time::sleep(Duration::from_millis(25)).await;
+        assert_eq!(values(queue), [0.0, 1.0, 2.0]);
+        assert_eq!(queue.dropped.load(Ordering::Relaxed), 0);
+
+        db.migrate().await.unwrap();
+        queue.close();
+        let finished = time::timeout(Duration::from_secs(5), writer).await;
+        finished.unwrap().unwrap();
+
+        let metrics = db.latest_metrics(10).await.unwrap();
+        let mut written: Vec<f64> = metrics.iter().map(|m| m.value).collect();
+        written.sort_by(f64::total_cmp);
+        assert_eq!(written, [0.0, 1.0, 2.0]);
+        assert_eq!(queue.dropped.load(Ordering::Relaxed), 0);
+        db.close().await;
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn batch_is_dropped_after_max_attempts() {
+        let (db, dir) = unmigrated_db("give-up").await;
+        let queue: &'static StorageQueue =
+            Box::leak(Box::new(StorageQueue::new(10, OverflowPolicy::Block)));
+        fill(queue, 3);
+        queue.close();
+        let writer = run_writer(
+            db.clone(),
+            queue,
+            10,
+            Duration::from_millis(1),
+            Duration::from_millis(1),
+        );
+
+        // 一直写不进去时不能卡死，放弃后计入丢弃条数
+        time::timeout(Duration::from_secs(5), writer).await.unwrap();
+        assert!(queue.is_empty());
+        assert_eq!(queue.dropped.load(Ordering::Relaxed), 3);
+        db.close().await;
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn block_is_released_by_close() {
+        let queue = Arc::new(StorageQueue::new(1, OverflowPolicy::Block));
+        fill(&queue, 1);
+
+        let sender = queue.clone();
+        let blocked = std::thread::spawn(move || sender.push(metric(1.0), sender.policy));
+        std::thread::sleep(Duration::from_millis(100));
+        queue.close();
+        blocked.join().unwrap();
+
+        assert_eq!(values(&queue), [0.0]);
+        assert_eq!(queue.dropped.load(Ordering::Relaxed), 1);
+    }
+}
@@ -0,0 +1,593 @@
+//! WebAssembly 插件（`.wasm`，WASI preview1）
+//!
+//! 原生插件和 host 共享地址空间，还必须用同一版本的工具链编译；WASM 插件跑在 wasmtime 的沙箱里，
+//! 只能通过下面这些导入和 host 交互，每次调用有 fuel（指令数）上限，线性内存有大小上限。
+//!
+//! 插件导出（对应原生插件的 `meta` / `run_with_ctx` / `plugin_init` / `plugin_shutdown`）：
+//!
+//! | 导出 | 说明 |
+//! |---|---|
+//! | `memory` | 线性内存 |
+//! | `meta() -> i64` | `(ptr << 32) \| len`，指向 UTF-8 JSON `{"name", "version", "kind"}` |
+//! | `run_with_ctx() -> i32` | 执行一轮，非 0 表示失败 |
+//! | `plugin_init() -> i32` | 可选，加载后调用一次，非 0 表示初始化失败 |
+//! | `plugin_shutdown()` | 可选，卸载前调用 |
+//!
+//! host 提供的导入（模块名 `bot_host`，对应 `PluginContext` 里的函数）：
+//!
+//! | 导入 | 说明 |
+//! |---|---|
+//! | `log(level: i32, ptr: i32, len: i32)` | level 同 `plugin_api::LogLevel` |
+//! | `emit_metric(name_ptr: i32, name_len: i32, value: f64, timestamp_ms: i64)` | timestamp_ms 为 0 时取当前时间 |
+//! | `get_config(buf_ptr: i32, buf_len: i32) -> i32` | 把 `[plugins.<name>]` 的 JSON 写进 buf，返回 JSON 的完整长度；大于 buf_len 时什么都不写 |
+//! | `report_failure(ptr: i32, len: i32)` | 把本次调用记为失败 |
+//!
+//! 另外链接了 WASI preview1：stdout / stderr 接到 host 的控制台，不开放文件系统、网络和环境变量。
+//! 插件 trap（包括 fuel 用完、内存超限）之后实例状态不可信，下次执行前重新实例化并再调一次 `plugin_init`。
+//!
+//! 编译时关闭 `wasm` feature 可以去掉 wasmtime 依赖，此时 `.wasm` 插件一律加载失败。
+
+use plugin_api::{CAP_EMIT_METRIC, CAP_LOG, CAP_PLUGIN_CONFIG, CAP_REPORT_FAILURE};
+
+/// WASM 插件的 ABI 版本，和原生插件的 `PLUGIN_ABI_VERSION` 分开计
+pub const WASM_ABI_VERSION: u32 = 1;
+
+/// WASM 插件能用的能力，就是上面这几个导入函数
+pub const WASM_CAPABILITIES: u64 = CAP_LOG | CAP_EMIT_METRIC | CAP_PLUGIN_CONFIG | CAP_REPORT_FAILURE;
+
+/// 插件 meta 导出的信息
+#[derive(Debug, serde::Deserialize)]
+pub struct WasmMeta {
+    pub name: String,
+    pub version: String,
+    #[serde(default)]
+    pub kind: String,
+}
+
+#[cfg(feature = "wasm")]
+pub use runtime::WasmPlugin;
+
+#[cfg(not(feature = "wasm"))]
+pub use disabled::WasmPlugin;
+
+#[cfg(feature = "wasm")]
+mod runtime {
+    use std::{
+        collections::HashMap,
+        sync::{Mutex, OnceLock},
+    };
+
+    use chrono::Utc;
+    use core_types::LogLevel;
+    use wasmtime::{
+        Caller, Config, Engine, Extern, Instance, Linker, Memory, Module, Store, StoreLimits,
+        StoreLimitsBuilder, Trap, TypedFunc,
+    };
+    use wasmtime_wasi::{preview1::WasiP1Ctx, WasiCtxBuilder};
+
+    use super::WasmMeta;
+    use crate::bridge::{emit_plugin_log, emit_plugin_metric, report_failure};
+    use crate::config::{plugin_config_json, plugin_wasm_limits};
+
+    /// 导入函数所在的模块名
+    const HOST_MODULE: &str = "bot_host";
+
+    /// 每次调用默认的 fuel，大约相当于这么多条 wasm 指令
+    const DEFAULT_FUEL: u64 = 1_000_000_000;
+    /// 线性内存默认上限（MB）
+    const DEFAULT_MAX_MEMORY_MB: u64 = 64;
+
+    /// 每个 Store 的宿主状态
+    struct HostState {
+        wasi: WasiP1Ctx,
+        limits: StoreLimits,
+        /// 读取 meta 之前是空字符串
+        plugin_name: String,
+    }
+
+    /// 所有 WASM 插件共用一个开启了 fuel 计量的 Engine
+    fn engine() -> Result<&'static Engine, String> {
+        static ENGINE: OnceLock<Result<Engine, String>> = OnceLock::new();
+        ENGINE
+            .get_or_init(|| {
+                let mut config = Config::new();
+                config.consume_fuel(true);
+                Engine::new(&config).map_err(|e| format!("初始化 wasmtime 失败: {e:#}"))
+            })
+            .as_ref()
+            .map_err(Clone::clone)
+    }
+
+    /// 一个实例和它的 Store；trap 之后丢掉重建
+    struct Live {
+        store: Store<HostState>,
+        instance: Instance,
+    }
+
+    /// 编译好的 WASM 插件
+    pub struct WasmPlugin {
+        name: String,
+        module: Module,
+        linker: Linker<HostState>,
+        fuel: u64,
+        max_memory_bytes: usize,
+        has_run: bool,
+        has_init: bool,
+        has_shutdown: bool,
+        live: Mutex<Option<Live>>,
+    }
+
+    impl WasmPlugin {
+        /// 编译、实例化并读取 meta；不调用 plugin_init
+        pub fn load(contents: &[u8]) -> Result<(Self, WasmMeta), String> {
+            let engine = engine()?;
+            let module =
+                Module::new(engine, contents).map_err(|e| format!("编译 WASM 模块失败: {e:#}"))?;
+            let has_export = |name: &str| module.get_export(name).is_some();
+            if !has_export("meta") {
+                return Err("缺少 meta 导出".to_string());
+            }
+            let (has_run, has_init, has_shutdown) = (
+                has_export("run_with_ctx"),
+                has_export("plugin_init"),
+                has_export("plugin_shutdown"),
+            );
+            if !has_run && !has_init {
+                return Err("既没有 run_with_ctx，也没有 plugin_init".to_string());
+            }
+
+            let mut linker = Linker::new(engine);
+            wasmtime_wasi::preview1::add_to_linker_sync(&mut linker, |s: &mut HostState| {
+                &mut s.wasi
+            })
+            .map_err(|e| format!("链接 WASI 失败: {e:#}"))?;
+            add_host_imports(&mut linker)?;
+
+            // 读 meta 时还不知道插件名，先按默认上限实例化
+            let mut plugin = Self {
+                name: String::new(),
+                module,
+                linker,
+                fuel: DEFAULT_FUEL,
+                max_memory_bytes: mb_to_bytes(DEFAULT_MAX_MEMORY_MB),
+                has_run,
+                has_init,
+                has_shutdown,
+                live: Mutex::new(None),
+            };
+            let mut live = plugin.instantiate()?;
+            let meta = read_meta(&mut live)?;
+
+            let limits = plugin_wasm_limits(&meta.name);
+            plugin.name = meta.name.clone();
+            plugin.fuel = limits.wasm_fuel.filter(|f| *f > 0).unwrap_or(DEFAULT_FUEL);
+            plugin.max_memory_bytes = mb_to_bytes(
+                limits
+                    .wasm_max_memory_mb
+                    .filter(|m| *m > 0)
+                    .unwrap_or(DEFAULT_MAX_MEMORY_MB),
+            );
+            live.store.data_mut().plugin_name = meta.name.clone();
+            live.store.data_mut().limits = plugin.store_limits();
+            *plugin.live.get_mut().unwrap_or_else(|e| e.into_inner()) = Some(live);
+            Ok((plugin, meta))
+        }
+
+        pub fn has_run(&self) -> bool {
+            self.has_run
+        }
+
+        pub fn has_init(&self) -> bool {
+            self.has_init
+        }
+
+        /// (每次调用的 fuel, 内存上限字节数)
+        pub fn limits(&self) -> (u64, usize) {
+            (self.fuel, self.max_memory_bytes)
+        }
+
+        /// 调用 plugin_init（没有导出则直接成功）
+        pub fn init(&self) -> Result<(), String> {
+            let mut slot = self.lock();
+            let live = match slot.as_mut() {
+                Some(live) => live,
+                None => slot.insert(self.instantiate()?),
+            };
+            let result = self.call_init(live);
+            if result.is_err() {
+                *slot = None;
+            }
+            result
+        }
+
+        /// 执行一轮；上次 trap 之后先重新实例化并调用 plugin_init
+        pub fn run(&self) -> Result<(), String> {
+            if !self.has_run {
+                return Ok(());
+            }
+            let mut slot = self.lock();
+            if slot.is_none() {
+                let mut live = self.instantiate()?;
+                self.call_init(&mut live)?;
+                *slot = Some(live);
+            }
+            let live = slot.as_mut().expect("实例已创建");
+
+            let result = self.call_i32(live, "run_with_ctx");
+            match result {
+                Ok(0) => Ok(()),
+                Ok(code) => Err(format!("run_with_ctx 返回错误码 {code}")),
+                Err(e) => {
+                    *slot = None;
+                    Err(e)
+                }
+            }
+        }
+
+        /// 调用 plugin_shutdown 并丢掉实例
+        pub fn shutdown(&self) -> Result<(), String> {
+            let Some(mut live) = self.lock().take() else {
+                return Ok(());
+            };
+            if !self.has_shutdown {
+                return Ok(());
+            }
+            let func: TypedFunc<(), ()> = live
+                .instance
+                .get_typed_func(&mut live.store, "plugin_shutdown")
+                .map_err(|e| format!("plugin_shutdown 签名不对: {e:#}"))?;
+            self.refuel(&mut live)?;
+            func.call(&mut live.store, ())
+                .map_err(|e| describe_trap(&e, self.fuel))
+        }
+
+        fn lock(&self) -> std::sync::MutexGuard<'_, Option<Live>> {
+            self.live.lock().unwrap_or_else(|e| e.into_inner())
+        }
+
+        fn store_limits(&self) -> StoreLimits {
+            StoreLimitsBuilder::new()
+                .memory_size(self.max_memory_bytes)
+                .instances(1)
+                .build()
+        }
+
+        fn instantiate(&self) -> Result<Live, String> {
+            let wasi = WasiCtxBuilder::new()
+                .inherit_stdout()
+                .inherit_stderr()
+                .build_p1();
+            let mut store = Store::new(
+                self.module.engine(),
+                HostState {
+                    wasi,
+                    limits: self.store_limits(),
+                    plugin_name: self.name.clone(),
+                },
+            );
+            store.limiter(|s| &mut s.limits);
+            store
+                .set_fuel(self.fuel)
+                .map_err(|e| format!("设置 fuel 失败: {e:#}"))?;
+
+            let instance = self
+                .linker
+                .instantiate(&mut store, &self.module)
+                .map_err(|e| format!("实例化 WASM 模块失败: {}", describe_trap(&e, self.fuel)))?;
+            let mut live = Live { store, instance };
+
+            // reactor 模块（cdylib）的全局构造在 _initialize 里
+            if let Ok(initialize) = live
+                .instance
+                .get_typed_func::<(), ()>(&mut live.store, "_initialize")
+            {
+                initialize
+                    .call(&mut live.store, ())
+                    .map_err(|e| format!("_initialize 失败: {}", describe_trap(&e, self.fuel)))?;
+            }
+            Ok(live)
+        }
+
+        fn call_init(&self, live: &mut Live) -> Result<(), String> {
+            if !self.has_init {
+                return Ok(());
+            }
+            match self.call_i32(live, "plugin_init")? {
+                0 => Ok(()),
+                code => Err(format!("plugin_init 返回错误码 {code}")),
+            }
+        }
+
+        fn call_i32(&self, live: &mut Live, name: &str) -> Result<i32, String> {
+            let func: TypedFunc<(), i32> = live
+                .instance
+                .get_typed_func(&mut live.store, name)
+                .map_err(|e| format!("{name} 签名不对，应为 () -> i32: {e:#}"))?;
+            self.refuel(live)?;
+            func.call(&mut live.store, ())
+                .map_err(|e| describe_trap(&e, self.fuel))
+        }
+
+        /// 每次调用前把 fuel 加满
+        fn refuel(&self, live: &mut Live) -> Result<(), String> {
+            live.store
+                .set_fuel(self.fuel)
+                .map_err(|e| format!("设置 fuel 失败: {e:#}"))
+        }
+    }
+
+    fn mb_to_bytes(mb: u64) -> usize {
+        usize::try_from(mb.saturating_mul(1024 * 1024)).unwrap_or(usize::MAX)
+    }
+
+    fn describe_trap(e: &wasmtime::Error, fuel: u64) -> String {
+        match e.downcast_ref::<Trap>() {
+            Some(Trap::OutOfFuel) => format!("fuel 用完（上限 {fuel}），插件可能陷入死循环"),
+            Some(trap) => format!("WASM trap: {trap}"),
+            None => format!("{e:#}"),
+        }
+    }
+
+    fn read_meta(live: &mut Live) -> Result<WasmMeta, String> {
+        let func: TypedFunc<(), i64> = live
+            .instance
+            .get_typed_func(&mut live.store, "meta")
+            .map_err(|e| format!("meta 签名不对，应为 () -> i64: {e:#}"))?;
+        let packed = func
+            .call(&mut live.store, ())
+            .map_err(|e| format!("调用 meta 失败: {e:#}"))?;
+        let (ptr, len) = ((packed as u64 >> 32) as u32, packed as u32);
+
+        let memory = live
+            .instance
+            .get_memory(&mut live.store, "memory")
+            .ok_or_else(|| "缺少 memory 导出".to_string())?;
+        let bytes = memory
+            .data(&live.store)
+            .get(ptr as usize..ptr as usize + len as usize)
+            .ok_or_else(|| "meta 返回的地址越界".to_string())?;
+        serde_json::from_slice(bytes).map_err(|e| format!("解析 meta JSON 失败: {e}"))
+    }
+
+    // ============ 导入函数 ============
+
+    fn add_host_imports(linker: &mut Linker<HostState>) -> Result<(), String> {
+        let err = |e: wasmtime::Error| format!("注册 host 导入函数失败: {e:#}");
+
+        linker
+            .func_wrap(
+                HOST_MODULE,
+                "log",
+                |mut caller: Caller<'_, HostState>, level: i32, ptr: i32, len: i32| {
+                    let message = read_string(&mut caller, ptr, len)?;
+                    let level = match level {
+                        0 => LogLevel::Debug,
+                        1 => LogLevel::Info,
+                        2 => LogLevel::Warn,
+                        _ => LogLevel::Error,
+                    };
+                    emit_plugin_log(level, message, HashMap::new());
+                    Ok(())
+                },
+            )
+            .map_err(err)?;
+
+        linker
+            .func_wrap(
+                HOST_MODULE,
+                "emit_metric",
+                |mut caller: Caller<'_, HostState>,
+                 name_ptr: i32,
+                 name_len: i32,
+                 value: f64,
+                 timestamp_ms: i64| {
+                    let name = read_string(&mut caller, name_ptr, name_len)?;
+                    let timestamp_ms = if timestamp_ms == 0 {
+                        Utc::now().timestamp_millis()
+                    } else {
+                        timestamp_ms
+                    };
+                    emit_plugin_metric(name, value, timestamp_ms, HashMap::new());
+                    Ok(())
+                },
+            )
+            .map_err(err)?;
+
+        linker
+            .func_wrap(
+                HOST_MODULE,
+                "get_config",
+                |mut caller: Caller<'_, HostState>, buf_ptr: i32, buf_len: i32| {
+                    let json = plugin_config_json(&caller.data().plugin_name);
+                    let len = i32::try_from(json.len())?;
+                    if len <= buf_len {
+                        let memory = guest_memory(&mut caller)?;
+                        memory.write(&mut caller, buf_ptr as u32 as usize, json.as_bytes())?;
+                    }
+                    Ok(len)
+                },
+            )
+            .map_err(err)?;
+
+        linker
+            .func_wrap(
+                HOST_MODULE,
+                "report_failure",
+                |mut caller: Caller<'_, HostState>, ptr: i32, len: i32| {
+                    report_failure(read_string(&mut caller, ptr, len)?);
+                    Ok(())
+                },
+            )
+            .map_err(err)?;
+
+        Ok(())
+    }
+
+    fn guest_memory(caller: &mut Caller<'_, HostState>) -> wasmtime::Result<Memory> {
+        match caller.get_export("memory") {
+            Some(Extern::Memory(memory)) => Ok(memory),
+            _ => Err(wasmtime::Error::msg("插件没有导出 memory")),
+        }
+    }
+
+    /// 越界时让本次调用 trap，不读别处的内存
+    fn read_string(
+        caller: &mut Caller<'_, HostState>,
+        ptr: i32,
+        len: i32,
+    ) -> wasmtime::Result<String> {
+        let memory = guest_memory(caller)?;
+        let (start, len) = (ptr as u32 as usize, len as u32 as usize);
+        let bytes = memory
+            .data(&caller)
+            .get(start..start + len)
+            .ok_or_else(|| wasmtime::Error::msg("字符串地址越界"))?;
+        Ok(String::from_utf8_lossy(bytes).into_owned())
+    }
+}
+
+#[cfg(not(feature = "wasm"))]
+mod disabled {
+    use std::convert::Infallible;
+
+    use super::WasmMeta;
+
+    /// 没有开启 `wasm` feature：无法构造，`load` 总是失败
+    pub struct WasmPlugin(Infallible);
+
+    impl WasmPlugin {
+        pub fn load(_contents: &[u8]) -> Result<(Self, WasmMeta), String> {
+            Err("bot-host 编译时没有开启 wasm feature，不支持 .wasm 插件".to_string())
+        }
+
+        pub fn has_run(&self) -> bool {
+            match self.0 {}
+        }
+
+        pub fn has_init(&self) -> bool {
+            match self.0 {}
+        }
+
+        pub fn limits(&self) -> (u64, usize) {
+            match self.0 {}
+        }
+
+        pub fn init(&self) -> Result<(), String> {
+            match self.0 {}
+        }
+
+        pub fn run(&self) -> Result<(), String> {
+            match self.0 {}
+        }
+
+        pub fn shutdown(&self) -> Result<(), String> {
+            match self.0 {}
+        }
+    }
+}
+
+#[cfg(all(test, feature = "wasm"))]
+mod tests {
+    use super::WasmPlugin;
+    use crate::config::set_plugin_config;
+
+    /// 用 WAT 拼一个最小的插件：meta 放在内存开头，`body` 里是其余的导出
+    fn plugin(name: &str, config: &str, body: &str) -> WasmPlugin {
+        set_plugin_config(name, config.to_string());
+        let meta = format!(r#"{{"name":"{name}","version":"0.1.0"}}"#);
+        let wat = format!(
+            r#"(module
+                (import "bot_host" "get_config" (func $get_config (param i32 i32) (result i32)))
+                (memory (export "memory") 1)
+                (data (i32.const 0) "{}")
+                (func (export "meta") (result i64) i64.const {})
+                {body})"#,
+            meta.replace('"', "\\\""),
+            meta.len(),
+        );
+        let (plugin, meta) = WasmPlugin::load(wat.as_bytes()).expect("加载 WAT 插件失败");
+        assert_eq!(meta.name, name);
+        plugin
+    }
+
+    #[test]
+    fn endless_loop_runs_out_of_fuel() {
+        let plugin = plugin(
+            "wasm-fuel",
+            r#"{"wasm_fuel": 10000}"#,
+            r#"(func (export "run_with_ctx") (result i32) (loop $spin br $spin) i32.const 0)"#,
+        );
+        assert_eq!(plugin.limits().0, 10000);
+
+        for _ in 0..2 {
+            let err = plugin.run().unwrap_err();
+            assert!(err.contains("fuel 用完"), "{err}");
+        }
+    }
+
+    #[test]
+    fn memory_cannot_grow_past_the_limit() {
+        let plugin = plugin(
+            "wasm-memory",
+            r#"{"wasm_max_memory_mb": 1}"#,
+            r#"(func (export "run_with_ctx") (result i32)
+                ;; 1 页 64KB 在上限之内，再要 2MB 就超了
+                (if (i32.ne (memory.grow (i32.const 1)) (i32.const 1)) (then (return (i32.const 1))))
+                (if (i32.ne (memory.grow (i32.const 32)) (i32.const -1)) (then (return (i32.const 2))))
+                i32.const 0)"#,
+        );
+        assert_eq!(plugin.limits().1, 1024 * 1024);
+
+        plugin.run().unwrap();
+    }
+
+    #[test]
+    fn trap_reinstantiates_and_calls_init_again() {
+        let plugin = plugin(
+            "wasm-trap",
+            "{}",
+            r#"(global $inited (mut i32) (i32.const 0))
+               (global $runs (mut i32) (i32.const 0))
+               (func (export "plugin_init") (result i32)
+                 (global.set $inited (i32.const 1))
+                 i32.const 0)
+               ;; 每个实例第二次执行时 trap；没调过 plugin_init 就返回 9
+               (func (export "run_with_ctx") (result i32)
+                 (global.set $runs (i32.add (global.get $runs) (i32.const 1)))
+                 (if (i32.eq (global.get $runs) (i32.const 2)) (then unreachable))
+                 (if (i32.eqz (global.get $inited)) (then (return (i32.const 9))))
+                 i32.const 0)"#,
+        );
+        plugin.init().unwrap();
+
+        plugin.run().unwrap();
+        assert!(plugin.run().unwrap_err().contains("WASM trap"));
+        // 新实例：计数从头开始，plugin_init 又调了一次
+        plugin.run().unwrap();
+        assert!(plugin.run().unwrap_err().contains("WASM trap"));
+    }
+
+    #[test]
+    fn get_config_leaves_a_small_buffer_untouched() {
+        let config = r#"{"greeting":"hello"}"#;
+        let body = format!(
+            r#"(data (i32.const 1024) "\aa")
+               (func (export "run_with_ctx") (result i32)
+                 ;; 缓冲区不够：返回完整长度，什么都不写
+                 (if (i32.ne (call $get_config (i32.const 1024) (i32.const 4)) (i32.const {len}))
+                   (then (return (i32.const 1))))
+                 (if (i32.ne (i32.load8_u (i32.const 1024)) (i32.const 0xaa))
+                   (then (return (i32.const 2))))
+                 ;; 缓冲区够大：写入 JSON
+                 (if (i32.ne (call $get_config (i32.const 1024) (i32.const 64)) (i32.const {len}))
+                   (then (return (i32.const 3))))
+                 (if (i32.ne (i32.load8_u (i32.const 1024)) (i32.const 0x7b))
+                   (then (return (i32.const 4))))
+                 i32.const 0)"#,
+            len = config.len(),
+        );
+        let plugin = plugin("wasm-config", config, &body);
+
+        plugin.run().unwrap();
+    }
+}
@@ -0,0 +1,364 @@
+//! 插件子进程隔离。
+//!
+//! `[plugins.<name>] isolation = "process"` 的插件，以及没有声明 `CAP_PANIC_SAFE`、
+//! 也没有配置 `isolation = "none"` 的原生插件，不在 bot-host 进程里执行，而是由
+//! `bot-host plugin-worker <动态库路径>` 子进程加载。插件 segfault / abort 只会带走
+//! 这个子进程，host 把本次执行记为失败，退避后重新拉起。
+//!
+//! 协议是 stdin / stdout 上逐行的 JSON：
+//!
+//! - host -> worker：[`WorkerCommand`]
+//! - worker -> host：[`WorkerEvent`]，每条命令（以及启动时的 plugin_init）对应一条 `done`
+//!
+//! worker 会把自己原来的 stdout 重定向到 stderr，插件里的 `println!` 不会打乱协议。
+//!
+//! 事件总线只转发一个方向：子进程里 `publish` 的事件交给父进程分发，
+//! `subscribe` 则直接被拒绝（返回 0），事件回调只能在 host 进程里调用。
+
+use std::{
+    env,
+    io::{self, BufRead, BufReader, Write},
+    path::Path,
+    process::{Child, ChildStdin, ChildStdout, Command, Stdio},
+    sync::{
+        mpsc::{self, RecvTimeoutError},
+        Mutex, OnceLock,
+    },
+    thread,
+    time::Duration,
+};
+
+use core_types::{AlertEvent, LogEvent, Metric};
+use plugin_api::{ApiListenAddr, CAP_QUERY_METRICS};
+use serde::{Deserialize, Serialize};
+use tracing::{error, info, warn};
+
+use crate::api_listen::{self, API_LISTEN_ENV};
+use crate::bridge::{record_alert, send_storage, set_query_backend, StorageMsg};
+use crate::config::{
+    database_settings, load_config, plugin_worker_timeout, set_plugin_config, set_plugin_configs,
+};
+use crate::event_bus;
+use crate::registry::LoadedPlugin;
+
+/// 子进程入口使用的子命令名
+pub const WORKER_SUBCOMMAND: &str = "plugin-worker";
+
+/// 等待 worker 响应 shutdown 的最长时间，超时直接 kill
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// worker 完成 dlopen + plugin_init 的默认期限，`[plugins.<name>].worker_timeout_secs` 可覆盖
+const INIT_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// 每条 run / reload_config 命令的默认期限，`[plugins.<name>].worker_timeout_secs` 可覆盖
+pub const RUN_TIMEOUT: Duration = Duration::from_secs(300);
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum WorkerCommand {
+    Run,
+    ReloadConfig { config: String },
+    Shutdown,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum WorkerEvent {
+    Log { event: LogEvent },
+    Metric { metric: Metric },
+    Alert { alert: AlertEvent },
+    /// 插件发布的事件，由父进程放进事件总线
+    Publish { topic: String, payload: String },
+    /// 插件通过 confirm_api_bind_fn 回报的监听地址
+    ApiBound { addr: String },
+    Done { error: Option<String> },
+}
+
+// ============ host 侧：管理一个 worker 子进程 ============
+
+pub struct WorkerProcess {
+    child: Child,
+    stdin: ChildStdin,
+    /// 每条 done 的结果；子进程退出后发送端被丢弃
+    replies: mpsc::Receiver<Result<(), String>>,
+}
+
+impl WorkerProcess {
+    /// 拉起子进程并等待它完成 dlopen + plugin_init
+    pub fn spawn(plugin_name: &str, path: &Path) -> Result<Self, String> {
+        let exe = env::current_exe().map_err(|e| format!("无法定位 bot-host 可执行文件: {e}"))?;
+        let mut command = Command::new(exe);
+        if let Some(addr) = api_listen::assigned(plugin_name) {
+            command.env(API_LISTEN_ENV, addr.to_string());
+        }
+        let mut child = command
+            .arg(WORKER_SUBCOMMAND)
+            .arg(path)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .spawn()
+            .map_err(|e| format!("启动 worker 子进程失败: {e}"))?;
+
+        let (Some(stdin), Some(stdout)) = (child.stdin.take(), child.stdout.take()) else {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err("worker 子进程缺少 stdin / stdout".to_string());
+        };
+
+        let (tx, replies) = mpsc::channel();
+        let name = plugin_name.to_string();
+        if let Err(e) = thread::Builder::new()
+            .name(format!("worker-{plugin_name}"))
+            .spawn(move || pump_events(&name, stdout, tx))
+        {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(format!("启动 worker 读取线程失败: {e}"));
+        }
+
+        let mut worker = Self {
+            child,
+            stdin,
+            replies,
+        };
+        info!(
+            "插件 {plugin_name} 在子进程中运行: pid={}",
+            worker.child.id()
+        );
+
+        // 第一条 done 是子进程里 plugin_init 的结果；卡住的 init 到期后直接 kill
+        let timeout = plugin_worker_timeout(plugin_name).unwrap_or(INIT_TIMEOUT);
+        if let Err(e) = worker.wait_reply(timeout) {
+            worker.kill();
+            return Err(e);
+        }
+        Ok(worker)
+    }
+
+    /// 发送一条命令并等待 done，超过 `timeout` 返回错误；调用方负责 kill 掉这个 worker
+    pub fn request(&mut self, cmd: &WorkerCommand, timeout: Duration) -> Result<(), String> {
+        let mut line = serde_json::to_string(cmd).map_err(|e| format!("序列化命令失败: {e}"))?;
+        line.push('\n');
+        if let Err(e) = self
+            .stdin
+            .write_all(line.as_bytes())
+            .and_then(|_| self.stdin.flush())
+        {
+            return Err(format!("写入 worker 失败: {e}; {}", self.exit_reason()));
+        }
+        self.wait_reply(timeout)
+    }
+
+    /// 请求 worker 调用 plugin_shutdown 并退出，超时或出错都直接 kill
+    pub fn shutdown(mut self, plugin_name: &str) {
+        if let Err(e) = self.request(&WorkerCommand::Shutdown, SHUTDOWN_TIMEOUT) {
+            warn!("插件 {plugin_name} 的 worker 没有正常退出: {e}");
+        }
+        self.kill();
+    }
+
+    pub fn kill(mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+
+    fn wait_reply(&mut self, timeout: Duration) -> Result<(), String> {
+        match self.replies.recv_timeout(timeout) {
+            Ok(r) => r,
+            Err(RecvTimeoutError::Timeout) => {
+                Err(format!("worker 超过 {}s 没有响应", timeout.as_secs_f64()))
+            }
+            Err(RecvTimeoutError::Disconnected) => Err(self.exit_reason()),
+        }
+    }
+
+    /// stdout 已经关闭，说明子进程退出了（通常是崩溃），拿到退出状态
+    fn exit_reason(&mut self) -> String {
+        match self.child.wait() {
+            Ok(status) => format!("worker 子进程已退出: {status}"),
+            Err(e) => format!("worker 子进程状态未知: {e}"),
+        }
+    }
+}
+
+/// 持续读取 worker 的输出：日志 / 指标进存储通道，done 交给等待中的调用方
+fn pump_events(plugin_name: &str, stdout: ChildStdout, replies: mpsc::Sender<Result<(), String>>) {
+    for line in BufReader::new(stdout).lines() {
+        let Ok(line) = line else {
+            break;
+        };
+        match serde_json::from_str::<WorkerEvent>(&line) {
+            Ok(WorkerEvent::Log { event }) => send_storage(StorageMsg::Log(event)),
+            Ok(WorkerEvent::Metric { metric }) => send_storage(StorageMsg::Metric(metric)),
+            Ok(WorkerEvent::Alert { alert }) => record_alert(alert),
+            Ok(WorkerEvent::Publish { topic, payload }) => {
+                event_bus::publish(plugin_name, &topic, payload)
+            }
+            Ok(WorkerEvent::ApiBound { addr }) => match addr.parse() {
+                Ok(addr) => api_listen::confirm(plugin_name, addr),
+                Err(e) => warn!("插件 {plugin_name} 回报的监听地址无效: {e}"),
+            },
+            Ok(WorkerEvent::Done { error }) => {
+                let _ = replies.send(error.map_or(Ok(()), Err));
+            }
+            // 不是协议消息，原样打印
+            Err(_) => info!("[{plugin_name}] {line}"),
+        }
+    }
+}
+
+// ============ worker 侧：子进程里的主循环 ============
+
+/// 子进程里发往父进程的协议输出；设置后本进程就是 worker
+static PARENT_PIPE: OnceLock<Mutex<Box<dyn Write + Send>>> = OnceLock::new();
+
+pub fn is_worker() -> bool {
+    PARENT_PIPE.get().is_some()
+}
+
+/// 在 worker 子进程里，日志 / 指标不写数据库，而是交给父进程
+pub fn forward_to_parent(msg: StorageMsg) {
+    let event = match msg {
+        StorageMsg::Log(event) => WorkerEvent::Log { event },
+        StorageMsg::Metric(metric) => WorkerEvent::Metric { metric },
+        StorageMsg::Alert(alert) => WorkerEvent::Alert { alert },
+        // 插件状态由父进程维护
+        StorageMsg::Plugin(_) => return,
+    };
+    send_event(&event);
+}
+
+pub fn forward_publish(topic: String, payload: String) {
+    send_event(&WorkerEvent::Publish { topic, payload });
+}
+
+pub fn forward_api_bound(addr: &ApiListenAddr) {
+    send_event(&WorkerEvent::ApiBound {
+        addr: addr.to_string(),
+    });
+}
+
+fn send_event(event: &WorkerEvent) {
+    let Some(pipe) = PARENT_PIPE.get() else {
+        return;
+    };
+    let Ok(mut line) = serde_json::to_string(event) else {
+        return;
+    };
+    line.push('\n');
+
+    let mut pipe = pipe.lock().unwrap_or_else(|e| e.into_inner());
+    let _ = pipe.write_all(line.as_bytes()).and_then(|_| pipe.flush());
+}
+
+fn reply(result: Result<(), String>) {
+    send_event(&WorkerEvent::Done {
+        error: result.err(),
+    });
+}
+
+/// 起一个只有一个工作线程的 runtime 驱动数据库连接；返回的 runtime 要活到子进程退出
+fn connect_query_backend() -> Option<tokio::runtime::Runtime> {
+    let runtime = match tokio::runtime::Builder::new_multi_thread()
+        .worker_threads(1)
+        .enable_all()
+        .build()
+    {
+        Ok(rt) => rt,
+        Err(e) => {
+            error!("worker 创建 runtime 失败，插件无法查询历史指标: {e}");
+            return None;
+        }
+    };
+
+    // 迁移由父进程在启动时做完；多个 worker 同时迁移会互相抢锁
+    let (db_type, db_url) = database_settings();
+    match runtime.block_on(storage::Db::open(db_type.as_deref(), Some(&db_url))) {
+        Ok(db) => {
+            set_query_backend(db, runtime.handle().clone());
+            Some(runtime)
+        }
+        Err(e) => {
+            error!("worker 连接数据库失败，插件无法查询历史指标: {e}");
+            None
+        }
+    }
+}
+
+/// `bot-host plugin-worker <path>` 的入口，返回进程退出码
+pub fn run_worker(path: &Path) -> i32 {
+    if PARENT_PIPE.set(Mutex::new(take_protocol_stdout())).is_err() {
+        return 1;
+    }
+    set_plugin_configs(&load_config());
+
+    let plugin = match LoadedPlugin::load(path) {
+        Ok(p) => p,
+        Err(e) => {
+            reply(Err(e.reason));
+            return 1;
+        }
+    };
+    if let Some(addr) = env::var(API_LISTEN_ENV).ok().and_then(|s| s.parse().ok()) {
+        api_listen::set_assigned(&plugin.name, addr);
+    }
+    // 要查询历史指标的插件在子进程里自己连数据库（只读，写入仍然交给父进程）
+    let _runtime = (plugin.capabilities & CAP_QUERY_METRICS != 0)
+        .then(connect_query_backend)
+        .flatten();
+    if let Err(e) = plugin.init() {
+        reply(Err(e));
+        // init 过程中可能已经起了后台线程，库不能卸载
+        std::mem::forget(plugin);
+        return 1;
+    }
+    reply(Ok(()));
+
+    for line in io::stdin().lock().lines() {
+        let Ok(line) = line else {
+            break;
+        };
+        match serde_json::from_str::<WorkerCommand>(&line) {
+            Ok(WorkerCommand::Run) => reply(plugin.run_once()),
+            Ok(WorkerCommand::ReloadConfig { config }) => {
+                set_plugin_config(&plugin.name, config.clone());
+                plugin.reload_config(&config);
+                reply(Ok(()));
+            }
+            Ok(WorkerCommand::Shutdown) => {
+                plugin.shutdown();
+                reply(Ok(()));
+                return 0;
+            }
+            Err(e) => {
+                error!("无法解析 host 命令: {e}");
+                reply(Err(format!("无法解析命令: {e}")));
+            }
+        }
+    }
+
+    // stdin 关闭说明父进程已经退出
+    plugin.shutdown();
+    0
+}
+
+/// 把进程原来的 stdout 留给协议用，fd 1 改指向 stderr
+#[cfg(unix)]
+fn take_protocol_stdout() -> Box<dyn Write + Send> {
+    use std::os::fd::FromRawFd;
+
+    unsafe {
+        let fd = libc::dup(libc::STDOUT_FILENO);
+        if fd >= 0 && libc::dup2(libc::STDERR_FILENO, libc::STDOUT_FILENO) >= 0 {
+            return Box::new(std::fs::File::from_raw_fd(fd));
+        }
+    }
+    Box::new(io::stdout())
+}
+
+/// 非 unix 平台没有重定向，插件的 println! 会被父进程当作普通输出打印
+#[cfg(not(unix))]
+fn take_protocol_stdout() -> Box<dyn Write + Send> {
+    Box::new(io::stdout())
+}
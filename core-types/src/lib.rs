@@ -50,3 +50,35 @@ pub struct AlertEvent {
     pub message: String,
     pub tags: HashMap<String, String>,
 }
+
+/// 插件在 bot-host 中的状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PluginStatus {
+    /// 已加载，最近一次执行成功（或还没执行过）
+    Loaded,
+    /// 已加载，但最近连续执行失败，处于退避中
+    Failing,
+    /// 被拒绝加载或初始化失败
+    Rejected,
+    /// 热加载时被卸载（动态库已删除或正在替换）
+    Unloaded,
+    /// bot-host 正常退出
+    Stopped,
+}
+
+/// 插件注册表中的一条记录（bot-host 维护，api-server / dashboard 读取）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PluginRecord {
+    pub name: String,
+    pub version: String,
+    pub kind: String,
+    /// 插件目录里的动态库路径
+    pub path: String,
+    pub status: PluginStatus,
+    pub loaded_at: Option<DateTime<Utc>>,
+    pub last_run_at: Option<DateTime<Utc>>,
+    pub last_error: Option<String>,
+    pub consecutive_failures: u32,
+    /// bot-host 最后一次写入这条记录的时间；运行中的插件会定期刷新，用来判断记录是否过期
+    pub updated_at: DateTime<Utc>,
+}
@@ -0,0 +1,60 @@
+use std::{future::Future, io};
+
+use axum::Router;
+
+use crate::ApiListener;
+
+/// 在 `listener` 上运行 `app`，`shutdown` 完成后停止接受新连接并返回。
+///
+/// 必须在 tokio runtime 里调用。TCP 直接用 `axum::serve`；
+/// Unix domain socket 用 hyper 逐个连接提供服务（axum 0.7 的 serve 只支持 TCP）。
+pub async fn serve(
+    listener: ApiListener,
+    app: Router,
+    shutdown: impl Future<Output = ()> + Send + 'static,
+) -> io::Result<()> {
+    match listener {
+        ApiListener::Tcp(listener) => {
+            let listener = tokio::net::TcpListener::from_std(listener)?;
+            axum::serve(listener, app)
+                .with_graceful_shutdown(shutdown)
+                .await
+        }
+        #[cfg(unix)]
+        ApiListener::Unix(listener, path) => {
+            let listener = tokio::net::UnixListener::from_std(listener)?;
+            let result = serve_unix(listener, app, shutdown).await;
+            let _ = std::fs::remove_file(path);
+            result
+        }
+    }
+}
+
+#[cfg(unix)]
+async fn serve_unix(
+    listener: tokio::net::UnixListener,
+    app: Router,
+    shutdown: impl Future<Output = ()> + Send + 'static,
+) -> io::Result<()> {
+    use hyper_util::{rt::TokioIo, service::TowerToHyperService};
+
+    tokio::pin!(shutdown);
+    loop {
+        let stream = tokio::select! {
+            _ = &mut shutdown => return Ok(()),
+            accepted = listener.accept() => match accepted {
+                Ok((stream, _)) => stream,
+                // 单个连接出错不影响继续接受新连接
+                Err(_) => continue,
+            },
+        };
+
+        let service = TowerToHyperService::new(app.clone());
+        tokio::spawn(async move {
+            let _ = hyper::server::conn::http1::Builder::new()
+                .serve_connection(TokioIo::new(stream), service)
+                .with_upgrades()
+                .await;
+        });
+    }
+}
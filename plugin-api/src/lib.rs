@@ -1,4 +1,136 @@
-use std::os::raw::{c_char, c_longlong};
+use std::collections::HashMap;
+use std::os::raw::{c_char, c_longlong, c_void};
+
+mod listen;
+pub use listen::{ApiListenAddr, ApiListener};
+
+/// 用 axum 在 host 分配的地址上提供 HTTP API（需要开启 `axum` feature）
+#[cfg(feature = "axum")]
+pub mod http;
+
+/// WASM 插件调用 host 导入函数的封装（只在 wasm32 目标下编译）
+#[cfg(target_arch = "wasm32")]
+pub mod wasm;
+
+// ============ ABI 版本 & 能力协商 ============
+
+/// 当前 plugin-api 的 ABI 版本。
+///
+/// - 版本 1：最初的 `PluginContext`（host_version / log_fn / emit_metric_fn），
+///   没有导出 `plugin_abi_version` 的插件一律按 1 处理
+/// - 版本 2：`PluginContext` 末尾追加 `capabilities` 字段，支持能力协商
+///
+/// 只有不兼容的布局变更才需要升级版本；新增的上下文字段一律追加在
+/// `PluginContext` 末尾，并配一个能力位，由双方协商后再使用。
+pub const PLUGIN_ABI_VERSION: u32 = 2;
+
+/// host 仍然能加载的最低 ABI 版本
+pub const MIN_COMPATIBLE_ABI_VERSION: u32 = 1;
+
+/// 能力位：host 提供 `log_fn`
+pub const CAP_LOG: u64 = 1 << 0;
+/// 能力位：host 提供 `emit_metric_fn`
+pub const CAP_EMIT_METRIC: u64 = 1 << 1;
+
+/// 能力位：host 提供 `emit_metric_with_labels_fn`
+pub const CAP_METRIC_LABELS: u64 = 1 << 2;
+/// 能力位：host 提供 `log_with_fields_fn`
+pub const CAP_LOG_FIELDS: u64 = 1 << 3;
+
+/// 能力位：host 会在合适的时机调用 `plugin_init` / `plugin_shutdown` / `plugin_reload_config`
+pub const CAP_LIFECYCLE: u64 = 1 << 4;
+
+/// 能力位：host 提供 `get_config_fn` / `free_string_fn`（读取 `[plugins.<name>]` 配置）
+pub const CAP_PLUGIN_CONFIG: u64 = 1 << 5;
+
+/// 能力位：host 提供 `report_failure_fn`（插件报告本次调用失败，host 据此退避重试）
+pub const CAP_REPORT_FAILURE: u64 = 1 << 6;
+
+/// 能力位：host 分配 HTTP API 的监听地址（`get_api_listen_fn` / `confirm_api_bind_fn`）
+pub const CAP_API_LISTEN: u64 = 1 << 7;
+
+/// 能力位：host 提供 `emit_alert_fn`（告警和日志 / 指标一样走 host 的存储通道）
+pub const CAP_EMIT_ALERT: u64 = 1 << 8;
+
+/// 能力位：host 提供 `query_metrics_fn` / `free_metric_buffer_fn`（直接从 host 的数据库读历史指标）
+pub const CAP_QUERY_METRICS: u64 = 1 << 9;
+
+/// 能力位：host 提供插件之间的发布 / 订阅总线（`publish_fn` / `subscribe_fn` / `unsubscribe_fn`）
+pub const CAP_EVENT_BUS: u64 = 1 << 10;
+
+/// 能力位：插件声明自己的每个导出函数都兜住了 panic（`catch_panic` / `catch_panic_no_ctx`）。
+///
+/// 这一位是插件单方面的承诺，host 总是接受；没有它的原生插件 panic 时会让整个 host abort，
+/// 所以默认放进 worker 子进程运行。请放在 `optional` 里，不认识这一位的老 host 照样能加载。
+pub const CAP_PANIC_SAFE: u64 = 1 << 11;
+
+/// host 把插件上报的每条告警（`AlertEvent` 的 JSON）发布到这个 topic
+pub const TOPIC_ALERT: &str = "alert";
+
+/// 旧版插件（ABI 1）默认具备的能力
+pub const LEGACY_CAPABILITIES: u64 = CAP_LOG | CAP_EMIT_METRIC;
+
+/// 插件声明的能力需求
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Default)]
+pub struct PluginCapabilities {
+    /// 必须由 host 提供的能力，缺任何一个 host 都会拒绝加载
+    pub required: u64,
+    /// 有就用、没有也能跑的能力
+    pub optional: u64,
+}
+
+/// host 端的协商规则，返回协商后的能力位（插件声明的能力 ∩ `host_capabilities`）。
+///
+/// - 插件 ABI 比 host 新、或低于最低兼容版本：拒绝
+/// - ABI 1 的插件，或者没有导出 `plugin_capabilities`（`declared` 为 None）：按 `LEGACY_CAPABILITIES` 处理
+/// - 插件 `required` 的能力 host 不提供：拒绝
+pub fn negotiate_capabilities(
+    abi_version: u32,
+    declared: Option<PluginCapabilities>,
+    host_capabilities: u64,
+) -> Result<u64, String> {
+    if abi_version > PLUGIN_ABI_VERSION {
+        return Err(format!(
+            "插件 ABI 版本 {abi_version} 高于 host 支持的 {PLUGIN_ABI_VERSION}，请升级 bot-host"
+        ));
+    }
+    if abi_version < MIN_COMPATIBLE_ABI_VERSION {
+        return Err(format!(
+            "插件 ABI 版本 {abi_version} 低于 host 最低兼容版本 {MIN_COMPATIBLE_ABI_VERSION}，请用新版 plugin-api 重新编译"
+        ));
+    }
+
+    let declared = declared
+        .filter(|_| abi_version >= 2)
+        .unwrap_or(PluginCapabilities {
+            required: LEGACY_CAPABILITIES,
+            optional: 0,
+        });
+    let missing = declared.required & !host_capabilities;
+    if missing != 0 {
+        return Err(format!(
+            "插件需要的能力 host 不支持: missing={missing:#x}, host={host_capabilities:#x}"
+        ));
+    }
+    Ok((declared.required | declared.optional) & host_capabilities)
+}
+
+/// 插件可以（建议）导出：
+///
+/// #[unsafe(no_mangle)]
+/// pub extern "C" fn plugin_abi_version() -> u32 { plugin_api::PLUGIN_ABI_VERSION }
+pub type PluginAbiVersionFunc = extern "C" fn() -> u32;
+
+/// 插件可以（可选）导出：
+///
+/// #[unsafe(no_mangle)]
+/// pub extern "C" fn plugin_capabilities() -> PluginCapabilities { ... }
+///
+/// 未导出时按 `required = LEGACY_CAPABILITIES, optional = 0` 处理。
+pub type PluginCapabilitiesFunc = extern "C" fn() -> PluginCapabilities;
+
+// ============ 基础 ABI ============
 
 /// 旧版：无上下文的运行函数
 pub type PluginRunFunc = extern "C" fn();
@@ -37,10 +169,104 @@ pub struct MetricSample {
     pub timestamp_ms: c_longlong,
 }
 
+/// FFI 版键值对，用于指标标签（labels）和日志字段（fields）
+///
+/// 指针只需在回调期间有效，host 会立即拷贝。
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct KeyValue {
+    pub key: *const c_char,
+    pub value: *const c_char,
+}
+
+/// 告警级别（给插件用的 FFI 版）
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub enum AlertSeverity {
+    Info = 0,
+    Warning = 1,
+    Critical = 2,
+}
+
+/// 告警（给插件用的 FFI 版）
+///
+/// 指针只需在回调期间有效，host 会立即拷贝。
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct AlertSample {
+    pub severity: AlertSeverity,
+    /// 告警针对的指标名，如 "cpu_usage"；可以为 null
+    pub metric_name: *const c_char,
+    pub title: *const c_char,
+    pub message: *const c_char,
+    /// 时间戳（毫秒），<= 0 时由 host 取当前时间
+    pub timestamp_ms: c_longlong,
+    /// 附加标签
+    pub tags: *const KeyValue,
+    pub tags_len: usize,
+}
+
+/// 历史指标查询条件（给插件用的 FFI 版）
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct MetricQuery {
+    /// 来源插件名，null 表示不限
+    pub plugin: *const c_char,
+    /// 指标名，null 表示不限
+    pub name: *const c_char,
+    /// 起始时间（毫秒，含），<= 0 表示不限
+    pub start_ms: c_longlong,
+    /// 结束时间（毫秒，不含），<= 0 表示不限
+    pub end_ms: c_longlong,
+    /// 最多返回多少条（取最新的），0 表示 host 默认值
+    pub limit: u32,
+}
+
+/// 查询结果中的一个点；字符串和标签归 `MetricBuffer` 所有
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct MetricPoint {
+    pub plugin: *const c_char,
+    pub name: *const c_char,
+    pub value: f64,
+    pub timestamp_ms: c_longlong,
+    pub labels: *const KeyValue,
+    pub labels_len: usize,
+}
+
+/// host 分配的查询结果，按时间正序排列；用完必须交给 `free_metric_buffer_fn` 释放
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct MetricBuffer {
+    pub points: *const MetricPoint,
+    pub len: usize,
+    /// host 内部使用，插件不要修改
+    pub handle: *mut c_void,
+}
+
+/// `PluginContext::query_metrics` 拷贝出来的一个点
+#[derive(Debug, Clone)]
+pub struct MetricRecord {
+    pub plugin: String,
+    pub name: String,
+    pub value: f64,
+    pub timestamp_ms: i64,
+    pub labels: HashMap<String, String>,
+}
+
+/// 事件回调：`(topic, payload_json, user_data)`，字符串只在回调期间有效。
+///
+/// 在 host 的事件分发线程上调用，不要在里面长时间阻塞；panic 同样不能穿过 `extern "C"`，
+/// 用 [`catch_panic_no_ctx`] 兜住。
+pub type EventCallback =
+    extern "C" fn(topic: *const c_char, payload: *const c_char, user_data: *mut c_void);
+
 /// 插件可以通过这个上下文调用 host 提供的功能
+///
+/// ⚠️ 字段只能追加不能调整顺序：旧插件只会读取它认识的前缀部分。
 #[repr(C)]
 pub struct PluginContext {
-    /// 主机版本（协议/ABI版本），后续做兼容判断用
+    /// 主机 ABI 版本（= host 编译时的 `PLUGIN_ABI_VERSION`）
     pub host_version: u32,
 
     /// 由 host 提供的日志函数：
@@ -50,6 +276,354 @@ pub struct PluginContext {
     /// 由 host 提供的指标上报函数：
     /// 插件调用时： emit_metric_fn(sample)
     pub emit_metric_fn: extern "C" fn(sample: MetricSample),
+
+    // ---- ABI 2 起 ----
+
+    /// 协商后的能力位（host 能力 ∩ 插件声明的能力）。
+    /// 仅当 `host_version >= 2` 时存在，请通过 `has_capability` 读取。
+    pub capabilities: u64,
+
+    /// 带标签的指标上报（需要 `CAP_METRIC_LABELS`）：
+    /// emit_metric_with_labels_fn(sample, labels_ptr, labels_len)
+    pub emit_metric_with_labels_fn:
+        extern "C" fn(sample: MetricSample, labels: *const KeyValue, labels_len: usize),
+
+    /// 带结构化字段的日志（需要 `CAP_LOG_FIELDS`）：
+    /// log_with_fields_fn(level, msg, fields_ptr, fields_len)
+    pub log_with_fields_fn: extern "C" fn(
+        level: LogLevel,
+        msg: *const c_char,
+        fields: *const KeyValue,
+        fields_len: usize,
+    ),
+
+    /// 读取插件配置（需要 `CAP_PLUGIN_CONFIG`）：
+    /// get_config_fn(plugin_name) 返回 `[plugins.<name>]` 的 JSON（没有配置时为 `{}`），
+    /// 字符串由 host 分配，用完必须交给 `free_string_fn` 释放。
+    pub get_config_fn: extern "C" fn(plugin: *const c_char) -> *mut c_char,
+
+    /// 释放 host 分配的字符串
+    pub free_string_fn: extern "C" fn(s: *mut c_char),
+
+    /// 报告本次调用失败（需要 `CAP_REPORT_FAILURE`），只在 run / init 调用期间有效。
+    /// host 会把插件标记为不健康，并按指数退避推迟下一次调度。
+    pub report_failure_fn: extern "C" fn(reason: *const c_char),
+
+    /// 读取 host 给插件分配的 HTTP API 监听地址（需要 `CAP_API_LISTEN`）：
+    /// `"127.0.0.1:5600"` 或 `"unix:/path/to/plugin.sock"`，没有分配时返回 null。
+    /// 字符串由 host 分配，用完必须交给 `free_string_fn` 释放。
+    pub get_api_listen_fn: extern "C" fn(plugin: *const c_char) -> *mut c_char,
+
+    /// 插件 bind 成功后回报实际监听的地址（格式同上，需要 `CAP_API_LISTEN`）。
+    /// host 用它生成写入 `plugin_apis` 的 base_url，必须在 `plugin_init` 返回之前调用。
+    pub confirm_api_bind_fn: extern "C" fn(plugin: *const c_char, addr: *const c_char),
+
+    /// 上报告警（需要 `CAP_EMIT_ALERT`），host 写入 alerts 表
+    pub emit_alert_fn: extern "C" fn(sample: AlertSample),
+
+    /// 查询历史指标（需要 `CAP_QUERY_METRICS`），查询失败时返回空的 buffer。
+    /// 会阻塞到数据库返回，不要在持有自己的锁时调用。
+    pub query_metrics_fn: extern "C" fn(query: MetricQuery) -> MetricBuffer,
+
+    /// 释放 `query_metrics_fn` 返回的 buffer
+    pub free_metric_buffer_fn: extern "C" fn(buffer: MetricBuffer),
+
+    /// 发布事件（需要 `CAP_EVENT_BUS`）：publish_fn(plugin, topic, payload_json)。
+    /// 只是入队，不会等订阅方处理完；可以在后台线程里调用。
+    pub publish_fn: extern "C" fn(plugin: *const c_char, topic: *const c_char, payload: *const c_char),
+
+    /// 订阅事件（需要 `CAP_EVENT_BUS`），返回订阅 id，失败时返回 0。
+    /// topic 以 `*` 结尾时按前缀匹配。插件卸载时 host 自动取消它的全部订阅。
+    pub subscribe_fn: extern "C" fn(
+        plugin: *const c_char,
+        topic: *const c_char,
+        callback: EventCallback,
+        user_data: *mut c_void,
+    ) -> u64,
+
+    /// 取消订阅
+    pub unsubscribe_fn: extern "C" fn(id: u64),
+}
+
+impl PluginContext {
+    /// 判断某个能力是否协商成功；老 host 传进来的上下文没有 `capabilities` 字段，
+    /// 这里会先看 `host_version`，避免越界读取。
+    pub fn has_capability(&self, cap: u64) -> bool {
+        if self.host_version < 2 {
+            return LEGACY_CAPABILITIES & cap == cap;
+        }
+        self.capabilities & cap == cap
+    }
+
+    /// 读取本插件的配置 JSON；host 不支持 `CAP_PLUGIN_CONFIG` 时返回 None。
+    /// 内部负责释放 host 分配的字符串。
+    pub fn config_json(&self, plugin_name: &str) -> Option<String> {
+        if !self.has_capability(CAP_PLUGIN_CONFIG) {
+            return None;
+        }
+
+        let name = std::ffi::CString::new(plugin_name).ok()?;
+        let raw = (self.get_config_fn)(name.as_ptr());
+        if raw.is_null() {
+            return None;
+        }
+
+        let json = unsafe { std::ffi::CStr::from_ptr(raw) }
+            .to_str()
+            .ok()
+            .map(|s| s.to_string());
+        (self.free_string_fn)(raw);
+        json
+    }
+
+    /// host 分配给本插件的 API 监听地址；host 不支持 `CAP_API_LISTEN` 或没有分配时返回 None
+    pub fn api_listen_addr(&self, plugin_name: &str) -> Option<ApiListenAddr> {
+        if !self.has_capability(CAP_API_LISTEN) {
+            return None;
+        }
+
+        let name = std::ffi::CString::new(plugin_name).ok()?;
+        let raw = (self.get_api_listen_fn)(name.as_ptr());
+        if raw.is_null() {
+            return None;
+        }
+
+        let addr = unsafe { std::ffi::CStr::from_ptr(raw) }
+            .to_str()
+            .ok()
+            .and_then(|s| s.parse().ok());
+        (self.free_string_fn)(raw);
+        addr
+    }
+
+    /// 告诉 host 实际监听的地址；host 不支持 `CAP_API_LISTEN` 时什么也不做
+    pub fn confirm_api_bind(&self, plugin_name: &str, addr: &ApiListenAddr) {
+        if !self.has_capability(CAP_API_LISTEN) {
+            return;
+        }
+        let (Ok(name), Ok(addr)) = (
+            std::ffi::CString::new(plugin_name),
+            std::ffi::CString::new(addr.to_string()),
+        ) else {
+            return;
+        };
+        (self.confirm_api_bind_fn)(name.as_ptr(), addr.as_ptr());
+    }
+
+    /// 上报一条告警；host 不支持 `CAP_EMIT_ALERT` 时返回 false。
+    /// 时间由 host 取当前时间，`metric_name` 为空字符串时不关联指标。
+    pub fn emit_alert(
+        &self,
+        severity: AlertSeverity,
+        metric_name: &str,
+        title: &str,
+        message: &str,
+        tags: &[(&str, &str)],
+    ) -> bool {
+        if !self.has_capability(CAP_EMIT_ALERT) {
+            return false;
+        }
+
+        let c_string = |s: &str| std::ffi::CString::new(s.replace('\0', " ")).unwrap_or_default();
+        let metric_name = c_string(metric_name);
+        let title = c_string(title);
+        let message = c_string(message);
+        let tag_strings: Vec<_> = tags.iter().map(|(k, v)| (c_string(k), c_string(v))).collect();
+        let tags: Vec<KeyValue> = tag_strings
+            .iter()
+            .map(|(k, v)| KeyValue {
+                key: k.as_ptr(),
+                value: v.as_ptr(),
+            })
+            .collect();
+
+        (self.emit_alert_fn)(AlertSample {
+            severity,
+            metric_name: if metric_name.is_empty() {
+                std::ptr::null()
+            } else {
+                metric_name.as_ptr()
+            },
+            title: title.as_ptr(),
+            message: message.as_ptr(),
+            timestamp_ms: 0,
+            tags: tags.as_ptr(),
+            tags_len: tags.len(),
+        });
+        true
+    }
+
+    /// 查询历史指标，返回按时间正序排列的最新 `limit` 条；host 不支持 `CAP_QUERY_METRICS` 时返回 None。
+    /// `plugin` / `name` 为 None 时不限，`start_ms` / `end_ms` 为 None 时不限时间范围。
+    /// 内部负责释放 host 分配的 buffer。
+    pub fn query_metrics(
+        &self,
+        plugin: Option<&str>,
+        name: Option<&str>,
+        start_ms: Option<i64>,
+        end_ms: Option<i64>,
+        limit: u32,
+    ) -> Option<Vec<MetricRecord>> {
+        if !self.has_capability(CAP_QUERY_METRICS) {
+            return None;
+        }
+
+        let plugin = plugin.map(std::ffi::CString::new).transpose().ok()?;
+        let name = name.map(std::ffi::CString::new).transpose().ok()?;
+        let buffer = (self.query_metrics_fn)(MetricQuery {
+            plugin: plugin.as_ref().map_or(std::ptr::null(), |s| s.as_ptr()),
+            name: name.as_ref().map_or(std::ptr::null(), |s| s.as_ptr()),
+            start_ms: start_ms.unwrap_or(0),
+            end_ms: end_ms.unwrap_or(0),
+            limit,
+        });
+
+        let c_str = |p: *const c_char| {
+            if p.is_null() {
+                return String::new();
+            }
+            unsafe { std::ffi::CStr::from_ptr(p) }
+                .to_string_lossy()
+                .into_owned()
+        };
+        let points = if buffer.points.is_null() || buffer.len == 0 {
+            &[][..]
+        } else {
+            unsafe { std::slice::from_raw_parts(buffer.points, buffer.len) }
+        };
+        let records = points
+            .iter()
+            .map(|p| {
+                let labels = if p.labels.is_null() || p.labels_len == 0 {
+                    &[][..]
+                } else {
+                    unsafe { std::slice::from_raw_parts(p.labels, p.labels_len) }
+                };
+                MetricRecord {
+                    plugin: c_str(p.plugin),
+                    name: c_str(p.name),
+                    value: p.value,
+                    timestamp_ms: p.timestamp_ms,
+                    labels: labels
+                        .iter()
+                        .map(|kv| (c_str(kv.key), c_str(kv.value)))
+                        .collect(),
+                }
+            })
+            .collect();
+        (self.free_metric_buffer_fn)(buffer);
+        Some(records)
+    }
+
+    /// 事件总线的回调；host 不支持 `CAP_EVENT_BUS` 时返回 None。
+    /// 返回的句柄可以拷贝到后台线程里使用。
+    pub fn event_bus(&self) -> Option<EventBus> {
+        self.has_capability(CAP_EVENT_BUS).then_some(EventBus {
+            publish_fn: self.publish_fn,
+            subscribe_fn: self.subscribe_fn,
+            unsubscribe_fn: self.unsubscribe_fn,
+        })
+    }
+
+    /// 在 host 分配的地址上 bind（没有分配时用 `fallback`），并把实际地址回报给 host。
+    ///
+    /// 在 `plugin_init` 里调用，bind 失败时应让 `plugin_init` 返回非 0。
+    pub fn bind_api(&self, plugin_name: &str, fallback: ApiListenAddr) -> std::io::Result<ApiListener> {
+        let addr = self.api_listen_addr(plugin_name).unwrap_or(fallback);
+        let listener = addr.bind()?;
+        self.confirm_api_bind(plugin_name, &listener.local_addr()?);
+        Ok(listener)
+    }
+}
+
+/// 从 `PluginContext` 拷贝出来的事件总线回调
+#[derive(Debug, Copy, Clone)]
+pub struct EventBus {
+    publish_fn: extern "C" fn(plugin: *const c_char, topic: *const c_char, payload: *const c_char),
+    subscribe_fn: extern "C" fn(
+        plugin: *const c_char,
+        topic: *const c_char,
+        callback: EventCallback,
+        user_data: *mut c_void,
+    ) -> u64,
+    unsubscribe_fn: extern "C" fn(id: u64),
+}
+
+impl EventBus {
+    /// 以 `plugin_name` 的名义发布一条事件，`payload` 应该是 JSON
+    pub fn publish(&self, plugin_name: &str, topic: &str, payload: &str) {
+        let (Ok(plugin), Ok(topic), Ok(payload)) = (
+            std::ffi::CString::new(plugin_name),
+            std::ffi::CString::new(topic),
+            std::ffi::CString::new(payload),
+        ) else {
+            return;
+        };
+        (self.publish_fn)(plugin.as_ptr(), topic.as_ptr(), payload.as_ptr());
+    }
+
+    /// 订阅 topic，返回订阅 id；host 拒绝时返回 None。
+    ///
+    /// 插件运行在 worker 子进程里时（`isolation = "process"`，或者没有声明 [`CAP_PANIC_SAFE`]），
+    /// 事件不会转发给子进程，订阅总是被拒绝；这种情况下只能 `publish`。
+    pub fn subscribe(
+        &self,
+        plugin_name: &str,
+        topic: &str,
+        callback: EventCallback,
+        user_data: *mut c_void,
+    ) -> Option<u64> {
+        let plugin = std::ffi::CString::new(plugin_name).ok()?;
+        let topic = std::ffi::CString::new(topic).ok()?;
+        let id = (self.subscribe_fn)(plugin.as_ptr(), topic.as_ptr(), callback, user_data);
+        (id != 0).then_some(id)
+    }
+
+    pub fn unsubscribe(&self, id: u64) {
+        (self.unsubscribe_fn)(id);
+    }
+}
+
+/// 在插件导出函数内部捕获 panic。
+///
+/// panic 不能穿过 `extern "C"` 边界，否则整个 bot-host 进程会直接 abort，host 那一侧兜不住。
+/// 用它包住 `run_with_ctx` 等函数的主体：捕获到 panic 时写一条错误日志、
+/// 通过 `report_failure_fn` 告诉 host 本次失败，并返回 None。
+/// 所有导出函数都兜住之后，在 `plugin_capabilities` 的 `optional` 里声明 [`CAP_PANIC_SAFE`]。
+///
+/// ```ignore
+/// #[unsafe(no_mangle)]
+/// pub extern "C" fn run_with_ctx(ctx: *mut PluginContext) {
+///     let ctx = unsafe { &*ctx };
+///     plugin_api::catch_panic(ctx, || do_work(ctx));
+/// }
+/// ```
+pub fn catch_panic<R>(ctx: &PluginContext, f: impl FnOnce() -> R) -> Option<R> {
+    let payload = match std::panic::catch_unwind(std::panic::AssertUnwindSafe(f)) {
+        Ok(r) => return Some(r),
+        Err(payload) => payload,
+    };
+
+    let detail = payload
+        .downcast_ref::<&str>()
+        .map(|s| s.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "<non-string panic payload>".to_string());
+    let reason = std::ffi::CString::new(format!("插件 panic: {detail}").replace('\0', " "))
+        .unwrap_or_default();
+
+    (ctx.log_fn)(LogLevel::Error, reason.as_ptr());
+    if ctx.has_capability(CAP_REPORT_FAILURE) {
+        (ctx.report_failure_fn)(reason.as_ptr());
+    }
+    None
+}
+
+/// 没有 `PluginContext` 的导出函数（`plugin_shutdown`、事件回调、旧版 `run` 等）用它兜住 panic。
+///
+/// panic 信息由默认的 panic hook 打到 stderr；捕获到 panic 时返回 None。
+pub fn catch_panic_no_ctx<R>(f: impl FnOnce() -> R) -> Option<R> {
+    std::panic::catch_unwind(std::panic::AssertUnwindSafe(f)).ok()
 }
 
 /// 新版：带上下文的运行函数签名
@@ -57,10 +631,38 @@ pub type PluginRunWithContextFunc = extern "C" fn(ctx: *mut PluginContext);
 
 
 
+// ============ 生命周期钩子（均为可选导出） ============
+
+/// 插件第一次被加载、执行 run 之前由 host 调用一次。
+///
+/// 适合启动后台线程 / HTTP server。`ctx` 只在调用期间有效，
+/// 需要长期使用的回调请把函数指针拷贝出来。
+/// 返回 0 表示成功；非 0 时 host 认为初始化失败，不再调度该插件。
+///
+/// 导出函数要解引用 `ctx`，声明成 `unsafe`（clippy 的 not_unsafe_ptr_arg_deref）：
+///
+/// #[unsafe(no_mangle)]
+/// pub unsafe extern "C" fn plugin_init(ctx: *mut PluginContext) -> i32 { ... }
+pub type PluginInitFunc = extern "C" fn(ctx: *mut PluginContext) -> i32;
+
+/// host 退出（SIGINT / SIGTERM）或卸载插件前调用，插件应在返回前停掉自己的后台任务。
+///
+/// #[unsafe(no_mangle)]
+/// pub extern "C" fn plugin_shutdown() { ... }
+pub type PluginShutdownFunc = extern "C" fn();
+
+/// 配置变更（如 SIGHUP）时调用，`config_json` 为该插件 `[plugins.<name>]` 配置的 JSON，
+/// 没有配置时为 `{}`。返回 0 表示已应用。
+///
+/// #[unsafe(no_mangle)]
+/// pub extern "C" fn plugin_reload_config(config_json: *const c_char) -> i32 { ... }
+pub type PluginReloadConfigFunc = extern "C" fn(config_json: *const c_char) -> i32;
+
 /// 插件对外暴露的 HTTP API 信息（可选）
 #[repr(C)]
 pub struct PluginApiInfo {
-    /// 插件内部 HTTP server 监听的端口，例如 5501
+    /// 插件内部 HTTP server 默认监听的端口，例如 5501。
+    /// 通过 `confirm_api_bind_fn` 回报过实际地址时 host 以回报的为准。
     pub port: u16,
     /// 统一前缀，例如 "/api" 或 "/"
     /// 如果不需要前缀，用 "/" 即可
@@ -72,3 +674,316 @@ pub struct PluginApiInfo {
 /// #[unsafe(no_mangle)]
 /// pub extern "C" fn plugin_api_info() -> PluginApiInfo { ... }
 pub type PluginApiInfoFunc = unsafe extern "C" fn() -> PluginApiInfo;
+
+// ============ 调度（可选导出） ============
+
+/// 插件自己声明的调度方式；`[plugins.<name>]` 里的 `cron` / `interval_secs` 优先级更高。
+///
+/// 两者都不指定时使用 `[plugin].default_interval`。
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct PluginSchedule {
+    /// 固定间隔（毫秒），0 表示不指定
+    pub interval_ms: u64,
+    /// cron 表达式（`秒 分 时 日 月 周 [年]`，UTC），null 表示不使用；优先于 `interval_ms`
+    pub cron: *const c_char,
+}
+
+/// 插件可以（可选）导出：
+///
+/// #[unsafe(no_mangle)]
+/// pub extern "C" fn plugin_schedule() -> PluginSchedule { ... }
+///
+/// 只在加载时读取一次，`cron` 指向的字符串需要是 'static 的。
+pub type PluginScheduleFunc = extern "C" fn() -> PluginSchedule;
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::ffi::{CStr, CString};
+
+    use super::*;
+
+    thread_local! {
+        /// 假 host 收到的调用，按顺序记录
+        static CALLS: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) };
+    }
+
+    fn record(call: String) {
+        CALLS.with(|c| c.borrow_mut().push(call));
+    }
+
+    fn take_calls() -> Vec<String> {
+        CALLS.with(|c| c.borrow_mut().split_off(0))
+    }
+
+    fn text(p: *const c_char) -> String {
+        if p.is_null() {
+            return "<null>".to_string();
+        }
+        unsafe { CStr::from_ptr(p) }.to_string_lossy().into_owned()
+    }
+
+    fn pairs(ptr: *const KeyValue, len: usize) -> String {
+        if len == 0 {
+            return String::new();
+        }
+        unsafe { std::slice::from_raw_parts(ptr, len) }
+            .iter()
+            .map(|kv| format!("{}={}", text(kv.key), text(kv.value)))
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+
+    extern "C" fn log(level: LogLevel, msg: *const c_char) {
+        record(format!("log {level:?} {}", text(msg)));
+    }
+    extern "C" fn emit_metric(_: MetricSample) {}
+    extern "C" fn emit_metric_with_labels(_: MetricSample, _: *const KeyValue, _: usize) {}
+    extern "C" fn log_with_fields(_: LogLevel, _: *const c_char, _: *const KeyValue, _: usize) {}
+    extern "C" fn get_config(plugin: *const c_char) -> *mut c_char {
+        CString::new(format!("{{\"plugin\":\"{}\"}}", text(plugin)))
+            .unwrap()
+            .into_raw()
+    }
+    extern "C" fn free_string(s: *mut c_char) {
+        record(format!("free {}", text(s)));
+        drop(unsafe { CString::from_raw(s) });
+    }
+    extern "C" fn report_failure(reason: *const c_char) {
+        record(format!("failure {}", text(reason)));
+    }
+    extern "C" fn get_api_listen(_: *const c_char) -> *mut c_char {
+        std::ptr::null_mut()
+    }
+    extern "C" fn confirm_api_bind(_: *const c_char, _: *const c_char) {}
+    extern "C" fn emit_alert(a: AlertSample) {
+        record(format!(
+            "alert {:?} {} {} {} [{}]",
+            a.severity,
+            text(a.metric_name),
+            text(a.title),
+            text(a.message),
+            pairs(a.tags, a.tags_len)
+        ));
+    }
+
+    /// 假 host 的查询结果：两个点，`handle` 指向拥有这些字符串的 Box
+    struct OwnedBuffer {
+        _strings: Vec<CString>,
+        _labels: Vec<KeyValue>,
+        points: Vec<MetricPoint>,
+    }
+
+    extern "C" fn query_metrics(q: MetricQuery) -> MetricBuffer {
+        record(format!(
+            "query {} {} {} {} {}",
+            text(q.plugin),
+            text(q.name),
+            q.start_ms,
+            q.end_ms,
+            q.limit
+        ));
+        let strings: Vec<CString> = ["cpu", "usage", "host", "a"]
+            .iter()
+            .map(|s| CString::new(*s).unwrap())
+            .collect();
+        let labels = vec![KeyValue {
+            key: strings[2].as_ptr(),
+            value: strings[3].as_ptr(),
+        }];
+        let point = |ms: i64, labels_len: usize| MetricPoint {
+            plugin: strings[0].as_ptr(),
+            name: strings[1].as_ptr(),
+            value: ms as f64 / 10.0,
+            timestamp_ms: ms,
+            labels: labels.as_ptr(),
+            labels_len,
+        };
+        let points = vec![point(10, 0), point(20, 1)];
+        let owned = Box::new(OwnedBuffer {
+            points,
+            _labels: labels,
+            _strings: strings,
+        });
+        MetricBuffer {
+            points: owned.points.as_ptr(),
+            len: owned.points.len(),
+            handle: Box::into_raw(owned).cast(),
+        }
+    }
+    extern "C" fn free_metric_buffer(buffer: MetricBuffer) {
+        record("free buffer".to_string());
+        drop(unsafe { Box::from_raw(buffer.handle.cast::<OwnedBuffer>()) });
+    }
+    extern "C" fn publish(_: *const c_char, _: *const c_char, _: *const c_char) {}
+    extern "C" fn subscribe(
+        _: *const c_char,
+        _: *const c_char,
+        _: EventCallback,
+        _: *mut c_void,
+    ) -> u64 {
+        0
+    }
+    extern "C" fn unsubscribe(_: u64) {}
+
+    fn context(host_version: u32, capabilities: u64) -> PluginContext {
+        PluginContext {
+            host_version,
+            log_fn: log,
+            emit_metric_fn: emit_metric,
+            capabilities,
+            emit_metric_with_labels_fn: emit_metric_with_labels,
+            log_with_fields_fn: log_with_fields,
+            get_config_fn: get_config,
+            free_string_fn: free_string,
+            report_failure_fn: report_failure,
+            get_api_listen_fn: get_api_listen,
+            confirm_api_bind_fn: confirm_api_bind,
+            emit_alert_fn: emit_alert,
+            query_metrics_fn: query_metrics,
+            free_metric_buffer_fn: free_metric_buffer,
+            publish_fn: publish,
+            subscribe_fn: subscribe,
+            unsubscribe_fn: unsubscribe,
+        }
+    }
+
+    const HOST: u64 = LEGACY_CAPABILITIES | CAP_PLUGIN_CONFIG | CAP_REPORT_FAILURE | CAP_EMIT_ALERT;
+
+    #[test]
+    fn negotiation_rejects_incompatible_abi_versions() {
+        let err = negotiate_capabilities(PLUGIN_ABI_VERSION + 1, None, HOST).unwrap_err();
+        assert!(err.contains("高于 host 支持的"), "{err}");
+        let err = negotiate_capabilities(MIN_COMPATIBLE_ABI_VERSION - 1, None, HOST).unwrap_err();
+        assert!(err.contains("低于 host 最低兼容版本"), "{err}");
+    }
+
+    #[test]
+    fn negotiation_rejects_missing_required_capabilities() {
+        let declared = PluginCapabilities {
+            required: CAP_LOG | CAP_EVENT_BUS | CAP_QUERY_METRICS,
+            optional: 0,
+        };
+        let err = negotiate_capabilities(2, Some(declared), HOST).unwrap_err();
+        assert!(
+            err.contains(&format!("missing={:#x}", CAP_EVENT_BUS | CAP_QUERY_METRICS)),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn negotiation_keeps_only_what_both_sides_support() {
+        let declared = PluginCapabilities {
+            required: CAP_LOG | CAP_PLUGIN_CONFIG,
+            optional: CAP_REPORT_FAILURE | CAP_EVENT_BUS,
+        };
+        assert_eq!(
+            negotiate_capabilities(2, Some(declared), HOST),
+            Ok(CAP_LOG | CAP_PLUGIN_CONFIG | CAP_REPORT_FAILURE)
+        );
+        // 没有导出 plugin_capabilities，或者是 ABI 1 的插件：只用旧版能力，声明的被忽略
+        assert_eq!(
+            negotiate_capabilities(2, None, HOST),
+            Ok(LEGACY_CAPABILITIES)
+        );
+        assert_eq!(
+            negotiate_capabilities(1, Some(declared), HOST),
+            Ok(LEGACY_CAPABILITIES)
+        );
+        // 旧版能力 host 不提供时连旧插件也拒绝
+        assert!(negotiate_capabilities(1, None, CAP_LOG).is_err());
+    }
+
+    #[test]
+    fn old_hosts_only_offer_legacy_capabilities() {
+        // ABI 1 的 host 没有 capabilities 字段，这里的值不能被读到
+        let ctx = context(1, u64::MAX);
+        assert!(ctx.has_capability(CAP_LOG | CAP_EMIT_METRIC));
+        assert!(!ctx.has_capability(CAP_PLUGIN_CONFIG));
+        assert_eq!(ctx.config_json("p"), None);
+        assert!(!ctx.emit_alert(AlertSeverity::Info, "", "t", "m", &[]));
+        assert!(ctx.query_metrics(None, None, None, None, 0).is_none());
+        assert!(ctx.event_bus().is_none());
+
+        let ctx = context(2, CAP_LOG | CAP_PLUGIN_CONFIG);
+        assert!(ctx.has_capability(CAP_PLUGIN_CONFIG));
+        assert!(!ctx.has_capability(CAP_PLUGIN_CONFIG | CAP_EMIT_METRIC));
+        assert!(take_calls().is_empty());
+    }
+
+    #[test]
+    fn config_json_frees_the_host_string() {
+        let ctx = context(2, HOST);
+        assert_eq!(
+            ctx.config_json("cpu").as_deref(),
+            Some(r#"{"plugin":"cpu"}"#)
+        );
+        assert_eq!(take_calls(), [r#"free {"plugin":"cpu"}"#]);
+    }
+
+    #[test]
+    fn catch_panic_logs_and_reports_the_failure() {
+        let ctx = context(2, HOST);
+        assert_eq!(catch_panic(&ctx, || 7), Some(7));
+        assert!(take_calls().is_empty());
+
+        assert_eq!(catch_panic(&ctx, || -> i32 { panic!("boom {}", 1) }), None);
+        assert_eq!(
+            take_calls(),
+            ["log Error 插件 panic: boom 1", "failure 插件 panic: boom 1"]
+        );
+
+        // host 不支持 CAP_REPORT_FAILURE 时只写日志
+        let ctx = context(2, LEGACY_CAPABILITIES);
+        assert_eq!(catch_panic(&ctx, || std::panic::panic_any(42)), None);
+        assert_eq!(
+            take_calls(),
+            ["log Error 插件 panic: <non-string panic payload>"]
+        );
+    }
+
+    #[test]
+    fn catch_panic_no_ctx_only_stops_the_unwind() {
+        assert_eq!(catch_panic_no_ctx(|| 7), Some(7));
+        assert_eq!(catch_panic_no_ctx(|| -> i32 { panic!("boom") }), None);
+        assert!(take_calls().is_empty());
+    }
+
+    #[test]
+    fn emit_alert_passes_tags_and_null_metric_name() {
+        let ctx = context(2, HOST);
+        assert!(ctx.emit_alert(
+            AlertSeverity::Critical,
+            "",
+            "磁盘",
+            "a\0b",
+            &[("host", "web-1"), ("disk", "/")],
+        ));
+        assert!(ctx.emit_alert(AlertSeverity::Info, "cpu_usage", "t", "m", &[]));
+        assert_eq!(
+            take_calls(),
+            [
+                "alert Critical <null> 磁盘 a b [host=web-1,disk=/]",
+                "alert Info cpu_usage t m []",
+            ]
+        );
+    }
+
+    #[test]
+    fn query_metrics_copies_points_and_frees_the_buffer() {
+        let ctx = context(2, HOST | CAP_QUERY_METRICS);
+        let records = ctx
+            .query_metrics(Some("cpu"), None, Some(5), None, 100)
+            .unwrap();
+        assert_eq!(take_calls(), ["query cpu <null> 5 0 100", "free buffer"]);
+
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].plugin, "cpu");
+        assert_eq!(records[0].name, "usage");
+        assert_eq!(records[0].timestamp_ms, 10);
+        assert!(records[0].labels.is_empty());
+        assert_eq!(records[1].value, 2.0);
+        assert_eq!(records[1].labels.get("host").map(String::as_str), Some("a"));
+    }
+}
@@ -0,0 +1,88 @@
+use std::{
+    fmt, io,
+    net::{SocketAddr, TcpListener},
+    path::PathBuf,
+    str::FromStr,
+};
+
+#[cfg(unix)]
+use std::os::unix::net::UnixListener;
+
+/// HTTP API 的监听地址，文本形式是 `127.0.0.1:5600` 或 `unix:/path/to/plugin.sock`
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ApiListenAddr {
+    Tcp(SocketAddr),
+    Unix(PathBuf),
+}
+
+impl ApiListenAddr {
+    /// 本机回环地址上的某个端口
+    pub fn localhost(port: u16) -> Self {
+        Self::Tcp(SocketAddr::from(([127, 0, 0, 1], port)))
+    }
+
+    /// 同步 bind，返回的 listener 已设为非阻塞，可以直接交给 tokio
+    pub fn bind(&self) -> io::Result<ApiListener> {
+        match self {
+            Self::Tcp(addr) => {
+                let listener = TcpListener::bind(addr)?;
+                listener.set_nonblocking(true)?;
+                Ok(ApiListener::Tcp(listener))
+            }
+            #[cfg(unix)]
+            Self::Unix(path) => {
+                let listener = UnixListener::bind(path)?;
+                listener.set_nonblocking(true)?;
+                Ok(ApiListener::Unix(listener, path.clone()))
+            }
+            #[cfg(not(unix))]
+            Self::Unix(_) => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "当前平台不支持 Unix domain socket",
+            )),
+        }
+    }
+}
+
+impl fmt::Display for ApiListenAddr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Tcp(addr) => write!(f, "{addr}"),
+            Self::Unix(path) => write!(f, "unix:{}", path.display()),
+        }
+    }
+}
+
+impl FromStr for ApiListenAddr {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.strip_prefix("unix:") {
+            Some("") => Err("unix 地址缺少 socket 路径".to_string()),
+            Some(path) => Ok(Self::Unix(PathBuf::from(path))),
+            None => s
+                .parse()
+                .map(Self::Tcp)
+                .map_err(|e| format!("无效的监听地址 {s}: {e}")),
+        }
+    }
+}
+
+/// 已经 bind 好的 HTTP API listener
+#[derive(Debug)]
+pub enum ApiListener {
+    Tcp(TcpListener),
+    #[cfg(unix)]
+    Unix(UnixListener, PathBuf),
+}
+
+impl ApiListener {
+    /// 实际监听的地址（TCP 端口为 0 时是系统分配的端口）
+    pub fn local_addr(&self) -> io::Result<ApiListenAddr> {
+        match self {
+            Self::Tcp(listener) => listener.local_addr().map(ApiListenAddr::Tcp),
+            #[cfg(unix)]
+            Self::Unix(_, path) => Ok(ApiListenAddr::Unix(path.clone())),
+        }
+    }
+}
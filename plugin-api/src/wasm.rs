@@ -0,0 +1,70 @@
+//! WASM 插件（`--target wasm32-wasip1`，`crate-type = ["cdylib"]`）用的 host 导入封装。
+//!
+//! ```ignore
+//! use plugin_api::{wasm, LogLevel};
+//!
+//! #[unsafe(no_mangle)]
+//! pub extern "C" fn meta() -> i64 {
+//!     wasm::pack_str(r#"{"name":"disk-usage","version":"0.1.0","kind":"disk"}"#)
+//! }
+//!
+//! #[unsafe(no_mangle)]
+//! pub extern "C" fn run_with_ctx() -> i32 {
+//!     wasm::log(LogLevel::Info, "开始执行");
+//!     wasm::emit_metric("disk_usage", 42.0);
+//!     0
+//! }
+//! ```
+
+use crate::LogLevel;
+
+#[link(wasm_import_module = "bot_host")]
+unsafe extern "C" {
+    #[link_name = "log"]
+    fn host_log(level: i32, ptr: *const u8, len: usize);
+    #[link_name = "emit_metric"]
+    fn host_emit_metric(name_ptr: *const u8, name_len: usize, value: f64, timestamp_ms: i64);
+    #[link_name = "get_config"]
+    fn host_get_config(buf_ptr: *mut u8, buf_len: usize) -> i32;
+    #[link_name = "report_failure"]
+    fn host_report_failure(ptr: *const u8, len: usize);
+}
+
+/// 把 `meta()` 要返回的字符串打包成 `(ptr << 32) | len`；字符串要一直有效，一般用字面量
+pub fn pack_str(s: &'static str) -> i64 {
+    (((s.as_ptr() as u64) << 32) | s.len() as u64) as i64
+}
+
+pub fn log(level: LogLevel, msg: &str) {
+    unsafe { host_log(level as i32, msg.as_ptr(), msg.len()) }
+}
+
+/// 时间取 host 的当前时间
+pub fn emit_metric(name: &str, value: f64) {
+    unsafe { host_emit_metric(name.as_ptr(), name.len(), value, 0) }
+}
+
+pub fn emit_metric_at(name: &str, value: f64, timestamp_ms: i64) {
+    unsafe { host_emit_metric(name.as_ptr(), name.len(), value, timestamp_ms) }
+}
+
+/// `[plugins.<name>]` 的 JSON，没有配置时是 `{}`
+pub fn config() -> String {
+    let mut buf = vec![0u8; 256];
+    loop {
+        let len = unsafe { host_get_config(buf.as_mut_ptr(), buf.len()) };
+        let Ok(len) = usize::try_from(len) else {
+            return "{}".to_string();
+        };
+        if len <= buf.len() {
+            buf.truncate(len);
+            return String::from_utf8(buf).unwrap_or_else(|_| "{}".to_string());
+        }
+        buf.resize(len, 0);
+    }
+}
+
+/// 把本次调用记为失败；`run_with_ctx` 返回非 0 也一样
+pub fn report_failure(reason: &str) {
+    unsafe { host_report_failure(reason.as_ptr(), reason.len()) }
+}
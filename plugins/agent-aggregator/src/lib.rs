@@ -1,4 +1,8 @@
-use plugin_api::{PluginContext, PluginMeta};
+use plugin_api::{
+    LogLevel, MetricSample, PluginCapabilities, PluginContext, PluginMeta, CAP_EMIT_METRIC,
+    CAP_LOG, CAP_PANIC_SAFE, CAP_QUERY_METRICS, PLUGIN_ABI_VERSION,
+};
+use std::collections::HashMap;
 use std::os::raw::c_char;
 use std::ffi::CString;
 use chrono::Utc;
@@ -8,10 +12,30 @@ static NAME: &str = "agent-aggregator";
 static VERSION: &str = "0.1.0";
 static KIND: &str = "agent";
 
+/// Agent 上报的 CPU 指标，带 `agent_id` 标签
+const AGENT_CPU_METRIC: &str = "agent_cpu_usage";
+/// 最近这么久内上报过的 agent 算在线
+const ONLINE_WINDOW_MS: i64 = 5 * 60 * 1000;
+/// 最新 CPU 超过这个值时告警日志
+const CPU_WARN_THRESHOLD: f64 = 90.0;
+
 fn c_string(s: &str) -> *const c_char {
     CString::new(s).unwrap().into_raw()
 }
 
+#[unsafe(no_mangle)]
+pub extern "C" fn plugin_abi_version() -> u32 {
+    PLUGIN_ABI_VERSION
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn plugin_capabilities() -> PluginCapabilities {
+    PluginCapabilities {
+        required: CAP_LOG | CAP_EMIT_METRIC | CAP_QUERY_METRICS,
+        optional: CAP_PANIC_SAFE,
+    }
+}
+
 #[unsafe(no_mangle)]
 pub extern "C" fn meta() -> PluginMeta {
     PluginMeta {
@@ -21,21 +45,67 @@ pub extern "C" fn meta() -> PluginMeta {
     }
 }
 
+/// # Safety
+///
+/// `ctx` 为空，或者在调用期间指向 host 提供的有效 `PluginContext`。
 #[unsafe(no_mangle)]
-pub extern "C" fn run_with_ctx(ctx: *mut PluginContext) {
+pub unsafe extern "C" fn run_with_ctx(ctx: *mut PluginContext) {
     if ctx.is_null() {
         return;
     }
-    let ctx = unsafe { &mut *ctx };
-
-    // 暂时只做一件事：记录一条“我还活着”的 log
-    let now = Utc::now().to_rfc3339();
-    let msg = format!("[agent-aggregator] 执行一次聚合任务 at {}", now);
-    ctx.log_info(&msg);
-
-    // TODO：
-    // 后面这里会：
-    // - 通过 storage 或 api-server 查询最近的 agent_* metric
-    // - 计算各 agent 健康状态
-    // - 对超阈值的情况 emit_metric / log / alert
+    let ctx = unsafe { &*ctx };
+    plugin_api::catch_panic(ctx, || aggregate(ctx));
+}
+
+fn log(ctx: &PluginContext, level: LogLevel, msg: &str) {
+    let c = CString::new(msg).unwrap_or_else(|_| CString::new("log error").unwrap());
+    (ctx.log_fn)(level, c.as_ptr());
+}
+
+/// 统计最近上报过的 agent，写一条在线数量指标；CPU 过高的 agent 打告警日志
+fn aggregate(ctx: &PluginContext) {
+    let now = Utc::now().timestamp_millis();
+    let Some(points) = ctx.query_metrics(
+        None,
+        Some(AGENT_CPU_METRIC),
+        Some(now - ONLINE_WINDOW_MS),
+        None,
+        0,
+    ) else {
+        log(ctx, LogLevel::Error, "[agent-aggregator] host 不支持查询历史指标");
+        return;
+    };
+
+    // 结果按时间正序，后出现的覆盖前面的，留下每个 agent 最新的一条
+    let mut latest: HashMap<String, f64> = HashMap::new();
+    for point in points {
+        let agent_id = point
+            .labels
+            .get("agent_id")
+            .cloned()
+            .unwrap_or_else(|| "agent-unknown".to_string());
+        latest.insert(agent_id, point.value);
+    }
+
+    for (agent_id, cpu) in &latest {
+        if *cpu > CPU_WARN_THRESHOLD {
+            log(
+                ctx,
+                LogLevel::Warn,
+                &format!("[agent-aggregator] agent {agent_id} CPU 使用率 {cpu:.1}%"),
+            );
+        }
+    }
+
+    let name = CString::new("agent_online_count").unwrap();
+    (ctx.emit_metric_fn)(MetricSample {
+        name: name.as_ptr(),
+        value: latest.len() as f64,
+        timestamp_ms: now,
+    });
+    log(
+        ctx,
+        LogLevel::Info,
+        &format!("[agent-aggregator] 最近 5 分钟在线 agent: {}", latest.len()),
+    );
 }
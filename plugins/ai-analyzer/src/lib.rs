@@ -3,9 +3,13 @@ use std::os::raw::c_char;
 use std::time::{SystemTime, UNIX_EPOCH};
 
 use chrono::{DateTime, Utc};
-use core_types::{AnomalyResult, Metric};
+use core_types::Metric;
 use dotenv::dotenv;
-use plugin_api::{LogLevel, MetricSample, PluginContext, PluginMeta};
+use plugin_api::{
+    AlertSeverity, LogLevel, MetricSample, PluginCapabilities, PluginContext, PluginMeta,
+    CAP_EMIT_ALERT, CAP_EMIT_METRIC, CAP_LOG, CAP_PANIC_SAFE, CAP_PLUGIN_CONFIG, CAP_QUERY_METRICS,
+    CAP_REPORT_FAILURE, PLUGIN_ABI_VERSION,
+};
 
 use reqwest::blocking::Client;
 use serde::{Deserialize, Serialize};
@@ -16,10 +20,28 @@ static PLUGIN_NAME: &[u8] = b"ai-analyzer\0";
 static PLUGIN_VERSION: &[u8] = b"0.1.0\0";
 static PLUGIN_KIND: &[u8] = b"ai\0";
 
+/// 每次分析读取的 cpu_usage 点数
+const HISTORY_LIMIT: u32 = 200;
+
 // 让 host 还能调用旧 run()，但主要用 run_with_ctx
 #[unsafe(no_mangle)]
 pub extern "C" fn run() {
-    println!("[ai-analyzer] run() 被调用（无上下文版本，仅调试用）");
+    plugin_api::catch_panic_no_ctx(|| {
+        println!("[ai-analyzer] run() 被调用（无上下文版本，仅调试用）");
+    });
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn plugin_abi_version() -> u32 {
+    PLUGIN_ABI_VERSION
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn plugin_capabilities() -> PluginCapabilities {
+    PluginCapabilities {
+        required: CAP_LOG | CAP_EMIT_METRIC | CAP_EMIT_ALERT | CAP_QUERY_METRICS,
+        optional: CAP_PLUGIN_CONFIG | CAP_REPORT_FAILURE | CAP_PANIC_SAFE,
+    }
 }
 
 #[unsafe(no_mangle)]
@@ -33,16 +55,26 @@ pub extern "C" fn meta() -> PluginMeta {
 
 // ============= run_with_ctx：AI 分析入口 =============
 
+/// # Safety
+///
+/// `ctx` 为空，或者在调用期间指向 host 提供的有效 `PluginContext`。
 #[unsafe(no_mangle)]
-pub extern "C" fn run_with_ctx(ctx: *mut PluginContext) {
-    dotenv().ok(); // 支持 .env
-
+pub unsafe extern "C" fn run_with_ctx(ctx: *mut PluginContext) {
     if ctx.is_null() {
-        println!("[ai-analyzer] ctx 为空，无法运行");
+        plugin_api::catch_panic_no_ctx(|| println!("[ai-analyzer] ctx 为空，无法运行"));
         return;
     }
     let ctx = unsafe { &*ctx };
 
+    // panic 不能穿过 FFI 边界，兜住后报告给 host
+    plugin_api::catch_panic(ctx, || {
+        dotenv().ok(); // 支持 .env
+        run_analysis(ctx)
+    });
+}
+
+/// 一轮 AI 分析
+fn run_analysis(ctx: &PluginContext) {
     let log = |level: LogLevel, msg: &str| {
         let c = CString::new(msg).unwrap_or_else(|_| CString::new("log error").unwrap());
         (ctx.log_fn)(level, c.as_ptr());
@@ -50,47 +82,45 @@ pub extern "C" fn run_with_ctx(ctx: *mut PluginContext) {
 
     log(LogLevel::Info, "[ai-analyzer] 开始执行 AI 分析");
 
-    // 1. 读取配置（简单版：环境变量）
-    let backend = std::env::var("AI_BACKEND").unwrap_or_else(|_| "python".to_string());
-    let api_server_base =
-        std::env::var("API_SERVER_BASE").unwrap_or_else(|_| "http://127.0.0.1:3001".to_string());
+    // 1. 读取配置：[plugins.ai-analyzer]，同名环境变量优先
+    let cfg = load_plugin_config(ctx);
+    let backend = std::env::var("AI_BACKEND")
+        .ok()
+        .or(cfg.backend)
+        .unwrap_or_else(|| "python".to_string());
+    let ai_engine_base = std::env::var("AI_ENGINE_BASE")
+        .ok()
+        .or(cfg.ai_engine_base)
+        .unwrap_or_else(|| "http://127.0.0.1:8000".to_string());
 
     log(
         LogLevel::Info,
         &format!(
-            "[ai-analyzer] 使用 AI_BACKEND = {}, API_SERVER_BASE = {}",
-            backend, api_server_base
+            "[ai-analyzer] 使用 AI_BACKEND = {}, AI_ENGINE_BASE = {}",
+            backend, ai_engine_base
         ),
     );
 
-    // 2. 拉取 metrics
-    let client = Client::new();
-    let metrics_url = format!("{}/metrics", api_server_base.trim_end_matches('/'));
-
-    let metrics: Vec<Metric> = match client.get(&metrics_url).send() {
-        Ok(resp) => match resp.json() {
-            Ok(m) => m,
-            Err(e) => {
-                log(
-                    LogLevel::Error,
-                    &format!("[ai-analyzer] 解析 /metrics 响应失败: {e}"),
-                );
-                return;
-            }
-        },
-        Err(e) => {
-            log(
-                LogLevel::Error,
-                &format!("[ai-analyzer] 请求 /metrics 失败: {e}"),
-            );
-            return;
-        }
+    // 2. 直接从 host 的数据库读取最近的 cpu-monitor / cpu_usage
+    let Some(points) =
+        ctx.query_metrics(Some("cpu-monitor"), Some("cpu_usage"), None, None, HISTORY_LIMIT)
+    else {
+        log(LogLevel::Error, "[ai-analyzer] host 不支持查询历史指标");
+        return;
     };
 
-    // 3. 过滤需要分析的序列，例如 cpu-monitor / cpu_usage
-    let mut series: Vec<Metric> = metrics
+    // host 返回的已经是按时间正序排列的
+    let series: Vec<Metric> = points
         .into_iter()
-        .filter(|m| m.plugin == "cpu-monitor" && m.name == "cpu_usage")
+        .filter_map(|p| {
+            Some(Metric {
+                time: DateTime::from_timestamp_millis(p.timestamp_ms)?,
+                plugin: p.plugin,
+                name: p.name,
+                value: p.value,
+                labels: p.labels,
+            })
+        })
         .collect();
 
     if series.len() < 5 {
@@ -101,12 +131,10 @@ pub extern "C" fn run_with_ctx(ctx: *mut PluginContext) {
         return;
     }
 
-    // 按时间正序
-    series.sort_by_key(|m| m.time);
-
-    // 4. 根据 backend 调不同 AI
+    // 3. 根据 backend 调不同 AI
+    let client = Client::new();
     let result = match backend.as_str() {
-        "python" => call_python_ai_engine(&client, &series, &log),
+        "python" => call_python_ai_engine(&client, &ai_engine_base, &series, &log),
         "openai" => call_openai_backend(&client, &series, &log),
         "deepseek" => call_deepseek_backend(&client, &series, &log),
         other => {
@@ -114,7 +142,7 @@ pub extern "C" fn run_with_ctx(ctx: *mut PluginContext) {
                 LogLevel::Warn,
                 &format!("[ai-analyzer] 未知 AI_BACKEND = {other}，默认使用 python"),
             );
-            call_python_ai_engine(&client, &series, &log)
+            call_python_ai_engine(&client, &ai_engine_base, &series, &log)
         }
     };
 
@@ -129,7 +157,7 @@ pub extern "C" fn run_with_ctx(ctx: *mut PluginContext) {
         }
     };
 
-    // 5. 结果写日志 + 写一条 anomaly_score 指标
+    // 4. 结果写日志 + 写一条 anomaly_score 指标
     log(
         if result.is_anomaly {
             LogLevel::Warn
@@ -149,102 +177,84 @@ pub extern "C" fn run_with_ctx(ctx: *mut PluginContext) {
         value: result.score,
         timestamp_ms: current_timestamp_ms(),
     };
-    unsafe {
-        (ctx.emit_metric_fn)(sample);
-    }
+    (ctx.emit_metric_fn)(sample);
 
-    // 6. 如果是异常，通过 HTTP 调用 /alerts 写入告警（由 api-server 统一落库）
+    // 5. 如果是异常，通过 emit_alert_fn 交给 host 写入告警
     if result.is_anomaly {
-        if let Err(e) = report_alert_to_api(&client, &api_server_base, &result, &log) {
-            log(
-                LogLevel::Error,
-                &format!("[ai-analyzer] 上报告警到 /alerts 失败: {e}"),
-            );
-        }
+        report_alert(ctx, &result, &log);
     }
 
     log(LogLevel::Info, "[ai-analyzer] 执行结束");
 }
 
-// ============= /alerts 上报 =============
+// ============= 插件配置：[plugins.ai-analyzer] =============
 
-#[derive(Serialize)]
-struct CreateAlertReq {
-    plugin: String,
-    metric_name: String,
-    severity: String, // "Info" | "Warning" | "Critical"
-    title: String,
-    message: String,
+#[derive(Debug, Default, Deserialize)]
+struct AiAnalyzerConfig {
+    /// python | openai | deepseek
+    backend: Option<String>,
+    ai_engine_base: Option<String>,
 }
 
-fn report_alert_to_api<F>(
-    client: &Client,
-    api_server_base: &str,
-    result: &AnomalyResult,
-    log: &F,
-) -> anyhow::Result<()>
+fn load_plugin_config(ctx: &PluginContext) -> AiAnalyzerConfig {
+    ctx.config_json("ai-analyzer")
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .unwrap_or_default()
+}
+
+// ============= 告警上报 =============
+
+fn report_alert<F>(ctx: &PluginContext, result: &AnomalyResult, log: &F)
 where
     F: Fn(LogLevel, &str),
 {
-    let url = format!("{}/alerts", api_server_base.trim_end_matches('/'));
-
     // 简单逻辑：score 越大，级别越高，你可以以后自己调规则
     let severity = if result.score > 5.0 {
-        "Critical"
+        AlertSeverity::Critical
     } else if result.score > 3.0 {
-        "Warning"
+        AlertSeverity::Warning
     } else {
-        "Info"
-    }
-    .to_string();
+        AlertSeverity::Info
+    };
 
-    let title = "CPU 使用率异常".to_string();
     let message = result
         .reason
         .clone()
         .unwrap_or_else(|| "AI 检测到异常".to_string());
+    let score = format!("{:.2}", result.score);
 
-    let req = CreateAlertReq {
-        plugin: "ai-analyzer".to_string(),
-        metric_name: "cpu_usage".to_string(),
+    if ctx.emit_alert(
         severity,
-        title,
-        message,
-    };
-
-    log(
-        LogLevel::Info,
-        &format!("[ai-analyzer] 向 {} 上报告警...", url),
-    );
-
-    let resp = client.post(&url).json(&req).send()?;
-    if !resp.status().is_success() {
-        log(
-            LogLevel::Error,
-            &format!(
-                "[ai-analyzer] /alerts 响应状态异常: {}",
-                resp.status()
-            ),
-        );
+        "cpu_usage",
+        "CPU 使用率异常",
+        &message,
+        &[("score", &score)],
+    ) {
+        log(LogLevel::Info, "[ai-analyzer] 已上报告警");
     } else {
-        log(LogLevel::Info, "[ai-analyzer] 成功上报告警到 /alerts");
+        log(LogLevel::Error, "[ai-analyzer] host 不支持上报告警");
     }
-
-    Ok(())
 }
 
 // ============= AI 后端调用 =============
 
+/// AI 后端给出的分析结果
+#[derive(Debug)]
+struct AnomalyResult {
+    is_anomaly: bool,
+    score: f64,
+    reason: Option<String>,
+}
+
 fn call_python_ai_engine<F>(
     client: &Client,
+    base: &str,
     series: &[Metric],
     log: &F,
 ) -> anyhow::Result<AnomalyResult>
 where
     F: Fn(LogLevel, &str),
 {
-    let base = std::env::var("AI_ENGINE_BASE")
-        .unwrap_or_else(|_| "http://127.0.0.1:8000".to_string());
     let url = format!("{}/infer/anomaly", base.trim_end_matches('/'));
 
     log(
@@ -1,12 +1,19 @@
 use std::ffi::CString;
 use std::fs;
 use std::os::raw::c_char;
-use std::sync::OnceLock;
+use std::sync::Mutex;
+use std::thread;
 use std::time::{SystemTime, UNIX_EPOCH};
 
 use anyhow::Result;
 use dotenv::dotenv;
-use plugin_api::{LogLevel, MetricSample, PluginApiInfo, PluginContext, PluginMeta};
+use plugin_api::{
+    ApiListenAddr, KeyValue, LogLevel, MetricSample, PluginApiInfo, PluginCapabilities, PluginContext,
+    PluginMeta, CAP_API_LISTEN, CAP_EMIT_METRIC, CAP_LIFECYCLE, CAP_LOG, CAP_METRIC_LABELS, CAP_PANIC_SAFE,
+    CAP_PLUGIN_CONFIG, CAP_REPORT_FAILURE, PLUGIN_ABI_VERSION,
+};
+use serde::Deserialize;
+use tokio::sync::oneshot;
 use serde_json::{json, Value};
 use workflow_core::{EngineKind, StartResult, WorkflowDefinition, WorkflowEngineRunner};
 
@@ -20,11 +27,12 @@ static PLUGIN_NAME: &[u8] = b"api-monitor\0";
 static PLUGIN_VERSION: &[u8] = b"0.2.0\0";
 static PLUGIN_KIND: &[u8] = b"workflow\0";
 
+/// 老 host 不分配监听地址时使用的端口
 const API_PORT: u16 = 5501;
 const API_PREFIX: &str = "/"; // 或 "/api"
 
-// 只启动一次 HTTP API server
-static START_API_ONCE: OnceLock<()> = OnceLock::new();
+// HTTP API server：停止信号 + 线程句柄（plugin_shutdown 时取出）
+static API_SERVER: Mutex<Option<(oneshot::Sender<()>, thread::JoinHandle<()>)>> = Mutex::new(None);
 
 // --------- C 字符串工具 ---------
 
@@ -43,9 +51,27 @@ pub extern "C" fn meta() -> PluginMeta {
     }
 }
 
+#[unsafe(no_mangle)]
+pub extern "C" fn plugin_abi_version() -> u32 {
+    PLUGIN_ABI_VERSION
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn plugin_capabilities() -> PluginCapabilities {
+    PluginCapabilities {
+        required: CAP_LOG | CAP_EMIT_METRIC | CAP_LIFECYCLE,
+        // 有标签能力时按工作流 key 打标签；有配置能力时读 [plugins.api-monitor]
+        optional: CAP_METRIC_LABELS
+            | CAP_PLUGIN_CONFIG
+            | CAP_REPORT_FAILURE
+            | CAP_API_LISTEN
+            | CAP_PANIC_SAFE,
+    }
+}
+
 #[unsafe(no_mangle)]
 pub extern "C" fn run() {
-    println!("[api-monitor] run() 无上下文版本，仅调试用");
+    plugin_api::catch_panic_no_ctx(|| println!("[api-monitor] run() 无上下文版本，仅调试用"));
 }
 
 #[unsafe(no_mangle)]
@@ -56,16 +82,96 @@ pub extern "C" fn plugin_api_info() -> PluginApiInfo {
     }
 }
 
+/// 启动插件自己的 HTTP API server（host 只会调用一次）
+///
+/// # Safety
+///
+/// `ctx` 为空，或者在调用期间指向 host 提供的有效 `PluginContext`。
 #[unsafe(no_mangle)]
-pub extern "C" fn run_with_ctx(ctx: *mut PluginContext) {
-    dotenv().ok();
+pub unsafe extern "C" fn plugin_init(ctx: *mut PluginContext) -> i32 {
+    if ctx.is_null() {
+        return -1;
+    }
+    let ctx = unsafe { &*ctx };
+
+    // panic 不能穿过 FFI 边界，兜住后报告给 host，算作初始化失败
+    plugin_api::catch_panic(ctx, || start_api_server(ctx)).unwrap_or(-1)
+}
 
+fn start_api_server(ctx: &PluginContext) -> i32 {
+    let mut guard = API_SERVER.lock().unwrap_or_else(|e| e.into_inner());
+    if guard.is_some() {
+        return 0;
+    }
+
+    // 在 host 分配的地址上 bind（老 host 不分配时用 API_PORT），bind 失败就让 init 失败
+    let listener = match ctx.bind_api(PLUGIN_NAME_STR, ApiListenAddr::localhost(API_PORT)) {
+        Ok(l) => l,
+        Err(e) => {
+            eprintln!("[api-monitor] HTTP server bind 失败: {e}");
+            return -1;
+        }
+    };
+    if let Ok(addr) = listener.local_addr() {
+        println!("[api-monitor] HTTP API 监听在 {addr}");
+    }
+
+    // 不能直接阻塞当前线程，开一个新线程+runtime
+    let (tx, rx) = oneshot::channel::<()>();
+    let handle = thread::spawn(move || {
+        use axum::{routing::get, Router};
+        use tokio::runtime::Runtime;
+
+        let rt = Runtime::new().expect("create tokio runtime for api-monitor");
+        rt.block_on(async move {
+            let app = Router::new()
+                .route("/health", get(api_health))
+                .route("/status", get(api_status));
+
+            let shutdown = async {
+                let _ = rx.await;
+            };
+            if let Err(e) = plugin_api::http::serve(listener, app, shutdown).await {
+                eprintln!("[api-monitor] HTTP server error: {e}");
+            }
+        });
+    });
+
+    *guard = Some((tx, handle));
+    0
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn plugin_shutdown() {
+    plugin_api::catch_panic_no_ctx(|| {
+        let server = API_SERVER.lock().unwrap_or_else(|e| e.into_inner()).take();
+        if let Some((tx, handle)) = server {
+            let _ = tx.send(());
+            let _ = handle.join();
+        }
+    });
+}
+
+/// # Safety
+///
+/// `ctx` 为空，或者在调用期间指向 host 提供的有效 `PluginContext`。
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn run_with_ctx(ctx: *mut PluginContext) {
     if ctx.is_null() {
-        println!("[api-monitor] ctx 为空，无法运行");
+        plugin_api::catch_panic_no_ctx(|| println!("[api-monitor] ctx 为空，无法运行"));
         return;
     }
     let ctx = unsafe { &*ctx };
 
+    // panic 不能穿过 FFI 边界，兜住后报告给 host
+    plugin_api::catch_panic(ctx, || {
+        dotenv().ok();
+        run_monitor(ctx)
+    });
+}
+
+/// 一轮工作流监控
+fn run_monitor(ctx: &PluginContext) {
     let log = |level: LogLevel, msg: &str| {
         let c = CString::new(msg).unwrap_or_else(|_| CString::new("log error").unwrap());
         (ctx.log_fn)(level, c.as_ptr());
@@ -73,40 +179,18 @@ pub extern "C" fn run_with_ctx(ctx: *mut PluginContext) {
 
     log(LogLevel::Info, "[api-monitor] run_with_ctx 被调用");
 
-    // 启动插件自己的 HTTP API server（只会启动一次）
-    START_API_ONCE.get_or_init(|| {
-        // 不能直接阻塞当前线程，开一个新线程+runtime
-        std::thread::spawn(|| {
-            use axum::{routing::get, Router};
-            use tokio::runtime::Runtime;
-
-            let rt = Runtime::new().expect("create tokio runtime for api-monitor");
-            rt.block_on(async move {
-                let app = Router::new()
-                    .route("/health", get(api_health))
-                    .route("/status", get(api_status));
-
-                let addr = format!("127.0.0.1:{}", API_PORT)
-                    .parse()
-                    .unwrap();
-                println!("[api-monitor] HTTP API 监听在 http://{}", addr);
-                if let Err(e) = axum::Server::bind(&addr)
-                    .serve(app.into_make_service())
-                    .await
-                {
-                    eprintln!("[api-monitor] HTTP server error: {e}");
-                }
-            });
-        });
-    });
-
     log(LogLevel::Info, "[api-monitor] 开始执行 LogicFlow JSON 工作流监控");
 
+    // 每轮都重新读配置，host 重新加载 config.toml 后自动生效
+    let cfg = load_plugin_config(ctx);
+
     // 1) 从目录加载 LogicFlow JSON 定义
-    // 建议使用新的环境变量：API_MONITOR_WORKFLOW_DIR
+    // 优先级：环境变量 API_MONITOR_WORKFLOW_DIR > config.toml 的 workflow_dir
     // 默认为：workflows/api-monitor
     let wf_dir = std::env::var("API_MONITOR_WORKFLOW_DIR")
-        .unwrap_or_else(|_| "workflows/api-monitor".to_string());
+        .ok()
+        .or(cfg.workflow_dir)
+        .unwrap_or_else(|| "workflows/api-monitor".to_string());
 
     let defs = match load_workflows_from_dir(&wf_dir) {
         Ok(list) => {
@@ -152,7 +236,9 @@ pub extern "C" fn run_with_ctx(ctx: *mut PluginContext) {
 
     // 2) 选择引擎类型（现在主要用 local_json，预留 flowable / zeebe）
     let engine_kind = match std::env::var("WORKFLOW_ENGINE")
-        .unwrap_or_else(|_| "local_json".to_string())
+        .ok()
+        .or(cfg.engine)
+        .unwrap_or_else(|| "local_json".to_string())
         .as_str()
     {
         "local_json" => EngineKind::LocalJson,
@@ -223,8 +309,8 @@ pub extern "C" fn run_with_ctx(ctx: *mut PluginContext) {
                         key, duration_ms
                     ),
                 );
-                emit_metric(ctx, "api_flow_success", 1.0);
-                emit_metric(ctx, "api_flow_duration_ms", duration_ms);
+                emit_metric(ctx, "api_flow_success", 1.0, &key);
+                emit_metric(ctx, "api_flow_duration_ms", duration_ms, &key);
             }
             Ok(StartResult {
                 success: _,
@@ -238,8 +324,8 @@ pub extern "C" fn run_with_ctx(ctx: *mut PluginContext) {
                         key, error_message, duration_ms
                     ),
                 );
-                emit_metric(ctx, "api_flow_success", 0.0);
-                emit_metric(ctx, "api_flow_duration_ms", duration_ms);
+                emit_metric(ctx, "api_flow_success", 0.0, &key);
+                emit_metric(ctx, "api_flow_duration_ms", duration_ms, &key);
             }
             Err(e) => {
                 log(
@@ -249,8 +335,8 @@ pub extern "C" fn run_with_ctx(ctx: *mut PluginContext) {
                         key, duration_ms
                     ),
                 );
-                emit_metric(ctx, "api_flow_success", 0.0);
-                emit_metric(ctx, "api_flow_duration_ms", duration_ms);
+                emit_metric(ctx, "api_flow_success", 0.0, &key);
+                emit_metric(ctx, "api_flow_duration_ms", duration_ms, &key);
             }
         }
     }
@@ -258,6 +344,22 @@ pub extern "C" fn run_with_ctx(ctx: *mut PluginContext) {
     log(LogLevel::Info, "[api-monitor] 本轮执行结束");
 }
 
+// --------- 插件配置：[plugins.api-monitor] ---------
+
+#[derive(Debug, Default, Deserialize)]
+struct ApiMonitorConfig {
+    /// LogicFlow JSON 工作流目录
+    workflow_dir: Option<String>,
+    /// 工作流引擎：local_json | flowable | zeebe
+    engine: Option<String>,
+}
+
+fn load_plugin_config(ctx: &PluginContext) -> ApiMonitorConfig {
+    ctx.config_json(PLUGIN_NAME_STR)
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .unwrap_or_default()
+}
+
 // --------- 同步封装：在有/无 tokio runtime 时都能安全执行 async 引擎 ---------
 
 fn run_workflow_once_blocking(
@@ -329,7 +431,8 @@ fn load_workflows_from_dir(dir: &str) -> Result<Vec<WorkflowDefinition>> {
 
 // --------- Metric 上报工具 ---------
 
-fn emit_metric(ctx: &PluginContext, name: &str, value: f64) {
+/// 上报一条指标；host 支持标签时带上 `workflow=<key>`
+fn emit_metric(ctx: &PluginContext, name: &str, value: f64, workflow_key: &str) {
     let cname =
         CString::new(name).unwrap_or_else(|_| CString::new("metric").unwrap());
     let sample = MetricSample {
@@ -338,7 +441,20 @@ fn emit_metric(ctx: &PluginContext, name: &str, value: f64) {
         timestamp_ms: current_timestamp_ms(),
     };
 
-    (ctx.emit_metric_fn)(sample);
+    if !ctx.has_capability(CAP_METRIC_LABELS) {
+        (ctx.emit_metric_fn)(sample);
+        return;
+    }
+
+    let label_key = CString::new("workflow").unwrap();
+    let label_value =
+        CString::new(workflow_key).unwrap_or_else(|_| CString::new("<invalid>").unwrap());
+    let labels = [KeyValue {
+        key: label_key.as_ptr(),
+        value: label_value.as_ptr(),
+    }];
+
+    (ctx.emit_metric_with_labels_fn)(sample, labels.as_ptr(), labels.len());
 }
 
 fn current_timestamp_ms() -> i64 {
@@ -4,8 +4,15 @@ use std::ffi::CString;
 use plugin_api::{
     LogLevel,
     MetricSample,
+    PluginCapabilities,
     PluginContext,
     PluginMeta,
+    PluginSchedule,
+    CAP_EMIT_METRIC,
+    CAP_LOG,
+    CAP_PANIC_SAFE,
+    CAP_REPORT_FAILURE,
+    PLUGIN_ABI_VERSION,
 };
 
 static PLUGIN_NAME: &[u8] = b"cpu-monitor\0";
@@ -15,23 +22,27 @@ static PLUGIN_KIND: &[u8] = b"cpu\0";
 /// 旧接口：无上下文，host 仍然可以调用
 #[unsafe(no_mangle)]
 pub extern "C" fn run() {
-    println!("[cpu-monitor] run() 被调用（无上下文版本）");
+    plugin_api::catch_panic_no_ctx(|| println!("[cpu-monitor] run() 被调用（无上下文版本）"));
 }
 
 /// 新接口：带上下文
+///
+/// # Safety
+///
+/// `ctx` 为空，或者在调用期间指向 host 提供的有效 `PluginContext`。
 #[unsafe(no_mangle)]
-pub extern "C" fn run_with_ctx(ctx: *mut PluginContext) {
+pub unsafe extern "C" fn run_with_ctx(ctx: *mut PluginContext) {
     // 安全起见先检查指针
     if ctx.is_null() {
         // 退而求其次，打印一下
-        println!("[cpu-monitor] run_with_ctx 收到空 ctx 指针");
+        plugin_api::catch_panic_no_ctx(|| println!("[cpu-monitor] run_with_ctx 收到空 ctx 指针"));
         return;
     }
 
-    // 安全块内操作
-    unsafe {
-        let ctx = &*ctx;
+    let ctx = unsafe { &*ctx };
 
+    // panic 不能穿过 FFI 边界，兜住后报告给 host
+    plugin_api::catch_panic(ctx, || {
         // 1. 通过 log_fn 写一条日志
         let msg = CString::new("CPU 插件开始执行").unwrap();
         (ctx.log_fn)(LogLevel::Info, msg.as_ptr());
@@ -49,6 +60,30 @@ pub extern "C" fn run_with_ctx(ctx: *mut PluginContext) {
         // 3. 再打一条日志
         let done_msg = CString::new("CPU 插件执行完毕").unwrap();
         (ctx.log_fn)(LogLevel::Debug, done_msg.as_ptr());
+    });
+}
+
+/// 声明本插件编译时使用的 ABI 版本
+#[unsafe(no_mangle)]
+pub extern "C" fn plugin_abi_version() -> u32 {
+    PLUGIN_ABI_VERSION
+}
+
+/// 只依赖日志和指标上报
+#[unsafe(no_mangle)]
+pub extern "C" fn plugin_capabilities() -> PluginCapabilities {
+    PluginCapabilities {
+        required: CAP_LOG | CAP_EMIT_METRIC,
+        optional: CAP_REPORT_FAILURE | CAP_PANIC_SAFE,
+    }
+}
+
+/// 默认每 5 秒采样一次，可以在 `[plugins.cpu-monitor]` 里用 interval_secs / cron 覆盖
+#[unsafe(no_mangle)]
+pub extern "C" fn plugin_schedule() -> PluginSchedule {
+    PluginSchedule {
+        interval_ms: 5_000,
+        cron: std::ptr::null(),
     }
 }
 
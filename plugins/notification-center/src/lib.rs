@@ -7,17 +7,32 @@ mod channel;
 mod types;
 mod metrics_bridge;
 
-use std::os::raw::c_char;
+use std::ffi::CStr;
+use std::os::raw::{c_char, c_void};
+use std::sync::Mutex;
 use std::thread;
 
 use once_cell::sync::OnceCell;
-use plugin_api::{PluginContext, PluginMeta, LogLevel};
+use plugin_api::{
+    ApiListenAddr, LogLevel, PluginApiInfo, PluginCapabilities, PluginContext, PluginMeta,
+    CAP_API_LISTEN, CAP_EMIT_METRIC, CAP_EVENT_BUS, CAP_LIFECYCLE, CAP_LOG, CAP_PANIC_SAFE,
+    CAP_PLUGIN_CONFIG, PLUGIN_ABI_VERSION, TOPIC_ALERT,
+};
+use tokio::sync::{mpsc, oneshot};
 
 use crate::metrics_bridge::HostBridge;
+use crate::types::SendRequest;
 
 // 给其它模块用的：全局 HostBridge
 static HOST_BRIDGE: OnceCell<HostBridge> = OnceCell::new();
 
+// 后台服务：停止信号 + 线程句柄（plugin_shutdown 时取出）
+static SERVER: Mutex<Option<(oneshot::Sender<()>, thread::JoinHandle<()>)>> = Mutex::new(None);
+
+// 订阅到的告警：收件人 + 转给后台 runtime 的发送端（plugin_shutdown 时清空）
+static ALERT_EVENTS: Mutex<Option<(String, mpsc::UnboundedSender<SendRequest>)>> =
+    Mutex::new(None);
+
 // 你之前的 meta 保持风格一致即可
 static NAME: &[u8] = b"notification-center\0";
 static VERSION: &[u8] = b"0.2.0\0";
@@ -32,30 +47,135 @@ pub extern "C" fn meta() -> PluginMeta {
     }
 }
 
-// 如果你的框架里有 plugin_api_info，这里可以继续保持原来的实现
-// 这里省略，只展示 run_with_ctx
+#[unsafe(no_mangle)]
+pub extern "C" fn plugin_abi_version() -> u32 {
+    PLUGIN_ABI_VERSION
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn plugin_capabilities() -> PluginCapabilities {
+    PluginCapabilities {
+        required: CAP_LOG | CAP_EMIT_METRIC | CAP_LIFECYCLE,
+        optional: CAP_PLUGIN_CONFIG | CAP_API_LISTEN | CAP_EVENT_BUS | CAP_PANIC_SAFE,
+    }
+}
+
+/// 老 host 不分配监听地址、也没有配置端口时使用
+const DEFAULT_PORT: u16 = 5601;
+static API_PREFIX: &[u8] = b"/\0";
+
+#[unsafe(no_mangle)]
+pub extern "C" fn plugin_api_info() -> PluginApiInfo {
+    PluginApiInfo {
+        port: DEFAULT_PORT,
+        prefix: API_PREFIX.as_ptr() as *const c_char,
+    }
+}
 
 #[unsafe(no_mangle)]
 pub extern "C" fn run() {
     // 兼容老的 host，简单输出一下
-    println!("[notification-center] run() called without context (legacy)");
+    plugin_api::catch_panic_no_ctx(|| {
+        println!("[notification-center] run() called without context (legacy)");
+    });
 }
 
+/// # Safety
+///
+/// `ctx` 为空，或者在调用期间指向 host 提供的有效 `PluginContext`。
 #[unsafe(no_mangle)]
-pub extern "C" fn run_with_ctx(ctx: *mut PluginContext) {
+pub unsafe extern "C" fn plugin_init(ctx: *mut PluginContext) -> i32 {
     if ctx.is_null() {
-        return;
+        return -1;
+    }
+    let ctx = unsafe { &*ctx };
+
+    // panic 不能穿过 FFI 边界，兜住后报告给 host，算作初始化失败
+    plugin_api::catch_panic(ctx, || start(ctx)).unwrap_or(-1)
+}
+
+fn start(ctx: &PluginContext) -> i32 {
+    let mut guard = SERVER.lock().unwrap_or_else(|e| e.into_inner());
+    if guard.is_some() {
+        return 0;
     }
-    let ctx = unsafe { &mut *ctx };
 
     // 初始化 HostBridge，让插件内部任意地方都能记日志 / 上报 metric
     let bridge = HostBridge::from_ctx(ctx, "notification-center".to_string());
     let _ = HOST_BRIDGE.set(bridge);
 
-    HostBridge::log_static(LogLevel::Info, "[notification-center] run_with_ctx");
+    HostBridge::log_static(LogLevel::Info, "[notification-center] plugin_init");
+
+    // 端口：环境变量 NC_PLUGIN_PORT > [plugins.notification-center].port > host 分配 > 5601
+    let config: serde_json::Value = ctx
+        .config_json("notification-center")
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .unwrap_or_default();
+    let fixed_port: Option<u16> = std::env::var("NC_PLUGIN_PORT")
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .or_else(|| {
+            config
+                .get("port")
+                .and_then(|v| v.as_u64())
+                .and_then(|p| u16::try_from(p).ok())
+        });
+    let bound = match fixed_port {
+        Some(port) => ApiListenAddr::localhost(port).bind().and_then(|listener| {
+            ctx.confirm_api_bind("notification-center", &listener.local_addr()?);
+            Ok(listener)
+        }),
+        None => ctx.bind_api("notification-center", ApiListenAddr::localhost(DEFAULT_PORT)),
+    };
+    let listener = match bound {
+        Ok(l) => l,
+        Err(e) => {
+            HostBridge::log_static(
+                LogLevel::Error,
+                &format!("[notification-center] http server bind error: {e}"),
+            );
+            return -1;
+        }
+    };
+
+    // 配置了 alert_user_id 时，把事件总线上的告警转成 scene=alert 的通知发给这个用户
+    let alert_user = config
+        .get("alert_user_id")
+        .and_then(|v| v.as_str())
+        .map(str::to_string);
+    let alerts = match (alert_user, ctx.event_bus()) {
+        (Some(user_id), Some(events)) => {
+            let (alert_tx, alert_rx) = mpsc::unbounded_channel();
+            *ALERT_EVENTS.lock().unwrap_or_else(|e| e.into_inner()) = Some((user_id, alert_tx));
+            events
+                .subscribe(
+                    "notification-center",
+                    TOPIC_ALERT,
+                    on_alert_event,
+                    std::ptr::null_mut(),
+                )
+                .map(|_| alert_rx)
+                .or_else(|| {
+                    HostBridge::log_static(
+                        LogLevel::Warn,
+                        "[notification-center] 订阅告警事件失败，alert_user_id 不生效",
+                    );
+                    None
+                })
+        }
+        (Some(_), None) => {
+            HostBridge::log_static(
+                LogLevel::Warn,
+                "[notification-center] host 不支持事件总线，alert_user_id 不生效",
+            );
+            None
+        }
+        (None, _) => None,
+    };
 
     // 起一个线程跑 tokio runtime（避免阻塞 host 主线程）
-    thread::spawn(|| {
+    let (tx, rx) = oneshot::channel::<()>();
+    let handle = thread::spawn(move || {
         let rt = tokio::runtime::Runtime::new().expect("create runtime failed");
         rt.block_on(async {
             // 1) 初始化自己的 DB 表（不会修改 storage）
@@ -68,7 +188,7 @@ pub extern "C" fn run_with_ctx(ctx: *mut PluginContext) {
             }
 
             // 2) 启动 HTTP 服务 + 异步发送队列 worker
-            if let Err(e) = router::start_server().await {
+            if let Err(e) = router::start_server(listener, rx, alerts).await {
                 HostBridge::log_static(
                     LogLevel::Error,
                     &format!("[notification-center] http server error: {e}"),
@@ -77,10 +197,59 @@ pub extern "C" fn run_with_ctx(ctx: *mut PluginContext) {
         });
     });
 
+    *guard = Some((tx, handle));
+
     HostBridge::log_static(
         LogLevel::Info,
         "[notification-center] background server spawned",
     );
+    0
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn plugin_shutdown() {
+    plugin_api::catch_panic_no_ctx(|| {
+        ALERT_EVENTS
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .take();
+        let server = SERVER.lock().unwrap_or_else(|e| e.into_inner()).take();
+        if let Some((tx, handle)) = server {
+            HostBridge::log_static(LogLevel::Info, "[notification-center] shutting down");
+            let _ = tx.send(());
+            let _ = handle.join();
+        }
+    });
+}
+
+/// alert 事件回调：在 host 的事件分发线程上执行，只负责转给后台 runtime
+extern "C" fn on_alert_event(_topic: *const c_char, payload: *const c_char, _user_data: *mut c_void) {
+    if payload.is_null() {
+        return;
+    }
+    let payload = unsafe { CStr::from_ptr(payload) };
+    plugin_api::catch_panic_no_ctx(|| forward_alert(&payload.to_string_lossy()));
+}
+
+fn forward_alert(payload: &str) {
+    let Ok(alert) = serde_json::from_str::<serde_json::Value>(payload) else {
+        HostBridge::log_static(
+            LogLevel::Warn,
+            &format!("[notification-center] 无法解析告警事件: {payload}"),
+        );
+        return;
+    };
+
+    let guard = ALERT_EVENTS.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some((user_id, tx)) = guard.as_ref() {
+        // 告警的 title / message / severity 等字段都可以在模板里用 {{title}} 引用
+        let _ = tx.send(SendRequest {
+            user_id: user_id.clone(),
+            scene: "alert".to_string(),
+            channel_hint: None,
+            vars: alert,
+        });
+    }
 }
 
 // 其它模块如果想用 HostBridge：直接 use crate::HOST_BRIDGE;
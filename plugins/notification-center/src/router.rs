@@ -1,7 +1,5 @@
 // plugins/notification-center/src/router.rs
-use std::net::SocketAddr;
 use std::sync::Arc;
-use tokio::net::TcpListener;
 
 use axum::{
     extract::Path,
@@ -9,8 +7,9 @@ use axum::{
     Json, Router,
 };
 use chrono::Utc;
+use plugin_api::ApiListener;
 use serde_json::{json, Value};
-use tokio::sync::mpsc;
+use tokio::sync::{mpsc, oneshot};
 
 use crate::channel;
 use crate::db;
@@ -21,13 +20,27 @@ use crate::types::{Channel, InternalMessage, MessageStatus, SendRequest};
 type Tx = mpsc::Sender<InternalMessage>;
 type Rx = mpsc::Receiver<InternalMessage>;
 
-pub async fn start_server() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+pub async fn start_server(
+    listener: ApiListener,
+    shutdown: oneshot::Receiver<()>,
+    alerts: Option<mpsc::UnboundedReceiver<SendRequest>>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     // 创建队列 & worker
     let (tx, rx) = mpsc::channel::<InternalMessage>(1000);
     tokio::spawn(worker(rx));
 
     let tx_filter = Arc::new(tx);
 
+    // 事件总线上的告警和 /send 走同一个队列
+    if let Some(mut alerts) = alerts {
+        let tx = tx_filter.clone();
+        tokio::spawn(async move {
+            while let Some(req) = alerts.recv().await {
+                enqueue(&tx, req).await;
+            }
+        });
+    }
+
     let app = Router::new()
         .route("/send", post({
             let tx = tx_filter.clone();
@@ -35,17 +48,14 @@ pub async fn start_server() -> Result<(), Box<dyn std::error::Error + Send + Syn
         }))
         .route("/message/:msg_id", get(api_get_message));
 
-    let port: u16 = std::env::var("NC_PLUGIN_PORT")
-        .ok()
-        .and_then(|s| s.parse().ok())
-        .unwrap_or(5601);
-
-    let addr: SocketAddr = format!("127.0.0.1:{port}").parse().unwrap();
-
-    println!("[notification-center] listen at http://{addr}");
+    if let Ok(addr) = listener.local_addr() {
+        println!("[notification-center] listen at {addr}");
+    }
 
-    let listener = TcpListener::bind(addr).await?;
-    axum::serve(listener, app.into_make_service()).await?;
+    plugin_api::http::serve(listener, app, async {
+        let _ = shutdown.await;
+    })
+    .await?;
 
     Ok(())
 }
@@ -56,6 +66,11 @@ async fn api_send(
     tx: Arc<Tx>,
     Json(req): Json<SendRequest>,
 ) -> Json<Value> {
+    Json(enqueue(&tx, req).await)
+}
+
+/// 写入 waiting 状态并入队，返回 msg_id / trace_id / status
+async fn enqueue(tx: &Tx, req: SendRequest) -> Value {
     let trace_id = uuid::Uuid::new_v4().to_string();
     let msg_id = format!("ntf_{}", uuid::Uuid::new_v4());
     let created_at = Utc::now();
@@ -69,11 +84,11 @@ async fn api_send(
             plugin_api::LogLevel::Error,
             &format!("[notification-center] insert_waiting error: {e}"),
         );
-        return Json(json!({
+        return json!({
             "msg_id": msg_id,
             "trace_id": trace_id,
             "status": "db_error"
-        }));
+        });
     }
 
     // 入队（异步 worker 去做后续步骤）
@@ -91,20 +106,20 @@ async fn api_send(
             plugin_api::LogLevel::Error,
             &format!("[notification-center] enqueue error: {e}"),
         );
-        return Json(json!({
+        return json!({
             "msg_id": msg_id,
             "trace_id": trace_id,
             "status": "queue_error"
-        }));
+        });
     }
 
     HostBridge::metric_static("notification_enqueue", 1.0);
 
-    Json(json!({
+    json!({
         "msg_id": msg_id,
         "trace_id": trace_id,
         "status": "queued"
-    }))
+    })
 }
 
 async fn api_get_message(Path(msg_id): Path<String>) -> Json<Value> {
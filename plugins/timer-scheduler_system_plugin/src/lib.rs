@@ -1,4 +1,10 @@
-use std::{ffi::CString, os::raw::c_char, time::Duration};
+use std::{
+    ffi::CString,
+    os::raw::c_char,
+    sync::Mutex,
+    thread,
+    time::Duration,
+};
 
 use axum::{
     extract::{Path, State},
@@ -7,28 +13,37 @@ use axum::{
     Json, Router,
 };
 use chrono::{DateTime, Utc};
-use once_cell::sync::OnceCell;
-use plugin_api::{LogLevel, MetricSample, PluginApiInfo, PluginContext, PluginMeta};
+use plugin_api::{
+    ApiListenAddr, ApiListener, EventBus, LogLevel, MetricSample, PluginApiInfo,
+    PluginCapabilities, PluginContext, PluginMeta, CAP_API_LISTEN, CAP_EMIT_METRIC,
+    CAP_EVENT_BUS, CAP_LIFECYCLE, CAP_LOG, CAP_PANIC_SAFE, PLUGIN_ABI_VERSION,
+};
 use serde::{Deserialize, Serialize};
 use sqlx::{sqlite::SqlitePoolOptions, SqlitePool};
+use tokio::sync::watch;
 use tokio::time::sleep;
 use tracing::{error, info};
 use tracing_subscriber::EnvFilter;
 
-// ==== 全局：只启动一次 ====
+// ==== 全局：后台任务的停止信号 + 线程句柄（plugin_shutdown 时取出） ====
 
-static STARTED: OnceCell<()> = OnceCell::new();
+static SERVER: Mutex<Option<(watch::Sender<bool>, thread::JoinHandle<()>)>> = Mutex::new(None);
 
-// ====== 主导出：meta / plugin_api_info / run_with_ctx ======
+// ====== 主导出：meta / plugin_api_info / plugin_init / plugin_shutdown ======
 
 const NAME: &str = "timer-scheduler_system_plugin";
 const VERSION: &str = "0.1.0";
 const KIND: &str = "timer";
 
-// 定时器插件内部 HTTP 服务端口（通过 plugin_api_info 告诉 api-server）
+// 定时器插件内部 HTTP 服务端口：只在老 host 不分配监听地址时使用
 const API_PORT: u16 = 5601;
 const API_PREFIX: &str = "/";
 
+// 每次执行任务后发布的事件
+const TICK_TOPIC: &str = "timer.tick";
+// target_url 用这个前缀时不发 HTTP 请求，而是发布到事件总线：event://<topic>?key=value&...
+const EVENT_TARGET_PREFIX: &str = "event://";
+
 fn c_string(s: &str) -> *const c_char {
     CString::new(s).unwrap().into_raw()
 }
@@ -51,30 +66,88 @@ pub extern "C" fn plugin_api_info() -> PluginApiInfo {
 }
 
 #[unsafe(no_mangle)]
-pub extern "C" fn run_with_ctx(ctx: *mut PluginContext) {
+pub extern "C" fn plugin_abi_version() -> u32 {
+    PLUGIN_ABI_VERSION
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn plugin_capabilities() -> PluginCapabilities {
+    PluginCapabilities {
+        required: CAP_LOG | CAP_EMIT_METRIC | CAP_LIFECYCLE,
+        optional: CAP_API_LISTEN | CAP_EVENT_BUS | CAP_PANIC_SAFE,
+    }
+}
+
+/// # Safety
+///
+/// `ctx` 为空，或者在调用期间指向 host 提供的有效 `PluginContext`。
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn plugin_init(ctx: *mut PluginContext) -> i32 {
     if ctx.is_null() {
-        return;
+        return -1;
     }
+    let ctx = unsafe { &*ctx };
 
-    // 确保只启动一次（bot-host 每轮都会调用 run_with_ctx）
-    if STARTED.set(()).is_err() {
-        // 已经启动过了，略过
-        return;
+    // panic 不能穿过 FFI 边界，兜住后报告给 host，算作初始化失败
+    plugin_api::catch_panic(ctx, || start(ctx)).unwrap_or(-1)
+}
+
+fn start(ctx_ref: &PluginContext) -> i32 {
+    let mut guard = SERVER.lock().unwrap_or_else(|e| e.into_inner());
+    if guard.is_some() {
+        return 0;
     }
 
     // 拷贝出需要的 host 回调，不能持有 ctx 指针本身
-    let ctx_ref = unsafe { &*ctx };
     let bridge = HostBridge {
         log_fn: ctx_ref.log_fn,
         emit_metric_fn: ctx_ref.emit_metric_fn,
+        events: ctx_ref.event_bus(),
     };
 
     // 初始化插件内部 tracing（方便在 console 看日志）
     init_tracing();
 
-    tokio::spawn(async move {
-        if let Err(e) = run_main(bridge).await {
-            error!("[timer-scheduler_system_plugin] 后台任务失败: {e}");
+    // 在 host 分配的地址上 bind，并回报实际地址；失败时 init 失败
+    let listener = match ctx_ref.bind_api(NAME, ApiListenAddr::localhost(API_PORT)) {
+        Ok(l) => l,
+        Err(e) => {
+            bridge.log(
+                LogLevel::Error,
+                &format!("[timer-scheduler_system_plugin] HTTP API bind 失败: {e}"),
+            );
+            return -1;
+        }
+    };
+
+    // 独立线程 + runtime，不占用 host 的调度线程
+    let (tx, rx) = watch::channel(false);
+    let handle = thread::spawn(move || {
+        let rt = match tokio::runtime::Runtime::new() {
+            Ok(rt) => rt,
+            Err(e) => {
+                error!("[timer-scheduler_system_plugin] 创建 tokio runtime 失败: {e}");
+                return;
+            }
+        };
+        rt.block_on(async move {
+            if let Err(e) = run_main(bridge, listener, rx).await {
+                error!("[timer-scheduler_system_plugin] 后台任务失败: {e}");
+            }
+        });
+    });
+
+    *guard = Some((tx, handle));
+    0
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn plugin_shutdown() {
+    plugin_api::catch_panic_no_ctx(|| {
+        let server = SERVER.lock().unwrap_or_else(|e| e.into_inner()).take();
+        if let Some((tx, handle)) = server {
+            let _ = tx.send(true);
+            let _ = handle.join();
         }
     });
 }
@@ -85,6 +158,8 @@ pub extern "C" fn run_with_ctx(ctx: *mut PluginContext) {
 struct HostBridge {
     log_fn: extern "C" fn(LogLevel, *const c_char),
     emit_metric_fn: extern "C" fn(MetricSample),
+    // host 不支持事件总线时为 None
+    events: Option<EventBus>,
 }
 
 impl HostBridge {
@@ -111,6 +186,16 @@ impl HostBridge {
         };
         (self.emit_metric_fn)(sample);
     }
+
+    fn publish(&self, topic: &str, payload: &serde_json::Value) -> bool {
+        match &self.events {
+            Some(events) => {
+                events.publish(NAME, topic, &payload.to_string());
+                true
+            }
+            None => false,
+        }
+    }
 }
 
 // ====== 插件主逻辑入口 ======
@@ -122,7 +207,11 @@ struct AppState {
     http: reqwest::Client,
 }
 
-async fn run_main(host: HostBridge) -> anyhow::Result<()> {
+async fn run_main(
+    host: HostBridge,
+    listener: ApiListener,
+    mut shutdown: watch::Receiver<bool>,
+) -> anyhow::Result<()> {
     host.log(LogLevel::Info, "[timer-scheduler_system_plugin] 启动中...");
 
     let db_url = std::env::var("MONITOR_AI_DB_URL")
@@ -143,19 +232,24 @@ async fn run_main(host: HostBridge) -> anyhow::Result<()> {
 
     // 启动 HTTP API server
     let app = build_router(state.clone());
-    let addr = format!("127.0.0.1:{API_PORT}");
-    host.log(
-        LogLevel::Info,
-        &format!("[timer-scheduler_system_plugin] HTTP API 监听在 http://{}", addr),
-    );
+    if let Ok(addr) = listener.local_addr() {
+        host.log(
+            LogLevel::Info,
+            &format!("[timer-scheduler_system_plugin] HTTP API 监听在 {addr}"),
+        );
+    }
 
     // 调度循环（后台）
-    tokio::spawn(run_scheduler_loop(state.clone()));
+    let scheduler = tokio::spawn(run_scheduler_loop(state.clone(), shutdown.clone()));
 
-    // 挂 HTTP 服务（阻塞当前任务）
-    axum::Server::bind(&addr.parse()?)
-        .serve(app.into_make_service())
-        .await?;
+    // 挂 HTTP 服务（阻塞当前任务，收到停止信号后退出）
+    plugin_api::http::serve(listener, app, async move {
+        let _ = shutdown.wait_for(|stop| *stop).await;
+    })
+    .await?;
+
+    let _ = scheduler.await;
+    host.log(LogLevel::Info, "[timer-scheduler_system_plugin] 已停止");
 
     Ok(())
 }
@@ -405,7 +499,7 @@ async fn insert_run_history(
 
 // ====== 调度循环 ======
 
-async fn run_scheduler_loop(state: AppState) {
+async fn run_scheduler_loop(state: AppState, mut shutdown: watch::Receiver<bool>) {
     state
         .host
         .log(LogLevel::Info, "[timer-scheduler_system_plugin] 调度循环启动");
@@ -429,7 +523,10 @@ async fn run_scheduler_loop(state: AppState) {
             }
         }
 
-        sleep(Duration::from_secs(1)).await;
+        tokio::select! {
+            _ = shutdown.wait_for(|stop| *stop) => break,
+            _ = sleep(Duration::from_secs(1)) => {}
+        }
     }
 }
 
@@ -442,20 +539,27 @@ async fn run_one_job(state: AppState, job: TimerJob) {
         &format!("[timer-scheduler_system_plugin] 执行定时任务: id={}, name={}", job.id, job.name),
     );
 
-    let result = do_call_target(&state, &job).await;
+    let result = match job.target_url.strip_prefix(EVENT_TARGET_PREFIX) {
+        Some(target) => publish_target(&state.host, &job, target).map(|_| None),
+        None => do_call_target(&state, &job)
+            .await
+            .map(|status_code| Some(status_code.as_u16())),
+    };
+
+    state.host.publish(
+        TICK_TOPIC,
+        &serde_json::json!({
+            "job_id": job.id,
+            "name": job.name,
+            "run_at": now,
+            "success": result.is_ok(),
+        }),
+    );
 
     match result {
         Ok(status_code) => {
             state.host.metric("timer_job_success", 1.0);
-            let _ = insert_run_history(
-                &state.pool,
-                job.id,
-                now,
-                true,
-                Some(status_code.as_u16()),
-                None,
-            )
-            .await;
+            let _ = insert_run_history(&state.pool, job.id, now, true, status_code, None).await;
         }
         Err(err_msg) => {
             state.host.metric("timer_job_failed", 1.0);
@@ -498,6 +602,38 @@ async fn do_call_target(state: &AppState, job: &TimerJob) -> Result<reqwest::Sta
     Ok(resp.status())
 }
 
+/// event://<topic>?key=value：把 query 参数（能解析成数字 / 布尔的按数字 / 布尔）
+/// 和任务信息合成 JSON，发布到 topic
+fn publish_target(host: &HostBridge, job: &TimerJob, target: &str) -> Result<(), String> {
+    let (topic, query) = target.split_once('?').unwrap_or((target, ""));
+    if topic.is_empty() {
+        return Err(format!("事件目标缺少 topic: {}", job.target_url));
+    }
+
+    let mut payload = serde_json::Map::new();
+    for pair in query.split('&').filter(|p| !p.is_empty()) {
+        let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+        let value = if let Ok(n) = value.parse::<i64>() {
+            serde_json::Value::from(n)
+        } else if let Ok(f) = value.parse::<f64>() {
+            serde_json::Value::from(f)
+        } else if let Ok(b) = value.parse::<bool>() {
+            serde_json::Value::from(b)
+        } else {
+            serde_json::Value::from(value)
+        };
+        payload.insert(key.to_string(), value);
+    }
+    payload.insert("job_id".to_string(), job.id.into());
+    payload.insert("job_name".to_string(), job.name.clone().into());
+
+    if host.publish(topic, &serde_json::Value::Object(payload)) {
+        Ok(())
+    } else {
+        Err("host 不支持事件总线，无法发布事件".to_string())
+    }
+}
+
 // ====== HTTP API（通过 api-server 的 /plugin-api/timer-scheduler_system_plugin/... 暴露） ======
 
 fn build_router(state: AppState) -> Router {
@@ -524,6 +660,7 @@ async fn list_jobs_handler(
 
 /// POST /plugin-api/timer-scheduler_system_plugin/jobs
 /// body: { "name": "...", "target_url": "http://...", "method": "POST", "interval_secs": 60 }
+/// target_url 也可以是 event://workflow.run?workflow_id=1，到点时发布事件而不是发 HTTP 请求
 async fn create_job_handler(
     State(state): State<AppState>,
     Json(input): Json<TimerJobInput>,
@@ -1,9 +1,9 @@
 // plugins/workflow-engine_system_plugin/src/lib.rs
 
 use std::{
-    ffi::CString,
-    os::raw::c_char,
-    sync::{Arc, Once},
+    ffi::{CStr, CString},
+    os::raw::{c_char, c_void},
+    sync::{Arc, Mutex},
     thread,
 };
 
@@ -14,26 +14,35 @@ use axum::{
 };
 use chrono::{DateTime, Utc};
 use dotenvy::dotenv;
-use plugin_api::{PluginApiInfo, PluginContext, PluginMeta};
+use plugin_api::{
+    ApiListenAddr, ApiListener, PluginApiInfo, PluginCapabilities, PluginContext, PluginMeta,
+    CAP_API_LISTEN, CAP_EVENT_BUS, CAP_LIFECYCLE, CAP_PANIC_SAFE, PLUGIN_ABI_VERSION,
+};
 use serde::{Deserialize, Serialize};
 use sqlx::{sqlite::SqlitePoolOptions, Pool, Sqlite};
 use tokio::runtime::Runtime;
+use tokio::sync::{mpsc, oneshot};
 use tracing::{error, info};
-use std::net::SocketAddr;
 
 const PLUGIN_NAME: &str = "workflow-engine";
 const PLUGIN_VERSION: &str = "0.1.0";
 const PLUGIN_KIND: &str = "workflow";
+// 老 host 不分配监听地址时使用的端口
 const API_PORT: u16 = 5601;
-// 注意：这里仍然是 /workflow，host 会用它拼 base_url=http://127.0.0.1:5601/workflow
+// 注意：这里仍然是 /workflow，host 会用它拼 base_url=http://<实际地址>/workflow
 const API_PREFIX: &str = "/workflow";
+// 订阅这个 topic，payload 和 POST /workflow/run 的 body 相同：{"workflow_id": 1}
+const RUN_TOPIC: &str = "workflow.run";
 
 fn cstr(s: &str) -> *const c_char {
     CString::new(s).unwrap().into_raw()
 }
 
-// 只启动一个 HTTP server
-static SERVER_ONCE: Once = Once::new();
+// 后台 HTTP server：停止信号 + 线程句柄（plugin_shutdown 时取出）
+static SERVER: Mutex<Option<(oneshot::Sender<()>, thread::JoinHandle<()>)>> = Mutex::new(None);
+
+// 事件回调把运行请求转给后台 runtime（plugin_shutdown 时清空）
+static RUN_EVENTS: Mutex<Option<mpsc::UnboundedSender<RunRequest>>> = Mutex::new(None);
 
 // ====== Plugin ABI ======
 
@@ -55,21 +64,108 @@ pub extern "C" fn plugin_api_info() -> PluginApiInfo {
 }
 
 #[unsafe(no_mangle)]
-pub extern "C" fn run_with_ctx(_ctx: *mut PluginContext) {
-    // host 会周期性调用 run_with_ctx，这里用 Once 确保 HTTP server 只起一次
-    SERVER_ONCE.call_once(|| {
-        thread::spawn(|| {
-            dotenv().ok();
-            let rt = Runtime::new().expect("创建 tokio runtime 失败");
-            rt.block_on(async {
-                if let Err(e) = start_server().await {
-                    eprintln!("[workflow-engine] server error: {e:?}");
-                }
-            });
+pub extern "C" fn plugin_abi_version() -> u32 {
+    PLUGIN_ABI_VERSION
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn plugin_capabilities() -> PluginCapabilities {
+    PluginCapabilities {
+        // HTTP server 靠 plugin_init / plugin_shutdown 启停
+        required: CAP_LIFECYCLE,
+        optional: CAP_API_LISTEN | CAP_EVENT_BUS | CAP_PANIC_SAFE,
+    }
+}
+
+/// # Safety
+///
+/// `ctx` 为空，或者在调用期间指向 host 提供的有效 `PluginContext`。
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn plugin_init(ctx: *mut PluginContext) -> i32 {
+    if ctx.is_null() {
+        return -1;
+    }
+    let ctx = unsafe { &*ctx };
+
+    // panic 不能穿过 FFI 边界，兜住后报告给 host，算作初始化失败
+    plugin_api::catch_panic(ctx, || start(ctx)).unwrap_or(-1)
+}
+
+fn start(ctx: &PluginContext) -> i32 {
+    let mut guard = SERVER.lock().unwrap_or_else(|e| e.into_inner());
+    if guard.is_some() {
+        return 0;
+    }
+
+    // 在 host 分配的地址上 bind，并回报实际地址
+    let listener = match ctx.bind_api(PLUGIN_NAME, ApiListenAddr::localhost(API_PORT)) {
+        Ok(l) => l,
+        Err(e) => {
+            eprintln!("[workflow-engine] bind error: {e}");
+            return -1;
+        }
+    };
+
+    // 支持事件总线时，收到 workflow.run 事件也执行工作流
+    let run_events = ctx.event_bus().and_then(|events| {
+        let (run_tx, run_rx) = mpsc::unbounded_channel();
+        *RUN_EVENTS.lock().unwrap_or_else(|e| e.into_inner()) = Some(run_tx);
+        match events.subscribe(PLUGIN_NAME, RUN_TOPIC, on_run_event, std::ptr::null_mut()) {
+            Some(_) => Some(run_rx),
+            None => {
+                eprintln!("[workflow-engine] 订阅 {RUN_TOPIC} 失败");
+                None
+            }
+        }
+    });
+
+    let (tx, rx) = oneshot::channel::<()>();
+    let handle = thread::spawn(move || {
+        dotenv().ok();
+        let rt = Runtime::new().expect("创建 tokio runtime 失败");
+        rt.block_on(async {
+            if let Err(e) = start_server(listener, rx, run_events).await {
+                eprintln!("[workflow-engine] server error: {e:?}");
+            }
         });
     });
 
-    // 注意：这里不要阻塞，host 会周期性调用插件（目前我们也没在这里做额外逻辑）
+    *guard = Some((tx, handle));
+    0
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn plugin_shutdown() {
+    plugin_api::catch_panic_no_ctx(|| {
+        RUN_EVENTS.lock().unwrap_or_else(|e| e.into_inner()).take();
+        let server = SERVER.lock().unwrap_or_else(|e| e.into_inner()).take();
+        if let Some((tx, handle)) = server {
+            let _ = tx.send(());
+            let _ = handle.join();
+        }
+    });
+}
+
+/// workflow.run 事件回调：在 host 的事件分发线程上执行，只负责转发给后台 runtime
+extern "C" fn on_run_event(_topic: *const c_char, payload: *const c_char, _user_data: *mut c_void) {
+    if payload.is_null() {
+        return;
+    }
+    let payload = unsafe { CStr::from_ptr(payload) };
+    plugin_api::catch_panic_no_ctx(|| forward_run_event(&payload.to_string_lossy()));
+}
+
+fn forward_run_event(payload: &str) {
+    let req = match serde_json::from_str::<RunRequest>(payload) {
+        Ok(req) => req,
+        Err(e) => {
+            error!("[workflow-engine] 无效的 {RUN_TOPIC} 事件: {e}, payload={payload}");
+            return;
+        }
+    };
+    if let Some(tx) = RUN_EVENTS.lock().unwrap_or_else(|e| e.into_inner()).as_ref() {
+        let _ = tx.send(req);
+    }
 }
 
 // ====== 内部状态 & DB 结构 ======
@@ -136,7 +232,11 @@ struct AiGenerateResponse {
 
 // ====== 启动 HTTP 服务 ======
 
-async fn start_server() -> Result<(), sqlx::Error> {
+async fn start_server(
+    listener: ApiListener,
+    shutdown: oneshot::Receiver<()>,
+    run_events: Option<mpsc::UnboundedReceiver<RunRequest>>,
+) -> Result<(), sqlx::Error> {
     init_tracing();
 
     let db_url = std::env::var("MONITOR_AI_DB_URL")
@@ -151,6 +251,25 @@ async fn start_server() -> Result<(), sqlx::Error> {
 
     init_schema(&pool).await?;
 
+    if let Some(mut run_events) = run_events {
+        let pool = pool.clone();
+        tokio::spawn(async move {
+            while let Some(req) = run_events.recv().await {
+                match execute_workflow(&pool, req.workflow_id).await {
+                    Ok(resp) => info!(
+                        "[workflow-engine] 事件触发工作流 {}: instance_id={}, status={}",
+                        req.workflow_id, resp.instance_id, resp.status
+                    ),
+                    Err(e) => error!(
+                        "[workflow-engine] 事件触发工作流 {} 失败: {e}",
+                        req.workflow_id
+                    ),
+                }
+            }
+        });
+        info!("[workflow-engine] 已订阅 {RUN_TOPIC} 事件");
+    }
+
     let state = AppState { pool };
 
     // 这里的路由全部带上 `/workflow` 前缀，和 API_PREFIX 对齐
@@ -171,19 +290,15 @@ async fn start_server() -> Result<(), sqlx::Error> {
         .route("/workflow/ai-generate", post(ai_generate))
         .with_state(Arc::new(state));
 
-    let addr: SocketAddr = format!("127.0.0.1:{API_PORT}")
-        .parse()
-        .expect("invalid API_PORT or bind address");
-    info!("[workflow-engine] HTTP server 启动于 http://{addr}{API_PREFIX}");
-
-    let listener = tokio::net::TcpListener::bind(addr)
-        .await
-        .map_err(|e| {
-            error!("[workflow-engine] bind error: {e}");
-            sqlx::Error::Protocol(e.to_string().into())
-        })?;
+    if let Ok(addr) = listener.local_addr() {
+        info!("[workflow-engine] HTTP server 启动于 {addr}{API_PREFIX}");
+    }
 
-    axum::serve(listener, app)
+    let shutdown = async {
+        let _ = shutdown.await;
+        info!("[workflow-engine] HTTP server 已停止");
+    };
+    plugin_api::http::serve(listener, app, shutdown)
         .await
         .map_err(|e| {
             error!("[workflow-engine] server error: {e}");
@@ -373,6 +488,11 @@ async fn run_workflow(
     State(state): State<Arc<AppState>>,
     Json(req): Json<RunRequest>,
 ) -> Result<Json<RunResponse>, String> {
+    execute_workflow(&state.pool, req.workflow_id).await.map(Json)
+}
+
+/// 执行一次工作流并记录实例；HTTP 和 workflow.run 事件共用
+async fn execute_workflow(pool: &Pool<Sqlite>, workflow_id: i64) -> Result<RunResponse, String> {
     // 1. 取出 workflow definition
     let row = sqlx::query_as::<_, (i64, String, Option<String>, String, String, String)>(
         r#"
@@ -381,8 +501,8 @@ async fn run_workflow(
         WHERE id = ?
         "#,
     )
-    .bind(req.workflow_id)
-    .fetch_one(&state.pool)
+    .bind(workflow_id)
+    .fetch_one(pool)
     .await
     .map_err(|e| e.to_string())?;
 
@@ -434,22 +554,22 @@ async fn run_workflow(
         VALUES (?, ?, ?, ?, ?, ?)
         "#,
     )
-    .bind(req.workflow_id)
+    .bind(workflow_id)
     .bind(status)
     .bind(&steps_str)
     .bind(&started_at_str)
     .bind(&finished_at_str)
     .bind(&last_error)
-    .execute(&state.pool)
+    .execute(pool)
     .await
     .map_err(|e| e.to_string())?;
 
     let instance_id = result.last_insert_rowid();
 
-    Ok(Json(RunResponse {
+    Ok(RunResponse {
         instance_id,
         status: status.to_string(),
-    }))
+    })
 }
 
 // GET /workflow/instances/:id
@@ -3,7 +3,8 @@ CREATE TABLE IF NOT EXISTS logs (
     time DATETIME NOT NULL,
     level VARCHAR(32) NOT NULL,
     plugin VARCHAR(128),
-    message TEXT NOT NULL
+    message TEXT NOT NULL,
+    fields TEXT
 );
 
 CREATE TABLE IF NOT EXISTS metrics (
@@ -11,7 +12,8 @@ CREATE TABLE IF NOT EXISTS metrics (
     time DATETIME NOT NULL,
     plugin VARCHAR(128) NOT NULL,
     name VARCHAR(128) NOT NULL,
-    value DOUBLE NOT NULL
+    value DOUBLE NOT NULL,
+    labels TEXT
 );
 
 CREATE TABLE IF NOT EXISTS alerts (
@@ -21,7 +23,8 @@ CREATE TABLE IF NOT EXISTS alerts (
     metric_name VARCHAR(128) NOT NULL,
     severity VARCHAR(32) NOT NULL,
     title VARCHAR(255) NOT NULL,
-    message TEXT NOT NULL
+    message TEXT NOT NULL,
+    tags TEXT
 );
 
 CREATE TABLE IF NOT EXISTS plugin_apis (
@@ -29,3 +32,16 @@ CREATE TABLE IF NOT EXISTS plugin_apis (
     base_url TEXT NOT NULL,
     updated_at DATETIME NOT NULL
 );
+
+CREATE TABLE IF NOT EXISTS plugins (
+    name VARCHAR(128) PRIMARY KEY,
+    version VARCHAR(64) NOT NULL,
+    kind VARCHAR(64) NOT NULL,
+    path TEXT NOT NULL,
+    status VARCHAR(32) NOT NULL,
+    loaded_at DATETIME,
+    last_run_at DATETIME,
+    last_error TEXT,
+    consecutive_failures INT NOT NULL DEFAULT 0,
+    updated_at DATETIME NOT NULL
+);
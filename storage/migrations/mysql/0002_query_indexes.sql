@@ -0,0 +1,9 @@
+-- 按插件 / 指标名 / 时间范围查询（MySQL 的 CREATE INDEX 不支持 IF NOT EXISTS，靠 schema_migrations 保证只执行一次）
+CREATE INDEX idx_metrics_plugin_name_time ON metrics (plugin, name, time);
+CREATE INDEX idx_metrics_time ON metrics (time);
+
+CREATE INDEX idx_logs_plugin_time ON logs (plugin, time);
+CREATE INDEX idx_logs_time ON logs (time);
+
+CREATE INDEX idx_alerts_plugin_metric_time ON alerts (plugin, metric_name, time);
+CREATE INDEX idx_alerts_time ON alerts (time);
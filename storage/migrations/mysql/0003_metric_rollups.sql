@@ -0,0 +1,24 @@
+-- 指标降采样：resolution 是桶宽（秒），time 是桶的起始时间，labels 为空时是空字符串
+CREATE TABLE IF NOT EXISTS metric_rollups (
+    id BIGINT AUTO_INCREMENT PRIMARY KEY,
+    resolution INT NOT NULL,
+    time DATETIME NOT NULL,
+    plugin VARCHAR(128) NOT NULL,
+    name VARCHAR(128) NOT NULL,
+    labels TEXT NOT NULL,
+    min_value DOUBLE NOT NULL,
+    max_value DOUBLE NOT NULL,
+    sum_value DOUBLE NOT NULL,
+    sample_count BIGINT NOT NULL,
+    last_value DOUBLE NOT NULL,
+    last_time DATETIME NOT NULL
+);
+
+CREATE INDEX idx_metric_rollups_series ON metric_rollups (resolution, plugin, name, time);
+CREATE INDEX idx_metric_rollups_time ON metric_rollups (resolution, time);
+
+-- 每一级已经汇总到哪里（不含）
+CREATE TABLE IF NOT EXISTS rollup_state (
+    resolution INT PRIMARY KEY,
+    done_until DATETIME NOT NULL
+);
@@ -0,0 +1,69 @@
+-- 时间列从 DATETIME 改成 Unix 毫秒（BIGINT，UTC）；AnyPool 绑定不了时间类型，三种后端统一存整数。
+-- 每张表：加一列换算好的毫秒 → 删掉旧列（先删用到它的索引）→ 新列改回原名 → 重建索引。
+-- TIMESTAMPDIFF 按 UTC 换算，不受会话时区影响
+
+ALTER TABLE logs ADD COLUMN time_ms BIGINT AFTER time;
+UPDATE logs SET time_ms = TIMESTAMPDIFF(MICROSECOND, '1970-01-01 00:00:00', time) DIV 1000;
+DROP INDEX idx_logs_plugin_time ON logs;
+DROP INDEX idx_logs_time ON logs;
+ALTER TABLE logs DROP COLUMN time;
+ALTER TABLE logs CHANGE COLUMN time_ms time BIGINT NOT NULL;
+CREATE INDEX idx_logs_plugin_time ON logs (plugin, time);
+CREATE INDEX idx_logs_time ON logs (time);
+
+ALTER TABLE metrics ADD COLUMN time_ms BIGINT AFTER time;
+UPDATE metrics SET time_ms = TIMESTAMPDIFF(MICROSECOND, '1970-01-01 00:00:00', time) DIV 1000;
+DROP INDEX idx_metrics_plugin_name_time ON metrics;
+DROP INDEX idx_metrics_time ON metrics;
+ALTER TABLE metrics DROP COLUMN time;
+ALTER TABLE metrics CHANGE COLUMN time_ms time BIGINT NOT NULL;
+CREATE INDEX idx_metrics_plugin_name_time ON metrics (plugin, name, time);
+CREATE INDEX idx_metrics_time ON metrics (time);
+
+ALTER TABLE alerts ADD COLUMN time_ms BIGINT AFTER time;
+UPDATE alerts SET time_ms = TIMESTAMPDIFF(MICROSECOND, '1970-01-01 00:00:00', time) DIV 1000;
+DROP INDEX idx_alerts_plugin_metric_time ON alerts;
+DROP INDEX idx_alerts_time ON alerts;
+ALTER TABLE alerts DROP COLUMN time;
+ALTER TABLE alerts CHANGE COLUMN time_ms time BIGINT NOT NULL;
+CREATE INDEX idx_alerts_plugin_metric_time ON alerts (plugin, metric_name, time);
+CREATE INDEX idx_alerts_time ON alerts (time);
+
+ALTER TABLE plugin_apis ADD COLUMN updated_at_ms BIGINT AFTER updated_at;
+UPDATE plugin_apis SET updated_at_ms = TIMESTAMPDIFF(MICROSECOND, '1970-01-01 00:00:00', updated_at) DIV 1000;
+ALTER TABLE plugin_apis DROP COLUMN updated_at;
+ALTER TABLE plugin_apis CHANGE COLUMN updated_at_ms updated_at BIGINT NOT NULL;
+
+ALTER TABLE plugins
+    ADD COLUMN loaded_at_ms BIGINT AFTER loaded_at,
+    ADD COLUMN last_run_at_ms BIGINT AFTER last_run_at,
+    ADD COLUMN updated_at_ms BIGINT AFTER updated_at;
+UPDATE plugins SET
+    loaded_at_ms = TIMESTAMPDIFF(MICROSECOND, '1970-01-01 00:00:00', loaded_at) DIV 1000,
+    last_run_at_ms = TIMESTAMPDIFF(MICROSECOND, '1970-01-01 00:00:00', last_run_at) DIV 1000,
+    updated_at_ms = TIMESTAMPDIFF(MICROSECOND, '1970-01-01 00:00:00', updated_at) DIV 1000;
+ALTER TABLE plugins DROP COLUMN loaded_at, DROP COLUMN last_run_at, DROP COLUMN updated_at;
+ALTER TABLE plugins
+    CHANGE COLUMN loaded_at_ms loaded_at BIGINT,
+    CHANGE COLUMN last_run_at_ms last_run_at BIGINT,
+    CHANGE COLUMN updated_at_ms updated_at BIGINT NOT NULL;
+
+ALTER TABLE metric_rollups
+    ADD COLUMN time_ms BIGINT AFTER time,
+    ADD COLUMN last_time_ms BIGINT AFTER last_time;
+UPDATE metric_rollups SET
+    time_ms = TIMESTAMPDIFF(MICROSECOND, '1970-01-01 00:00:00', time) DIV 1000,
+    last_time_ms = TIMESTAMPDIFF(MICROSECOND, '1970-01-01 00:00:00', last_time) DIV 1000;
+DROP INDEX idx_metric_rollups_series ON metric_rollups;
+DROP INDEX idx_metric_rollups_time ON metric_rollups;
+ALTER TABLE metric_rollups DROP COLUMN time, DROP COLUMN last_time;
+ALTER TABLE metric_rollups
+    CHANGE COLUMN time_ms time BIGINT NOT NULL,
+    CHANGE COLUMN last_time_ms last_time BIGINT NOT NULL;
+CREATE INDEX idx_metric_rollups_series ON metric_rollups (resolution, plugin, name, time);
+CREATE INDEX idx_metric_rollups_time ON metric_rollups (resolution, time);
+
+ALTER TABLE rollup_state ADD COLUMN done_until_ms BIGINT AFTER done_until;
+UPDATE rollup_state SET done_until_ms = TIMESTAMPDIFF(MICROSECOND, '1970-01-01 00:00:00', done_until) DIV 1000;
+ALTER TABLE rollup_state DROP COLUMN done_until;
+ALTER TABLE rollup_state CHANGE COLUMN done_until_ms done_until BIGINT NOT NULL;
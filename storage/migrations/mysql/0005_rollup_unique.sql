@@ -0,0 +1,12 @@
+-- 每个汇总桶（resolution + plugin + name + labels + time）只能有一行，汇总改成 upsert。
+-- 加唯一索引之前先去重：两个进程同时汇总同一段时间时可能写了两份，只留 id 最小的那行。
+-- TEXT 列不能整列进索引，唯一索引建在 labels 的 SHA-256 上
+
+DELETE a FROM metric_rollups a
+JOIN metric_rollups b
+    ON a.resolution = b.resolution AND a.plugin = b.plugin AND a.name = b.name
+    AND a.labels = b.labels AND a.time = b.time AND a.id > b.id;
+
+ALTER TABLE metric_rollups ADD COLUMN labels_hash CHAR(64) AS (SHA2(labels, 256)) STORED;
+CREATE UNIQUE INDEX idx_metric_rollups_bucket
+    ON metric_rollups (resolution, plugin, name, labels_hash, time);
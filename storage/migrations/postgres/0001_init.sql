@@ -3,7 +3,8 @@ CREATE TABLE IF NOT EXISTS logs (
     time TIMESTAMP NOT NULL,
     level TEXT NOT NULL,
     plugin TEXT,
-    message TEXT NOT NULL
+    message TEXT NOT NULL,
+    fields TEXT
 );
 
 CREATE TABLE IF NOT EXISTS metrics (
@@ -11,7 +12,8 @@ CREATE TABLE IF NOT EXISTS metrics (
     time TIMESTAMP NOT NULL,
     plugin TEXT NOT NULL,
     name TEXT NOT NULL,
-    value DOUBLE PRECISION NOT NULL
+    value DOUBLE PRECISION NOT NULL,
+    labels TEXT
 );
 
 CREATE TABLE IF NOT EXISTS alerts (
@@ -21,7 +23,8 @@ CREATE TABLE IF NOT EXISTS alerts (
     metric_name TEXT NOT NULL,
     severity TEXT NOT NULL,
     title TEXT NOT NULL,
-    message TEXT NOT NULL
+    message TEXT NOT NULL,
+    tags TEXT
 );
 
 CREATE TABLE IF NOT EXISTS plugin_apis (
@@ -29,3 +32,16 @@ CREATE TABLE IF NOT EXISTS plugin_apis (
     base_url TEXT NOT NULL,
     updated_at TIMESTAMP NOT NULL
 );
+
+CREATE TABLE IF NOT EXISTS plugins (
+    name TEXT PRIMARY KEY,
+    version TEXT NOT NULL,
+    kind TEXT NOT NULL,
+    path TEXT NOT NULL,
+    status TEXT NOT NULL,
+    loaded_at TIMESTAMP,
+    last_run_at TIMESTAMP,
+    last_error TEXT,
+    consecutive_failures INTEGER NOT NULL DEFAULT 0,
+    updated_at TIMESTAMP NOT NULL
+);
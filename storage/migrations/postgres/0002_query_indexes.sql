@@ -0,0 +1,9 @@
+-- 按插件 / 指标名 / 时间范围查询
+CREATE INDEX IF NOT EXISTS idx_metrics_plugin_name_time ON metrics (plugin, name, time);
+CREATE INDEX IF NOT EXISTS idx_metrics_time ON metrics (time);
+
+CREATE INDEX IF NOT EXISTS idx_logs_plugin_time ON logs (plugin, time);
+CREATE INDEX IF NOT EXISTS idx_logs_time ON logs (time);
+
+CREATE INDEX IF NOT EXISTS idx_alerts_plugin_metric_time ON alerts (plugin, metric_name, time);
+CREATE INDEX IF NOT EXISTS idx_alerts_time ON alerts (time);
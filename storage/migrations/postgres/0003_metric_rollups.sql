@@ -0,0 +1,24 @@
+-- 指标降采样：resolution 是桶宽（秒），time 是桶的起始时间，labels 为空时是空字符串
+CREATE TABLE IF NOT EXISTS metric_rollups (
+    id BIGSERIAL PRIMARY KEY,
+    resolution INTEGER NOT NULL,
+    time TIMESTAMP NOT NULL,
+    plugin TEXT NOT NULL,
+    name TEXT NOT NULL,
+    labels TEXT NOT NULL,
+    min_value DOUBLE PRECISION NOT NULL,
+    max_value DOUBLE PRECISION NOT NULL,
+    sum_value DOUBLE PRECISION NOT NULL,
+    sample_count BIGINT NOT NULL,
+    last_value DOUBLE PRECISION NOT NULL,
+    last_time TIMESTAMP NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_metric_rollups_series ON metric_rollups (resolution, plugin, name, time);
+CREATE INDEX IF NOT EXISTS idx_metric_rollups_time ON metric_rollups (resolution, time);
+
+-- 每一级已经汇总到哪里（不含）
+CREATE TABLE IF NOT EXISTS rollup_state (
+    resolution INTEGER PRIMARY KEY,
+    done_until TIMESTAMP NOT NULL
+);
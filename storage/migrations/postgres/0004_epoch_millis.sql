@@ -0,0 +1,21 @@
+-- 时间列从 TIMESTAMP 改成 Unix 毫秒（BIGINT，UTC）；AnyPool 绑定不了时间类型，三种后端统一存整数。
+-- 索引随列类型一起重建
+
+ALTER TABLE logs ALTER COLUMN time TYPE BIGINT USING (EXTRACT(EPOCH FROM time) * 1000)::BIGINT;
+ALTER TABLE metrics ALTER COLUMN time TYPE BIGINT USING (EXTRACT(EPOCH FROM time) * 1000)::BIGINT;
+ALTER TABLE alerts ALTER COLUMN time TYPE BIGINT USING (EXTRACT(EPOCH FROM time) * 1000)::BIGINT;
+
+ALTER TABLE plugin_apis
+    ALTER COLUMN updated_at TYPE BIGINT USING (EXTRACT(EPOCH FROM updated_at) * 1000)::BIGINT;
+
+ALTER TABLE plugins
+    ALTER COLUMN loaded_at TYPE BIGINT USING (EXTRACT(EPOCH FROM loaded_at) * 1000)::BIGINT,
+    ALTER COLUMN last_run_at TYPE BIGINT USING (EXTRACT(EPOCH FROM last_run_at) * 1000)::BIGINT,
+    ALTER COLUMN updated_at TYPE BIGINT USING (EXTRACT(EPOCH FROM updated_at) * 1000)::BIGINT;
+
+ALTER TABLE metric_rollups
+    ALTER COLUMN time TYPE BIGINT USING (EXTRACT(EPOCH FROM time) * 1000)::BIGINT,
+    ALTER COLUMN last_time TYPE BIGINT USING (EXTRACT(EPOCH FROM last_time) * 1000)::BIGINT;
+
+ALTER TABLE rollup_state
+    ALTER COLUMN done_until TYPE BIGINT USING (EXTRACT(EPOCH FROM done_until) * 1000)::BIGINT;
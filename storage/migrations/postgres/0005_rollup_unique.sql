@@ -0,0 +1,10 @@
+-- 每个汇总桶（resolution + plugin + name + labels + time）只能有一行，汇总改成 upsert。
+-- 加唯一索引之前先去重：两个进程同时汇总同一段时间时可能写了两份，只留 id 最小的那行
+
+DELETE FROM metric_rollups
+WHERE id NOT IN (
+    SELECT MIN(id) FROM metric_rollups GROUP BY resolution, plugin, name, labels, time
+);
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_metric_rollups_bucket
+    ON metric_rollups (resolution, plugin, name, labels, time);
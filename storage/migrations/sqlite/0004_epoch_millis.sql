@@ -0,0 +1,123 @@
+-- 时间列从 RFC3339 文本改成 Unix 毫秒（INTEGER，UTC）。
+-- SQLite 改不了列类型，逐表重建：建新表 → 换算后拷过去 → 删旧表 → 改名 → 重建索引。
+-- 解析不了的时间换算出来是 NULL，违反 NOT NULL，整个迁移回滚；修好或删掉那些行后重新执行。
+
+CREATE TABLE logs_new (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    time INTEGER NOT NULL,
+    level TEXT NOT NULL,
+    plugin TEXT,
+    message TEXT NOT NULL,
+    fields TEXT
+);
+INSERT INTO logs_new (id, time, level, plugin, message, fields)
+SELECT id, CAST(ROUND((julianday(time) - 2440587.5) * 86400000) AS INTEGER), level, plugin, message, fields
+FROM logs;
+DROP TABLE logs;
+ALTER TABLE logs_new RENAME TO logs;
+CREATE INDEX idx_logs_plugin_time ON logs (plugin, time);
+CREATE INDEX idx_logs_time ON logs (time);
+
+CREATE TABLE metrics_new (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    time INTEGER NOT NULL,
+    plugin TEXT NOT NULL,
+    name TEXT NOT NULL,
+    value REAL NOT NULL,
+    labels TEXT
+);
+INSERT INTO metrics_new (id, time, plugin, name, value, labels)
+SELECT id, CAST(ROUND((julianday(time) - 2440587.5) * 86400000) AS INTEGER), plugin, name, value, labels
+FROM metrics;
+DROP TABLE metrics;
+ALTER TABLE metrics_new RENAME TO metrics;
+CREATE INDEX idx_metrics_plugin_name_time ON metrics (plugin, name, time);
+CREATE INDEX idx_metrics_time ON metrics (time);
+
+CREATE TABLE alerts_new (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    time INTEGER NOT NULL,
+    plugin TEXT NOT NULL,
+    metric_name TEXT NOT NULL,
+    severity TEXT NOT NULL,
+    title TEXT NOT NULL,
+    message TEXT NOT NULL,
+    tags TEXT
+);
+INSERT INTO alerts_new (id, time, plugin, metric_name, severity, title, message, tags)
+SELECT id, CAST(ROUND((julianday(time) - 2440587.5) * 86400000) AS INTEGER), plugin, metric_name,
+    severity, title, message, tags
+FROM alerts;
+DROP TABLE alerts;
+ALTER TABLE alerts_new RENAME TO alerts;
+CREATE INDEX idx_alerts_plugin_metric_time ON alerts (plugin, metric_name, time);
+CREATE INDEX idx_alerts_time ON alerts (time);
+
+CREATE TABLE plugin_apis_new (
+    plugin TEXT PRIMARY KEY,
+    base_url TEXT NOT NULL,
+    updated_at INTEGER NOT NULL
+);
+INSERT INTO plugin_apis_new (plugin, base_url, updated_at)
+SELECT plugin, base_url, CAST(ROUND((julianday(updated_at) - 2440587.5) * 86400000) AS INTEGER)
+FROM plugin_apis;
+DROP TABLE plugin_apis;
+ALTER TABLE plugin_apis_new RENAME TO plugin_apis;
+
+CREATE TABLE plugins_new (
+    name TEXT PRIMARY KEY,
+    version TEXT NOT NULL,
+    kind TEXT NOT NULL,
+    path TEXT NOT NULL,
+    status TEXT NOT NULL,
+    loaded_at INTEGER,
+    last_run_at INTEGER,
+    last_error TEXT,
+    consecutive_failures INTEGER NOT NULL DEFAULT 0,
+    updated_at INTEGER NOT NULL
+);
+INSERT INTO plugins_new (name, version, kind, path, status, loaded_at, last_run_at, last_error,
+    consecutive_failures, updated_at)
+SELECT name, version, kind, path, status,
+    CAST(ROUND((julianday(loaded_at) - 2440587.5) * 86400000) AS INTEGER),
+    CAST(ROUND((julianday(last_run_at) - 2440587.5) * 86400000) AS INTEGER),
+    last_error, consecutive_failures,
+    CAST(ROUND((julianday(updated_at) - 2440587.5) * 86400000) AS INTEGER)
+FROM plugins;
+DROP TABLE plugins;
+ALTER TABLE plugins_new RENAME TO plugins;
+
+CREATE TABLE metric_rollups_new (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    resolution INTEGER NOT NULL,
+    time INTEGER NOT NULL,
+    plugin TEXT NOT NULL,
+    name TEXT NOT NULL,
+    labels TEXT NOT NULL,
+    min_value REAL NOT NULL,
+    max_value REAL NOT NULL,
+    sum_value REAL NOT NULL,
+    sample_count INTEGER NOT NULL,
+    last_value REAL NOT NULL,
+    last_time INTEGER NOT NULL
+);
+INSERT INTO metric_rollups_new (id, resolution, time, plugin, name, labels, min_value, max_value,
+    sum_value, sample_count, last_value, last_time)
+SELECT id, resolution, CAST(ROUND((julianday(time) - 2440587.5) * 86400000) AS INTEGER), plugin, name,
+    labels, min_value, max_value, sum_value, sample_count, last_value,
+    CAST(ROUND((julianday(last_time) - 2440587.5) * 86400000) AS INTEGER)
+FROM metric_rollups;
+DROP TABLE metric_rollups;
+ALTER TABLE metric_rollups_new RENAME TO metric_rollups;
+CREATE INDEX idx_metric_rollups_series ON metric_rollups (resolution, plugin, name, time);
+CREATE INDEX idx_metric_rollups_time ON metric_rollups (resolution, time);
+
+CREATE TABLE rollup_state_new (
+    resolution INTEGER PRIMARY KEY,
+    done_until INTEGER NOT NULL
+);
+INSERT INTO rollup_state_new (resolution, done_until)
+SELECT resolution, CAST(ROUND((julianday(done_until) - 2440587.5) * 86400000) AS INTEGER)
+FROM rollup_state;
+DROP TABLE rollup_state;
+ALTER TABLE rollup_state_new RENAME TO rollup_state;
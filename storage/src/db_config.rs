@@ -1,5 +1,13 @@
 use sqlx::{any::Any, migrate::MigrateDatabase, AnyPool, Pool};
 
+/// 数据库后端；未知的 db_type 按 SQLite 处理
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backend {
+    Sqlite,
+    Postgres,
+    MySql,
+}
+
 #[derive(Debug, Clone)]
 pub struct DbConfig {
     pub db_type: String,
@@ -21,16 +29,25 @@ impl DbConfig {
 
         DbConfig { db_type, url }
     }
+
+    pub fn backend(&self) -> Backend {
+        match self.db_type.as_str() {
+            "postgres" => Backend::Postgres,
+            "mysql" => Backend::MySql,
+            _ => Backend::Sqlite,
+        }
+    }
 }
 
 pub async fn create_pool(config: &DbConfig) -> sqlx::Result<AnyPool> {
     let db_url = &config.url;
 
     // 检查数据库是否存在，不存在则创建（仅支持 SQLite）
+    // 提示写到 stderr：worker 子进程的 stdout 是协议通道，库里不往 stdout 打印
     match config.db_type.as_str() {
         "sqlite" => {
             if !sqlx::Sqlite::database_exists(db_url).await.unwrap_or(false) {
-                println!("SQLite 数据库不存在，正在创建: {}", db_url);
+                eprintln!("SQLite 数据库不存在，正在创建: {}", db_url);
                 sqlx::Sqlite::create_database(db_url)
                     .await
                     .expect("无法创建 SQLite 数据库");
@@ -38,7 +55,7 @@ pub async fn create_pool(config: &DbConfig) -> sqlx::Result<AnyPool> {
         }
         "postgres" => {
             if !sqlx::Postgres::database_exists(db_url).await.unwrap_or(false) {
-                println!("PostgreSQL 数据库不存在，正在创建...");
+                eprintln!("PostgreSQL 数据库不存在，正在创建...");
                 sqlx::Postgres::create_database(db_url)
                     .await
                     .expect("无法创建 PostgreSQL 数据库");
@@ -46,14 +63,14 @@ pub async fn create_pool(config: &DbConfig) -> sqlx::Result<AnyPool> {
         }
         "mysql" => {
             if !sqlx::MySql::database_exists(db_url).await.unwrap_or(false) {
-                println!("MySQL 数据库不存在，正在创建...");
+                eprintln!("MySQL 数据库不存在，正在创建...");
                 sqlx::MySql::create_database(db_url)
                     .await
                     .expect("无法创建 MySQL 数据库");
             }
         }
         other => {
-            println!("未知数据库类型 {}，跳过创建逻辑", other);
+            eprintln!("未知数据库类型 {}，跳过创建逻辑", other);
         }
     }
 
@@ -1,15 +1,37 @@
 // File: storage/src/lib.rs
-use core_types::{AlertEvent, AlertSeverity, LogEvent, Metric};
+use core_types::{AlertEvent, AlertSeverity, LogEvent, Metric, PluginRecord, PluginStatus};
 use chrono::{DateTime, Utc};
 use sqlx::{AnyPool, FromRow};
-mod init;
+use std::collections::{BTreeMap, HashMap};
+mod migrate;
 mod db_config;
-use crate::db_config::{DbConfig, create_pool};
+mod query;
+mod retention;
+mod rollup;
+mod timestamp;
+#[cfg(test)]
+mod test_support;
+use crate::db_config::{Backend, DbConfig, create_pool};
+use crate::migrate::Migration;
+use crate::timestamp::{from_millis, select_millis, select_optional_millis, to_millis, Millis, RowId};
+pub use crate::migrate::MigrationStatus;
+pub use crate::query::{
+    AlertFilter, Cursor, LabelMatcher, LogFilter, MetricFilter, Page, PageRequest,
+};
+pub use crate::retention::{
+    PruneReport, RetentionPolicy, RetentionRule, RetentionTarget, DEFAULT_PRUNE_BATCH,
+};
+pub use crate::rollup::{
+    Resolution, Series, SeriesPoint, SeriesQuery, SeriesSet, MAX_SERIES_POINTS, MAX_STEP_SECS,
+};
 
 
 #[derive(Clone)]
 pub struct Db {
     pool: AnyPool,
+    backend: Backend,
+    /// 当前后端的嵌入迁移
+    migrations: &'static [Migration],
 }
 
 #[derive(FromRow)]
@@ -21,22 +43,50 @@ struct PluginApiRow {
 
 impl Db {
     
-    pub async fn connect(db_type: Option<&str>, db_url: Option<&str>) -> sqlx::Result<Self> {
+    /// 连接数据库并执行还没执行的迁移，同时返回这次执行的迁移，由调用方记日志。
+    ///
+    /// 这里不能往 stdout 打印：worker 子进程的 stdout 是和 host 通信的协议通道
+    pub async fn connect(
+        db_type: Option<&str>,
+        db_url: Option<&str>,
+    ) -> sqlx::Result<(Self, Vec<MigrationStatus>)> {
+        let db = Self::open(db_type, db_url).await?;
+        let applied = db.migrate().await?;
+        Ok((db, applied))
+    }
+
+    /// 只连接、不迁移：查看迁移状态，或者库已经由别的进程迁移过（比如 worker 子进程）
+    pub async fn open(db_type: Option<&str>, db_url: Option<&str>) -> sqlx::Result<Self> {
         let config = DbConfig::from_args(db_type, db_url);
         let pool = create_pool(&config).await?;
-        init::init_schema(&pool, &config.db_type).await?;
-        Ok(Self { pool })
+        let backend = config.backend();
+        Ok(Self {
+            pool,
+            backend,
+            migrations: migrate::migrations_for(backend),
+        })
+    }
+
+    /// 按版本号依次执行还没执行的迁移，返回这次执行的那些；重复调用是安全的
+    pub async fn migrate(&self) -> sqlx::Result<Vec<MigrationStatus>> {
+        migrate::run(&self.pool, self.migrations).await
+    }
+
+    /// 所有迁移的执行情况，按版本号排序
+    pub async fn migration_status(&self) -> sqlx::Result<Vec<MigrationStatus>> {
+        migrate::status(&self.pool, self.migrations).await
     }
 
 
     pub async fn insert_log(&self, e: &LogEvent) -> sqlx::Result<()> {
         sqlx::query(
-            r#"INSERT INTO logs (time, level, plugin, message) VALUES (?1, ?2, ?3, ?4)"#,
+            r#"INSERT INTO logs (time, level, plugin, message, fields) VALUES (?1, ?2, ?3, ?4, ?5)"#,
         )
-        .bind(e.time.to_rfc3339())
+        .bind(to_millis(e.time))
         .bind(format!("{:?}", e.level))
         .bind(e.plugin.clone())
         .bind(&e.message)
+        .bind(encode_map(&e.fields))
         .execute(&self.pool)
         .await?;
         Ok(())
@@ -44,70 +94,133 @@ impl Db {
 
     pub async fn insert_metric(&self, m: &Metric) -> sqlx::Result<()> {
         sqlx::query(
-            r#"INSERT INTO metrics (time, plugin, name, value) VALUES (?1, ?2, ?3, ?4)"#,
+            r#"INSERT INTO metrics (time, plugin, name, value, labels) VALUES (?1, ?2, ?3, ?4, ?5)"#,
         )
-        .bind(m.time.to_rfc3339())
+        .bind(to_millis(m.time))
         .bind(&m.plugin)
         .bind(&m.name)
         .bind(m.value)
+        .bind(encode_map(&m.labels))
         .execute(&self.pool)
         .await?;
         Ok(())
     }
 
+    /// 关闭连接池，等正在执行的查询结束；之后所有查询都会失败
+    pub async fn close(&self) {
+        self.pool.close().await;
+    }
+
+    /// 批量写入日志：每 MAX_ROWS_PER_INSERT 条一个多行 INSERT，整批在一个事务里
+    pub async fn insert_logs(&self, events: &[LogEvent]) -> sqlx::Result<()> {
+        if events.is_empty() {
+            return Ok(());
+        }
+        let mut tx = self.pool.begin().await?;
+        for chunk in events.chunks(MAX_ROWS_PER_INSERT) {
+            let sql = format!(
+                "INSERT INTO logs (time, level, plugin, message, fields) VALUES {}",
+                values_placeholders(5, chunk.len())
+            );
+            let mut query = sqlx::query(&sql);
+            for e in chunk {
+                query = query
+                    .bind(to_millis(e.time))
+                    .bind(format!("{:?}", e.level))
+                    .bind(e.plugin.clone())
+                    .bind(e.message.clone())
+                    .bind(encode_map(&e.fields));
+            }
+            query.execute(&mut *tx).await?;
+        }
+        tx.commit().await
+    }
+
+    /// 批量写入指标，做法同 insert_logs
+    pub async fn insert_metrics(&self, metrics: &[Metric]) -> sqlx::Result<()> {
+        if metrics.is_empty() {
+            return Ok(());
+        }
+        let mut tx = self.pool.begin().await?;
+        for chunk in metrics.chunks(MAX_ROWS_PER_INSERT) {
+            let sql = format!(
+                "INSERT INTO metrics (time, plugin, name, value, labels) VALUES {}",
+                values_placeholders(5, chunk.len())
+            );
+            let mut query = sqlx::query(&sql);
+            for m in chunk {
+                query = query
+                    .bind(to_millis(m.time))
+                    .bind(m.plugin.clone())
+                    .bind(m.name.clone())
+                    .bind(m.value)
+                    .bind(encode_map(&m.labels));
+            }
+            query.execute(&mut *tx).await?;
+        }
+        tx.commit().await
+    }
+
     pub async fn latest_logs(&self, limit: i64) -> sqlx::Result<Vec<LogEvent>> {
-        let rows = sqlx::query_as::<_, LogRow>(
-            r#"SELECT time, level, plugin, message FROM logs ORDER BY id DESC LIMIT ?1"#,
-        )
-        .bind(limit)
-        .fetch_all(&self.pool)
-        .await?;
+        let sql = format!(
+            "SELECT {}, level, COALESCE(plugin, '') AS plugin, message,
+            COALESCE(fields, '') AS fields FROM logs ORDER BY id DESC LIMIT ?1",
+            select_millis(self.backend, "time")
+        );
+        let rows = sqlx::query_as::<_, LogRow>(&sql)
+            .bind(limit)
+            .fetch_all(&self.pool)
+            .await?;
 
-        Ok(rows.into_iter().map(|r| r.into()).collect())
+        rows.into_iter().map(TryInto::try_into).collect()
     }
 
     pub async fn latest_metrics(&self, limit: i64) -> sqlx::Result<Vec<Metric>> {
-        let rows = sqlx::query_as::<_, MetricRow>(
-            r#"SELECT time, plugin, name, value FROM metrics ORDER BY id DESC LIMIT ?1"#,
-        )
-        .bind(limit)
-        .fetch_all(&self.pool)
-        .await?;
+        let sql = format!(
+            "SELECT {}, plugin, name, value, COALESCE(labels, '') AS labels
+            FROM metrics ORDER BY id DESC LIMIT ?1",
+            select_millis(self.backend, "time")
+        );
+        let rows = sqlx::query_as::<_, MetricRow>(&sql)
+            .bind(limit)
+            .fetch_all(&self.pool)
+            .await?;
 
-        Ok(rows.into_iter().map(|r| r.into()).collect())
+        rows.into_iter().map(TryInto::try_into).collect()
     }
 
     pub async fn insert_alert(&self, a: &AlertEvent) -> sqlx::Result<()> {
         sqlx::query(
-            r#"INSERT INTO alerts (time, plugin, metric_name, severity, title, message)
-            VALUES (?1, ?2, ?3, ?4, ?5, ?6)"#,
+            r#"INSERT INTO alerts (time, plugin, metric_name, severity, title, message, tags)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)"#,
         )
-        .bind(a.time.to_rfc3339())
+        .bind(to_millis(a.time))
         .bind(&a.plugin)
         .bind(&a.metric_name)
         .bind(format!("{:?}", a.severity))
         .bind(&a.title)
         .bind(&a.message)
+        .bind(encode_map(&a.tags))
         .execute(&self.pool)
         .await?;
         Ok(())
     }
 
     pub async fn latest_alerts(&self, limit: i64) -> sqlx::Result<Vec<AlertEvent>> {
-        let rows = sqlx::query_as::<_, AlertRow>(
-            r#"SELECT time, plugin, metric_name, severity, title, message FROM alerts ORDER BY id DESC LIMIT ?1"#,
-        )
-        .bind(limit)
-        .fetch_all(&self.pool)
-        .await?;
+        let sql = format!(
+            "SELECT {}, plugin, metric_name, severity, title, message, COALESCE(tags, '') AS tags
+            FROM alerts ORDER BY id DESC LIMIT ?1",
+            select_millis(self.backend, "time")
+        );
+        let rows = sqlx::query_as::<_, AlertRow>(&sql)
+            .bind(limit)
+            .fetch_all(&self.pool)
+            .await?;
 
-        Ok(rows.into_iter().map(|r| r.into()).collect())
+        rows.into_iter().map(TryInto::try_into).collect()
     }
 
     pub async fn upsert_plugin_api(&self, plugin: &str, base_url: &str) -> sqlx::Result<()> {
-        let now: DateTime<Utc> = Utc::now();
-        let now_str = now.to_rfc3339();
-
         sqlx::query(
             r#"INSERT INTO plugin_apis (plugin, base_url, updated_at)
             VALUES (?1, ?2, ?3)
@@ -117,13 +230,23 @@ impl Db {
         )
         .bind(plugin)
         .bind(base_url)
-        .bind(now_str)
+        .bind(to_millis(Utc::now()))
         .execute(&self.pool)
         .await?;
 
         Ok(())
     }
 
+    /// 插件被卸载后删除它的 API 映射
+    pub async fn delete_plugin_api(&self, plugin: &str) -> sqlx::Result<()> {
+        sqlx::query(r#"DELETE FROM plugin_apis WHERE plugin = ?1"#)
+            .bind(plugin)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
     /// 读取所有插件 API 映射（给 api-server 启动时缓存用）
     pub async fn get_all_plugin_apis(&self) -> sqlx::Result<Vec<(String, String)>> {
         let rows = sqlx::query_as::<_, PluginApiRow>(
@@ -134,19 +257,124 @@ impl Db {
 
         Ok(rows.into_iter().map(|r| (r.plugin, r.base_url)).collect())
     }
+
+    /// 写入（或覆盖）插件注册表中的一条记录
+    pub async fn upsert_plugin(&self, p: &PluginRecord) -> sqlx::Result<()> {
+        sqlx::query(
+            r#"INSERT INTO plugins (name, version, kind, path, status, loaded_at, last_run_at,
+                last_error, consecutive_failures, updated_at)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)
+            ON CONFLICT(name) DO UPDATE SET
+                version = excluded.version,
+                kind = excluded.kind,
+                path = excluded.path,
+                status = excluded.status,
+                loaded_at = excluded.loaded_at,
+                last_run_at = excluded.last_run_at,
+                last_error = excluded.last_error,
+                consecutive_failures = excluded.consecutive_failures,
+                updated_at = excluded.updated_at"#,
+        )
+        .bind(&p.name)
+        .bind(&p.version)
+        .bind(&p.kind)
+        .bind(&p.path)
+        .bind(format!("{:?}", p.status))
+        .bind(p.loaded_at.map(to_millis))
+        .bind(p.last_run_at.map(to_millis))
+        .bind(p.last_error.clone())
+        .bind(i64::from(p.consecutive_failures))
+        .bind(to_millis(p.updated_at))
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn list_plugins(&self) -> sqlx::Result<Vec<PluginRecord>> {
+        let sql = format!(
+            "SELECT name, version, kind, path, status, {} FROM plugins ORDER BY name",
+            self.plugin_columns()
+        );
+        let rows = sqlx::query_as::<_, PluginRow>(&sql)
+            .fetch_all(&self.pool)
+            .await?;
+
+        rows.into_iter().map(TryInto::try_into).collect()
+    }
+
+    pub async fn get_plugin(&self, name: &str) -> sqlx::Result<Option<PluginRecord>> {
+        let sql = format!(
+            "SELECT name, version, kind, path, status, {} FROM plugins WHERE name = ?1",
+            self.plugin_columns()
+        );
+        let row = sqlx::query_as::<_, PluginRow>(&sql)
+            .bind(name)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        row.map(TryInto::try_into).transpose()
+    }
+
+    /// plugins 表除 name / version / kind / path / status 以外的列
+    fn plugin_columns(&self) -> String {
+        format!(
+            "{}, {}, COALESCE(last_error, '') AS last_error, consecutive_failures, {}",
+            select_optional_millis(self.backend, "loaded_at"),
+            select_optional_millis(self.backend, "last_run_at"),
+            select_millis(self.backend, "updated_at")
+        )
+    }
 }
 
+/// labels / fields 以 JSON 对象存成一列文本；空 map 存 NULL
+/// 多行 INSERT 每条语句最多的行数；5 列时 500 个参数，低于 SQLite 老版本 999 的上限
+const MAX_ROWS_PER_INSERT: usize = 100;
+
+/// `(?1, ?2), (?3, ?4)` 形式的 VALUES 占位符
+fn values_placeholders(columns: usize, rows: usize) -> String {
+    (0..rows)
+        .map(|row| {
+            let params: Vec<String> = (1..=columns)
+                .map(|col| format!("?{}", row * columns + col))
+                .collect();
+            format!("({})", params.join(", "))
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// 按 key 排序后序列化，同一组标签总是同一个字符串
+fn encode_map(map: &HashMap<String, String>) -> Option<String> {
+    if map.is_empty() {
+        return None;
+    }
+    serde_json::to_string(&map.iter().collect::<BTreeMap<_, _>>()).ok()
+}
+
+fn decode_map(raw: Option<&str>) -> HashMap<String, String> {
+    raw.and_then(|s| serde_json::from_str(s).ok())
+        .unwrap_or_default()
+}
+
+/// `id` 只有分页查询会选出来，用来生成游标
 #[derive(FromRow)]
 struct LogRow {
-    time: String,
+    #[sqlx(rename = "id_int", default)]
+    id: RowId,
+    #[sqlx(rename = "time_ms")]
+    time: Millis,
     level: String,
-    plugin: Option<String>,
+    /// 下面两列为 NULL 时是空字符串（AnyPool 解不出 NULL）
+    plugin: String,
     message: String,
+    fields: String,
 }
 
-impl From<LogRow> for LogEvent {
-    fn from(row: LogRow) -> Self {
-        let time = row.time.parse().unwrap_or_else(|_| Utc::now());
+impl TryFrom<LogRow> for LogEvent {
+    type Error = sqlx::Error;
+
+    fn try_from(row: LogRow) -> sqlx::Result<Self> {
+        let time = from_millis("time", row.time)?;
         let level = match row.level.as_str() {
             "Debug" => core_types::LogLevel::Debug,
             "Info" => core_types::LogLevel::Info,
@@ -154,64 +382,130 @@ impl From<LogRow> for LogEvent {
             "Error" => core_types::LogLevel::Error,
             _ => core_types::LogLevel::Info,
         };
-        Self {
+        Ok(Self {
             time,
             level,
-            plugin: row.plugin,
+            plugin: Some(row.plugin).filter(|p| !p.is_empty()),
             message: row.message,
-            fields: Default::default(),
-        }
+            fields: decode_map(Some(&row.fields)),
+        })
     }
 }
 
 #[derive(FromRow)]
 struct MetricRow {
-    time: String,
+    #[sqlx(rename = "id_int", default)]
+    id: RowId,
+    #[sqlx(rename = "time_ms")]
+    time: Millis,
     plugin: String,
     name: String,
     value: f64,
+    /// 没有标签时是空字符串（AnyPool 解不出 NULL）
+    labels: String,
 }
 
-impl From<MetricRow> for Metric {
-    fn from(row: MetricRow) -> Self {
-        let time = row.time.parse().unwrap_or_else(|_| Utc::now());
-        Self {
+impl TryFrom<MetricRow> for Metric {
+    type Error = sqlx::Error;
+
+    fn try_from(row: MetricRow) -> sqlx::Result<Self> {
+        let time = from_millis("time", row.time)?;
+        Ok(Self {
             time,
             plugin: row.plugin,
             name: row.name,
             value: row.value,
-            labels: Default::default(),
-        }
+            labels: decode_map(Some(&row.labels)),
+        })
     }
 }
 
 #[derive(FromRow)]
 struct AlertRow {
-    time: String,
+    #[sqlx(rename = "id_int", default)]
+    id: RowId,
+    #[sqlx(rename = "time_ms")]
+    time: Millis,
     plugin: String,
     metric_name: String,
     severity: String,
     title: String,
     message: String,
+    /// 没有标签时是空字符串（AnyPool 解不出 NULL）
+    tags: String,
 }
 
-impl From<AlertRow> for AlertEvent {
-    fn from(row: AlertRow) -> Self {
-        let time = row.time.parse().unwrap_or_else(|_| Utc::now());
+impl TryFrom<AlertRow> for AlertEvent {
+    type Error = sqlx::Error;
+
+    fn try_from(row: AlertRow) -> sqlx::Result<Self> {
+        let time = from_millis("time", row.time)?;
         let severity = match row.severity.as_str() {
             "Info" => AlertSeverity::Info,
             "Warning" => AlertSeverity::Warning,
             "Critical" => AlertSeverity::Critical,
             _ => AlertSeverity::Info,
         };
-        Self {
+        Ok(Self {
             time,
             plugin: row.plugin,
             metric_name: row.metric_name,
             severity,
             title: row.title,
             message: row.message,
-            tags: std::collections::HashMap::new(),
-        }
+            tags: decode_map(Some(&row.tags)),
+        })
     }
-}
\ No newline at end of file
+}
+
+/// AnyPool 解不出 NULL，可空列在查询里 COALESCE 成空字符串（时间列 COALESCE 成 0）
+#[derive(FromRow)]
+struct PluginRow {
+    name: String,
+    version: String,
+    kind: String,
+    path: String,
+    status: String,
+    #[sqlx(rename = "loaded_at_ms")]
+    loaded_at: Millis,
+    #[sqlx(rename = "last_run_at_ms")]
+    last_run_at: Millis,
+    last_error: String,
+    consecutive_failures: i64,
+    #[sqlx(rename = "updated_at_ms")]
+    updated_at: Millis,
+}
+
+/// 可空的时间列，0 表示 NULL
+fn optional_millis(column: &str, millis: Millis) -> sqlx::Result<Option<DateTime<Utc>>> {
+    match millis {
+        Millis(0) => Ok(None),
+        _ => from_millis(column, millis).map(Some),
+    }
+}
+
+impl TryFrom<PluginRow> for PluginRecord {
+    type Error = sqlx::Error;
+
+    fn try_from(row: PluginRow) -> sqlx::Result<Self> {
+        let status = match row.status.as_str() {
+            "Loaded" => PluginStatus::Loaded,
+            "Failing" => PluginStatus::Failing,
+            "Rejected" => PluginStatus::Rejected,
+            "Unloaded" => PluginStatus::Unloaded,
+            _ => PluginStatus::Stopped,
+        };
+        Ok(Self {
+            name: row.name,
+            version: row.version,
+            kind: row.kind,
+            path: row.path,
+            status,
+            loaded_at: optional_millis("loaded_at", row.loaded_at)?,
+            last_run_at: optional_millis("last_run_at", row.last_run_at)?,
+            last_error: Some(row.last_error).filter(|e| !e.is_empty()),
+            consecutive_failures: u32::try_from(row.consecutive_failures).unwrap_or(0),
+            updated_at: from_millis("updated_at", row.updated_at)?,
+        })
+    }
+}
@@ -0,0 +1,341 @@
+//! 按编号执行的 schema 迁移。
+//!
+//! 每种后端一套 SQL，放在 `storage/migrations/<后端>/NNNN_<名字>.sql`，编译时嵌进二进制，
+//! 不依赖启动目录。已执行的版本记在 `schema_migrations` 表里，每个迁移只执行一次。
+//! 新增迁移：加一个编号更大的文件，再在下面对应后端的列表末尾登记；已发布的迁移不要再改。
+
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Utc};
+use sqlx::{AnyPool, FromRow};
+
+use crate::db_config::Backend;
+
+/// 一个嵌入的迁移脚本
+#[derive(Debug)]
+pub struct Migration {
+    pub version: i64,
+    pub name: &'static str,
+    sql: &'static str,
+}
+
+macro_rules! migration {
+    ($version:literal, $name:literal, $file:literal) => {
+        Migration {
+            version: $version,
+            name: $name,
+            sql: include_str!(concat!("../migrations/", $file)),
+        }
+    };
+}
+
+const SQLITE: &[Migration] = &[
+    migration!(1, "init", "sqlite/0001_init.sql"),
+    migration!(2, "query_indexes", "sqlite/0002_query_indexes.sql"),
+    migration!(3, "metric_rollups", "sqlite/0003_metric_rollups.sql"),
+    migration!(4, "epoch_millis", "sqlite/0004_epoch_millis.sql"),
+    migration!(5, "rollup_unique", "sqlite/0005_rollup_unique.sql"),
+];
+
+const POSTGRES: &[Migration] = &[
+    migration!(1, "init", "postgres/0001_init.sql"),
+    migration!(2, "query_indexes", "postgres/0002_query_indexes.sql"),
+    migration!(3, "metric_rollups", "postgres/0003_metric_rollups.sql"),
+    migration!(4, "epoch_millis", "postgres/0004_epoch_millis.sql"),
+    migration!(5, "rollup_unique", "postgres/0005_rollup_unique.sql"),
+];
+
+const MYSQL: &[Migration] = &[
+    migration!(1, "init", "mysql/0001_init.sql"),
+    migration!(2, "query_indexes", "mysql/0002_query_indexes.sql"),
+    migration!(3, "metric_rollups", "mysql/0003_metric_rollups.sql"),
+    migration!(4, "epoch_millis", "mysql/0004_epoch_millis.sql"),
+    migration!(5, "rollup_unique", "mysql/0005_rollup_unique.sql"),
+];
+
+pub fn migrations_for(backend: Backend) -> &'static [Migration] {
+    match backend {
+        Backend::Sqlite => SQLITE,
+        Backend::Postgres => POSTGRES,
+        Backend::MySql => MYSQL,
+    }
+}
+
+/// 某个迁移的状态；`applied_at` 为 None 表示还没执行
+#[derive(Debug, Clone)]
+pub struct MigrationStatus {
+    pub version: i64,
+    pub name: String,
+    pub applied_at: Option<DateTime<Utc>>,
+    /// 库里记着、但当前二进制里没有这个迁移（库被更新版本的程序升级过）
+    pub unknown: bool,
+}
+
+/// 三种后端都认的写法；这张表要在执行任何迁移之前就能读写，所以 applied_at 一直存 RFC3339 文本，
+/// 没有跟 0004 一起改成毫秒
+const CREATE_MIGRATIONS_TABLE: &str = "CREATE TABLE IF NOT EXISTS schema_migrations (
+    version BIGINT PRIMARY KEY,
+    name VARCHAR(255) NOT NULL,
+    applied_at VARCHAR(64) NOT NULL
+)";
+
+/// 引入迁移之前建的库没有 schema_migrations，0001 的 CREATE TABLE IF NOT EXISTS 不会补列，
+/// 这里把当时靠 ALTER 补的列补上；列已存在时报错，忽略即可。
+const LEGACY_UPGRADE_STATEMENTS: &[&str] = &[
+    "ALTER TABLE logs ADD COLUMN fields TEXT",
+    "ALTER TABLE metrics ADD COLUMN labels TEXT",
+    "ALTER TABLE alerts ADD COLUMN tags TEXT",
+];
+
+#[derive(FromRow)]
+struct AppliedRow {
+    version: i64,
+    name: String,
+    applied_at: String,
+}
+
+impl AppliedRow {
+    fn applied_at(&self) -> sqlx::Result<DateTime<Utc>> {
+        self.applied_at
+            .parse()
+            .map_err(|e| sqlx::Error::ColumnDecode {
+                index: "applied_at".to_string(),
+                source: Box::new(e),
+            })
+    }
+}
+
+/// 已执行的迁移，按版本号排序；还没有 schema_migrations 表时返回 None
+async fn applied(pool: &AnyPool) -> sqlx::Result<Option<BTreeMap<i64, AppliedRow>>> {
+    let rows = sqlx::query_as::<_, AppliedRow>(
+        "SELECT version, name, applied_at FROM schema_migrations ORDER BY version",
+    )
+    .fetch_all(pool)
+    .await;
+    match rows {
+        Ok(rows) => Ok(Some(rows.into_iter().map(|r| (r.version, r)).collect())),
+        Err(sqlx::Error::Database(_)) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+async fn table_exists(pool: &AnyPool, table: &str) -> bool {
+    sqlx::query(&format!("SELECT 1 FROM {table} LIMIT 1"))
+        .fetch_optional(pool)
+        .await
+        .is_ok()
+}
+
+/// 执行所有还没执行的迁移，返回这次执行的那些
+pub async fn run(pool: &AnyPool, migrations: &[Migration]) -> sqlx::Result<Vec<MigrationStatus>> {
+    let already = match applied(pool).await? {
+        Some(already) => already,
+        None => {
+            if table_exists(pool, "logs").await {
+                for stmt in LEGACY_UPGRADE_STATEMENTS {
+                    let _ = sqlx::query(stmt).execute(pool).await;
+                }
+            }
+            sqlx::query(CREATE_MIGRATIONS_TABLE).execute(pool).await?;
+            BTreeMap::new()
+        }
+    };
+
+    let mut done = Vec::new();
+    for migration in migrations {
+        if already.contains_key(&migration.version) {
+            continue;
+        }
+        let applied_at = Utc::now();
+        match apply(pool, migration, applied_at).await {
+            Ok(()) => done.push(MigrationStatus {
+                version: migration.version,
+                name: migration.name.to_string(),
+                applied_at: Some(applied_at),
+                unknown: false,
+            }),
+            // 另一个进程（比如同时启动的 api-server）已经执行了这个迁移
+            Err(e) => {
+                let now_applied = applied(pool).await?.unwrap_or_default();
+                if !now_applied.contains_key(&migration.version) {
+                    return Err(e);
+                }
+            }
+        }
+    }
+    Ok(done)
+}
+
+/// 迁移脚本和版本记录在同一个事务里；MySQL 的 DDL 会隐式提交，失败时可能只执行了一半
+async fn apply(
+    pool: &AnyPool,
+    migration: &Migration,
+    applied_at: DateTime<Utc>,
+) -> sqlx::Result<()> {
+    let mut tx = pool.begin().await?;
+    sqlx::raw_sql(migration.sql).execute(&mut *tx).await?;
+    sqlx::query("INSERT INTO schema_migrations (version, name, applied_at) VALUES (?1, ?2, ?3)")
+        .bind(migration.version)
+        .bind(migration.name)
+        .bind(applied_at.to_rfc3339())
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await
+}
+
+/// 所有迁移（当前二进制里的和库里记着的）的状态，按版本号排序
+pub async fn status(
+    pool: &AnyPool,
+    migrations: &[Migration],
+) -> sqlx::Result<Vec<MigrationStatus>> {
+    let mut applied = applied(pool).await?.unwrap_or_default();
+    let mut statuses = Vec::new();
+    for m in migrations {
+        statuses.push(MigrationStatus {
+            version: m.version,
+            name: m.name.to_string(),
+            applied_at: applied
+                .remove(&m.version)
+                .map(|row| row.applied_at())
+                .transpose()?,
+            unknown: false,
+        });
+    }
+    for row in applied.into_values() {
+        statuses.push(MigrationStatus {
+            version: row.version,
+            applied_at: Some(row.applied_at()?),
+            name: row.name,
+            unknown: true,
+        });
+    }
+    statuses.sort_by_key(|s| s.version);
+    Ok(statuses)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+    use crate::query::{MetricFilter, PageRequest};
+    use crate::test_support::TempDb;
+
+    /// 只执行到 0003，库里的时间列还是 RFC3339 文本
+    async fn pre_epoch_millis(test: &str) -> TempDb {
+        let temp = TempDb::open(test).await;
+        let migrations = migrations_for(Backend::Sqlite);
+        assert_eq!(migrations[3].version, 4);
+        run(&temp.db.pool, &migrations[..3]).await.unwrap();
+        temp
+    }
+
+    async fn insert_metric_at(temp: &TempDb, time: &str) {
+        sqlx::query("INSERT INTO metrics (time, plugin, name, value) VALUES (?1, 'p', 'm', 1.0)")
+            .bind(time)
+            .execute(&temp.db.pool)
+            .await
+            .unwrap();
+    }
+
+    async fn metric_times(temp: &TempDb) -> sqlx::Result<Vec<DateTime<Utc>>> {
+        let page = PageRequest {
+            limit: 10,
+            ..Default::default()
+        };
+        let page = temp
+            .db
+            .metrics_page(&MetricFilter::default(), &page)
+            .await?;
+        Ok(page.items.into_iter().map(|m| m.time).collect())
+    }
+
+    #[tokio::test]
+    async fn epoch_millis_migration_keeps_rfc3339_instants() {
+        let temp = pre_epoch_millis("migrate-0004").await;
+        // 旧版本用 to_rfc3339() 写入，带偏移量，可能有纳秒
+        insert_metric_at(&temp, "2026-10-15T08:30:44.363+00:00").await;
+        insert_metric_at(&temp, "2026-10-15T16:30:45.5+08:00").await;
+        insert_metric_at(&temp, "1969-12-31T23:59:59.999999999+00:00").await;
+
+        let applied = temp.db.migrate().await.unwrap();
+        assert_eq!(
+            applied.iter().map(|m| m.version).collect::<Vec<_>>(),
+            [4, 5]
+        );
+
+        // 分页查询按时间从旧到新返回；纳秒四舍五入到毫秒
+        assert_eq!(
+            metric_times(&temp).await.unwrap(),
+            [0, 1_792_053_044_363, 1_792_053_045_500]
+                .map(|ms| Utc.timestamp_millis_opt(ms).unwrap())
+        );
+    }
+
+    /// 换算不了的旧值让 0004 整体回滚，不会留下半迁移的库
+    #[tokio::test]
+    async fn unparsable_rfc3339_rolls_back_the_migration() {
+        let temp = pre_epoch_millis("migrate-0004-bad").await;
+        insert_metric_at(&temp, "2026-10-15T08:30:44+00:00").await;
+        insert_metric_at(&temp, "yesterday").await;
+
+        assert!(temp.db.migrate().await.is_err());
+        let status = temp.db.migration_status().await.unwrap();
+        assert!(status[3].applied_at.is_none());
+        let text: String = sqlx::query_scalar("SELECT time FROM metrics ORDER BY id LIMIT 1")
+            .fetch_one(&temp.db.pool)
+            .await
+            .unwrap();
+        assert_eq!(text, "2026-10-15T08:30:44+00:00");
+    }
+
+    /// 迁移之后混进来的坏值读出来是错误，不会被当成 0 或截断
+    #[tokio::test]
+    async fn corrupt_time_values_fail_to_decode() {
+        for (i, bad) in ["'2026-10-15T08:30:44+00:00'", "1.5", "9223372036854775807"]
+            .into_iter()
+            .enumerate()
+        {
+            let temp = TempDb::migrated(&format!("migrate-corrupt-{i}")).await;
+            sqlx::query(&format!(
+                "INSERT INTO metrics (time, plugin, name, value) VALUES ({bad}, 'p', 'm', 1.0)"
+            ))
+            .execute(&temp.db.pool)
+            .await
+            .unwrap();
+            assert!(metric_times(&temp).await.is_err(), "{bad} 应该读不出来");
+        }
+    }
+
+    /// 0005 之前可能重复写入的汇总行只留一行，之后同一个桶写不进第二行
+    #[tokio::test]
+    async fn rollup_unique_migration_drops_duplicate_buckets() {
+        let temp = TempDb::open("migrate-0005").await;
+        let migrations = migrations_for(Backend::Sqlite);
+        assert_eq!(migrations[4].version, 5);
+        run(&temp.db.pool, &migrations[..4]).await.unwrap();
+
+        let insert = |labels: &'static str, sum: f64| {
+            sqlx::query(
+                "INSERT INTO metric_rollups (resolution, time, plugin, name, labels, min_value, \
+                 max_value, sum_value, sample_count, last_value, last_time) \
+                 VALUES (60, 1792000000000, 'p', 'm', ?1, 1, 1, ?2, 1, 1, 1792000000000)",
+            )
+            .bind(labels)
+            .bind(sum)
+            .execute(&temp.db.pool)
+        };
+        insert("", 1.0).await.unwrap();
+        insert("", 2.0).await.unwrap();
+        insert(r#"{"host":"a"}"#, 3.0).await.unwrap();
+
+        temp.db.migrate().await.unwrap();
+        let mut sums: Vec<f64> = sqlx::query_scalar("SELECT sum_value FROM metric_rollups")
+            .fetch_all(&temp.db.pool)
+            .await
+            .unwrap();
+        sums.sort_by(f64::total_cmp);
+        assert_eq!(sums, [1.0, 3.0]);
+        assert!(insert("", 4.0).await.is_err());
+    }
+}
@@ -0,0 +1,714 @@
+//! 按条件查询指标 / 日志 / 告警，带游标分页。
+//!
+//! 结果按 `(time, id)` 排序，游标记的是上一页最后一行的 `(time, id)`，
+//! 翻页期间有新数据写入也不会重复或漏行。
+
+use std::{fmt, str::FromStr};
+
+use chrono::{DateTime, Utc};
+use core_types::{AlertEvent, AlertSeverity, LogEvent, LogLevel, Metric};
+use sqlx::{
+    any::{AnyArguments, AnyRow},
+    query::{Query, QueryAs},
+    Any, FromRow,
+};
+
+use crate::timestamp::{select_id, select_millis, to_millis};
+use crate::{AlertRow, Db, LogRow, MetricRow};
+
+/// 历史指标查询条件；为 None 的条件不限制
+#[derive(Debug, Clone, Default)]
+pub struct MetricFilter {
+    pub plugin: Option<String>,
+    pub name: Option<String>,
+    /// 起始时间（含）
+    pub start: Option<DateTime<Utc>>,
+    /// 结束时间（不含）
+    pub end: Option<DateTime<Utc>>,
+    /// 标签条件，全部满足才返回
+    pub labels: Vec<LabelMatcher>,
+    /// 最多返回多少条（取最新的）；分页查询时不看这个字段，用 [`PageRequest::limit`]
+    pub limit: i64,
+}
+
+/// 日志查询条件
+#[derive(Debug, Clone, Default)]
+pub struct LogFilter {
+    pub plugin: Option<String>,
+    /// 只返回不低于这个级别的日志
+    pub min_level: Option<LogLevel>,
+    pub start: Option<DateTime<Utc>>,
+    pub end: Option<DateTime<Utc>>,
+    /// 结构化字段条件
+    pub fields: Vec<LabelMatcher>,
+}
+
+/// 告警查询条件
+#[derive(Debug, Clone, Default)]
+pub struct AlertFilter {
+    pub plugin: Option<String>,
+    pub metric_name: Option<String>,
+    /// 只返回不低于这个级别的告警
+    pub min_severity: Option<AlertSeverity>,
+    pub start: Option<DateTime<Utc>>,
+    pub end: Option<DateTime<Utc>>,
+    pub tags: Vec<LabelMatcher>,
+}
+
+/// 标签（日志字段 / 告警标签）条件
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LabelMatcher {
+    /// 有这个标签且值相等
+    Eq(String, String),
+    /// 没有这个标签，或者值不相等
+    NotEq(String, String),
+}
+
+impl FromStr for LabelMatcher {
+    type Err = String;
+
+    /// `key=value` 或 `key!=value`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("标签条件 \"{s}\" 应该是 key=value 或 key!=value");
+        if let Some((key, value)) = s.split_once("!=") {
+            if key.trim().is_empty() {
+                return Err(invalid());
+            }
+            return Ok(Self::NotEq(
+                key.trim().to_string(),
+                value.trim().to_string(),
+            ));
+        }
+        match s.split_once('=') {
+            Some((key, value)) if !key.trim().is_empty() => {
+                Ok(Self::Eq(key.trim().to_string(), value.trim().to_string()))
+            }
+            _ => Err(invalid()),
+        }
+    }
+}
+
+/// 分页参数
+#[derive(Debug, Clone, Default)]
+pub struct PageRequest {
+    /// 上一页返回的 [`Page::next_cursor`]；None 表示第一页
+    pub cursor: Option<Cursor>,
+    /// 每页条数
+    pub limit: i64,
+    /// true 时从新到旧
+    pub descending: bool,
+}
+
+/// 一页结果
+#[derive(Debug, Clone)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    /// 还有下一页时的游标
+    pub next_cursor: Option<Cursor>,
+}
+
+/// 翻页游标；对外是一个不透明的字符串（`to_string()` / `parse()`），可以直接放进 URL
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cursor {
+    /// Unix 毫秒
+    time: i64,
+    id: i64,
+}
+
+impl fmt::Display for Cursor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for b in format!("{}/{}", self.time, self.id).bytes() {
+            write!(f, "{b:02x}")?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Cursor {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("无效的游标: {s}");
+        let bytes = (0..s.len())
+            .step_by(2)
+            .map(|i| s.get(i..i + 2).and_then(|h| u8::from_str_radix(h, 16).ok()))
+            .collect::<Option<Vec<u8>>>()
+            .ok_or_else(invalid)?;
+        let raw = String::from_utf8(bytes).map_err(|_| invalid())?;
+        let (time, id) = raw.rsplit_once('/').ok_or_else(invalid)?;
+        Ok(Self {
+            time: time.parse().map_err(|_| invalid())?,
+            id: id.parse().map_err(|_| invalid())?,
+        })
+    }
+}
+
+// ============ WHERE 子句 ============
+
+pub(crate) enum Param {
+    Text(String),
+    Int(i64),
+}
+
+/// 拼 `WHERE a = ?1 AND b >= ?2 ...`，参数按出现顺序编号
+#[derive(Default)]
+pub(crate) struct Conditions {
+    clauses: Vec<String>,
+    params: Vec<Param>,
+}
+
+impl Conditions {
+    /// 登记一个参数，返回它的占位符
+    pub(crate) fn param(&mut self, param: Param) -> String {
+        self.params.push(param);
+        format!("?{}", self.params.len())
+    }
+
+    pub(crate) fn compare(&mut self, column: &str, op: &str, value: Option<String>) {
+        if let Some(value) = value {
+            let p = self.param(Param::Text(value));
+            self.clauses.push(format!("{column} {op} {p}"));
+        }
+    }
+
+    pub(crate) fn param_int(&mut self, value: i64) -> String {
+        self.param(Param::Int(value))
+    }
+
+    pub(crate) fn compare_int(&mut self, column: &str, op: &str, value: i64) {
+        let p = self.param_int(value);
+        self.clauses.push(format!("{column} {op} {p}"));
+    }
+
+    pub(crate) fn equals_int(&mut self, column: &str, value: i64) {
+        self.compare_int(column, "=", value);
+    }
+
+    /// `NOT (a = ?1 AND b = ?2)`，值为 None 的列不限制
+    pub(crate) fn exclude(&mut self, matches: &[(&str, Option<String>)]) {
+        let parts: Vec<String> = matches
+            .iter()
+            .filter_map(|(column, value)| {
+                let p = self.param(Param::Text(value.clone()?));
+                Some(format!("{column} = {p}"))
+            })
+            .collect();
+        if !parts.is_empty() {
+            self.clauses.push(format!("NOT ({})", parts.join(" AND ")));
+        }
+    }
+
+    pub(crate) fn time_range(&mut self, start: Option<DateTime<Utc>>, end: Option<DateTime<Utc>>) {
+        if let Some(start) = start {
+            self.compare_int("time", ">=", to_millis(start));
+        }
+        if let Some(end) = end {
+            self.compare_int("time", "<", to_millis(end));
+        }
+    }
+
+    fn one_of(&mut self, column: &str, values: Vec<String>) {
+        if values.is_empty() {
+            return;
+        }
+        let placeholders: Vec<String> = values
+            .into_iter()
+            .map(|v| self.param(Param::Text(v)))
+            .collect();
+        self.clauses
+            .push(format!("{column} IN ({})", placeholders.join(", ")));
+    }
+
+    /// 标签列存的是 serde_json 序列化的对象（没有空格），`"key":"value"` 作为子串出现即匹配；
+    /// 字符串里的引号会被转义成 `\"`，不会误匹配。`!` 作 LIKE 的转义符，MySQL 里 `\` 不好写
+    pub(crate) fn labels(&mut self, column: &str, matchers: &[LabelMatcher]) {
+        for matcher in matchers {
+            let (key, value, negate) = match matcher {
+                LabelMatcher::Eq(k, v) => (k, v, false),
+                LabelMatcher::NotEq(k, v) => (k, v, true),
+            };
+            let pair = format!(
+                "{}:{}",
+                serde_json::Value::from(key.as_str()),
+                serde_json::Value::from(value.as_str())
+            );
+            let pattern = format!("%{}%", escape_like(&pair));
+            let p = self.param(Param::Text(pattern));
+            self.clauses.push(if negate {
+                format!("({column} IS NULL OR {column} NOT LIKE {p} ESCAPE '!')")
+            } else {
+                format!("{column} LIKE {p} ESCAPE '!'")
+            });
+        }
+    }
+
+    /// 接在游标之后：正序取更晚的，倒序取更早的
+    fn after(&mut self, cursor: Option<&Cursor>, descending: bool) {
+        let Some(cursor) = cursor else {
+            return;
+        };
+        let op = if descending { "<" } else { ">" };
+        let time = self.param(Param::Int(cursor.time));
+        let id = self.param(Param::Int(cursor.id));
+        self.clauses.push(format!(
+            "(time {op} {time} OR (time = {time} AND id {op} {id}))"
+        ));
+    }
+
+    pub(crate) fn where_clause(&self) -> String {
+        if self.clauses.is_empty() {
+            String::new()
+        } else {
+            format!("WHERE {}", self.clauses.join(" AND "))
+        }
+    }
+
+    pub(crate) fn bind_query<'q>(
+        self,
+        mut query: Query<'q, Any, AnyArguments<'q>>,
+    ) -> Query<'q, Any, AnyArguments<'q>> {
+        for param in self.params {
+            query = match param {
+                Param::Text(s) => query.bind(s),
+                Param::Int(i) => query.bind(i),
+            };
+        }
+        query
+    }
+
+    pub(crate) fn bind<'q, R>(
+        self,
+        mut query: QueryAs<'q, Any, R, AnyArguments<'q>>,
+    ) -> QueryAs<'q, Any, R, AnyArguments<'q>> {
+        for param in self.params {
+            query = match param {
+                Param::Text(s) => query.bind(s),
+                Param::Int(i) => query.bind(i),
+            };
+        }
+        query
+    }
+}
+
+fn escape_like(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        if matches!(c, '%' | '_' | '!') {
+            out.push('!');
+        }
+        out.push(c);
+    }
+    out
+}
+
+fn metric_conditions(filter: &MetricFilter) -> Conditions {
+    let mut c = Conditions::default();
+    c.compare("plugin", "=", filter.plugin.clone());
+    c.compare("name", "=", filter.name.clone());
+    c.time_range(filter.start, filter.end);
+    c.labels("labels", &filter.labels);
+    c
+}
+
+const LOG_LEVELS: [LogLevel; 4] = [
+    LogLevel::Debug,
+    LogLevel::Info,
+    LogLevel::Warn,
+    LogLevel::Error,
+];
+
+const ALERT_SEVERITIES: [AlertSeverity; 3] = [
+    AlertSeverity::Info,
+    AlertSeverity::Warning,
+    AlertSeverity::Critical,
+];
+
+// ============ 查询 ============
+
+/// 能当游标的行
+trait Keyed {
+    fn cursor(&self) -> Cursor;
+}
+
+macro_rules! impl_keyed {
+    ($($row:ty),*) => {
+        $(impl Keyed for $row {
+            fn cursor(&self) -> Cursor {
+                Cursor {
+                    time: self.time.0,
+                    id: self.id.0,
+                }
+            }
+        })*
+    };
+}
+
+impl_keyed!(MetricRow, LogRow, AlertRow);
+
+impl Db {
+    /// 按条件查询最新的 `filter.limit` 条指标，结果按时间正序排列
+    pub async fn query_metrics(&self, filter: &MetricFilter) -> sqlx::Result<Vec<Metric>> {
+        let mut conditions = metric_conditions(filter);
+        let limit = conditions.param(Param::Int(filter.limit));
+        let sql = format!(
+            "SELECT {}, {}, plugin, name, value, COALESCE(labels, '') AS labels FROM metrics \
+             {} ORDER BY time DESC, id DESC LIMIT {limit}",
+            select_id(self.backend),
+            select_millis(self.backend, "time"),
+            conditions.where_clause()
+        );
+        let query = conditions.bind(sqlx::query_as::<_, MetricRow>(&sql));
+        let rows = query.fetch_all(&self.pool).await?;
+
+        rows.into_iter().rev().map(TryInto::try_into).collect()
+    }
+
+    /// 分页查询指标，比如某个插件某个指标最近一小时的全部点
+    pub async fn metrics_page(
+        &self,
+        filter: &MetricFilter,
+        page: &PageRequest,
+    ) -> sqlx::Result<Page<Metric>> {
+        let select = format!(
+            "SELECT {}, {}, plugin, name, value, COALESCE(labels, '') AS labels FROM metrics",
+            select_id(self.backend),
+            select_millis(self.backend, "time")
+        );
+        self.fetch_page::<MetricRow, _>(&select, metric_conditions(filter), page)
+            .await
+    }
+
+    /// 分页查询日志
+    pub async fn logs_page(
+        &self,
+        filter: &LogFilter,
+        page: &PageRequest,
+    ) -> sqlx::Result<Page<LogEvent>> {
+        let mut c = Conditions::default();
+        c.compare("plugin", "=", filter.plugin.clone());
+        if let Some(min) = filter.min_level {
+            let levels = LOG_LEVELS
+                .iter()
+                .filter(|l| **l as u8 >= min as u8)
+                .map(|l| format!("{l:?}"))
+                .collect();
+            c.one_of("level", levels);
+        }
+        c.time_range(filter.start, filter.end);
+        c.labels("fields", &filter.fields);
+        let select = format!(
+            "SELECT {}, {}, level, COALESCE(plugin, '') AS plugin, message, \
+             COALESCE(fields, '') AS fields FROM logs",
+            select_id(self.backend),
+            select_millis(self.backend, "time")
+        );
+        self.fetch_page::<LogRow, _>(&select, c, page).await
+    }
+
+    /// 分页查询告警
+    pub async fn alerts_page(
+        &self,
+        filter: &AlertFilter,
+        page: &PageRequest,
+    ) -> sqlx::Result<Page<AlertEvent>> {
+        let mut c = Conditions::default();
+        c.compare("plugin", "=", filter.plugin.clone());
+        c.compare("metric_name", "=", filter.metric_name.clone());
+        if let Some(min) = filter.min_severity {
+            let severities = ALERT_SEVERITIES
+                .iter()
+                .filter(|s| **s as u8 >= min as u8)
+                .map(|s| format!("{s:?}"))
+                .collect();
+            c.one_of("severity", severities);
+        }
+        c.time_range(filter.start, filter.end);
+        c.labels("tags", &filter.tags);
+        let select = format!(
+            "SELECT {}, {}, plugin, metric_name, severity, title, message, \
+             COALESCE(tags, '') AS tags FROM alerts",
+            select_id(self.backend),
+            select_millis(self.backend, "time")
+        );
+        self.fetch_page::<AlertRow, _>(&select, c, page).await
+    }
+
+    /// 多取一行判断有没有下一页
+    async fn fetch_page<R, T>(
+        &self,
+        select: &str,
+        mut conditions: Conditions,
+        page: &PageRequest,
+    ) -> sqlx::Result<Page<T>>
+    where
+        R: for<'r> FromRow<'r, AnyRow> + Keyed + Send + Unpin,
+        T: TryFrom<R, Error = sqlx::Error>,
+    {
+        let limit = page.limit.max(1);
+        conditions.after(page.cursor.as_ref(), page.descending);
+        let limit_param = conditions.param(Param::Int(limit + 1));
+        let order = if page.descending { "DESC" } else { "ASC" };
+        let sql = format!(
+            "{select} {} ORDER BY time {order}, id {order} LIMIT {limit_param}",
+            conditions.where_clause()
+        );
+        let query = conditions.bind(sqlx::query_as::<_, R>(&sql));
+        let mut rows = query.fetch_all(&self.pool).await?;
+
+        let next_cursor = if rows.len() as i64 > limit {
+            rows.truncate(limit as usize);
+            rows.last().map(Keyed::cursor)
+        } else {
+            None
+        };
+        Ok(Page {
+            items: rows
+                .into_iter()
+                .map(T::try_from)
+                .collect::<sqlx::Result<_>>()?,
+            next_cursor,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use chrono::TimeZone;
+
+    use super::*;
+    use crate::test_support::TempDb;
+
+    #[test]
+    fn cursor_round_trips_through_its_string_form() {
+        let cursor = Cursor {
+            time: 1_792_212_244_363,
+            id: 42,
+        };
+        let text = cursor.to_string();
+        assert!(text.bytes().all(|b| b.is_ascii_hexdigit()), "{text}");
+        assert_eq!(text.parse::<Cursor>(), Ok(cursor));
+
+        let negative = Cursor { time: -1, id: 0 };
+        assert_eq!(negative.to_string().parse::<Cursor>(), Ok(negative));
+    }
+
+    #[test]
+    fn malformed_cursors_are_rejected() {
+        let valid = Cursor { time: 1, id: 2 }.to_string();
+        for bad in [
+            "",
+            &valid[..valid.len() - 1], // 奇数长度
+            "zz",                      // 不是 hex
+            "3g31",
+            "é1",       // 多字节字符
+            "31323334", // 没有 '/'
+            "612f31",   // "a/1"
+        ] {
+            assert!(bad.parse::<Cursor>().is_err(), "{bad:?} 应该被拒绝");
+        }
+    }
+
+    #[test]
+    fn label_matchers_parse_eq_and_not_eq() {
+        assert_eq!(
+            "env = prod".parse(),
+            Ok(LabelMatcher::Eq("env".into(), "prod".into()))
+        );
+        assert_eq!(
+            "env!=prod".parse(),
+            Ok(LabelMatcher::NotEq("env".into(), "prod".into()))
+        );
+        // 值里可以有 '='
+        assert_eq!(
+            "query=a=b".parse(),
+            Ok(LabelMatcher::Eq("query".into(), "a=b".into()))
+        );
+        assert_eq!(
+            "env!=".parse(),
+            Ok(LabelMatcher::NotEq("env".into(), String::new()))
+        );
+        for bad in ["env", "=prod", "!=prod", " != prod"] {
+            assert!(bad.parse::<LabelMatcher>().is_err(), "{bad:?} 应该被拒绝");
+        }
+    }
+
+    #[test]
+    fn like_wildcards_and_escape_char_are_escaped() {
+        assert_eq!(escape_like("100%_done!"), "100!%!_done!!");
+        assert_eq!(escape_like("plain"), "plain");
+
+        let mut c = Conditions::default();
+        c.labels("tags", &[LabelMatcher::Eq("a_b".into(), "5%".into())]);
+        assert_eq!(c.where_clause(), "WHERE tags LIKE ?1 ESCAPE '!'");
+        assert!(matches!(&c.params[..], [Param::Text(p)] if p == r#"%"a!_b":"5!%"%"#));
+    }
+
+    #[test]
+    fn keyset_condition_follows_sort_direction() {
+        let cursor = Cursor { time: 1000, id: 7 };
+
+        let mut asc = Conditions::default();
+        asc.after(Some(&cursor), false);
+        assert_eq!(
+            asc.where_clause(),
+            "WHERE (time > ?1 OR (time = ?1 AND id > ?2))"
+        );
+
+        let mut desc = Conditions::default();
+        desc.after(Some(&cursor), true);
+        assert_eq!(
+            desc.where_clause(),
+            "WHERE (time < ?1 OR (time = ?1 AND id < ?2))"
+        );
+        assert!(matches!(
+            &desc.params[..],
+            [Param::Int(1000), Param::Int(7)]
+        ));
+    }
+
+    fn metric(ms: i64, labels: &[(&str, &str)]) -> Metric {
+        Metric {
+            time: Utc.timestamp_millis_opt(ms).unwrap(),
+            plugin: "p".to_string(),
+            name: "m".to_string(),
+            value: ms as f64,
+            labels: labels
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect::<HashMap<_, _>>(),
+        }
+    }
+
+    /// 每页 2 条翻到底，返回各行相对 `base` 的毫秒数；翻页停不下来时失败，不会卡住测试
+    async fn all_pages(db: &Db, base: i64, descending: bool) -> Vec<i64> {
+        let mut seen = Vec::new();
+        let mut cursor = None;
+        for _ in 0..100 {
+            let request = PageRequest {
+                cursor,
+                limit: 2,
+                descending,
+            };
+            let page = db
+                .metrics_page(&MetricFilter::default(), &request)
+                .await
+                .unwrap();
+            seen.extend(page.items.iter().map(|m| m.time.timestamp_millis() - base));
+            // 游标要经过字符串往返，和 HTTP 接口一样
+            cursor = match page.next_cursor {
+                Some(c) => Some(c.to_string().parse().unwrap()),
+                None => return seen,
+            };
+        }
+        panic!("翻了 100 页还没结束，已读到 {seen:?}");
+    }
+
+    /// 同一毫秒的多行跨页时靠 id 区分，不重复也不漏
+    #[tokio::test]
+    async fn pages_cover_rows_sharing_a_timestamp() {
+        let temp = TempDb::migrated("query-pages").await;
+        let base = 1_792_000_000_000;
+        let rows: Vec<Metric> = [0, 0, 0, 1, 1, 2]
+            .iter()
+            .map(|offset| metric(base + offset, &[]))
+            .collect();
+        temp.db.insert_metrics(&rows).await.unwrap();
+
+        for descending in [false, true] {
+            let mut expected = vec![0, 0, 0, 1, 1, 2];
+            if descending {
+                expected.reverse();
+            }
+            let seen = all_pages(&temp.db, base, descending).await;
+            assert_eq!(seen, expected, "descending={descending}");
+        }
+    }
+
+    /// id 超过 i32::MAX 之后游标里的 id 不能被截断，否则同一毫秒的行会翻来覆去
+    #[tokio::test]
+    async fn pages_past_i32_ids() {
+        let temp = TempDb::migrated("query-big-ids").await;
+        let base = 1_792_000_000_000;
+        let first_id = i64::from(i32::MAX) + 10;
+        sqlx::query(
+            "INSERT INTO metrics (id, time, plugin, name, value, labels) \
+             VALUES (?1, ?2, 'p', 'm', 0, '{}')",
+        )
+        .bind(first_id)
+        .bind(base)
+        .execute(&temp.db.pool)
+        .await
+        .unwrap();
+        // 之后的自增 id 都接在 first_id 后面
+        let rows: Vec<Metric> = [0, 0, 0, 1, 1]
+            .iter()
+            .map(|o| metric(base + o, &[]))
+            .collect();
+        temp.db.insert_metrics(&rows).await.unwrap();
+
+        let page = temp
+            .db
+            .metrics_page(
+                &MetricFilter::default(),
+                &PageRequest {
+                    limit: 1,
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+        assert_eq!(page.next_cursor.map(|c| c.id), Some(first_id));
+
+        for descending in [false, true] {
+            let mut expected = vec![0, 0, 0, 0, 1, 1];
+            if descending {
+                expected.reverse();
+            }
+            let seen = all_pages(&temp.db, base, descending).await;
+            assert_eq!(seen, expected, "descending={descending}");
+        }
+    }
+
+    #[tokio::test]
+    async fn label_filter_treats_like_wildcards_literally() {
+        let temp = TempDb::migrated("query-like").await;
+        temp.db
+            .insert_metrics(&[
+                metric(1, &[("path", "/a_b")]),
+                metric(2, &[("path", "/axb")]),
+                metric(3, &[("path", "100%")]),
+                metric(4, &[("path", "1000")]),
+            ])
+            .await
+            .unwrap();
+
+        let times = |labels: Vec<LabelMatcher>| {
+            let db = temp.db.clone();
+            async move {
+                let filter = MetricFilter {
+                    labels,
+                    ..Default::default()
+                };
+                db.metrics_page(
+                    &filter,
+                    &PageRequest {
+                        limit: 10,
+                        ..Default::default()
+                    },
+                )
+                .await
+                .unwrap()
+                .items
+                .iter()
+                .map(|m| m.time.timestamp_millis())
+                .collect::<Vec<_>>()
+            }
+        };
+
+        assert_eq!(times(vec!["path=/a_b".parse().unwrap()]).await, [1]);
+        assert_eq!(times(vec!["path=100%".parse().unwrap()]).await, [3]);
+        assert_eq!(times(vec!["path!=/a_b".parse().unwrap()]).await, [2, 3, 4]);
+    }
+}
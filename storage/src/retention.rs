@@ -0,0 +1,388 @@
+//! 按保留期限清理旧数据。
+//!
+//! 每条规则针对一张表（汇总表按粒度区分），可以再限定插件和指标名；同一行命中多条规则时
+//! 以最具体的那条为准，比如 "metrics 保留 7 天" + "cpu-monitor 的指标保留 30 天"，
+//! cpu-monitor 的指标就保留 30 天。没有规则的表不清理。
+//!
+//! 删除分批进行，每批最多 `batch_size` 行，一批一个语句，不会长时间锁表。
+//! SQLite 删完之后做一次增量 vacuum，把空闲页还给文件系统。
+
+use chrono::{DateTime, Duration, Utc};
+use sqlx::FromRow;
+
+use crate::db_config::Backend;
+use crate::query::Conditions;
+use crate::rollup::Resolution;
+use crate::timestamp::{select_id, RowId};
+use crate::Db;
+
+/// 默认每批删除的行数
+pub const DEFAULT_PRUNE_BATCH: i64 = 5000;
+
+/// 规则作用的表
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RetentionTarget {
+    Metrics,
+    Logs,
+    Alerts,
+    /// `metric_rollups` 里某一级的汇总
+    Rollups(Resolution),
+}
+
+impl RetentionTarget {
+    fn table(self) -> &'static str {
+        match self {
+            RetentionTarget::Metrics => "metrics",
+            RetentionTarget::Logs => "logs",
+            RetentionTarget::Alerts => "alerts",
+            RetentionTarget::Rollups(_) => "metric_rollups",
+        }
+    }
+
+    /// 规则里 `name` 对应的列；日志没有指标名
+    fn name_column(self) -> Option<&'static str> {
+        match self {
+            RetentionTarget::Metrics | RetentionTarget::Rollups(_) => Some("name"),
+            RetentionTarget::Alerts => Some("metric_name"),
+            RetentionTarget::Logs => None,
+        }
+    }
+}
+
+/// 一条保留规则
+#[derive(Debug, Clone)]
+pub struct RetentionRule {
+    pub target: RetentionTarget,
+    /// 只作用于这个插件；None 表示所有插件
+    pub plugin: Option<String>,
+    /// 只作用于这个指标名（告警是关联的指标名）；日志上的这个条件会被忽略
+    pub name: Option<String>,
+    /// 保留多久，更早的行被删除
+    pub keep: Duration,
+}
+
+impl RetentionRule {
+    /// `other` 命中的行是否都落在 `self` 的范围里，并且 `other` 更具体
+    fn is_narrowed_by(&self, other: &RetentionRule) -> bool {
+        let name_column = self.target.name_column();
+        let narrower = |mine: &Option<String>, theirs: &Option<String>| match (mine, theirs) {
+            (None, _) => true,
+            (Some(a), Some(b)) => a == b,
+            (Some(_), None) => false,
+        };
+        let specificity = |r: &RetentionRule| {
+            usize::from(r.plugin.is_some()) + usize::from(name_column.is_some() && r.name.is_some())
+        };
+        other.target == self.target
+            && narrower(&self.plugin, &other.plugin)
+            && (name_column.is_none() || narrower(&self.name, &other.name))
+            && specificity(other) > specificity(self)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct RetentionPolicy {
+    pub rules: Vec<RetentionRule>,
+    /// 每批最多删除多少行，0 时用默认值
+    pub batch_size: i64,
+}
+
+/// 一次清理删除的行数
+#[derive(Debug, Clone, Default)]
+pub struct PruneReport {
+    pub metrics: u64,
+    pub logs: u64,
+    pub alerts: u64,
+    pub rollups: u64,
+    /// SQLite 增量 vacuum 释放的页数
+    pub vacuumed_pages: u64,
+}
+
+impl PruneReport {
+    pub fn total(&self) -> u64 {
+        self.metrics + self.logs + self.alerts + self.rollups
+    }
+}
+
+#[derive(FromRow)]
+struct IdRow {
+    #[sqlx(rename = "id_int")]
+    id: RowId,
+}
+
+impl Db {
+    /// 按规则删除过期的行；返回每张表删除的行数
+    pub async fn prune(&self, policy: &RetentionPolicy) -> sqlx::Result<PruneReport> {
+        let batch = match policy.batch_size {
+            n if n > 0 => n,
+            _ => DEFAULT_PRUNE_BATCH,
+        };
+        let now = Utc::now();
+        let mut report = PruneReport::default();
+        for rule in &policy.rules {
+            // 更具体的规则命中的行交给那条规则处理
+            let narrower: Vec<&RetentionRule> = policy
+                .rules
+                .iter()
+                .filter(|other| rule.is_narrowed_by(other))
+                .collect();
+            let removed = self
+                .prune_rule(rule, &narrower, now - rule.keep, batch)
+                .await?;
+            match rule.target {
+                RetentionTarget::Metrics => report.metrics += removed,
+                RetentionTarget::Logs => report.logs += removed,
+                RetentionTarget::Alerts => report.alerts += removed,
+                RetentionTarget::Rollups(_) => report.rollups += removed,
+            }
+        }
+        if self.backend == Backend::Sqlite && report.total() > 0 {
+            report.vacuumed_pages = self.incremental_vacuum().await?;
+        }
+        Ok(report)
+    }
+
+    async fn prune_rule(
+        &self,
+        rule: &RetentionRule,
+        narrower: &[&RetentionRule],
+        cutoff: DateTime<Utc>,
+        batch: i64,
+    ) -> sqlx::Result<u64> {
+        let table = rule.target.table();
+        let name_column = rule.target.name_column();
+        let conditions = || {
+            let mut c = Conditions::default();
+            c.time_range(None, Some(cutoff));
+            if let RetentionTarget::Rollups(resolution) = rule.target {
+                c.equals_int("resolution", resolution.secs());
+            }
+            c.compare("plugin", "=", rule.plugin.clone());
+            if let Some(column) = name_column {
+                c.compare(column, "=", rule.name.clone());
+            }
+            for other in narrower {
+                // 日志的 plugin 可以为 NULL，NOT (NULL = ?) 不成立，会漏删
+                let mut matches = vec![("COALESCE(plugin, '')", other.plugin.clone())];
+                if let Some(column) = name_column {
+                    matches.push((column, other.name.clone()));
+                }
+                c.exclude(&matches);
+            }
+            c
+        };
+
+        let mut removed = 0;
+        loop {
+            // 先找出这一批的最后一个 id，再按 id 上限删除；三种后端都支持这种写法
+            let mut c = conditions();
+            let offset = c.param_int(batch - 1);
+            let sql = format!(
+                "SELECT {} FROM {table} {} ORDER BY id LIMIT 1 OFFSET {offset}",
+                select_id(self.backend),
+                c.where_clause()
+            );
+            let bound = c
+                .bind(sqlx::query_as::<_, IdRow>(&sql))
+                .fetch_optional(&self.pool)
+                .await?;
+
+            let mut c = conditions();
+            if let Some(bound) = &bound {
+                c.compare_int("id", "<=", bound.id.0);
+            }
+            let sql = format!("DELETE FROM {table} {}", c.where_clause());
+            let result = c.bind_query(sqlx::query(&sql)).execute(&self.pool).await?;
+            removed += result.rows_affected();
+            // 找到了上限就至少能删掉它自己；一行都没删说明上限不对，再循环也不会有进展
+            if bound.is_none() || result.rows_affected() == 0 {
+                return Ok(removed);
+            }
+        }
+    }
+
+    /// 第一次清理时把库切换到 auto_vacuum = INCREMENTAL（要做一次完整的 VACUUM），
+    /// 之后每次只释放空闲页；返回释放的页数
+    async fn incremental_vacuum(&self) -> sqlx::Result<u64> {
+        // 切换模式的 PRAGMA 和 VACUUM 必须在同一个连接上执行
+        let mut conn = self.pool.acquire().await?;
+        let free: i64 = sqlx::query_scalar("PRAGMA freelist_count")
+            .fetch_one(&mut *conn)
+            .await?;
+        let mode: i64 = sqlx::query_scalar("PRAGMA auto_vacuum")
+            .fetch_one(&mut *conn)
+            .await?;
+        // 0 = NONE，1 = FULL，2 = INCREMENTAL
+        if mode == 2 {
+            sqlx::query("PRAGMA incremental_vacuum")
+                .execute(&mut *conn)
+                .await?;
+        } else {
+            sqlx::query("PRAGMA auto_vacuum = INCREMENTAL")
+                .execute(&mut *conn)
+                .await?;
+            sqlx::query("VACUUM").execute(&mut *conn).await?;
+        }
+        Ok(u64::try_from(free).unwrap_or(0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use core_types::Metric;
+
+    use super::*;
+    use crate::query::{MetricFilter, PageRequest};
+    use crate::test_support::TempDb;
+
+    fn rule(
+        target: RetentionTarget,
+        plugin: Option<&str>,
+        name: Option<&str>,
+        days: i64,
+    ) -> RetentionRule {
+        RetentionRule {
+            target,
+            plugin: plugin.map(str::to_string),
+            name: name.map(str::to_string),
+            keep: Duration::days(days),
+        }
+    }
+
+    #[test]
+    fn only_more_specific_rules_on_the_same_table_narrow() {
+        let all = rule(RetentionTarget::Metrics, None, None, 7);
+        let cpu = rule(RetentionTarget::Metrics, Some("cpu"), None, 30);
+        let cpu_usage = rule(RetentionTarget::Metrics, Some("cpu"), Some("usage"), 90);
+        let mem = rule(RetentionTarget::Metrics, Some("mem"), None, 30);
+        let usage = rule(RetentionTarget::Metrics, None, Some("usage"), 30);
+
+        assert!(all.is_narrowed_by(&cpu));
+        assert!(all.is_narrowed_by(&cpu_usage));
+        assert!(cpu.is_narrowed_by(&cpu_usage));
+        assert!(usage.is_narrowed_by(&cpu_usage));
+        // 反过来、互不包含、或同样具体的都不算
+        assert!(!cpu.is_narrowed_by(&all));
+        assert!(!cpu.is_narrowed_by(&mem));
+        assert!(!cpu.is_narrowed_by(&usage));
+        assert!(!all.is_narrowed_by(&all.clone()));
+        // 不同的表互不影响
+        assert!(!all.is_narrowed_by(&rule(RetentionTarget::Alerts, Some("cpu"), None, 30)));
+        assert!(
+            !rule(RetentionTarget::Rollups(Resolution::Minute), None, None, 7).is_narrowed_by(
+                &rule(
+                    RetentionTarget::Rollups(Resolution::Hour),
+                    Some("cpu"),
+                    None,
+                    30
+                )
+            )
+        );
+        // 日志没有指标名，只按插件区分
+        let logs = rule(RetentionTarget::Logs, None, None, 7);
+        assert!(!logs.is_narrowed_by(&rule(RetentionTarget::Logs, None, Some("x"), 30)));
+        assert!(logs.is_narrowed_by(&rule(RetentionTarget::Logs, Some("cpu"), None, 30)));
+    }
+
+    fn metric(plugin: &str, days_ago: i64) -> Metric {
+        Metric {
+            time: Utc::now() - Duration::days(days_ago),
+            plugin: plugin.to_string(),
+            name: "usage".to_string(),
+            value: days_ago as f64,
+            labels: HashMap::new(),
+        }
+    }
+
+    /// 通用规则不删更具体的规则要保留的行，具体规则也不碰别的插件
+    #[tokio::test]
+    async fn generic_and_plugin_rules_each_delete_only_their_rows() {
+        let temp = TempDb::migrated("retention-narrow").await;
+        temp.db
+            .insert_metrics(&[
+                metric("cpu", 1),
+                metric("cpu", 10),
+                metric("cpu", 40),
+                metric("mem", 1),
+                metric("mem", 10),
+                metric("mem", 40),
+            ])
+            .await
+            .unwrap();
+
+        let policy = RetentionPolicy {
+            rules: vec![
+                rule(RetentionTarget::Metrics, None, None, 7),
+                rule(RetentionTarget::Metrics, Some("cpu"), None, 30),
+            ],
+            // 每批一行，顺便走一遍分批删除
+            batch_size: 1,
+        };
+        let report = temp.db.prune(&policy).await.unwrap();
+        assert_eq!(report.metrics, 3);
+        assert_eq!(report.total(), 3);
+
+        let remaining = |plugin: &str| {
+            let db = temp.db.clone();
+            let filter = MetricFilter {
+                plugin: Some(plugin.to_string()),
+                ..Default::default()
+            };
+            async move {
+                let page = PageRequest {
+                    limit: 10,
+                    ..Default::default()
+                };
+                db.metrics_page(&filter, &page)
+                    .await
+                    .unwrap()
+                    .items
+                    .iter()
+                    .map(|m| m.value as i64)
+                    .collect::<Vec<_>>()
+            }
+        };
+        assert_eq!(remaining("cpu").await, [10, 1]);
+        assert_eq!(remaining("mem").await, [1]);
+
+        // 再跑一次没有可删的
+        assert_eq!(temp.db.prune(&policy).await.unwrap().total(), 0);
+    }
+
+    /// id 超过 i32::MAX 之后分批删除也要能删完、停下来
+    #[tokio::test]
+    async fn prunes_rows_past_i32_ids() {
+        let temp = TempDb::migrated("retention-big-ids").await;
+        let old = Utc::now() - Duration::days(10);
+        sqlx::query(
+            "INSERT INTO metrics (id, time, plugin, name, value, labels) \
+             VALUES (?1, ?2, 'cpu', 'usage', 10, '{}')",
+        )
+        .bind(i64::from(i32::MAX) + 10)
+        .bind(old.timestamp_millis())
+        .execute(&temp.db.pool)
+        .await
+        .unwrap();
+        // 之后的自增 id 都接在上面那行后面
+        temp.db
+            .insert_metrics(&[metric("cpu", 10), metric("cpu", 10), metric("cpu", 1)])
+            .await
+            .unwrap();
+
+        let policy = RetentionPolicy {
+            rules: vec![rule(RetentionTarget::Metrics, None, None, 7)],
+            batch_size: 1,
+        };
+        let limit = std::time::Duration::from_secs(10);
+        let report = tokio::time::timeout(limit, temp.db.prune(&policy))
+            .await
+            .expect("清理没有停下来")
+            .unwrap();
+        assert_eq!(report.metrics, 3);
+
+        let latest = temp.db.latest_metrics(10).await.unwrap();
+        assert_eq!(latest.iter().map(|m| m.value).collect::<Vec<_>>(), [1.0]);
+    }
+}
@@ -0,0 +1,638 @@
+//! 指标降采样。
+//!
+//! 原始指标按 1 分钟、1 小时、1 天三级汇总进 `metric_rollups`，每个桶按 plugin + name + labels
+//! 记 min / max / sum / count / last。1 分钟级从原始数据汇总，1 小时级从 1 分钟级汇总，1 天级从
+//! 1 小时级汇总；每一级汇总到哪里记在 `rollup_state`，只处理已经结束的桶，每个桶只汇总一次。
+//! 汇总之后才写进来、时间又早于汇总进度的指标不会进入汇总。
+//! 每个桶在表里只有一行（唯一索引），重复汇总同一段时间时覆盖原来的值。
+
+use std::collections::{BTreeMap, HashMap};
+
+use chrono::{DateTime, Duration, Utc};
+use serde::Serialize;
+use sqlx::FromRow;
+
+use crate::db_config::Backend;
+use crate::query::{Conditions, LabelMatcher};
+use crate::timestamp::{from_millis, select_millis, to_millis, Millis};
+use crate::{decode_map, encode_map, values_placeholders, Db, MetricRow};
+
+/// 数据的时间粒度
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Resolution {
+    /// 原始数据
+    Raw,
+    Minute,
+    Hour,
+    Day,
+}
+
+impl Resolution {
+    /// 桶宽（秒）；原始数据为 0
+    pub fn secs(self) -> i64 {
+        match self {
+            Resolution::Raw => 0,
+            Resolution::Minute => 60,
+            Resolution::Hour => 3600,
+            Resolution::Day => 86400,
+        }
+    }
+
+    /// 这一级从哪一级汇总
+    fn source(self) -> Resolution {
+        match self {
+            Resolution::Raw | Resolution::Minute => Resolution::Raw,
+            Resolution::Hour => Resolution::Minute,
+            Resolution::Day => Resolution::Hour,
+        }
+    }
+
+    /// 汇总时一次最多读多长时间的源数据
+    fn window(self) -> Duration {
+        match self {
+            Resolution::Raw | Resolution::Minute => Duration::hours(1),
+            Resolution::Hour => Duration::days(1),
+            Resolution::Day => Duration::days(30),
+        }
+    }
+}
+
+/// 从细到粗
+const ROLLUP_LEVELS: [Resolution; 3] = [Resolution::Minute, Resolution::Hour, Resolution::Day];
+
+/// 1 分钟级只汇总这么久以前就已经结束的桶，给写入队列留出落库的时间
+const ROLLUP_DELAY: Duration = Duration::minutes(2);
+
+/// 不指定 step 时，按最多返回这么多个点来选粒度
+const DEFAULT_MAX_POINTS: i64 = 1000;
+
+/// step 的上限（10 年，秒）；更大的 step 按这个值处理，对齐到汇总粒度时不会溢出
+pub const MAX_STEP_SECS: i64 = 10 * 366 * 86_400;
+
+/// 每条序列最多返回多少个点；step 太小、按它算出来的点数超过这个值的查询直接拒绝，不去读库
+pub const MAX_SERIES_POINTS: i64 = 10 * DEFAULT_MAX_POINTS;
+
+/// 11 列，每条语句 50 行 550 个参数，低于 SQLite 老版本 999 的上限
+const ROLLUP_ROWS_PER_INSERT: usize = 50;
+
+/// 按粒度查询一段时间的指标
+#[derive(Debug, Clone, Default)]
+pub struct SeriesQuery {
+    pub plugin: Option<String>,
+    pub name: Option<String>,
+    pub labels: Vec<LabelMatcher>,
+    /// 起始时间（含），会向下对齐到 step
+    pub start: DateTime<Utc>,
+    /// 结束时间（不含）
+    pub end: DateTime<Utc>,
+    /// 每个点代表多长时间（秒）；None 时按时间范围自动选，最多 1000 个点。
+    /// 超过 [`MAX_STEP_SECS`] 时按 [`MAX_STEP_SECS`] 处理
+    pub step_secs: Option<i64>,
+}
+
+impl SeriesQuery {
+    /// 实际读的粒度和 step：不超过 step 的最粗粒度，step 取它的整数倍
+    fn resolve(&self) -> (Resolution, i64) {
+        let range_secs = (self.end - self.start).num_seconds().max(1);
+        let step_secs = self
+            .step_secs
+            .unwrap_or((range_secs + DEFAULT_MAX_POINTS - 1) / DEFAULT_MAX_POINTS)
+            .clamp(1, MAX_STEP_SECS);
+        let resolution = ROLLUP_LEVELS
+            .iter()
+            .rev()
+            .copied()
+            .find(|level| level.secs() <= step_secs)
+            .unwrap_or(Resolution::Raw);
+        // step 取 resolution 的整数倍，一个汇总桶不会跨两个点
+        let step_secs = match resolution.secs() {
+            0 => step_secs,
+            res => (step_secs + res - 1) / res * res,
+        };
+        (resolution, step_secs)
+    }
+
+    /// 按实际的 step 算，每条序列最多有多少个点；超过 [`MAX_SERIES_POINTS`] 的查询会被拒绝
+    pub fn point_count(&self) -> i64 {
+        let (_, step_secs) = self.resolve();
+        let from = floor(self.start, step_secs).timestamp();
+        let range_secs = (self.end.timestamp() - from).max(1);
+        (range_secs + step_secs - 1) / step_secs
+    }
+}
+
+/// [`Db::query_series`] 的结果
+#[derive(Debug, Clone, Serialize)]
+pub struct SeriesSet {
+    /// 实际读的粒度
+    pub resolution: Resolution,
+    /// 实际的 step，是 resolution 的整数倍
+    pub step_secs: i64,
+    pub series: Vec<Series>,
+}
+
+/// 一条时间序列：plugin + name + labels 相同的点
+#[derive(Debug, Clone, Serialize)]
+pub struct Series {
+    pub plugin: String,
+    pub name: String,
+    pub labels: HashMap<String, String>,
+    pub points: Vec<SeriesPoint>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SeriesPoint {
+    /// 桶的起始时间
+    pub time: DateTime<Utc>,
+    pub min: f64,
+    pub max: f64,
+    pub avg: f64,
+    pub sum: f64,
+    pub count: i64,
+    /// 桶里时间最晚的那个值
+    pub last: f64,
+}
+
+/// 一个桶的汇总值
+#[derive(Debug, Clone)]
+struct Agg {
+    min: f64,
+    max: f64,
+    sum: f64,
+    count: i64,
+    last: f64,
+    last_time: DateTime<Utc>,
+}
+
+impl Agg {
+    fn sample(time: DateTime<Utc>, value: f64) -> Self {
+        Self {
+            min: value,
+            max: value,
+            sum: value,
+            count: 1,
+            last: value,
+            last_time: time,
+        }
+    }
+
+    fn merge(&mut self, other: &Agg) {
+        self.min = self.min.min(other.min);
+        self.max = self.max.max(other.max);
+        self.sum += other.sum;
+        self.count += other.count;
+        if other.last_time >= self.last_time {
+            self.last = other.last;
+            self.last_time = other.last_time;
+        }
+    }
+}
+
+/// (plugin, name, 规范化后的 labels)
+type SeriesKey = (String, String, String);
+
+/// 读出来的一个原始点或一个汇总桶
+struct Sample {
+    key: SeriesKey,
+    time: DateTime<Utc>,
+    agg: Agg,
+}
+
+/// 每条序列按桶起始时间排好的汇总值
+type Buckets = BTreeMap<SeriesKey, BTreeMap<DateTime<Utc>, Agg>>;
+
+fn add_to_buckets(buckets: &mut Buckets, samples: Vec<Sample>, step_secs: i64) {
+    for sample in samples {
+        let bucket = floor(sample.time, step_secs);
+        buckets
+            .entry(sample.key)
+            .or_default()
+            .entry(bucket)
+            .and_modify(|agg| agg.merge(&sample.agg))
+            .or_insert(sample.agg);
+    }
+}
+
+/// 向下对齐到 secs 的整数倍（从 1970-01-01 UTC 算起）
+fn floor(time: DateTime<Utc>, secs: i64) -> DateTime<Utc> {
+    if secs <= 0 {
+        return time;
+    }
+    DateTime::from_timestamp(time.timestamp().div_euclid(secs) * secs, 0).unwrap_or(time)
+}
+
+/// 桶已经存在时（比如两个进程同时汇总了同一段时间）用新算出来的值覆盖
+fn rollup_upsert(backend: Backend) -> &'static str {
+    match backend {
+        Backend::Sqlite | Backend::Postgres => {
+            "ON CONFLICT (resolution, plugin, name, labels, time) DO UPDATE SET \
+             min_value = excluded.min_value, max_value = excluded.max_value, \
+             sum_value = excluded.sum_value, sample_count = excluded.sample_count, \
+             last_value = excluded.last_value, last_time = excluded.last_time"
+        }
+        // MySQL 的唯一索引建在 labels 的哈希上，只能用 ON DUPLICATE KEY
+        Backend::MySql => {
+            "ON DUPLICATE KEY UPDATE min_value = VALUES(min_value), max_value = VALUES(max_value), \
+             sum_value = VALUES(sum_value), sample_count = VALUES(sample_count), \
+             last_value = VALUES(last_value), last_time = VALUES(last_time)"
+        }
+    }
+}
+
+/// 同一组标签序列化成同一个字符串，才能归到同一条序列
+fn canonical_labels(raw: &str) -> String {
+    encode_map(&decode_map(Some(raw))).unwrap_or_default()
+}
+
+#[derive(FromRow)]
+struct RollupRow {
+    #[sqlx(rename = "time_ms")]
+    time: Millis,
+    plugin: String,
+    name: String,
+    labels: String,
+    min_value: f64,
+    max_value: f64,
+    sum_value: f64,
+    sample_count: i64,
+    last_value: f64,
+    #[sqlx(rename = "last_time_ms")]
+    last_time: Millis,
+}
+
+#[derive(FromRow)]
+struct StateRow {
+    #[sqlx(rename = "done_until_ms")]
+    done_until: Millis,
+}
+
+#[derive(FromRow)]
+struct TimeRow {
+    #[sqlx(rename = "time_ms")]
+    time: Millis,
+}
+
+impl Db {
+    /// 把已经结束的桶汇总进 `metric_rollups`，返回写入的汇总行数。
+    /// bot-host 的后台任务定期调用；重复调用是安全的，没有新的完整桶时什么都不做
+    pub async fn rollup_metrics(&self) -> sqlx::Result<u64> {
+        let mut written = 0;
+        let mut ready = Some(Utc::now() - ROLLUP_DELAY);
+        for level in ROLLUP_LEVELS {
+            // 上一级汇总到哪里，这一级最多就汇总到哪里
+            let Some(until) = ready else {
+                break;
+            };
+            written += self.rollup_level(level, floor(until, level.secs())).await?;
+            ready = self.rollup_watermark(level).await?;
+        }
+        Ok(written)
+    }
+
+    async fn rollup_level(&self, level: Resolution, until: DateTime<Utc>) -> sqlx::Result<u64> {
+        let mut from = match self.rollup_watermark(level).await? {
+            Some(done) => done,
+            None => match self.first_sample_time(level.source()).await? {
+                Some(first) => floor(first, level.secs()),
+                None => return Ok(0),
+            },
+        };
+
+        let mut written = 0;
+        while from < until {
+            let to = (from + level.window()).min(until);
+            let samples = self
+                .fetch_samples(level.source(), from, to, Conditions::default())
+                .await?;
+            let mut buckets = Buckets::new();
+            add_to_buckets(&mut buckets, samples, level.secs());
+
+            let rows: Vec<(&SeriesKey, &DateTime<Utc>, &Agg)> = buckets
+                .iter()
+                .flat_map(|(key, series)| series.iter().map(move |(t, agg)| (key, t, agg)))
+                .collect();
+
+            let mut tx = self.pool.begin().await?;
+            for chunk in rows.chunks(ROLLUP_ROWS_PER_INSERT) {
+                let sql = format!(
+                    "INSERT INTO metric_rollups (resolution, time, plugin, name, labels, min_value, \
+                     max_value, sum_value, sample_count, last_value, last_time) VALUES {} {}",
+                    values_placeholders(11, chunk.len()),
+                    rollup_upsert(self.backend)
+                );
+                let mut query = sqlx::query(&sql);
+                for ((plugin, name, labels), time, agg) in chunk {
+                    query = query
+                        .bind(level.secs())
+                        .bind(to_millis(**time))
+                        .bind(plugin.clone())
+                        .bind(name.clone())
+                        .bind(labels.clone())
+                        .bind(agg.min)
+                        .bind(agg.max)
+                        .bind(agg.sum)
+                        .bind(agg.count)
+                        .bind(agg.last)
+                        .bind(to_millis(agg.last_time));
+                }
+                query.execute(&mut *tx).await?;
+            }
+            // 没有 upsert 的通用写法：先 UPDATE，没有这一行再 INSERT
+            let updated =
+                sqlx::query("UPDATE rollup_state SET done_until = ?1 WHERE resolution = ?2")
+                    .bind(to_millis(to))
+                    .bind(level.secs())
+                    .execute(&mut *tx)
+                    .await?;
+            if updated.rows_affected() == 0 {
+                sqlx::query("INSERT INTO rollup_state (resolution, done_until) VALUES (?1, ?2)")
+                    .bind(level.secs())
+                    .bind(to_millis(to))
+                    .execute(&mut *tx)
+                    .await?;
+            }
+            tx.commit().await?;
+
+            written += rows.len() as u64;
+            from = to;
+        }
+        Ok(written)
+    }
+
+    /// 某一级已经汇总到哪里（不含）；还没汇总过时返回 None
+    async fn rollup_watermark(&self, level: Resolution) -> sqlx::Result<Option<DateTime<Utc>>> {
+        let sql = format!(
+            "SELECT {} FROM rollup_state WHERE resolution = ?1",
+            select_millis(self.backend, "done_until")
+        );
+        let row = sqlx::query_as::<_, StateRow>(&sql)
+            .bind(level.secs())
+            .fetch_optional(&self.pool)
+            .await?;
+        row.map(|r| from_millis("done_until", r.done_until))
+            .transpose()
+    }
+
+    async fn first_sample_time(&self, level: Resolution) -> sqlx::Result<Option<DateTime<Utc>>> {
+        let time = select_millis(self.backend, "time");
+        let row = if level == Resolution::Raw {
+            let sql = format!("SELECT {time} FROM metrics ORDER BY time LIMIT 1");
+            sqlx::query_as::<_, TimeRow>(&sql)
+                .fetch_optional(&self.pool)
+                .await?
+        } else {
+            let sql = format!(
+                "SELECT {time} FROM metric_rollups WHERE resolution = ?1 ORDER BY time LIMIT 1"
+            );
+            sqlx::query_as::<_, TimeRow>(&sql)
+                .bind(level.secs())
+                .fetch_optional(&self.pool)
+                .await?
+        };
+        row.map(|r| from_millis("time", r.time)).transpose()
+    }
+
+    /// 读 `[from, to)` 内某一级的数据；`conditions` 里是 plugin / name / labels 条件
+    async fn fetch_samples(
+        &self,
+        level: Resolution,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+        mut conditions: Conditions,
+    ) -> sqlx::Result<Vec<Sample>> {
+        conditions.time_range(Some(from), Some(to));
+        if level == Resolution::Raw {
+            let sql = format!(
+                "SELECT {}, plugin, name, value, COALESCE(labels, '') AS labels FROM metrics {}",
+                select_millis(self.backend, "time"),
+                conditions.where_clause()
+            );
+            let rows = conditions
+                .bind(sqlx::query_as::<_, MetricRow>(&sql))
+                .fetch_all(&self.pool)
+                .await?;
+            return rows
+                .into_iter()
+                .map(|r| {
+                    let time = from_millis("time", r.time)?;
+                    Ok(Sample {
+                        key: (r.plugin, r.name, canonical_labels(&r.labels)),
+                        time,
+                        agg: Agg::sample(time, r.value),
+                    })
+                })
+                .collect();
+        }
+
+        conditions.equals_int("resolution", level.secs());
+        let sql = format!(
+            "SELECT {}, plugin, name, labels, min_value, max_value, sum_value, sample_count, \
+             last_value, {} FROM metric_rollups {}",
+            select_millis(self.backend, "time"),
+            select_millis(self.backend, "last_time"),
+            conditions.where_clause()
+        );
+        let rows = conditions
+            .bind(sqlx::query_as::<_, RollupRow>(&sql))
+            .fetch_all(&self.pool)
+            .await?;
+        rows.into_iter()
+            .map(|r| {
+                Ok(Sample {
+                    time: from_millis("time", r.time)?,
+                    agg: Agg {
+                        min: r.min_value,
+                        max: r.max_value,
+                        sum: r.sum_value,
+                        count: r.sample_count,
+                        last: r.last_value,
+                        last_time: from_millis("last_time", r.last_time)?,
+                    },
+                    key: (r.plugin, r.name, r.labels),
+                })
+            })
+            .collect()
+    }
+
+    /// 按 step 查询一段时间的指标，自动选不超过 step 的最粗粒度：
+    /// step 不到 1 分钟读原始数据，不到 1 小时读 1 分钟汇总，依此类推。
+    /// 选定粒度还没汇总到的那一段依次用更细的汇总和原始数据补齐。
+    ///
+    /// 点数超过 [`MAX_SERIES_POINTS`] 时返回错误，调用方应该先用 [`SeriesQuery::point_count`] 检查。
+    pub async fn query_series(&self, query: &SeriesQuery) -> sqlx::Result<SeriesSet> {
+        let points = query.point_count();
+        if points > MAX_SERIES_POINTS {
+            return Err(sqlx::Error::Protocol(format!(
+                "查询 {points} 个点，超过上限 {MAX_SERIES_POINTS}，请加大 step 或缩小时间范围"
+            )));
+        }
+        let (resolution, step_secs) = query.resolve();
+
+        let filter = || {
+            let mut c = Conditions::default();
+            c.compare("plugin", "=", query.plugin.clone());
+            c.compare("name", "=", query.name.clone());
+            c.labels("labels", &query.labels);
+            c
+        };
+
+        let mut buckets = Buckets::new();
+        let mut from = floor(query.start, step_secs);
+        let sources = ROLLUP_LEVELS
+            .iter()
+            .rev()
+            .copied()
+            .filter(|level| *level <= resolution)
+            .chain([Resolution::Raw]);
+        for level in sources {
+            if from >= query.end {
+                break;
+            }
+            let until = match level {
+                Resolution::Raw => query.end,
+                _ => match self.rollup_watermark(level).await? {
+                    Some(done) => done.min(query.end),
+                    None => continue,
+                },
+            };
+            if until <= from {
+                continue;
+            }
+            let samples = self.fetch_samples(level, from, until, filter()).await?;
+            add_to_buckets(&mut buckets, samples, step_secs);
+            from = until;
+        }
+
+        let series = buckets
+            .into_iter()
+            .map(|((plugin, name, labels), points)| Series {
+                plugin,
+                name,
+                labels: decode_map(Some(&labels)),
+                points: points
+                    .into_iter()
+                    .map(|(time, agg)| SeriesPoint {
+                        time,
+                        min: agg.min,
+                        max: agg.max,
+                        avg: agg.sum / agg.count as f64,
+                        sum: agg.sum,
+                        count: agg.count,
+                        last: agg.last,
+                    })
+                    .collect(),
+            })
+            .collect();
+        Ok(SeriesSet {
+            resolution,
+            step_secs,
+            series,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+    use core_types::Metric;
+
+    use super::*;
+    use crate::test_support::TempDb;
+
+    #[tokio::test]
+    async fn huge_step_is_clamped_instead_of_overflowing() {
+        let temp = TempDb::migrated("rollup-step").await;
+        let time = Utc.timestamp_opt(1_792_000_000, 0).unwrap();
+        temp.db
+            .insert_metrics(&[Metric {
+                time,
+                plugin: "p".to_string(),
+                name: "m".to_string(),
+                value: 1.0,
+                labels: HashMap::new(),
+            }])
+            .await
+            .unwrap();
+
+        for step in [i64::MAX, i64::MAX - 1, MAX_STEP_SECS + 1] {
+            let set = temp
+                .db
+                .query_series(&SeriesQuery {
+                    start: time - Duration::hours(1),
+                    end: time + Duration::hours(1),
+                    step_secs: Some(step),
+                    ..Default::default()
+                })
+                .await
+                .unwrap();
+            assert_eq!(set.resolution, Resolution::Day);
+            assert!(set.step_secs <= MAX_STEP_SECS, "step={}", set.step_secs);
+            assert_eq!(set.step_secs % Resolution::Day.secs(), 0);
+        }
+    }
+
+    #[tokio::test]
+    async fn too_many_points_are_rejected_before_reading() {
+        let temp = TempDb::migrated("rollup-points").await;
+        // 对齐到天，起点不会因为向下对齐多出一个点
+        let end = Utc.timestamp_opt(20_741 * 86_400, 0).unwrap();
+        let query = |days: i64, step_secs: Option<i64>| SeriesQuery {
+            start: end - Duration::days(days),
+            end,
+            step_secs,
+            ..Default::default()
+        };
+
+        // 自动选的 step 不会超
+        assert!(query(3650, None).point_count() <= DEFAULT_MAX_POINTS + 1);
+        assert!(temp.db.query_series(&query(3650, None)).await.is_ok());
+        // 一天按 1 秒一个点是 86400 个
+        let too_fine = query(1, Some(1));
+        assert_eq!(too_fine.point_count(), 86_400);
+        assert!(temp.db.query_series(&too_fine).await.is_err());
+        // 对齐到汇总粒度之后再算：一年按 61 秒实际是 120 秒一个点
+        assert_eq!(query(365, Some(61)).point_count(), 365 * 720);
+        assert_eq!(query(1, Some(10)).point_count(), 8640);
+    }
+
+    /// 同一段时间再汇总一次（比如两个进程同时汇总）时覆盖原来的桶，不会重复计数
+    #[tokio::test]
+    async fn rolling_up_twice_overwrites_buckets() {
+        let temp = TempDb::migrated("rollup-upsert").await;
+        let time = floor(Utc::now() - Duration::hours(3), 60);
+        let sample = |offset: i64, value: f64| Metric {
+            time: time + Duration::seconds(offset),
+            plugin: "p".to_string(),
+            name: "m".to_string(),
+            value,
+            labels: HashMap::new(),
+        };
+        temp.db
+            .insert_metrics(&[sample(0, 1.0), sample(10, 3.0)])
+            .await
+            .unwrap();
+
+        let written = temp.db.rollup_metrics().await.unwrap();
+        assert!(written > 0);
+        // 忘掉汇总进度，从头再来一遍
+        sqlx::query("DELETE FROM rollup_state")
+            .execute(&temp.db.pool)
+            .await
+            .unwrap();
+        assert_eq!(temp.db.rollup_metrics().await.unwrap(), written);
+
+        let minute = sqlx::query_as::<_, RollupRow>(&format!(
+            "SELECT {}, plugin, name, labels, min_value, max_value, sum_value, sample_count, \
+             last_value, {} FROM metric_rollups WHERE resolution = 60",
+            select_millis(temp.db.backend, "time"),
+            select_millis(temp.db.backend, "last_time"),
+        ))
+        .fetch_all(&temp.db.pool)
+        .await
+        .unwrap();
+        assert_eq!(minute.len(), 1);
+        assert_eq!(minute[0].sample_count, 2);
+        assert_eq!(minute[0].sum_value, 4.0);
+    }
+}
@@ -0,0 +1,37 @@
+//! 测试用的临时 SQLite 库，每个测试一个文件，测试结束后删除
+
+use std::{env, fs, path::PathBuf};
+
+use crate::Db;
+
+pub(crate) struct TempDb {
+    pub(crate) db: Db,
+    dir: PathBuf,
+}
+
+impl TempDb {
+    /// 建一个新库并执行全部迁移
+    pub(crate) async fn migrated(test: &str) -> Self {
+        let temp = Self::open(test).await;
+        temp.db.migrate().await.expect("执行迁移失败");
+        temp
+    }
+
+    /// 建一个新库，不执行迁移
+    pub(crate) async fn open(test: &str) -> Self {
+        let dir = env::temp_dir().join(format!("storage-test-{}-{test}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).expect("创建临时目录失败");
+        let url = format!("sqlite://{}", dir.join("test.db").display());
+        let db = Db::open(Some("sqlite"), Some(&url))
+            .await
+            .expect("打开临时 SQLite 库失败");
+        Self { db, dir }
+    }
+}
+
+impl Drop for TempDb {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
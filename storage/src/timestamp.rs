@@ -0,0 +1,109 @@
+//! 时间列的存储格式。
+//!
+//! 所有时间列都存 Unix 毫秒（BIGINT，UTC）：按整数比较和排序，不受时区写法影响；
+//! AnyPool 绑定不了 chrono 类型，三种后端用同一种写法读写。
+//!
+//! sqlx 0.7 的 AnyPool 按值的类型解码 SQLite 的整数，一律当 i32，毫秒时间戳会被截断。
+//! 所以 SELECT 里的时间列都用 [`select_millis`] 读：SQLite 上先转成 REAL（2^53 毫秒以内没有
+//! 精度损失），别名是 `<列名>_ms`，`WHERE` / `ORDER BY` 里的 `time` 仍然是原来的整数列，能走索引。
+//! 自增 `id` 同理：超过 i32::MAX 之后同样会被截断，用 [`select_id`] 读成 [`RowId`]。
+
+use chrono::{DateTime, Utc};
+use sqlx::any::{AnyTypeInfo, AnyValueRef};
+use sqlx::error::BoxDynError;
+use sqlx::{Any, Decode, Type};
+
+use crate::db_config::Backend;
+
+/// f64 能精确表示的最大整数
+const MAX_EXACT_F64: f64 = 9_007_199_254_740_992.0;
+
+/// 从库里读出来的时间列（Unix 毫秒），整数和浮点都接受
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct Millis(pub(crate) i64);
+
+impl Type<Any> for Millis {
+    fn type_info() -> AnyTypeInfo {
+        <i64 as Type<Any>>::type_info()
+    }
+
+    fn compatible(ty: &AnyTypeInfo) -> bool {
+        <i64 as Type<Any>>::compatible(ty) || <f64 as Type<Any>>::compatible(ty)
+    }
+}
+
+impl<'r> Decode<'r, Any> for Millis {
+    fn decode(value: AnyValueRef<'r>) -> Result<Self, BoxDynError> {
+        decode_i64(value, "毫秒时间戳").map(Millis)
+    }
+}
+
+/// 从库里读出来的自增 id，和 [`Millis`] 一样整数和浮点都接受
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct RowId(pub(crate) i64);
+
+impl Type<Any> for RowId {
+    fn type_info() -> AnyTypeInfo {
+        <Millis as Type<Any>>::type_info()
+    }
+
+    fn compatible(ty: &AnyTypeInfo) -> bool {
+        <Millis as Type<Any>>::compatible(ty)
+    }
+}
+
+impl<'r> Decode<'r, Any> for RowId {
+    fn decode(value: AnyValueRef<'r>) -> Result<Self, BoxDynError> {
+        decode_i64(value, "id").map(RowId)
+    }
+}
+
+fn decode_i64(value: AnyValueRef<'_>, what: &str) -> Result<i64, BoxDynError> {
+    if let Ok(n) = <i64 as Decode<Any>>::decode(value.clone()) {
+        return Ok(n);
+    }
+    let n = <f64 as Decode<Any>>::decode(value)?;
+    if n.fract() != 0.0 || n.abs() > MAX_EXACT_F64 {
+        return Err(format!("{n} 不是有效的{what}").into());
+    }
+    Ok(n as i64)
+}
+
+pub(crate) fn to_millis(time: DateTime<Utc>) -> i64 {
+    time.timestamp_millis()
+}
+
+/// 超出能表示的范围时返回解码错误，不拿当前时间顶替
+pub(crate) fn from_millis(column: &str, millis: Millis) -> sqlx::Result<DateTime<Utc>> {
+    DateTime::from_timestamp_millis(millis.0).ok_or_else(|| sqlx::Error::ColumnDecode {
+        index: column.to_string(),
+        source: format!("时间戳 {} 超出范围", millis.0).into(),
+    })
+}
+
+/// SELECT 里读时间列的写法，结果列名是 `<column>_ms`
+pub(crate) fn select_millis(backend: Backend, column: &str) -> String {
+    read_as(backend, column, &format!("{column}_ms"))
+}
+
+/// 可空的时间列，NULL 读成 0（AnyPool 解不出 NULL）
+pub(crate) fn select_optional_millis(backend: Backend, column: &str) -> String {
+    let expr = format!("COALESCE({column}, 0)");
+    read_as(backend, &expr, &format!("{column}_ms"))
+}
+
+/// SELECT 里读 `id` 列的写法，结果列名是 `id_int`；`ORDER BY id` 仍然按原来的整数列排序
+pub(crate) fn select_id(backend: Backend) -> String {
+    read_as(backend, "id", "id_int")
+}
+
+fn read_as(backend: Backend, expr: &str, alias: &str) -> String {
+    match backend {
+        // 只转整数；列里混进了文本时原样读出来，解码报错，不会被 CAST 成 0
+        Backend::Sqlite => format!(
+            "CASE typeof({expr}) WHEN 'integer' THEN CAST({expr} AS REAL) ELSE {expr} END \
+             AS {alias}"
+        ),
+        Backend::Postgres | Backend::MySql => format!("{expr} AS {alias}"),
+    }
+}